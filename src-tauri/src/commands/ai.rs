@@ -1,3 +1,4 @@
+use crate::commands::{large, operations};
 use crate::error::AppError;
 use crate::models::session::OnnxState;
 use image::DynamicImage;
@@ -72,7 +73,7 @@ pub fn remove_background(
     )
     .ok();
 
-    let img = image::open(&path)?;
+    let img = operations::open_image(&path)?;
     let (orig_w, orig_h) = (img.width(), img.height());
 
     let resized = img.resize_exact(320, 320, image::imageops::FilterType::Lanczos3);
@@ -160,17 +161,14 @@ pub fn remove_background(
     Ok(output_path)
 }
 
-/// Core function for batch processing (no State<> wrapper)
-pub fn remove_background_core(
+/// Run U²-Net on `img` and return its foreground mask at the image's own size.
+pub fn foreground_mask(
     app: &AppHandle,
     state: &OnnxState,
-    path: &str,
-) -> Result<String, AppError> {
-    use tauri::Emitter;
-
+    img: &DynamicImage,
+) -> Result<image::GrayImage, AppError> {
     get_or_create_session(app, state, "u2net")?;
 
-    let img = image::open(path)?;
     let (orig_w, orig_h) = (img.width(), img.height());
     let resized = img.resize_exact(320, 320, image::imageops::FilterType::Lanczos3);
     let rgb = resized.to_rgb8();
@@ -218,15 +216,25 @@ pub fn remove_background_core(
         }
     }
 
-    let mask_resized = image::imageops::resize(
+    Ok(image::imageops::resize(
         &mask_img,
         orig_w,
         orig_h,
         image::imageops::FilterType::Lanczos3,
-    );
+    ))
+}
+
+/// Core function for batch processing (no State<> wrapper)
+pub fn remove_background_core(
+    app: &AppHandle,
+    state: &OnnxState,
+    path: &str,
+) -> Result<String, AppError> {
+    let img = operations::open_image(path)?;
+    let mask = foreground_mask(app, state, &img)?;
 
     let mut rgba = img.to_rgba8();
-    for (rgba_pixel, mask_pixel) in rgba.pixels_mut().zip(mask_resized.pixels()) {
+    for (rgba_pixel, mask_pixel) in rgba.pixels_mut().zip(mask.pixels()) {
         rgba_pixel[3] = mask_pixel[0];
     }
 
@@ -265,7 +273,7 @@ pub fn classify_image(
     )
     .ok();
 
-    let img = image::open(&path)?;
+    let img = operations::open_image(&path)?;
     let resized = img.resize_exact(224, 224, image::imageops::FilterType::Lanczos3);
     let rgb = resized.to_rgb8();
 
@@ -373,7 +381,7 @@ pub fn apply_style_transfer(
     )
     .ok();
 
-    let img = image::open(&path)?;
+    let img = operations::open_image(&path)?;
     let (orig_w, orig_h) = (img.width(), img.height());
 
     // Size guard: cap at 2048px on any side to prevent OOM
@@ -441,30 +449,28 @@ pub fn apply_style_transfer(
         }
     }
 
-    // Blend with original: result = original * (1 - strength) + styled * strength
-    let original_rgb = process_img.to_rgb8();
+    // Blend with original in linear light: result = original * (1 - strength) + styled * strength
+    let original_lin = operations::to_linear(&DynamicImage::ImageRgb8(process_img.to_rgb8()));
+    let styled_lin = operations::to_linear(&DynamicImage::ImageRgb8(styled));
     let s = strength.clamp(0.0, 1.0);
-    let mut blended = image::RgbImage::new(pw as u32, ph as u32);
-    for y in 0..ph {
-        for x in 0..pw {
-            let orig = original_rgb.get_pixel(x as u32, y as u32);
-            let sty = styled.get_pixel(x as u32, y as u32);
-            let r = (orig[0] as f32 * (1.0 - s) + sty[0] as f32 * s).round() as u8;
-            let g = (orig[1] as f32 * (1.0 - s) + sty[1] as f32 * s).round() as u8;
-            let b = (orig[2] as f32 * (1.0 - s) + sty[2] as f32 * s).round() as u8;
-            blended.put_pixel(x as u32, y as u32, image::Rgb([r, g, b]));
+    let mut blended = original_lin;
+    for (orig, sty) in blended.pixels_mut().zip(styled_lin.pixels()) {
+        for c in 0..3 {
+            orig[c] = orig[c] * (1.0 - s) + sty[c] * s;
         }
     }
 
     // If we downscaled for processing, resize back to original
     let final_img = if orig_w > max_dim || orig_h > max_dim {
-        DynamicImage::ImageRgb8(blended).resize_exact(
+        let resized = image::imageops::resize(
+            &blended,
             orig_w,
             orig_h,
             image::imageops::FilterType::Lanczos3,
-        )
+        );
+        operations::from_linear(resized, image::ColorType::Rgb8)
     } else {
-        DynamicImage::ImageRgb8(blended)
+        operations::from_linear(blended, image::ColorType::Rgb8)
     };
 
     let output = save_temp_image(&final_img)?;
@@ -521,6 +527,74 @@ fn compute_tiles(img_w: u32, img_h: u32, tile_size: u32, overlap: u32) -> Vec<Ti
     tiles
 }
 
+/// Blends overlapping upscaled tiles in linear light, holding only the
+/// output rows a tile can still reach. Tiles arrive row by row, so rows above
+/// the current tile are final and go straight into the output.
+struct TileBlender {
+    out_w: usize,
+    /// Ring of `rows` output rows, indexed by row modulo `rows`
+    rows: usize,
+    /// First row not yet written out
+    top: usize,
+    accum: Vec<f32>,
+    weights: Vec<f32>,
+}
+
+impl TileBlender {
+    fn new(out_w: u32, rows: u32) -> Self {
+        let len = out_w as usize * rows as usize;
+        Self {
+            out_w: out_w as usize,
+            rows: rows as usize,
+            top: 0,
+            accum: vec![0.0; len * 3],
+            weights: vec![0.0; len],
+        }
+    }
+
+    fn add(&mut self, x: usize, y: usize, linear: [f32; 3], w: f32) {
+        let idx = (y % self.rows) * self.out_w + x;
+        for (sum, v) in self.accum[idx * 3..idx * 3 + 3].iter_mut().zip(linear) {
+            *sum += v * w;
+        }
+        self.weights[idx] += w;
+    }
+
+    /// Write out and clear every row above `row`.
+    fn settle(&mut self, row: usize, output: &mut image::RgbImage) {
+        while self.top < row.min(output.height() as usize) {
+            let start = (self.top % self.rows) * self.out_w;
+            for x in 0..self.out_w {
+                let idx = start + x;
+                let weight = self.weights[idx];
+                let rgb = std::array::from_fn(|c| {
+                    if weight > 0.0 {
+                        let v = operations::linear_to_srgb(self.accum[idx * 3 + c] / weight);
+                        (v * 255.0).round() as u8
+                    } else {
+                        0
+                    }
+                });
+                output.put_pixel(x as u32, self.top as u32, image::Rgb(rgb));
+            }
+            self.accum[start * 3..(start + self.out_w) * 3].fill(0.0);
+            self.weights[start..start + self.out_w].fill(0.0);
+            self.top += 1;
+        }
+    }
+}
+
+/// Bytes an upscale holds at its peak: the RGB copy of the source, the
+/// output, the blend band and, for 2x, the linear-light downsize.
+fn upscale_bytes(orig_w: u32, orig_h: u32, tile_size: u32, scale: u32) -> u64 {
+    let (w, h, scale) = (u64::from(orig_w), u64::from(orig_h), u64::from(scale));
+    let out_w = w * scale;
+    let out = out_w * h * scale * 3;
+    let band = out_w * u64::from(tile_size) * scale * 16;
+    let downsize = if scale == 2 { out * 16 / 3 } else { 0 };
+    w * h * 3 + out + band + downsize
+}
+
 #[tauri::command]
 pub fn upscale_image(
     app: AppHandle,
@@ -546,7 +620,7 @@ pub fn upscale_image(
     )
     .ok();
 
-    let img = image::open(&path)?;
+    let img = operations::open_image(&path)?;
     let (orig_w, orig_h) = (img.width(), img.height());
     let rgb = img.to_rgb8();
 
@@ -557,9 +631,12 @@ pub fn upscale_image(
 
     let out_w = orig_w * scale;
     let out_h = orig_h * scale;
-    let pixel_count = (out_w as usize) * (out_h as usize);
-    let mut accum = vec![0.0_f32; pixel_count * 3];
-    let mut weights = vec![0.0_f32; pixel_count * 3];
+    large::check_budget(
+        upscale_bytes(orig_w, orig_h, tile_size, scale),
+        &format!("Upscaling {orig_w}×{orig_h} by {scale}x"),
+    )?;
+    let mut output = image::RgbImage::new(out_w, out_h);
+    let mut blender = TileBlender::new(out_w, tile_size * scale);
 
     let mut sessions = state
         .sessions
@@ -605,6 +682,7 @@ pub fn upscale_image(
         let out_x = (tile.src_x * scale) as usize;
         let out_y = (tile.src_y * scale) as usize;
         let overlap_scaled = (overlap * scale) as usize;
+        blender.settle(out_y, &mut output);
 
         for y in 0..out_tile_h {
             for x in 0..out_tile_w {
@@ -614,7 +692,7 @@ pub fn upscale_image(
                     continue;
                 }
 
-                // Linear blend weight for overlap regions
+                // Linear blend weight for overlap regions, accumulated in linear light
                 let wx = if out_tile_w <= overlap_scaled * 2 {
                     1.0
                 } else if x < overlap_scaled {
@@ -635,14 +713,13 @@ pub fn upscale_image(
                 };
                 let w = (wx * wy).max(0.001);
 
-                let idx = (oy * out_w as usize + ox) * 3;
-                for c in 0..3 {
-                    let val = result_data[c * out_tile_h * out_tile_w + y * out_tile_w + x]
-                        .clamp(0.0, 1.0)
-                        * 255.0;
-                    accum[idx + c] += val * w;
-                    weights[idx + c] += w;
-                }
+                let linear = std::array::from_fn(|c| {
+                    operations::srgb_to_linear(
+                        result_data[c * out_tile_h * out_tile_w + y * out_tile_w + x]
+                            .clamp(0.0, 1.0),
+                    )
+                });
+                blender.add(ox, oy, linear, w);
             }
         }
     }
@@ -654,32 +731,12 @@ pub fn upscale_image(
     .ok();
 
     // Finalize output image
-    let mut output = image::RgbImage::new(out_w, out_h);
-    for y in 0..out_h {
-        for x in 0..out_w {
-            let idx = (y as usize * out_w as usize + x as usize) * 3;
-            let r = if weights[idx] > 0.0 {
-                (accum[idx] / weights[idx]).round() as u8
-            } else {
-                0
-            };
-            let g = if weights[idx + 1] > 0.0 {
-                (accum[idx + 1] / weights[idx + 1]).round() as u8
-            } else {
-                0
-            };
-            let b = if weights[idx + 2] > 0.0 {
-                (accum[idx + 2] / weights[idx + 2]).round() as u8
-            } else {
-                0
-            };
-            output.put_pixel(x, y, image::Rgb([r, g, b]));
-        }
-    }
+    blender.settle(out_h as usize, &mut output);
 
     // If scale was 2 but model is 4x, resize down
     let final_img = if scale == 2 {
-        DynamicImage::ImageRgb8(output).resize_exact(
+        operations::resize_linear(
+            &DynamicImage::ImageRgb8(output),
             orig_w * 2,
             orig_h * 2,
             image::imageops::FilterType::Lanczos3,
@@ -709,7 +766,7 @@ pub fn upscale_image_core(
     // For batch, reuse the tile logic but without progress events
     get_or_create_session(app, state, "realesrgan-x4")?;
 
-    let img = image::open(path)?;
+    let img = operations::open_image(path)?;
     let (orig_w, orig_h) = (img.width(), img.height());
     let rgb = img.to_rgb8();
     let scale = if scale == 2 || scale == 4 { scale } else { 4 };
@@ -720,9 +777,12 @@ pub fn upscale_image_core(
 
     let out_w = orig_w * scale;
     let out_h = orig_h * scale;
-    let pixel_count = (out_w as usize) * (out_h as usize);
-    let mut accum = vec![0.0_f32; pixel_count * 3];
-    let mut weights = vec![0.0_f32; pixel_count * 3];
+    large::check_budget(
+        upscale_bytes(orig_w, orig_h, tile_size, scale),
+        &format!("Upscaling {orig_w}×{orig_h} by {scale}x"),
+    )?;
+    let mut output = image::RgbImage::new(out_w, out_h);
+    let mut blender = TileBlender::new(out_w, tile_size * scale);
 
     let mut sessions = state
         .sessions
@@ -760,6 +820,7 @@ pub fn upscale_image_core(
         let out_x = (tile.src_x * scale) as usize;
         let out_y = (tile.src_y * scale) as usize;
         let overlap_scaled = (overlap * scale) as usize;
+        blender.settle(out_y, &mut output);
 
         for y in 0..out_tile_h {
             for x in 0..out_tile_w {
@@ -787,43 +848,22 @@ pub fn upscale_image_core(
                     1.0
                 };
                 let w = (wx * wy).max(0.001);
-                let idx = (oy * out_w as usize + ox) * 3;
-                for c in 0..3 {
-                    let val = result_data[c * out_tile_h * out_tile_w + y * out_tile_w + x]
-                        .clamp(0.0, 1.0)
-                        * 255.0;
-                    accum[idx + c] += val * w;
-                    weights[idx + c] += w;
-                }
+                let linear = std::array::from_fn(|c| {
+                    operations::srgb_to_linear(
+                        result_data[c * out_tile_h * out_tile_w + y * out_tile_w + x]
+                            .clamp(0.0, 1.0),
+                    )
+                });
+                blender.add(ox, oy, linear, w);
             }
         }
     }
 
-    let mut output = image::RgbImage::new(out_w, out_h);
-    for y in 0..out_h {
-        for x in 0..out_w {
-            let idx = (y as usize * out_w as usize + x as usize) * 3;
-            let r = if weights[idx] > 0.0 {
-                (accum[idx] / weights[idx]).round() as u8
-            } else {
-                0
-            };
-            let g = if weights[idx + 1] > 0.0 {
-                (accum[idx + 1] / weights[idx + 1]).round() as u8
-            } else {
-                0
-            };
-            let b = if weights[idx + 2] > 0.0 {
-                (accum[idx + 2] / weights[idx + 2]).round() as u8
-            } else {
-                0
-            };
-            output.put_pixel(x, y, image::Rgb([r, g, b]));
-        }
-    }
+    blender.settle(out_h as usize, &mut output);
 
     let final_img = if scale == 2 {
-        DynamicImage::ImageRgb8(output).resize_exact(
+        operations::resize_linear(
+            &DynamicImage::ImageRgb8(output),
             orig_w * 2,
             orig_h * 2,
             image::imageops::FilterType::Lanczos3,
@@ -862,7 +902,7 @@ pub fn apply_inpainting(
     )
     .ok();
 
-    let img = image::open(&image_path)?;
+    let img = operations::open_image(&image_path)?;
     let (orig_w, orig_h) = (img.width(), img.height());
 
     // Reconstruct mask from raw bytes
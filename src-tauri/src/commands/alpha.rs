@@ -0,0 +1,255 @@
+use crate::commands::operations::{self, srgb_to_linear};
+use crate::commands::tonemap;
+use crate::error::AppError;
+use image::{DynamicImage, ImageFormat, Rgba32FImage};
+use serde::Deserialize;
+use std::borrow::Cow;
+
+// ── Alpha flattening ────────────────────────────────────────────────
+//
+// Formats without an alpha channel get the image composited over a matte
+// before encoding, instead of whatever each encoder does with the
+// transparent pixels' leftover colors.
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FlattenMode {
+    /// Flatten for JPEG, BMP and HDR. BMP counts as alpha-less since most
+    /// readers ignore its alpha.
+    #[default]
+    Auto,
+    Always,
+    /// Leave alpha to the encoder
+    Never,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Matte {
+    /// Solid `color`
+    #[default]
+    Color,
+    /// White and light grey squares, for previewing transparency
+    Checkerboard,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AlphaOptions {
+    pub flatten: FlattenMode,
+    pub matte: Matte,
+    pub color: [u8; 3],
+    /// Checkerboard square size in pixels
+    pub checker_size: u32,
+    /// Store TIFF color premultiplied by alpha (associated alpha). EXR is
+    /// always premultiplied, as the format defines it.
+    pub premultiplied: bool,
+}
+
+impl Default for AlphaOptions {
+    fn default() -> Self {
+        Self {
+            flatten: FlattenMode::Auto,
+            matte: Matte::Color,
+            color: [255, 255, 255],
+            checker_size: 8,
+            premultiplied: false,
+        }
+    }
+}
+
+fn keeps_alpha(format: ImageFormat) -> bool {
+    !matches!(
+        format,
+        ImageFormat::Jpeg | ImageFormat::Bmp | ImageFormat::Hdr
+    )
+}
+
+/// Composite over the matte, keeping the bit depth; float images blend in
+/// linear light against a linearized matte.
+pub fn flatten(img: &DynamicImage, options: &AlphaOptions) -> DynamicImage {
+    let float = tonemap::is_float(img.color());
+    let size = options.checker_size.max(1);
+    let matte_at = |x: u32, y: u32| -> [f32; 3] {
+        let rgb = match options.matte {
+            Matte::Color => options.color,
+            Matte::Checkerboard if (x / size + y / size).is_multiple_of(2) => [255; 3],
+            Matte::Checkerboard => [204; 3],
+        };
+        rgb.map(|v| {
+            let v = f32::from(v) / 255.0;
+            if float {
+                srgb_to_linear(v)
+            } else {
+                v
+            }
+        })
+    };
+
+    let mut out: Rgba32FImage = img.to_rgba32f();
+    for (x, y, p) in out.enumerate_pixels_mut() {
+        let a = p[3].clamp(0.0, 1.0);
+        let m = matte_at(x, y);
+        for c in 0..3 {
+            p[c] = p[c] * a + m[c] * (1.0 - a);
+        }
+        p[3] = 1.0;
+    }
+    let out = DynamicImage::ImageRgba32F(out);
+    if float {
+        DynamicImage::ImageRgb32F(out.to_rgb32f())
+    } else if img.color().bytes_per_pixel() > img.color().channel_count() {
+        DynamicImage::ImageRgb16(out.to_rgb16())
+    } else {
+        DynamicImage::ImageRgb8(out.to_rgb8())
+    }
+}
+
+/// Multiply color by alpha, keeping the bit depth. Gray+alpha widens to RGBA.
+pub fn premultiply(img: &DynamicImage) -> DynamicImage {
+    match img {
+        DynamicImage::ImageRgba32F(_) => {
+            let mut out = img.to_rgba32f();
+            for p in out.pixels_mut() {
+                for c in 0..3 {
+                    p[c] *= p[3];
+                }
+            }
+            DynamicImage::ImageRgba32F(out)
+        }
+        img if img.color().bytes_per_pixel() > img.color().channel_count() => {
+            let mut out = img.to_rgba16();
+            for p in out.pixels_mut() {
+                for c in 0..3 {
+                    p[c] = ((u32::from(p[c]) * u32::from(p[3]) + 32767) / 65535) as u16;
+                }
+            }
+            DynamicImage::ImageRgba16(out)
+        }
+        img => {
+            let mut out = img.to_rgba8();
+            for p in out.pixels_mut() {
+                for c in 0..3 {
+                    p[c] = ((u16::from(p[c]) * u16::from(p[3]) + 127) / 255) as u8;
+                }
+            }
+            DynamicImage::ImageRgba8(out)
+        }
+    }
+}
+
+/// Whether `options` flatten exports of `format`.
+pub fn flattens(format: ImageFormat, options: &AlphaOptions) -> bool {
+    match options.flatten {
+        FlattenMode::Auto => !keeps_alpha(format),
+        FlattenMode::Always => true,
+        FlattenMode::Never => false,
+    }
+}
+
+/// The image an export of `format` should encode: flattened when `options`
+/// call for it and the image has alpha, otherwise unchanged.
+pub fn prepare<'a>(
+    img: &'a DynamicImage,
+    format: ImageFormat,
+    options: &AlphaOptions,
+) -> Cow<'a, DynamicImage> {
+    if flattens(format, options) && img.color().has_alpha() {
+        Cow::Owned(flatten(img, options))
+    } else {
+        Cow::Borrowed(img)
+    }
+}
+
+/// Flatten onto a matte or checkerboard as a new temp image.
+#[tauri::command]
+pub fn flatten_alpha(path: String, options: Option<AlphaOptions>) -> Result<String, AppError> {
+    let img = operations::open_image(&path)?;
+    operations::save_temp_image(&flatten(&img, &options.unwrap_or_default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn half_transparent() -> DynamicImage {
+        DynamicImage::ImageRgba8(image::RgbaImage::from_fn(16, 16, |x, _| {
+            if x < 8 {
+                image::Rgba([200, 0, 0, 255])
+            } else {
+                // Leftover color under full transparency, as background removal leaves
+                image::Rgba([0, 255, 0, 0])
+            }
+        }))
+    }
+
+    #[test]
+    fn flatten_uses_the_matte_and_keeps_depth() {
+        let options = AlphaOptions {
+            color: [10, 20, 30],
+            ..AlphaOptions::default()
+        };
+        let flat = flatten(&half_transparent(), &options);
+        assert_eq!(flat.color(), image::ColorType::Rgb8);
+        let flat = flat.to_rgb8();
+        assert_eq!(flat.get_pixel(0, 0).0, [200, 0, 0]);
+        assert_eq!(flat.get_pixel(12, 0).0, [10, 20, 30]);
+
+        let sixteen = DynamicImage::ImageRgba16(half_transparent().to_rgba16());
+        assert_eq!(flatten(&sixteen, &options).color(), image::ColorType::Rgb16);
+
+        let checker = flatten(
+            &half_transparent(),
+            &AlphaOptions {
+                matte: Matte::Checkerboard,
+                checker_size: 4,
+                ..AlphaOptions::default()
+            },
+        )
+        .to_rgb8();
+        assert_eq!(checker.get_pixel(8, 0).0, [255; 3]);
+        assert_eq!(checker.get_pixel(12, 0).0, [204; 3]);
+        assert_eq!(checker.get_pixel(12, 4).0, [255; 3]);
+    }
+
+    #[test]
+    fn prepare_flattens_only_alpha_less_formats_by_default() {
+        let img = half_transparent();
+        let options = AlphaOptions::default();
+        for format in [ImageFormat::Jpeg, ImageFormat::Bmp] {
+            assert!(!prepare(&img, format, &options).color().has_alpha());
+        }
+        for format in [ImageFormat::Png, ImageFormat::WebP, ImageFormat::Tiff] {
+            assert!(matches!(prepare(&img, format, &options), Cow::Borrowed(_)));
+        }
+        let always = AlphaOptions {
+            flatten: FlattenMode::Always,
+            ..AlphaOptions::default()
+        };
+        assert!(!prepare(&img, ImageFormat::Png, &always).color().has_alpha());
+        let never = AlphaOptions {
+            flatten: FlattenMode::Never,
+            ..AlphaOptions::default()
+        };
+        assert!(matches!(
+            prepare(&img, ImageFormat::Jpeg, &never),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn premultiply_scales_color_by_alpha() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            1,
+            1,
+            image::Rgba([200, 100, 50, 128]),
+        ));
+        assert_eq!(
+            premultiply(&img).to_rgba8().get_pixel(0, 0).0,
+            [100, 50, 25, 128]
+        );
+        let float = DynamicImage::ImageRgba32F(img.to_rgba32f());
+        let p = premultiply(&float).to_rgba32f().get_pixel(0, 0).0;
+        assert!((p[0] - 200.0 / 255.0 * 128.0 / 255.0).abs() < 1e-6);
+    }
+}
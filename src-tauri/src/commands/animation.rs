@@ -0,0 +1,624 @@
+use crate::commands::alpha;
+use crate::commands::export::{self, EncoderOptions, GifOptions, PngOptions};
+use crate::commands::image::{file_format, FileFormat};
+use crate::commands::quantize::{self, QuantizeOptions};
+use crate::commands::webp::{self, push_riff_chunk, push_u24, WebpOptions};
+use crate::error::AppError;
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::{WebPDecoder, WebPEncoder};
+use image::{AnimationDecoder, DynamicImage, ExtendedColorType, ImageFormat, RgbaImage};
+use serde::Serialize;
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+
+// ── Animated GIF / APNG / WebP ──────────────────────────────────────
+//
+// The decoders hand back frames already composited onto the full canvas, so
+// an animation is kept as a list of same-sized RGBA frames. Edits run on each
+// frame and the result is written as an APNG temp file, which the webview
+// plays and later edits decode again. Encoders write full frames with no
+// blending, which costs some size but can't go wrong on disposal.
+
+#[derive(Debug, Clone)]
+pub struct AnimationFrame {
+    pub image: RgbaImage,
+    pub delay_ms: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Animation {
+    pub frames: Vec<AnimationFrame>,
+    /// How many times the animation plays; 0 loops forever
+    pub plays: u32,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnimationInfo {
+    pub frame_count: u32,
+    pub plays: u32,
+    pub delays_ms: Vec<u32>,
+}
+
+/// Browsers clamp shorter GIF delays to 100 ms, so a zero delay is read as that.
+const DEFAULT_DELAY_MS: u32 = 100;
+
+type Reader = BufReader<std::fs::File>;
+
+fn read_bytes<const N: usize>(reader: &mut Reader) -> Option<[u8; N]> {
+    let mut buf = [0; N];
+    reader.read_exact(&mut buf).ok()?;
+    Some(buf)
+}
+
+fn skip(reader: &mut Reader, n: usize) -> Option<()> {
+    reader.seek_relative(n as i64).ok()
+}
+
+/// Walk GIF blocks, counting image descriptors and reading the NETSCAPE loop.
+fn scan_gif(reader: &mut Reader) -> Option<(u32, u32)> {
+    let skip_sub_blocks = |reader: &mut Reader| -> Option<()> {
+        loop {
+            let [len] = read_bytes(reader)?;
+            if len == 0 {
+                return Some(());
+            }
+            skip(reader, len as usize)?;
+        }
+    };
+    let color_table = |packed: u8| {
+        if packed & 0x80 != 0 {
+            3 << ((packed & 7) + 1)
+        } else {
+            0
+        }
+    };
+
+    let header: [u8; 13] = read_bytes(reader)?;
+    skip(reader, color_table(header[10]))?;
+    let (mut frames, mut plays) = (0, 1);
+    while let Some([block]) = read_bytes(reader) {
+        match block {
+            0x21 => {
+                let [label] = read_bytes(reader)?;
+                if label == 0xFF {
+                    let [len] = read_bytes(reader)?;
+                    let mut app = vec![0; len as usize];
+                    reader.read_exact(&mut app).ok()?;
+                    if app == b"NETSCAPE2.0" {
+                        let [len] = read_bytes(reader)?;
+                        let mut data = vec![0; len as usize];
+                        reader.read_exact(&mut data).ok()?;
+                        if let [1, lo, hi] = data[..] {
+                            // The extension counts repeats after the first play
+                            plays = match u16::from_le_bytes([lo, hi]) {
+                                0 => 0,
+                                n => n as u32 + 1,
+                            };
+                        }
+                        if len == 0 {
+                            continue;
+                        }
+                    }
+                }
+                skip_sub_blocks(reader)?;
+            }
+            0x2C => {
+                frames += 1;
+                let descriptor: [u8; 9] = read_bytes(reader)?;
+                // Local color table, then the LZW minimum code size
+                skip(reader, color_table(descriptor[8]) + 1)?;
+                skip_sub_blocks(reader)?;
+            }
+            _ => break,
+        }
+    }
+    Some((frames, plays))
+}
+
+/// `acTL` comes before the first `IDAT`; a PNG without it is a still.
+fn scan_png(reader: &mut Reader) -> Option<(u32, u32)> {
+    skip(reader, 8)?;
+    while let Some(header) = read_bytes::<8>(reader) {
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        match &header[4..] {
+            b"acTL" => {
+                let data: [u8; 8] = read_bytes(reader)?;
+                return Some((
+                    u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+                    u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+                ));
+            }
+            b"IDAT" | b"IEND" => break,
+            _ => skip(reader, len + 4)?,
+        }
+    }
+    Some((1, 1))
+}
+
+fn scan_webp(reader: &mut Reader) -> Option<(u32, u32)> {
+    let (mut frames, mut plays) = (0, 1);
+    skip(reader, 12)?;
+    while let Some(header) = read_bytes::<8>(reader) {
+        let mut len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        len += len % 2;
+        match &header[..4] {
+            b"ANIM" => {
+                let data: [u8; 6] = read_bytes(reader)?;
+                plays = u16::from_le_bytes([data[4], data[5]]) as u32;
+                len = len.saturating_sub(6);
+            }
+            b"ANMF" => frames += 1,
+            _ => {}
+        }
+        skip(reader, len)?;
+    }
+    Some(if frames == 0 { (1, 1) } else { (frames, plays) })
+}
+
+/// Frame count and plays read from the container, without decoding pixels.
+/// Leaves the reader positioned somewhere in the file.
+fn scan(reader: &mut Reader, format: ImageFormat) -> Option<(u32, u32)> {
+    match format {
+        ImageFormat::Gif => scan_gif(reader),
+        ImageFormat::Png => scan_png(reader),
+        ImageFormat::WebP => scan_webp(reader),
+        _ => Some((1, 1)),
+    }
+}
+
+/// The format of an animatable file and a reader over it, or `None`.
+fn open_animatable(path: &Path) -> Result<Option<(ImageFormat, Reader)>, AppError> {
+    Ok(match file_format(path)? {
+        FileFormat::Image(format @ (ImageFormat::Gif | ImageFormat::Png | ImageFormat::WebP)) => {
+            Some((format, BufReader::new(std::fs::File::open(path)?)))
+        }
+        _ => None,
+    })
+}
+
+/// Number of frames in the file; 1 for stills and formats without animation.
+pub fn frame_count(path: &Path) -> Result<u32, AppError> {
+    Ok(match open_animatable(path)? {
+        Some((format, mut reader)) => {
+            scan(&mut reader, format).map_or(1, |(frames, _)| frames.max(1))
+        }
+        None => 1,
+    })
+}
+
+/// Decode every frame of an animated file, or `None` for a single-frame image.
+pub fn decode(path: &Path) -> Result<Option<Animation>, AppError> {
+    let Some((format, mut reader)) = open_animatable(path)? else {
+        return Ok(None);
+    };
+    let plays = match scan(&mut reader, format) {
+        Some((frames, plays)) if frames > 1 => plays,
+        _ => return Ok(None),
+    };
+
+    reader.rewind()?;
+    let frames = match format {
+        ImageFormat::Gif => GifDecoder::new(reader)?.into_frames(),
+        ImageFormat::Png => PngDecoder::new(reader)?.apng()?.into_frames(),
+        _ => WebPDecoder::new(reader)?.into_frames(),
+    }
+    .collect_frames()?;
+
+    let frames = frames
+        .into_iter()
+        .map(|frame| {
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            let delay_ms = match numer / denom.max(1) {
+                0 => DEFAULT_DELAY_MS,
+                ms => ms,
+            };
+            AnimationFrame {
+                image: frame.into_buffer(),
+                delay_ms,
+            }
+        })
+        .collect();
+    Ok(Some(Animation { frames, plays }))
+}
+
+impl Animation {
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.frames.first().map_or((0, 0), |f| f.image.dimensions())
+    }
+
+    /// Apply `op` to every frame, keeping delays and plays.
+    pub fn map_frames(
+        self,
+        op: impl Fn(DynamicImage) -> Result<DynamicImage, AppError>,
+    ) -> Result<Animation, AppError> {
+        let frames = self
+            .frames
+            .into_iter()
+            .map(|frame| {
+                Ok(AnimationFrame {
+                    image: op(DynamicImage::ImageRgba8(frame.image))?.to_rgba8(),
+                    delay_ms: frame.delay_ms,
+                })
+            })
+            .collect::<Result<Vec<_>, AppError>>()?;
+        Ok(Animation {
+            frames,
+            plays: self.plays,
+        })
+    }
+}
+
+/// Each frame quantized on its own and written with a local palette, or
+/// left to the encoder's NeuQuant without quantize settings.
+fn encode_gif(animation: &Animation, options: &GifOptions) -> Result<Vec<u8>, AppError> {
+    let (width, height) = animation.dimensions();
+    let (Ok(width), Ok(height)) = (u16::try_from(width), u16::try_from(height)) else {
+        return Err(AppError::SaveFailed(
+            "GIF dimensions are limited to 65535 pixels".into(),
+        ));
+    };
+    let mut bytes = Vec::new();
+    {
+        let mut encoder =
+            gif::Encoder::new(&mut bytes, width, height, &[]).map_err(AppError::save_failed)?;
+        // Without the loop extension a GIF plays once
+        match animation.plays {
+            0 => encoder.set_repeat(gif::Repeat::Infinite),
+            1 => Ok(()),
+            n => encoder.set_repeat(gif::Repeat::Finite((n - 1).min(u16::MAX as u32) as u16)),
+        }
+        .map_err(AppError::save_failed)?;
+        let settings = options.quantize.as_ref().map(|settings| QuantizeOptions {
+            alpha_aware: false,
+            ..settings.clone()
+        });
+        for frame in &animation.frames {
+            let indexed = match &settings {
+                Some(settings) => Some(quantize::quantize(
+                    &DynamicImage::ImageRgba8(frame.image.clone()),
+                    settings,
+                )?),
+                None => None,
+            };
+            let mut gif_frame = match &indexed {
+                Some(indexed) => indexed.gif_frame()?,
+                None => {
+                    let mut rgba = frame.image.as_raw().clone();
+                    // Speed 10 quantizes several times faster than 1 at a barely visible cost
+                    gif::Frame::from_rgba_speed(width, height, &mut rgba, 10)
+                }
+            };
+            // GIF delays are in hundredths of a second
+            gif_frame.delay = (frame.delay_ms / 10).min(u16::MAX as u32) as u16;
+            // Clear to transparent after each frame, so none shows through the next
+            gif_frame.dispose = gif::DisposalMethod::Background;
+            encoder
+                .write_frame(&gif_frame)
+                .map_err(AppError::save_failed)?;
+        }
+    }
+    Ok(bytes)
+}
+
+/// All frames share one palette in an indexed APNG, so they are quantized
+/// together.
+fn encode_apng(animation: &Animation, options: &PngOptions) -> Result<Vec<u8>, AppError> {
+    let (width, height) = animation.dimensions();
+    let (compression, filter) = export::png_settings(options)?;
+    let indexed = match &options.quantize {
+        Some(settings) => {
+            let mut stacked =
+                Vec::with_capacity((width * height) as usize * 4 * animation.frames.len());
+            for frame in &animation.frames {
+                stacked.extend_from_slice(frame.image.as_raw());
+            }
+            let stacked =
+                RgbaImage::from_raw(width, height * animation.frames.len() as u32, stacked)
+                    .ok_or_else(|| AppError::General("Animation frames differ in size".into()))?;
+            Some(quantize::quantize(
+                &DynamicImage::ImageRgba8(stacked),
+                settings,
+            )?)
+        }
+        None => None,
+    };
+
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, width, height);
+    encoder.set_depth(png::BitDepth::Eight);
+    match &indexed {
+        Some(indexed) => {
+            encoder.set_color(png::ColorType::Indexed);
+            let (plte, trns) = indexed.png_palette();
+            encoder.set_palette(plte);
+            if !trns.is_empty() {
+                encoder.set_trns(trns);
+            }
+        }
+        None => encoder.set_color(png::ColorType::Rgba),
+    }
+    encoder.set_deflate_compression(compression);
+    encoder.set_filter(filter);
+    encoder
+        .set_animated(animation.frames.len() as u32, animation.plays)
+        .map_err(AppError::save_failed)?;
+    let mut writer = encoder.write_header().map_err(AppError::save_failed)?;
+    let frame_len = (width * height) as usize;
+    for (i, frame) in animation.frames.iter().enumerate() {
+        writer
+            .set_frame_delay(frame.delay_ms.min(u16::MAX as u32) as u16, 1000)
+            .map_err(AppError::save_failed)?;
+        let data = match &indexed {
+            Some(indexed) => &indexed.indices[i * frame_len..(i + 1) * frame_len],
+            None => frame.image.as_raw(),
+        };
+        writer
+            .write_image_data(data)
+            .map_err(AppError::save_failed)?;
+    }
+    writer.finish().map_err(AppError::save_failed)?;
+    Ok(bytes)
+}
+
+const WEBP_FLAG_ALPHA: u8 = 0x10;
+const WEBP_FLAG_ANIMATION: u8 = 0x02;
+/// ANMF flag: overwrite the canvas instead of alpha-blending onto it
+const WEBP_NO_BLEND: u8 = 0x02;
+
+/// The `ALPH` and `VP8 `/`VP8L` chunks of a still WebP, which make up the
+/// frame data of an `ANMF` chunk.
+fn webp_frame_chunks(still: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut pos = 12;
+    while let Some(header) = still.get(pos..pos + 8) {
+        let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        let end = (pos + 8 + len + len % 2).min(still.len());
+        if matches!(&header[..4], b"ALPH" | b"VP8 " | b"VP8L") {
+            out.extend_from_slice(&still[pos..end]);
+        }
+        pos = end;
+    }
+    out
+}
+
+/// Mux frames, each encoded as a still with the export's WebP settings, into
+/// an extended-format WebP, since the encoders only write stills.
+fn encode_webp(
+    animation: &Animation,
+    quality: u8,
+    options: &WebpOptions,
+) -> Result<Vec<u8>, AppError> {
+    let (width, height) = animation.dimensions();
+    let mut body = b"WEBP".to_vec();
+
+    let mut vp8x = vec![WEBP_FLAG_ALPHA | WEBP_FLAG_ANIMATION, 0, 0, 0];
+    push_u24(&mut vp8x, width - 1);
+    push_u24(&mut vp8x, height - 1);
+    push_riff_chunk(&mut body, b"VP8X", &vp8x);
+
+    let mut anim = vec![0; 4];
+    anim.extend_from_slice(&(animation.plays.min(u16::MAX as u32) as u16).to_le_bytes());
+    push_riff_chunk(&mut body, b"ANIM", &anim);
+
+    for frame in &animation.frames {
+        let still = if options.lossless {
+            let mut still = Vec::new();
+            WebPEncoder::new_lossless(&mut still)
+                .encode(
+                    frame.image.as_raw(),
+                    width,
+                    height,
+                    ExtendedColorType::Rgba8,
+                )
+                .map_err(AppError::save_failed)?;
+            still
+        } else {
+            webp::encode_lossy(
+                &DynamicImage::ImageRgba8(frame.image.clone()),
+                options.quality.unwrap_or(quality),
+            )?
+        };
+        // Frame offsets (0, 0), size, duration and flags, then the bitstream
+        let mut anmf = vec![0; 6];
+        push_u24(&mut anmf, width - 1);
+        push_u24(&mut anmf, height - 1);
+        push_u24(&mut anmf, frame.delay_ms.min(0xFF_FFFF));
+        anmf.push(WEBP_NO_BLEND);
+        anmf.extend_from_slice(&webp_frame_chunks(&still));
+        push_riff_chunk(&mut body, b"ANMF", &anmf);
+    }
+
+    let mut out = Vec::with_capacity(body.len() + 8);
+    push_riff_chunk(&mut out, b"RIFF", &body);
+    Ok(out)
+}
+
+/// Encode as an animated GIF, APNG or WebP with the format's settings from
+/// `options`; `quality` applies where those leave it unset.
+pub fn encode(
+    animation: &Animation,
+    format: ImageFormat,
+    quality: u8,
+    options: &EncoderOptions,
+) -> Result<Vec<u8>, AppError> {
+    if animation.frames.is_empty() {
+        return Err(AppError::General("Animation has no frames".into()));
+    }
+    // Frames follow the alpha settings a still export would
+    let flattened;
+    let animation = if alpha::flattens(format, &options.alpha) {
+        let frames = animation
+            .frames
+            .iter()
+            .map(|frame| {
+                let image = DynamicImage::ImageRgba8(frame.image.clone());
+                AnimationFrame {
+                    image: alpha::prepare(&image, format, &options.alpha).to_rgba8(),
+                    delay_ms: frame.delay_ms,
+                }
+            })
+            .collect();
+        flattened = Animation {
+            frames,
+            plays: animation.plays,
+        };
+        &flattened
+    } else {
+        animation
+    };
+    match format {
+        ImageFormat::Gif => encode_gif(animation, &options.gif),
+        ImageFormat::Png => encode_apng(animation, &options.png),
+        ImageFormat::WebP => encode_webp(animation, quality, &options.webp),
+        other => Err(AppError::UnsupportedFormat(format!(
+            "{} cannot hold an animation",
+            other.extensions_str()[0]
+        ))),
+    }
+}
+
+/// Write an animation as a temp APNG.
+pub fn save_temp_animation(animation: &Animation) -> Result<String, AppError> {
+    let path = std::env::temp_dir().join(format!("pixelforge_{}.png", uuid::Uuid::new_v4()));
+    std::fs::write(
+        &path,
+        encode(animation, ImageFormat::Png, 100, &EncoderOptions::default())?,
+    )
+    .map_err(AppError::save_failed)?;
+    Ok(path.to_string_lossy().into_owned())
+}
+
+#[tauri::command]
+pub fn get_animation_info(path: String) -> Result<AnimationInfo, AppError> {
+    Ok(match decode(Path::new(&path))? {
+        Some(animation) => AnimationInfo {
+            frame_count: animation.frames.len() as u32,
+            plays: animation.plays,
+            delays_ms: animation.frames.iter().map(|f| f.delay_ms).collect(),
+        },
+        None => AnimationInfo {
+            frame_count: 1,
+            plays: 1,
+            delays_ms: Vec::new(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(plays: u32) -> Animation {
+        let frames = [[255, 0, 0, 255], [0, 0, 255, 128], [0, 255, 0, 0]]
+            .into_iter()
+            .enumerate()
+            .map(|(i, color)| AnimationFrame {
+                image: RgbaImage::from_pixel(6, 4, image::Rgba(color)),
+                delay_ms: 40 * (i as u32 + 1),
+            })
+            .collect();
+        Animation { frames, plays }
+    }
+
+    fn write_temp(bytes: &[u8], ext: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "pixelforge_anim_test_{}.{ext}",
+            uuid::Uuid::new_v4()
+        ));
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn animations_roundtrip_through_each_format() {
+        for (format, ext, plays) in [
+            (ImageFormat::Png, "png", 3),
+            (ImageFormat::WebP, "webp", 0),
+            (ImageFormat::Gif, "gif", 2),
+        ] {
+            let original = sample(plays);
+            let bytes = encode(&original, format, 90, &EncoderOptions::default()).unwrap();
+            let path = write_temp(&bytes, ext);
+            assert_eq!(frame_count(&path).unwrap(), 3, "{ext}");
+
+            let decoded = decode(&path).unwrap().expect(ext);
+            assert_eq!(decoded.plays, plays, "{ext}");
+            assert_eq!(decoded.dimensions(), (6, 4));
+            let delays: Vec<u32> = decoded.frames.iter().map(|f| f.delay_ms).collect();
+            assert_eq!(delays, [40, 80, 120], "{ext}");
+            let px = decoded.frames[0].image.get_pixel(2, 2).0;
+            assert!(px[0] > 240 && px[1] < 16 && px[3] == 255, "{ext}: {px:?}");
+            // GIF quantizes and has 1-bit alpha, the others are lossless
+            if format != ImageFormat::Gif {
+                assert_eq!(decoded.frames[1].image.get_pixel(2, 2).0, [0, 0, 255, 128]);
+            }
+            assert_eq!(decoded.frames[2].image.get_pixel(0, 0).0[3], 0, "{ext}");
+            std::fs::remove_file(path).ok();
+        }
+    }
+
+    #[test]
+    fn animations_follow_encoder_options() {
+        let quantize = Some(QuantizeOptions {
+            colors: 4,
+            ..QuantizeOptions::default()
+        });
+        let options = EncoderOptions {
+            png: PngOptions {
+                quantize: quantize.clone(),
+                ..PngOptions::default()
+            },
+            gif: GifOptions { quantize },
+            webp: WebpOptions {
+                lossless: false,
+                quality: Some(80),
+            },
+            ..EncoderOptions::default()
+        };
+        for (format, ext) in [
+            (ImageFormat::Png, "png"),
+            (ImageFormat::Gif, "gif"),
+            (ImageFormat::WebP, "webp"),
+        ] {
+            let bytes = encode(&sample(0), format, 90, &options).unwrap();
+            let path = write_temp(&bytes, ext);
+            let decoded = decode(&path).unwrap().expect(ext);
+            assert_eq!(decoded.frames.len(), 3, "{ext}");
+            let px = decoded.frames[0].image.get_pixel(2, 2).0;
+            assert!(px[0] > 200 && px[1] < 40 && px[3] == 255, "{ext}: {px:?}");
+            std::fs::remove_file(path).ok();
+        }
+
+        let apng = encode(&sample(0), ImageFormat::Png, 90, &options).unwrap();
+        let info = png::Decoder::new(std::io::Cursor::new(&apng))
+            .read_info()
+            .unwrap();
+        assert_eq!(info.info().color_type, png::ColorType::Indexed);
+        // Lossy frames carry a VP8 bitstream and keep alpha in ALPH
+        let webp = encode(&sample(0), ImageFormat::WebP, 90, &options).unwrap();
+        assert!(webp.windows(4).any(|w| w == b"VP8 "));
+        assert!(webp.windows(4).any(|w| w == b"ALPH"));
+
+        // Flattening applies to every frame, as for stills
+        let mut flatten = EncoderOptions::default();
+        flatten.alpha.flatten = alpha::FlattenMode::Always;
+        let apng = encode(&sample(0), ImageFormat::Png, 90, &flatten).unwrap();
+        let path = write_temp(&apng, "png");
+        let decoded = decode(&path).unwrap().unwrap();
+        assert_eq!(decoded.frames[2].image.get_pixel(0, 0).0, [255; 4]);
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn stills_are_not_animations() {
+        let path = write_temp(&[], "png");
+        image::RgbaImage::new(3, 3)
+            .save_with_format(&path, ImageFormat::Png)
+            .unwrap();
+        assert_eq!(frame_count(&path).unwrap(), 1);
+        assert!(decode(&path).unwrap().is_none());
+        std::fs::remove_file(path).ok();
+    }
+}
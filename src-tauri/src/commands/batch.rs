@@ -1,8 +1,12 @@
-use crate::commands::{export, operations};
+use crate::commands::metadata::{self, MetadataMode, ScrubOptions};
+use crate::commands::operations::ResizeOptions;
+use crate::commands::responsive::{self, ResponsiveImage, ResponsiveOptions};
+use crate::commands::{export, operations, retarget};
 use crate::error::AppError;
+use crate::models::session::OnnxState;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
-use tauri::AppHandle;
+use tauri::{AppHandle, State};
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -12,8 +16,47 @@ pub struct BatchResizeRequest {
     pub width: u32,
     pub height: u32,
     pub filter: String,
+    #[serde(default)]
+    pub resize_options: ResizeOptions,
+    /// Crop each input to its most salient `width:height` window before resizing
+    #[serde(default)]
+    pub smart_crop: bool,
     pub format: String,
     pub quality: u8,
+    #[serde(default)]
+    pub encoder: export::EncoderOptions,
+    /// Fit each output under a byte size instead of using `quality`
+    #[serde(default)]
+    pub target_size: Option<export::TargetSize>,
+    /// What happens to each input's EXIF/XMP/IPTC/ICC in its output
+    #[serde(default)]
+    pub metadata: MetadataMode,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResponsiveSetRequest {
+    pub input_paths: Vec<String>,
+    pub output_dir: String,
+    #[serde(default)]
+    pub options: ResponsiveOptions,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ResponsiveManifest<'a> {
+    images: &'a [ResponsiveImage],
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchScrubRequest {
+    pub input_paths: Vec<String>,
+    /// Where scrubbed copies go; inputs are scrubbed in place when absent
+    #[serde(default)]
+    pub output_dir: Option<String>,
+    #[serde(default)]
+    pub options: ScrubOptions,
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -24,6 +67,9 @@ pub struct BatchResult {
     pub failed: u32,
     pub outputs: Vec<String>,
     pub errors: Vec<String>,
+    /// Size and quality reached per output of a target-size batch
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sized: Vec<export::SizedExport>,
 }
 
 #[derive(Debug, Serialize)]
@@ -57,20 +103,23 @@ fn build_output_path(output_dir: &Path, input_path: &str, ext: &str) -> PathBuf
 }
 
 fn build_unique_output_path(output_dir: &Path, input_path: &str, ext: &str) -> PathBuf {
-    let base = build_output_path(output_dir, input_path, ext);
-    if !base.exists() {
-        return base;
-    }
-
     let source = Path::new(input_path);
     let stem = source
         .file_stem()
         .and_then(|s| s.to_str())
         .unwrap_or("image");
+    unique_path(output_dir, &format!("{}_resized", stem), ext)
+}
+
+pub(crate) fn unique_path(output_dir: &Path, stem: &str, ext: &str) -> PathBuf {
+    let base = output_dir.join(format!("{}.{}", stem, ext));
+    if !base.exists() {
+        return base;
+    }
 
     let mut index = 2_u32;
     loop {
-        let candidate = output_dir.join(format!("{}_resized_{}.{}", stem, index, ext));
+        let candidate = output_dir.join(format!("{}_{}.{}", stem, index, ext));
         if !candidate.exists() {
             return candidate;
         }
@@ -78,70 +127,44 @@ fn build_unique_output_path(output_dir: &Path, input_path: &str, ext: &str) -> P
     }
 }
 
-#[tauri::command]
-pub fn run_batch_resize_export(
-    app: AppHandle,
-    request: BatchResizeRequest,
-) -> Result<BatchResult, AppError> {
+/// Reports `batch-progress` events to the frontend.
+fn emit_progress(app: &AppHandle) -> impl FnMut(BatchProgressEvent) + '_ {
     use tauri::Emitter;
 
-    if request.input_paths.is_empty() {
-        return Err(AppError::General("No input files selected".into()));
-    }
-
-    if request.width == 0 || request.height == 0 {
-        return Err(AppError::General(
-            "Width and height must be greater than 0".into(),
-        ));
-    }
-
-    let output_dir = Path::new(&request.output_dir);
-    if !output_dir.exists() {
-        std::fs::create_dir_all(output_dir)?;
+    move |event| {
+        app.emit("batch-progress", event).ok();
     }
+}
 
-    let ext = format_extension(&request.format)?;
-
-    let total = request.input_paths.len() as u32;
+/// Run `process` over every input, reporting progress before each file.
+/// Failures are collected, not fatal.
+fn run_batch(
+    inputs: &[String],
+    progress: &mut dyn FnMut(BatchProgressEvent),
+    mut process: impl FnMut(&str) -> Result<String, AppError>,
+) -> BatchResult {
+    let total = inputs.len() as u32;
     let mut processed = 0_u32;
     let mut failed = 0_u32;
     let mut outputs = Vec::new();
     let mut errors = Vec::new();
 
-    for (index, input) in request.input_paths.iter().enumerate() {
+    for (index, input) in inputs.iter().enumerate() {
         let current = index as u32 + 1;
         let percent = ((current as f64 / total as f64) * 100.0).round() as u32;
 
-        app.emit(
-            "batch-progress",
-            BatchProgressEvent {
-                current,
-                total,
-                percent,
-                file: input.clone(),
-            },
-        )
-        .ok();
-
-        let result: Result<String, AppError> = (|| {
-            if !Path::new(input).exists() {
-                return Err(AppError::FileRead("Input file does not exist".into()));
-            }
+        progress(BatchProgressEvent {
+            current,
+            total,
+            percent,
+            file: input.clone(),
+        });
 
-            let resized_path =
-                operations::resize_image(input, request.width, request.height, &request.filter)?;
-
-            let output_path = build_unique_output_path(output_dir, input, ext);
-            export::save_image(
-                resized_path.clone(),
-                output_path.to_string_lossy().into_owned(),
-                request.format.clone(),
-                request.quality,
-            )?;
-
-            std::fs::remove_file(&resized_path).ok();
-            Ok(output_path.to_string_lossy().into_owned())
-        })();
+        let result = if Path::new(input).exists() {
+            process(input)
+        } else {
+            Err(AppError::FileRead("Input file does not exist".into()))
+        };
 
         match result {
             Ok(path) => {
@@ -155,16 +178,183 @@ pub fn run_batch_resize_export(
         }
     }
 
-    let summary = BatchResult {
+    BatchResult {
         total,
         processed,
         failed,
         outputs,
         errors,
-    };
+        sized: Vec::new(),
+    }
+}
+
+/// Emit `batch-complete` with the summary and hand it back.
+fn complete(app: &AppHandle, summary: BatchResult) -> BatchResult {
+    use tauri::Emitter;
 
     app.emit("batch-complete", summary.clone()).ok();
+    summary
+}
+
+#[tauri::command]
+pub fn run_batch_resize_export(
+    app: AppHandle,
+    state: State<'_, OnnxState>,
+    request: BatchResizeRequest,
+) -> Result<BatchResult, AppError> {
+    if request.input_paths.is_empty() {
+        return Err(AppError::General("No input files selected".into()));
+    }
+
+    operations::validate_resize_target(request.width, request.height, &request.resize_options)?;
+    operations::parse_filter(&request.filter)?;
+
+    let output_dir = Path::new(&request.output_dir);
+    if !output_dir.exists() {
+        std::fs::create_dir_all(output_dir)?;
+    }
+
+    let ext = format_extension(&request.format)?;
+
+    let mut sized = Vec::new();
+    let mut summary = run_batch(&request.input_paths, &mut emit_progress(&app), |input| {
+        let cropped_path = if request.smart_crop {
+            Some(retarget::smart_crop_image(
+                &app,
+                &state,
+                input,
+                request.width,
+                request.height,
+                &request.resize_options,
+            )?)
+        } else {
+            None
+        };
+
+        let resized_path = operations::resize_image(
+            cropped_path.as_deref().unwrap_or(input),
+            request.width,
+            request.height,
+            &request.filter,
+            &request.resize_options,
+        )?;
+        if let Some(cropped) = &cropped_path {
+            std::fs::remove_file(cropped).ok();
+        }
+
+        let output_path = build_unique_output_path(output_dir, input, ext);
+        let options = export::ExportOptions {
+            metadata_source: Some(input.to_string()),
+            metadata: request.metadata,
+            encoder: request.encoder.clone(),
+            ..export::ExportOptions::default()
+        };
+        let saved = match &request.target_size {
+            Some(target) => export::save_image_to_size(
+                resized_path.clone(),
+                output_path.to_string_lossy().into_owned(),
+                request.format.clone(),
+                target.clone(),
+                Some(options),
+            )
+            .map(|result| sized.push(result)),
+            None => export::save_image(
+                resized_path.clone(),
+                output_path.to_string_lossy().into_owned(),
+                request.format.clone(),
+                request.quality,
+                Some(options),
+            ),
+        };
+
+        std::fs::remove_file(&resized_path).ok();
+        saved?;
+        Ok(output_path.to_string_lossy().into_owned())
+    });
+    summary.sized = sized;
+    Ok(complete(&app, summary))
+}
+
+/// Render every input as a responsive set. Outputs are the `<picture>`
+/// snippet files; `manifest.json` in `output_dir` lists every variant with
+/// its dimensions and byte size.
+#[tauri::command]
+pub fn run_batch_responsive(
+    app: AppHandle,
+    request: ResponsiveSetRequest,
+) -> Result<BatchResult, AppError> {
+    let summary = responsive_batch(&request, &mut emit_progress(&app))?;
+    Ok(complete(&app, summary))
+}
+
+fn responsive_batch(
+    request: &ResponsiveSetRequest,
+    progress: &mut dyn FnMut(BatchProgressEvent),
+) -> Result<BatchResult, AppError> {
+    if request.input_paths.is_empty() {
+        return Err(AppError::General("No input files selected".into()));
+    }
+
+    let output_dir = Path::new(&request.output_dir);
+    if !output_dir.exists() {
+        std::fs::create_dir_all(output_dir)?;
+    }
+
+    let mut images = Vec::new();
+    let summary = run_batch(&request.input_paths, progress, |input| {
+        let set = responsive::render_set(input, output_dir, &request.options)?;
+        let snippet = set.snippet.clone();
+        images.push(set);
+        Ok(snippet)
+    });
+
+    let manifest = serde_json::to_string_pretty(&ResponsiveManifest { images: &images })
+        .map_err(|e| AppError::SaveFailed(e.to_string()))?;
+    std::fs::write(output_dir.join("manifest.json"), manifest)
+        .map_err(|e| AppError::SaveFailed(e.to_string()))?;
+    Ok(summary)
+}
 
+#[tauri::command]
+pub fn run_batch_scrub(
+    app: AppHandle,
+    request: BatchScrubRequest,
+) -> Result<BatchResult, AppError> {
+    let summary = scrub_batch(&request, &mut emit_progress(&app))?;
+    Ok(complete(&app, summary))
+}
+
+fn scrub_batch(
+    request: &BatchScrubRequest,
+    progress: &mut dyn FnMut(BatchProgressEvent),
+) -> Result<BatchResult, AppError> {
+    if request.input_paths.is_empty() {
+        return Err(AppError::General("No input files selected".into()));
+    }
+
+    let output_dir = request.output_dir.as_deref().map(Path::new);
+    if let Some(dir) = output_dir {
+        if !dir.exists() {
+            std::fs::create_dir_all(dir)?;
+        }
+    }
+
+    let summary = run_batch(&request.input_paths, progress, |input| {
+        let source = Path::new(input);
+        let dest = match output_dir {
+            Some(dir) => {
+                let stem = source
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or("image");
+                let ext = source.extension().and_then(|e| e.to_str()).unwrap_or("");
+                unique_path(dir, stem, ext)
+            }
+            None => source.to_path_buf(),
+        };
+        metadata::scrub_file(source, &dest, &request.options)?;
+        Ok(dest.to_string_lossy().into_owned())
+    });
     Ok(summary)
 }
 
@@ -199,4 +389,115 @@ mod tests {
 
         std::fs::remove_dir_all(&dir).ok();
     }
+
+    fn temp_dir() -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("pixelforge_batch_test_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).expect("create temp dir");
+        dir
+    }
+
+    #[test]
+    fn scrub_batch_writes_clean_copies() {
+        use exif::{Field, In, Tag, Value};
+        let mut writer = exif::experimental::Writer::new();
+        let fields = [
+            (Tag::Model, "EOS R6"),
+            (Tag::Artist, "Ada"),
+            (Tag::GPSLatitudeRef, "N"),
+        ]
+        .map(|(tag, text)| Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value: Value::Ascii(vec![text.as_bytes().to_vec()]),
+        });
+        for field in &fields {
+            writer.push_field(field);
+        }
+        let mut blob = std::io::Cursor::new(Vec::new());
+        writer.write(&mut blob, false).expect("write exif");
+        let blob = blob.into_inner();
+
+        let dir = temp_dir();
+        let img = image::DynamicImage::ImageRgb8(image::RgbImage::new(8, 8));
+        let jpeg = dir.join("photo.jpg");
+        let mut file = std::fs::File::create(&jpeg).expect("create jpeg");
+        let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut file, 90);
+        image::ImageEncoder::set_exif_metadata(&mut encoder, blob.clone()).expect("jpeg exif");
+        img.write_with_encoder(encoder).expect("write jpeg");
+        let png = dir.join("photo.png");
+        let mut file = std::fs::File::create(&png).expect("create png");
+        let mut encoder = image::codecs::png::PngEncoder::new(&mut file);
+        image::ImageEncoder::set_exif_metadata(&mut encoder, blob).expect("png exif");
+        img.write_with_encoder(encoder).expect("write png");
+
+        let request = BatchScrubRequest {
+            input_paths: [&jpeg, &png]
+                .map(|p| p.to_string_lossy().into_owned())
+                .to_vec(),
+            output_dir: Some(dir.join("out").to_string_lossy().into_owned()),
+            options: ScrubOptions::default(),
+        };
+        let mut reported = 0;
+        let summary = scrub_batch(&request, &mut |_| reported += 1).expect("scrub batch");
+        assert_eq!((summary.processed, summary.failed, reported), (2, 0, 2));
+        for output in &summary.outputs {
+            assert!(output.contains("out"), "{output}");
+            let exif = metadata::read_exif(Path::new(output)).expect("exif kept");
+            assert!(
+                exif.get_field(Tag::Artist, In::PRIMARY).is_none(),
+                "{output}"
+            );
+            assert!(exif.get_field(Tag::GPSLatitudeRef, In::PRIMARY).is_none());
+            assert!(exif.get_field(Tag::Model, In::PRIMARY).is_some());
+        }
+        // The inputs are left as they were
+        let exif = metadata::read_exif(&jpeg).expect("input exif");
+        assert!(exif.get_field(Tag::Artist, In::PRIMARY).is_some());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn responsive_batch_writes_variants_and_manifest() {
+        let dir = temp_dir();
+        let input = dir.join("hero.png");
+        image::RgbImage::from_fn(100, 60, |x, y| image::Rgb([x as u8, y as u8, 90]))
+            .save(&input)
+            .expect("write input");
+        let out = dir.join("out");
+        let request = ResponsiveSetRequest {
+            input_paths: vec![input.to_string_lossy().into_owned()],
+            output_dir: out.to_string_lossy().into_owned(),
+            options: ResponsiveOptions {
+                widths: vec![40, 80, 200],
+                formats: vec!["webp".into(), "png".into()],
+                ..ResponsiveOptions::default()
+            },
+        };
+        let summary = responsive_batch(&request, &mut |_| {}).expect("responsive batch");
+        assert_eq!((summary.processed, summary.failed), (1, 0));
+        assert!(summary.outputs[0].ends_with(".html"));
+
+        let manifest: serde_json::Value = serde_json::from_slice(
+            &std::fs::read(out.join("manifest.json")).expect("manifest written"),
+        )
+        .expect("manifest is JSON");
+        let image = &manifest["images"][0];
+        assert_eq!(
+            (image["width"].as_u64(), image["height"].as_u64()),
+            (Some(100), Some(60))
+        );
+        let variants = image["variants"].as_array().expect("variants");
+        // 200 is wider than the source, so two widths in two formats
+        assert_eq!(variants.len(), 4);
+        for variant in variants {
+            let file = out.join(variant["file"].as_str().expect("file name"));
+            let bytes = std::fs::metadata(&file).expect("variant written").len();
+            assert_eq!(variant["bytes"].as_u64(), Some(bytes));
+            let width = variant["width"].as_u64().expect("width") as u32;
+            assert!([40, 80].contains(&width));
+            assert_eq!(image::open(&file).expect("variant decodes").width(), width);
+        }
+        std::fs::remove_dir_all(&dir).ok();
+    }
 }
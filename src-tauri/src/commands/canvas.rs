@@ -0,0 +1,438 @@
+use crate::commands::{large, operations};
+use crate::error::AppError;
+use image::{DynamicImage, RgbaImage};
+use serde::Deserialize;
+
+// ── Canvas size and padding ─────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    #[default]
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// Horizontal and vertical placement as 0 (start), 1 (middle) or 2 (end).
+    fn weights(self) -> (i64, i64) {
+        match self {
+            Anchor::TopLeft => (0, 0),
+            Anchor::Top => (1, 0),
+            Anchor::TopRight => (2, 0),
+            Anchor::Left => (0, 1),
+            Anchor::Center => (1, 1),
+            Anchor::Right => (2, 1),
+            Anchor::BottomLeft => (0, 2),
+            Anchor::Bottom => (1, 2),
+            Anchor::BottomRight => (2, 2),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CanvasFill {
+    /// Solid `color`
+    #[default]
+    Color,
+    Transparent,
+    /// Repeat the outermost row/column
+    Edge,
+    /// Reflect the image across its borders
+    Mirror,
+    /// A blurred copy of the image scaled to cover the canvas
+    Blur,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CanvasOptions {
+    pub anchor: Anchor,
+    pub fill: CanvasFill,
+    pub color: [u8; 4],
+    pub blur_sigma: f32,
+}
+
+impl Default for CanvasOptions {
+    fn default() -> Self {
+        Self {
+            anchor: Anchor::Center,
+            fill: CanvasFill::Color,
+            color: [255, 255, 255, 255],
+            blur_sigma: 20.0,
+        }
+    }
+}
+
+/// Index into `0..n` reflecting at the borders (…2 1 0 | 0 1 2 … n-1 | n-1 n-2…).
+fn reflect(v: i64, n: i64) -> i64 {
+    let m = v.rem_euclid(2 * n);
+    if m < n {
+        m
+    } else {
+        2 * n - 1 - m
+    }
+}
+
+/// Place `img` at (`left`, `top`) on a `width` x `height` canvas. Negative
+/// offsets or a smaller canvas crop the image.
+pub fn extend_canvas(
+    img: &DynamicImage,
+    left: i64,
+    top: i64,
+    width: u32,
+    height: u32,
+    options: &CanvasOptions,
+) -> Result<DynamicImage, AppError> {
+    if width == 0 || height == 0 {
+        return Err(AppError::General(
+            "Width and height must be greater than 0".into(),
+        ));
+    }
+    large::check_budget(
+        u64::from(width) * u64::from(height) * 4,
+        &format!("A {width}×{height} canvas"),
+    )?;
+    let src = img.to_rgba8();
+    let (sw, sh) = (src.width() as i64, src.height() as i64);
+
+    let backdrop = if options.fill == CanvasFill::Blur {
+        let cover = operations::resize_dynamic(
+            img,
+            width,
+            height,
+            "bilinear",
+            &operations::ResizeOptions {
+                mode: operations::ResizeMode::Fill,
+                ..operations::ResizeOptions::default()
+            },
+        )?;
+        Some(operations::blur_linear(&cover, options.blur_sigma.clamp(0.1, 100.0)).to_rgba8())
+    } else {
+        None
+    };
+
+    let out = RgbaImage::from_fn(width, height, |x, y| {
+        let sx = x as i64 - left;
+        let sy = y as i64 - top;
+        if sx >= 0 && sy >= 0 && sx < sw && sy < sh {
+            return *src.get_pixel(sx as u32, sy as u32);
+        }
+        match options.fill {
+            CanvasFill::Color => image::Rgba(options.color),
+            CanvasFill::Transparent => image::Rgba([0, 0, 0, 0]),
+            CanvasFill::Edge => {
+                *src.get_pixel(sx.clamp(0, sw - 1) as u32, sy.clamp(0, sh - 1) as u32)
+            }
+            CanvasFill::Mirror => *src.get_pixel(reflect(sx, sw) as u32, reflect(sy, sh) as u32),
+            CanvasFill::Blur => match &backdrop {
+                Some(b) => *b.get_pixel(x, y),
+                None => image::Rgba(options.color),
+            },
+        }
+    });
+
+    let keeps_alpha = img.color().has_alpha()
+        || options.fill == CanvasFill::Transparent
+        || (options.fill == CanvasFill::Color && options.color[3] < 255);
+    if keeps_alpha {
+        Ok(DynamicImage::ImageRgba8(out))
+    } else {
+        Ok(DynamicImage::ImageRgb8(
+            DynamicImage::ImageRgba8(out).to_rgb8(),
+        ))
+    }
+}
+
+/// Resize the canvas to `width` x `height`, positioning the image by `anchor`.
+pub fn canvas_size(
+    img: &DynamicImage,
+    width: u32,
+    height: u32,
+    options: &CanvasOptions,
+) -> Result<DynamicImage, AppError> {
+    let (ax, ay) = options.anchor.weights();
+    let left = (width as i64 - img.width() as i64) * ax / 2;
+    let top = (height as i64 - img.height() as i64) * ay / 2;
+    extend_canvas(img, left, top, width, height, options)
+}
+
+#[tauri::command]
+pub fn apply_canvas_size(
+    path: String,
+    width: u32,
+    height: u32,
+    options: Option<CanvasOptions>,
+) -> Result<String, AppError> {
+    let img = operations::open_image(&path)?;
+    let result = canvas_size(&img, width, height, &options.unwrap_or_default())?;
+    operations::save_temp_image(&result)
+}
+
+#[tauri::command]
+pub fn apply_padding(
+    path: String,
+    top: u32,
+    right: u32,
+    bottom: u32,
+    left: u32,
+    options: Option<CanvasOptions>,
+) -> Result<String, AppError> {
+    let img = operations::open_image(&path)?;
+    let too_large = || AppError::General("Padded canvas is too large".into());
+    let width = img
+        .width()
+        .checked_add(left)
+        .and_then(|w| w.checked_add(right))
+        .ok_or_else(too_large)?;
+    let height = img
+        .height()
+        .checked_add(top)
+        .and_then(|h| h.checked_add(bottom))
+        .ok_or_else(too_large)?;
+    let result = extend_canvas(
+        &img,
+        left as i64,
+        top as i64,
+        width,
+        height,
+        &options.unwrap_or_default(),
+    )?;
+    operations::save_temp_image(&result)
+}
+
+// ── Auto-trim ───────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TrimReference {
+    /// Transparent if the top-left pixel is, otherwise its color
+    #[default]
+    Auto,
+    Transparent,
+    TopLeft,
+    BottomRight,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TrimOptions {
+    pub reference: TrimReference,
+    /// Maximum per-channel difference (or alpha, when trimming transparency)
+    /// still treated as border
+    pub tolerance: u8,
+    /// Margin of original pixels kept around the content, clamped to the image
+    pub padding: u32,
+}
+
+impl Default for TrimOptions {
+    fn default() -> Self {
+        Self {
+            reference: TrimReference::Auto,
+            tolerance: 8,
+            padding: 0,
+        }
+    }
+}
+
+/// Bounding box `(x, y, width, height)` of everything that is not border, or
+/// `None` when the whole image matches the border.
+pub fn trim_bounds(img: &DynamicImage, options: &TrimOptions) -> Option<(u32, u32, u32, u32)> {
+    let src = img.to_rgba8();
+    let (w, h) = src.dimensions();
+    if w == 0 || h == 0 {
+        return None;
+    }
+    let tol = options.tolerance;
+    let corner = match options.reference {
+        TrimReference::BottomRight => *src.get_pixel(w - 1, h - 1),
+        _ => *src.get_pixel(0, 0),
+    };
+    let transparent = match options.reference {
+        TrimReference::Transparent => true,
+        TrimReference::Auto => corner[3] <= tol,
+        _ => false,
+    };
+    let is_border = |p: &image::Rgba<u8>| {
+        if transparent {
+            p[3] <= tol
+        } else {
+            (0..4).all(|c| p[c].abs_diff(corner[c]) <= tol)
+        }
+    };
+
+    let (mut x0, mut y0, mut x1, mut y1) = (w, h, 0, 0);
+    for (x, y, p) in src.enumerate_pixels() {
+        if !is_border(p) {
+            x0 = x0.min(x);
+            y0 = y0.min(y);
+            x1 = x1.max(x);
+            y1 = y1.max(y);
+        }
+    }
+    if x0 > x1 {
+        return None;
+    }
+    let pad = options.padding;
+    let x0 = x0.saturating_sub(pad);
+    let y0 = y0.saturating_sub(pad);
+    let x1 = x1.saturating_add(pad).min(w - 1);
+    let y1 = y1.saturating_add(pad).min(h - 1);
+    Some((x0, y0, x1 - x0 + 1, y1 - y0 + 1))
+}
+
+pub fn trim(img: &DynamicImage, options: &TrimOptions) -> Result<DynamicImage, AppError> {
+    let (x, y, w, h) = trim_bounds(img, options)
+        .ok_or_else(|| AppError::General("Image has no content to trim".into()))?;
+    Ok(img.crop_imm(x, y, w, h))
+}
+
+#[tauri::command]
+pub fn apply_trim(path: String, options: Option<TrimOptions>) -> Result<String, AppError> {
+    let img = operations::open_image(&path)?;
+    let result = trim(&img, &options.unwrap_or_default())?;
+    operations::save_temp_image(&result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ramp(w: u32, h: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(image::RgbImage::from_fn(w, h, |x, y| {
+            image::Rgb([(x * 10) as u8, (y * 10) as u8, 0])
+        }))
+    }
+
+    fn options(anchor: Anchor, fill: CanvasFill) -> CanvasOptions {
+        CanvasOptions {
+            anchor,
+            fill,
+            ..CanvasOptions::default()
+        }
+    }
+
+    #[test]
+    fn grow_centered_with_color() {
+        let img = ramp(4, 4);
+        let out = canvas_size(&img, 8, 6, &options(Anchor::Center, CanvasFill::Color)).unwrap();
+        let out = out.to_rgb8();
+        assert_eq!(out.dimensions(), (8, 6));
+        assert_eq!(out.get_pixel(0, 0), &image::Rgb([255, 255, 255]));
+        assert_eq!(out.get_pixel(2, 1), &image::Rgb([0, 0, 0]));
+        assert_eq!(out.get_pixel(5, 4), &image::Rgb([30, 30, 0]));
+    }
+
+    #[test]
+    fn anchor_bottom_right_and_shrink() {
+        let img = ramp(4, 4);
+        let grown = canvas_size(&img, 6, 6, &options(Anchor::BottomRight, CanvasFill::Color))
+            .unwrap()
+            .to_rgb8();
+        assert_eq!(grown.get_pixel(2, 2), &image::Rgb([0, 0, 0]));
+        let shrunk = canvas_size(&img, 2, 2, &options(Anchor::BottomRight, CanvasFill::Color))
+            .unwrap()
+            .to_rgb8();
+        assert_eq!(shrunk.get_pixel(0, 0), &image::Rgb([20, 20, 0]));
+    }
+
+    #[test]
+    fn edge_and_mirror_fills() {
+        let img = ramp(4, 1);
+        let edge = extend_canvas(&img, 2, 0, 8, 1, &options(Anchor::Center, CanvasFill::Edge))
+            .unwrap()
+            .to_rgb8();
+        assert_eq!(edge.get_pixel(0, 0)[0], 0);
+        assert_eq!(edge.get_pixel(7, 0)[0], 30);
+        let mirror = extend_canvas(
+            &img,
+            2,
+            0,
+            8,
+            1,
+            &options(Anchor::Center, CanvasFill::Mirror),
+        )
+        .unwrap()
+        .to_rgb8();
+        let row: Vec<u8> = (0..8).map(|x| mirror.get_pixel(x, 0)[0]).collect();
+        assert_eq!(row, vec![10, 0, 0, 10, 20, 30, 30, 20]);
+    }
+
+    #[test]
+    fn transparent_and_blur_fills() {
+        let img = ramp(10, 10);
+        let clear = canvas_size(
+            &img,
+            20,
+            10,
+            &options(Anchor::Left, CanvasFill::Transparent),
+        )
+        .unwrap();
+        assert_eq!(clear.color(), image::ColorType::Rgba8);
+        assert_eq!(clear.to_rgba8().get_pixel(15, 5)[3], 0);
+        let blur = canvas_size(&img, 20, 10, &options(Anchor::Center, CanvasFill::Blur)).unwrap();
+        assert_eq!(blur.color(), image::ColorType::Rgb8);
+        assert_ne!(blur.to_rgb8().get_pixel(0, 5), &image::Rgb([255, 255, 255]));
+    }
+
+    #[test]
+    fn padding_command_adds_margins() {
+        let path = operations::save_temp_image(&ramp(10, 10)).unwrap();
+        let result = apply_padding(path.clone(), 1, 2, 3, 4, None).unwrap();
+        let out = image::open(&result).unwrap();
+        assert_eq!((out.width(), out.height()), (16, 14));
+        assert_eq!(out.to_rgb8().get_pixel(4, 1), &image::Rgb([0, 0, 0]));
+        assert!(apply_padding(path.clone(), 0, u32::MAX, 0, 1, None).is_err());
+        assert!(apply_padding(path.clone(), 100_000, 100_000, 100_000, 100_000, None).is_err());
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_file(&result).ok();
+    }
+
+    #[test]
+    fn trim_transparent_margins_with_padding() {
+        let mut img = RgbaImage::new(20, 10);
+        for y in 3..6 {
+            for x in 5..9 {
+                img.put_pixel(x, y, image::Rgba([200, 10, 10, 255]));
+            }
+        }
+        let img = DynamicImage::ImageRgba8(img);
+        assert_eq!(
+            trim_bounds(&img, &TrimOptions::default()),
+            Some((5, 3, 4, 3))
+        );
+        let padded = TrimOptions {
+            padding: 4,
+            ..TrimOptions::default()
+        };
+        assert_eq!(trim_bounds(&img, &padded), Some((1, 0, 12, 10)));
+    }
+
+    #[test]
+    fn trim_uniform_border_within_tolerance() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_fn(12, 12, |x, y| {
+            if (4..8).contains(&x) && (2..10).contains(&y) {
+                image::Rgb([0, 0, 0])
+            } else {
+                image::Rgb([250 - (x % 3) as u8, 250, 250])
+            }
+        }));
+        let out = trim(&img, &TrimOptions::default()).unwrap();
+        assert_eq!((out.width(), out.height()), (4, 8));
+        let strict = TrimOptions {
+            tolerance: 0,
+            ..TrimOptions::default()
+        };
+        assert_eq!(trim_bounds(&img, &strict), Some((1, 0, 11, 12)));
+        let blank = DynamicImage::ImageRgb8(image::RgbImage::new(4, 4));
+        assert!(trim(&blank, &TrimOptions::default()).is_err());
+    }
+}
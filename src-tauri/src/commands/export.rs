@@ -1,4 +1,342 @@
+use crate::commands::alpha::{self, AlphaOptions};
+use crate::commands::jpeg::{self, JpegOptions};
+use crate::commands::metadata::{self, MetadataMode};
+use crate::commands::multipage::TiffCompression;
+use crate::commands::quantize::{self, QuantizeOptions};
+use crate::commands::webp::{self, WebpOptions};
+use crate::commands::{animation, batch, metrics, multipage, operations, tonemap};
 use crate::error::AppError;
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Instant;
+use tauri::ipc::{InvokeResponseBody, Response};
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ExportOptions {
+    /// File the edits started from. Operations pass temp PNGs along, which
+    /// carry no metadata, so it is read from here; defaults to `source_path`.
+    pub metadata_source: Option<String>,
+    pub metadata: MetadataMode,
+    /// Recorded as EXIF Software and as the agent of history entries
+    pub software: Option<String>,
+    /// Edit steps appended to the XMP history
+    pub history: Vec<String>,
+    pub encoder: EncoderOptions,
+}
+
+/// Settings for each output format; an export reads only its own format's.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EncoderOptions {
+    pub jpeg: JpegOptions,
+    pub png: PngOptions,
+    pub gif: GifOptions,
+    pub webp: WebpOptions,
+    pub avif: AvifOptions,
+    pub tiff: TiffOptions,
+    /// Flattening and premultiplication, for whichever format is written
+    pub alpha: AlphaOptions,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PngFilter {
+    None,
+    Sub,
+    Up,
+    Average,
+    Paeth,
+    /// Picks a filter per row
+    #[default]
+    Adaptive,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PngOptions {
+    /// zlib level 0–9; `None` favours speed
+    pub compression_level: Option<u8>,
+    pub filter: PngFilter,
+    /// Write 8-bit indexed color, quantized with these settings
+    pub quantize: Option<QuantizeOptions>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GifOptions {
+    /// Palette settings; `None` uses the encoder's own NeuQuant palette.
+    /// Alpha is always reduced to on/off.
+    pub quantize: Option<QuantizeOptions>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AvifOptions {
+    /// 1–100; `None` uses the export's quality
+    pub quality: Option<u8>,
+    /// 1 (slowest, smallest) to 10 (fastest)
+    pub speed: u8,
+    /// 8 or 10
+    pub bit_depth: u8,
+}
+
+impl Default for AvifOptions {
+    fn default() -> Self {
+        Self {
+            quality: None,
+            speed: 4,
+            bit_depth: 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TiffOptions {
+    pub compression: TiffCompression,
+}
+
+/// Export mode that searches the quality, and optionally the resolution,
+/// for the best JPEG, WebP or AVIF that fits in `max_bytes`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TargetSize {
+    pub max_bytes: u64,
+    /// Lowest quality accepted at a resolution
+    pub min_quality: u8,
+    /// Step the resolution down once `min_quality` no longer fits
+    pub allow_downscale: bool,
+    /// Smallest fraction of the source dimensions tried
+    pub min_scale: f32,
+}
+
+impl Default for TargetSize {
+    fn default() -> Self {
+        Self {
+            max_bytes: 500 * 1024,
+            min_quality: 10,
+            allow_downscale: false,
+            min_scale: 0.25,
+        }
+    }
+}
+
+/// What a target-size export settled on.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SizedExport {
+    pub path: String,
+    pub bytes: u64,
+    pub quality: u8,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub(crate) fn image_format(format: &str) -> Result<image::ImageFormat, AppError> {
+    match format {
+        "jpeg" | "jpg" => Ok(image::ImageFormat::Jpeg),
+        "png" => Ok(image::ImageFormat::Png),
+        "gif" => Ok(image::ImageFormat::Gif),
+        "webp" => Ok(image::ImageFormat::WebP),
+        "bmp" => Ok(image::ImageFormat::Bmp),
+        "tiff" => Ok(image::ImageFormat::Tiff),
+        "avif" => Ok(image::ImageFormat::Avif),
+        "exr" => Ok(image::ImageFormat::OpenExr),
+        "hdr" => Ok(image::ImageFormat::Hdr),
+        other => Err(AppError::UnsupportedFormat(other.into())),
+    }
+}
+
+fn png_level(options: &PngOptions) -> Result<Option<u8>, AppError> {
+    match options.compression_level {
+        level @ (None | Some(0..=9)) => Ok(level),
+        Some(level) => Err(AppError::General(format!(
+            "PNG compression level {level} is not between 0 and 9"
+        ))),
+    }
+}
+
+/// Compression and filter for the png crate, which writes indexed and
+/// animated PNGs.
+pub(crate) fn png_settings(
+    options: &PngOptions,
+) -> Result<(png::DeflateCompression, png::Filter), AppError> {
+    let compression = match png_level(options)? {
+        None => png::DeflateCompression::FdeflateUltraFast,
+        Some(0) => png::DeflateCompression::NoCompression,
+        Some(level) => png::DeflateCompression::Level(level),
+    };
+    let filter = match options.filter {
+        PngFilter::None => png::Filter::NoFilter,
+        PngFilter::Sub => png::Filter::Sub,
+        PngFilter::Up => png::Filter::Up,
+        PngFilter::Average => png::Filter::Avg,
+        PngFilter::Paeth => png::Filter::Paeth,
+        PngFilter::Adaptive => png::Filter::Adaptive,
+    };
+    Ok((compression, filter))
+}
+
+fn encode_png(img: &image::DynamicImage, options: &PngOptions) -> Result<Vec<u8>, AppError> {
+    let level = png_level(options)?;
+    if let Some(settings) = &options.quantize {
+        let (compression, filter) = png_settings(options)?;
+        return quantize::quantize(img, settings)?.to_png(compression, filter);
+    }
+
+    let compression = match level {
+        None => CompressionType::Fast,
+        Some(0) => CompressionType::Uncompressed,
+        Some(level) => CompressionType::Level(level),
+    };
+    let filter = match options.filter {
+        PngFilter::None => FilterType::NoFilter,
+        PngFilter::Sub => FilterType::Sub,
+        PngFilter::Up => FilterType::Up,
+        PngFilter::Average => FilterType::Avg,
+        PngFilter::Paeth => FilterType::Paeth,
+        PngFilter::Adaptive => FilterType::Adaptive,
+    };
+    let mut bytes = Vec::new();
+    img.write_with_encoder(PngEncoder::new_with_quality(
+        &mut bytes,
+        compression,
+        filter,
+    ))
+    .map_err(AppError::save_failed)?;
+    Ok(bytes)
+}
+
+/// AVIF through ravif directly, since the image crate's encoder is fixed
+/// at 8 bits. 10-bit output is fed the source's 16-bit samples, so deep
+/// images keep their gradations.
+fn encode_avif(
+    img: &image::DynamicImage,
+    quality: u8,
+    options: &AvifOptions,
+) -> Result<Vec<u8>, AppError> {
+    let quality = f32::from(quality.clamp(1, 100));
+    let encoder = ravif::Encoder::new()
+        .with_quality(quality)
+        .with_alpha_quality(quality)
+        .with_speed(options.speed.clamp(1, 10));
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let encoded = match options.bit_depth {
+        8 => {
+            let rgba = img.to_rgba8();
+            let pixels: Vec<ravif::RGBA8> = rgba
+                .pixels()
+                .map(|p| ravif::RGBA8::new(p[0], p[1], p[2], p[3]))
+                .collect();
+            encoder
+                .with_bit_depth(ravif::BitDepth::Eight)
+                .encode_rgba(ravif::Img::new(&pixels[..], width, height))
+        }
+        10 => {
+            let rgba = img.to_rgba16();
+            let planes = rgba.pixels().map(|p| ycbcr_10_bit([p[0], p[1], p[2]]));
+            let alpha = rgba
+                .pixels()
+                .any(|p| p[3] < u16::MAX)
+                .then(|| rgba.pixels().map(|p| to_10_bit(f32::from(p[3]))));
+            encoder.encode_raw_planes_10_bit(
+                width,
+                height,
+                planes,
+                alpha,
+                ravif::PixelRange::Full,
+                ravif::MatrixCoefficients::BT601,
+            )
+        }
+        other => {
+            return Err(AppError::General(format!(
+                "AVIF bit depth must be 8 or 10, not {other}"
+            )))
+        }
+    }
+    .map_err(AppError::save_failed)?;
+    Ok(encoded.avif_file)
+}
+
+/// A 16-bit sample rescaled to 0–1023
+fn to_10_bit(v: f32) -> u16 {
+    (v * 1023.0 / 65535.0).round().clamp(0.0, 1023.0) as u16
+}
+
+/// Full-range BT.601 YCbCr, as ravif signals for its own 8-bit input
+fn ycbcr_10_bit([r, g, b]: [u16; 3]) -> [u16; 3] {
+    let [r, g, b] = [r, g, b].map(f32::from);
+    let y = 0.299 * r + 0.587 * g + 0.114 * b;
+    let half = 32768.0;
+    [
+        to_10_bit(y),
+        to_10_bit((b - y) / 1.772 + half),
+        to_10_bit((r - y) / 1.402 + half),
+    ]
+}
+
+fn write_to(img: &image::DynamicImage, format: image::ImageFormat) -> Result<Vec<u8>, AppError> {
+    let mut bytes: Vec<u8> = Vec::new();
+    img.write_to(&mut std::io::Cursor::new(&mut bytes), format)
+        .map_err(AppError::save_failed)?;
+    Ok(bytes)
+}
+
+/// Encode with the format's own settings from `options`; `quality` applies
+/// where those leave it unset.
+fn encode(
+    img: &image::DynamicImage,
+    format: image::ImageFormat,
+    quality: u8,
+    options: &EncoderOptions,
+) -> Result<Vec<u8>, AppError> {
+    let prepared = alpha::prepare(img, format, &options.alpha);
+    let img = prepared.as_ref();
+    match format {
+        image::ImageFormat::Jpeg => {
+            jpeg::encode(img, options.jpeg.quality.unwrap_or(quality), &options.jpeg)
+        }
+        image::ImageFormat::Png => encode_png(img, &options.png),
+        image::ImageFormat::Gif => match &options.gif.quantize {
+            Some(settings) => {
+                let settings = QuantizeOptions {
+                    alpha_aware: false,
+                    ..settings.clone()
+                };
+                quantize::quantize(img, &settings)?.to_gif()
+            }
+            None => write_to(img, format),
+        },
+        image::ImageFormat::WebP if !options.webp.lossless => {
+            webp::encode_lossy(img, options.webp.quality.unwrap_or(quality))
+        }
+        image::ImageFormat::Avif => {
+            encode_avif(img, options.avif.quality.unwrap_or(quality), &options.avif)
+        }
+        image::ImageFormat::Tiff => multipage::encode_pages(
+            std::slice::from_ref(img),
+            options.tiff.compression,
+            options.alpha.premultiplied,
+        ),
+        // Float formats keep HDR values; sRGB sources are linearized. EXR
+        // color is premultiplied by definition.
+        image::ImageFormat::OpenExr => {
+            let float = tonemap::to_float(img);
+            if float.color().has_alpha() {
+                write_to(&alpha::premultiply(&float), format)
+            } else {
+                write_to(&float, format)
+            }
+        }
+        image::ImageFormat::Hdr => write_to(
+            &image::DynamicImage::ImageRgb32F(tonemap::to_float(img).to_rgb32f()),
+            format,
+        ),
+        other => write_to(img, other),
+    }
+}
 
 #[tauri::command]
 pub fn save_image(
@@ -6,42 +344,384 @@ pub fn save_image(
     dest_path: String,
     format: String,
     quality: u8,
+    options: Option<ExportOptions>,
 ) -> Result<(), AppError> {
-    let img = image::open(&source_path)?;
+    let options = options.unwrap_or_default();
+    let format = image_format(&format)?;
+    let img = operations::open_image(&source_path)?;
+    let bytes = export_bytes(&img, &source_path, format, quality, &options)?;
 
-    match format.as_str() {
-        "jpeg" | "jpg" => {
-            let mut file = std::fs::File::create(&dest_path)
-                .map_err(|e| AppError::SaveFailed(e.to_string()))?;
-            let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut file, quality);
-            img.write_with_encoder(encoder)
-                .map_err(|e| AppError::SaveFailed(e.to_string()))?;
-        }
-        "png" => {
-            img.save(&dest_path)
-                .map_err(|e| AppError::SaveFailed(e.to_string()))?;
-        }
-        "webp" => {
-            img.save(&dest_path)
-                .map_err(|e| AppError::SaveFailed(e.to_string()))?;
+    std::fs::write(&dest_path, bytes).map_err(|e| AppError::SaveFailed(e.to_string()))?;
+    Ok(())
+}
+
+/// The file `save_image` writes for `img`, opened from `source_path`.
+fn export_bytes(
+    img: &image::DynamicImage,
+    source_path: &str,
+    format: image::ImageFormat,
+    quality: u8,
+    options: &ExportOptions,
+) -> Result<Vec<u8>, AppError> {
+    // Pixels are written upright, and the carried-over metadata has its
+    // orientation reset, so viewers can't apply it a second time.
+    // GIF, PNG and WebP keep every frame of an animated source
+    let frames = match format {
+        image::ImageFormat::Gif | image::ImageFormat::Png | image::ImageFormat::WebP => {
+            animation::decode(Path::new(source_path))?
         }
-        "bmp" => {
-            img.save(&dest_path)
-                .map_err(|e| AppError::SaveFailed(e.to_string()))?;
+        _ => None,
+    };
+    let bytes = match &frames {
+        Some(frames) => animation::encode(frames, format, quality, &options.encoder)?,
+        None => encode(img, format, quality, &options.encoder)?,
+    };
+
+    with_metadata(bytes, img, format, options, source_path)
+}
+
+/// What an export would produce, measured without writing it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportEstimate {
+    /// File size, metadata included
+    pub bytes: u64,
+    pub encode_ms: f64,
+    /// Against the source, in dB; `None` when the output can't be decoded
+    /// here (AVIF). Animated sources compare their first frame.
+    pub psnr: Option<f64>,
+    pub ssim: Option<f64>,
+}
+
+/// 8-bit RGBA to compare with; float images are sRGB-encoded first.
+fn comparable(img: &image::DynamicImage) -> image::RgbaImage {
+    if tonemap::is_float(img.color()) {
+        let display = tonemap::ToneMapOptions {
+            bit_depth: 8,
+            ..tonemap::ToneMapOptions::display()
+        };
+        tonemap::tone_map(img, &display).to_rgba8()
+    } else {
+        img.to_rgba8()
+    }
+}
+
+/// Encode in memory with the given format and options, and report the
+/// file size, encode time and PSNR/SSIM against the source. AVIF gets no
+/// PSNR/SSIM: this build encodes it but has no AVIF decoder to score with.
+#[tauri::command]
+pub fn estimate_export(
+    source_path: String,
+    format: String,
+    quality: u8,
+    options: Option<ExportOptions>,
+) -> Result<ExportEstimate, AppError> {
+    let options = options.unwrap_or_default();
+    let format = image_format(&format)?;
+    let img = operations::open_image(&source_path)?;
+    let started = Instant::now();
+    let bytes = export_bytes(&img, &source_path, format, quality, &options)?;
+    let encode_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+    let (psnr, ssim) = match image::load_from_memory_with_format(&bytes, format) {
+        Ok(decoded) if (decoded.width(), decoded.height()) == (img.width(), img.height()) => {
+            // Against what was encoded, so flattening alpha isn't scored as loss
+            let prepared = alpha::prepare(&img, format, &options.encoder.alpha);
+            let (source, output) = (comparable(&prepared), comparable(&decoded));
+            (
+                Some(metrics::psnr(&source, &output)),
+                Some(metrics::ssim(&source, &output)),
+            )
         }
-        "tiff" => {
-            img.save(&dest_path)
-                .map_err(|e| AppError::SaveFailed(e.to_string()))?;
+        _ => (None, None),
+    };
+    Ok(ExportEstimate {
+        bytes: bytes.len() as u64,
+        encode_ms,
+        psnr,
+        ssim,
+    })
+}
+
+/// The export over the raw IPC channel: the file itself for formats the
+/// webview shows natively, otherwise its decoded pixels as PNG.
+#[tauri::command]
+pub fn preview_export(
+    source_path: String,
+    format: String,
+    quality: u8,
+    options: Option<ExportOptions>,
+) -> Result<Response, AppError> {
+    let bytes = preview_bytes(&source_path, &format, quality, &options.unwrap_or_default())?;
+    Ok(Response::new(InvokeResponseBody::Raw(bytes)))
+}
+
+fn preview_bytes(
+    source_path: &str,
+    format: &str,
+    quality: u8,
+    options: &ExportOptions,
+) -> Result<Vec<u8>, AppError> {
+    let format = image_format(format)?;
+    let img = operations::open_image(source_path)?;
+    let bytes = export_bytes(&img, source_path, format, quality, options)?;
+    match format {
+        image::ImageFormat::Jpeg
+        | image::ImageFormat::Png
+        | image::ImageFormat::Gif
+        | image::ImageFormat::WebP
+        | image::ImageFormat::Bmp
+        | image::ImageFormat::Avif => Ok(bytes),
+        _ => {
+            let decoded = image::load_from_memory_with_format(&bytes, format)
+                .map_err(|e| AppError::ImageDecode(e.to_string()))?;
+            let decoded = operations::png_compatible(&decoded).unwrap_or(decoded);
+            write_to(&decoded, image::ImageFormat::Png)
         }
-        "avif" => {
-            img.save(&dest_path)
-                .map_err(|e| AppError::SaveFailed(e.to_string()))?;
+    }
+}
+
+/// Save the highest quality, at the largest scale, whose file (metadata
+/// included) fits in `target.max_bytes`. Animated sources export their
+/// first frame.
+#[tauri::command]
+pub fn save_image_to_size(
+    source_path: String,
+    dest_path: String,
+    format: String,
+    target: TargetSize,
+    options: Option<ExportOptions>,
+) -> Result<SizedExport, AppError> {
+    let options = options.unwrap_or_default();
+    let format = image_format(&format)?;
+    if !matches!(
+        format,
+        image::ImageFormat::Jpeg | image::ImageFormat::WebP | image::ImageFormat::Avif
+    ) {
+        return Err(AppError::UnsupportedFormat(
+            "Target size export supports JPEG, WebP and AVIF".into(),
+        ));
+    }
+    if target.max_bytes == 0 {
+        return Err(AppError::General(
+            "Target size must be above 0 bytes".into(),
+        ));
+    }
+    let img = operations::open_image(&source_path)?;
+    let (bytes, quality, width, height) =
+        fit_to_size(&img, format, &target, &options, &source_path)?;
+    std::fs::write(&dest_path, &bytes).map_err(|e| AppError::SaveFailed(e.to_string()))?;
+    Ok(SizedExport {
+        path: dest_path,
+        bytes: bytes.len() as u64,
+        quality,
+        width,
+        height,
+    })
+}
+
+/// Binary-search the quality at each scale, stepping the scale down by the
+/// estimated overshoot until `target.min_scale`.
+fn fit_to_size(
+    img: &image::DynamicImage,
+    format: image::ImageFormat,
+    target: &TargetSize,
+    options: &ExportOptions,
+    source_path: &str,
+) -> Result<(Vec<u8>, u8, u32, u32), AppError> {
+    // The search owns the quality, and lossless WebP would ignore it
+    let mut encoder = options.encoder.clone();
+    encoder.jpeg.quality = None;
+    encoder.webp.quality = None;
+    encoder.avif.quality = None;
+    encoder.webp.lossless = false;
+
+    let fits = |bytes: &[u8]| bytes.len() as u64 <= target.max_bytes;
+    let min_quality = target.min_quality.clamp(1, 100);
+    let min_scale = if target.allow_downscale {
+        target.min_scale.clamp(0.01, 1.0)
+    } else {
+        1.0
+    };
+    // AVIF probes at its fastest speed, and only the chosen quality is
+    // encoded at the requested one
+    let mut probe = encoder.clone();
+    if format == image::ImageFormat::Avif {
+        probe.avif.speed = 10;
+    }
+    let by_width = operations::ResizeOptions {
+        mode: operations::ResizeMode::Width,
+        ..operations::ResizeOptions::default()
+    };
+    // Read once; only the dimensions written into it change between scales
+    let source = source_blobs(options, source_path);
+    let mut scale = 1.0_f32;
+    let mut smallest = u64::MAX;
+    loop {
+        let scaled = if scale < 1.0 {
+            let width = ((img.width() as f32 * scale).round() as u32).max(1);
+            operations::resize_dynamic(img, width, 0, "lanczos", &by_width)?
+        } else {
+            img.clone()
+        };
+        let blobs = export_blobs(&source, &scaled, options);
+        let encode_at = |quality: u8, encoder: &EncoderOptions| {
+            let bytes = encode(&scaled, format, quality, encoder)?;
+            metadata::embed_metadata(bytes, format, &blobs)
+        };
+
+        let floor = encode_at(min_quality, &probe)?;
+        if fits(&floor) {
+            let (mut low, mut high, mut best) = (min_quality, 100_u8, floor);
+            while low < high {
+                let mid = low + (high - low).div_ceil(2);
+                let bytes = encode_at(mid, &probe)?;
+                if fits(&bytes) {
+                    low = mid;
+                    best = bytes;
+                } else {
+                    high = mid - 1;
+                }
+            }
+            if probe.avif.speed != encoder.avif.speed {
+                // Slower speeds almost always come out smaller; keep the
+                // probe if this one doesn't
+                let slow = encode_at(low, &encoder)?;
+                if fits(&slow) {
+                    best = slow;
+                }
+            }
+            return Ok((best, low, scaled.width(), scaled.height()));
         }
-        other => {
-            return Err(AppError::UnsupportedFormat(other.into()));
+
+        smallest = smallest.min(floor.len() as u64);
+        if scale <= min_scale {
+            break;
         }
+        // File size grows roughly with pixel count
+        let estimate = scale * (target.max_bytes as f32 / floor.len() as f32).sqrt() * 0.95;
+        scale = estimate.min(scale * 0.9).max(min_scale);
     }
+    Err(AppError::SaveFailed(format!(
+        "Could not fit under {} bytes; the smallest attempt was {} bytes",
+        target.max_bytes, smallest
+    )))
+}
+
+/// One frame of `img` as `format`, with the metadata `options` chooses.
+pub(crate) fn encode_still(
+    img: &image::DynamicImage,
+    source_path: &str,
+    format: image::ImageFormat,
+    quality: u8,
+    options: &ExportOptions,
+) -> Result<Vec<u8>, AppError> {
+    let bytes = encode(img, format, quality, &options.encoder)?;
+    with_metadata(bytes, img, format, options, source_path)
+}
+
+/// Embed the metadata chosen in `options`, read from its `metadata_source`
+/// or else from `source_path`.
+fn with_metadata(
+    bytes: Vec<u8>,
+    img: &image::DynamicImage,
+    format: image::ImageFormat,
+    options: &ExportOptions,
+    source_path: &str,
+) -> Result<Vec<u8>, AppError> {
+    let blobs = export_blobs(&source_blobs(options, source_path), img, options);
+    metadata::embed_metadata(bytes, format, &blobs)
+}
+
+/// The metadata of `options.metadata_source`, or else of `source_path`.
+fn source_blobs(options: &ExportOptions, source_path: &str) -> metadata::MetadataBlobs {
+    let metadata_source = options.metadata_source.as_deref().unwrap_or(source_path);
+    // A source that has since moved shouldn't block the export itself
+    metadata::read_blobs(Path::new(metadata_source)).unwrap_or_default()
+}
+
+/// `source` as written with an `img`-sized export under `options`.
+fn export_blobs(
+    source: &metadata::MetadataBlobs,
+    img: &image::DynamicImage,
+    options: &ExportOptions,
+) -> metadata::MetadataBlobs {
+    metadata::prepare_blobs(
+        source,
+        &metadata::MetadataUpdate {
+            mode: options.metadata,
+            width: img.width(),
+            height: img.height(),
+            software: options.software.as_deref(),
+            history: &options.history,
+        },
+    )
+}
+
+/// Export every page of a multi-page TIFF as its own file, named
+/// `<stem>_page<N>` in `output_dir`. Other files export as a single page.
+#[tauri::command]
+pub fn export_pages(
+    path: String,
+    output_dir: String,
+    format: String,
+    quality: u8,
+    options: Option<ExportOptions>,
+) -> Result<Vec<String>, AppError> {
+    let options = options.unwrap_or_default();
+    let format = image_format(&format)?;
+    let source = Path::new(&path);
+    let stem = source
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("image");
+    let output_dir = Path::new(&output_dir);
+    std::fs::create_dir_all(output_dir)?;
 
+    (0..multipage::page_count(source)?)
+        .map(|page| {
+            let img = multipage::decode_page(source, page)?;
+            let bytes = encode(&img, format, quality, &options.encoder)?;
+            let bytes = with_metadata(bytes, &img, format, &options, &path)?;
+            let dest = batch::unique_path(
+                output_dir,
+                &format!("{}_page{}", stem, page + 1),
+                format.extensions_str()[0],
+            );
+            std::fs::write(&dest, bytes).map_err(|e| AppError::SaveFailed(e.to_string()))?;
+            Ok(dest.to_string_lossy().into_owned())
+        })
+        .collect()
+}
+
+/// Assemble the images, in order, into one multi-page TIFF. Metadata comes
+/// from the first source unless `options` names another.
+#[tauri::command]
+pub fn save_multipage_tiff(
+    source_paths: Vec<String>,
+    dest_path: String,
+    options: Option<ExportOptions>,
+) -> Result<(), AppError> {
+    let options = options.unwrap_or_default();
+    let first = source_paths
+        .first()
+        .ok_or_else(|| AppError::General("No pages selected".into()))?;
+    let alpha_options = &options.encoder.alpha;
+    let pages = source_paths
+        .iter()
+        .map(|path| {
+            let page = operations::open_image(path)?;
+            Ok(alpha::prepare(&page, image::ImageFormat::Tiff, alpha_options).into_owned())
+        })
+        .collect::<Result<Vec<_>, AppError>>()?;
+    let bytes = multipage::encode_pages(
+        &pages,
+        options.encoder.tiff.compression,
+        alpha_options.premultiplied,
+    )?;
+    let bytes = with_metadata(bytes, &pages[0], image::ImageFormat::Tiff, &options, first)?;
+
+    std::fs::write(&dest_path, bytes).map_err(|e| AppError::SaveFailed(e.to_string()))?;
     Ok(())
 }
 
@@ -63,7 +743,7 @@ mod tests {
     fn test_save_jpeg() {
         let src = create_test_image();
         let dest = std::env::temp_dir().join("pixelforge_test_out.jpg");
-        save_image(src, dest.to_string_lossy().into(), "jpeg".into(), 85).unwrap();
+        save_image(src, dest.to_string_lossy().into(), "jpeg".into(), 85, None).unwrap();
         assert!(dest.exists());
         // Verify JPEG magic bytes
         let bytes = std::fs::read(&dest).unwrap();
@@ -76,18 +756,404 @@ mod tests {
     fn test_save_png() {
         let src = create_test_image();
         let dest = std::env::temp_dir().join("pixelforge_test_out.png");
-        save_image(src, dest.to_string_lossy().into(), "png".into(), 100).unwrap();
+        save_image(src, dest.to_string_lossy().into(), "png".into(), 100, None).unwrap();
         assert!(dest.exists());
         let bytes = std::fs::read(&dest).unwrap();
         assert_eq!(&bytes[0..4], &[0x89, 0x50, 0x4E, 0x47]);
         std::fs::remove_file(&dest).ok();
     }
 
+    #[test]
+    fn test_multipage_tiff_assembles_and_splits() {
+        let pages: Vec<String> = [(10, 10), (6, 4)]
+            .iter()
+            .map(|&(w, h)| {
+                operations::save_temp_image(&image::DynamicImage::ImageRgb8(image::RgbImage::new(
+                    w, h,
+                )))
+                .unwrap()
+            })
+            .collect();
+        let dir =
+            std::env::temp_dir().join(format!("pixelforge_export_test_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let tiff = dir.join("scan.tif");
+        save_multipage_tiff(pages.clone(), tiff.to_string_lossy().into(), None).unwrap();
+        assert_eq!(multipage::page_count(&tiff).unwrap(), 2);
+
+        let outputs = export_pages(
+            tiff.to_string_lossy().into(),
+            dir.to_string_lossy().into(),
+            "png".into(),
+            100,
+            None,
+        )
+        .unwrap();
+        assert_eq!(outputs.len(), 2);
+        assert!(outputs[1].ends_with("scan_page2.png"));
+        assert_eq!(image::open(&outputs[1]).unwrap().width(), 6);
+
+        std::fs::remove_dir_all(dir).ok();
+        for path in pages {
+            std::fs::remove_file(path).ok();
+        }
+    }
+
+    #[test]
+    fn test_exr_keeps_float_values_through_edits() {
+        let hdr = image::DynamicImage::ImageRgb32F(image::ImageBuffer::from_pixel(
+            8,
+            8,
+            image::Rgb([8.0, 0.5, 0.0]),
+        ));
+        let original = operations::save_temp_image(&hdr).unwrap();
+        assert!(original.ends_with(".exr"));
+
+        let resized =
+            operations::apply_resize(original.clone(), 4, 4, "bilinear".into(), None).unwrap();
+        let dest = std::env::temp_dir().join(format!(
+            "pixelforge_export_test_{}.exr",
+            uuid::Uuid::new_v4()
+        ));
+        save_image(
+            resized.clone(),
+            dest.to_string_lossy().into(),
+            "exr".into(),
+            100,
+            None,
+        )
+        .unwrap();
+
+        let out = operations::open_image(&dest).unwrap();
+        assert_eq!(out.color(), image::ColorType::Rgb32F);
+        let px = out.to_rgb32f().get_pixel(2, 2).0;
+        assert!(
+            (px[0] - 8.0).abs() < 1e-3 && (px[1] - 0.5).abs() < 1e-3,
+            "{px:?}"
+        );
+        for path in [original, resized, dest.to_string_lossy().into()] {
+            std::fs::remove_file(path).ok();
+        }
+    }
+
+    #[test]
+    fn test_encoder_options_reach_each_format() {
+        let img = image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(24, 16, |x, y| {
+            image::Rgb([(x * 10) as u8, (y * 15) as u8, 90])
+        }));
+        let encode_with = |format, options: &EncoderOptions| encode(&img, format, 80, options);
+
+        let mut options = EncoderOptions::default();
+        options.jpeg.progressive = true;
+        let jpeg = encode_with(image::ImageFormat::Jpeg, &options).unwrap();
+        assert!(jpeg.windows(2).any(|w| w == [0xFF, 0xC2]));
+
+        options.webp.lossless = false;
+        let webp = encode_with(image::ImageFormat::WebP, &options).unwrap();
+        assert_eq!(&webp[12..16], b"VP8 ");
+
+        options.png.compression_level = Some(0);
+        let stored = encode_with(image::ImageFormat::Png, &options).unwrap();
+        options.png.compression_level = Some(9);
+        let packed = encode_with(image::ImageFormat::Png, &options).unwrap();
+        assert!(packed.len() < stored.len());
+        options.png.compression_level = Some(10);
+        assert!(encode_with(image::ImageFormat::Png, &options).is_err());
+        options.png.compression_level = Some(9);
+
+        options.png.quantize = Some(QuantizeOptions {
+            colors: 16,
+            ..QuantizeOptions::default()
+        });
+        let indexed = encode_with(image::ImageFormat::Png, &options).unwrap();
+        // IHDR color type 3: palette
+        assert_eq!(indexed[25], 3);
+        options.gif.quantize = options.png.quantize.clone();
+        let gif = encode_with(image::ImageFormat::Gif, &options).unwrap();
+        let decoded = image::load_from_memory(&gif).unwrap().to_rgba8();
+        let colors: std::collections::HashSet<_> = decoded.pixels().collect();
+        assert!(colors.len() <= 16);
+        options.png.quantize = None;
+
+        options.tiff.compression = TiffCompression::Deflate;
+        let tiff = encode_with(image::ImageFormat::Tiff, &options).unwrap();
+        let decoded = image::load_from_memory(&tiff).unwrap();
+        assert_eq!(decoded.to_rgb8(), img.to_rgb8());
+
+        options.avif.speed = 10;
+        let eight = encode_with(image::ImageFormat::Avif, &options).unwrap();
+        options.avif.bit_depth = 10;
+        let ten = encode_with(image::ImageFormat::Avif, &options).unwrap();
+        assert_eq!(&eight[4..8], b"ftyp");
+        assert_ne!(eight, ten);
+        options.avif.bit_depth = 12;
+        assert!(encode_with(image::ImageFormat::Avif, &options).is_err());
+
+        for format in [
+            image::ImageFormat::Jpeg,
+            image::ImageFormat::WebP,
+            image::ImageFormat::Png,
+        ] {
+            let bytes = encode_with(format, &options).unwrap();
+            let decoded = image::load_from_memory_with_format(&bytes, format).unwrap();
+            assert_eq!((decoded.width(), decoded.height()), (24, 16));
+        }
+    }
+
+    #[test]
+    fn test_target_size_searches_quality_then_scale() {
+        let img = image::RgbImage::from_fn(96, 64, |x, y| {
+            let n = (x * 7919 + y * 104_729) % 251;
+            image::Rgb([n as u8, (x * 2) as u8, (y * 3) as u8])
+        });
+        let src = std::env::temp_dir().join(format!(
+            "pixelforge_export_noise_{}.png",
+            uuid::Uuid::new_v4()
+        ));
+        img.save(&src).unwrap();
+        let src = src.to_string_lossy().into_owned();
+        let dest = std::env::temp_dir().join(format!(
+            "pixelforge_export_sized_{}.jpg",
+            uuid::Uuid::new_v4()
+        ));
+        let dest = dest.to_string_lossy().into_owned();
+        let save = |format: &str, target: TargetSize| {
+            save_image_to_size(src.clone(), dest.clone(), format.into(), target, None)
+        };
+
+        for (format, codec) in [
+            ("jpeg", image::ImageFormat::Jpeg),
+            ("webp", image::ImageFormat::WebP),
+            ("avif", image::ImageFormat::Avif),
+        ] {
+            let mut lossy = EncoderOptions::default();
+            lossy.webp.lossless = false;
+            let best = encode(
+                &image::DynamicImage::ImageRgb8(img.clone()),
+                codec,
+                100,
+                &lossy,
+            )
+            .unwrap()
+            .len() as u64;
+            let sized = save(
+                format,
+                TargetSize {
+                    max_bytes: best / 2,
+                    ..TargetSize::default()
+                },
+            )
+            .unwrap();
+            assert!(sized.bytes <= best / 2, "{format}: {sized:?}");
+            assert_eq!(std::fs::metadata(&dest).unwrap().len(), sized.bytes);
+            assert_eq!((sized.width, sized.height), (96, 64));
+            assert!(
+                sized.quality > 10 && sized.quality < 100,
+                "{format}: {sized:?}"
+            );
+        }
+
+        let tiny = TargetSize {
+            max_bytes: 1200,
+            min_quality: 50,
+            ..TargetSize::default()
+        };
+        assert!(save("jpeg", tiny.clone()).is_err());
+        let sized = save(
+            "jpeg",
+            TargetSize {
+                allow_downscale: true,
+                ..tiny
+            },
+        )
+        .unwrap();
+        assert!(sized.bytes <= 1200 && sized.quality >= 50, "{sized:?}");
+        assert!(sized.width < 96);
+        assert!((sized.width as f32 / sized.height as f32 - 1.5).abs() < 0.1);
+        assert!(save("png", TargetSize::default()).is_err());
+
+        std::fs::remove_file(&src).ok();
+        std::fs::remove_file(&dest).ok();
+    }
+
+    #[test]
+    fn test_estimate_and_preview_stay_in_memory() {
+        let img = image::RgbImage::from_fn(40, 30, |x, y| {
+            image::Rgb([(x * 6) as u8, (y * 8) as u8, ((x * y) % 256) as u8])
+        });
+        let src = std::env::temp_dir().join(format!(
+            "pixelforge_export_estimate_{}.png",
+            uuid::Uuid::new_v4()
+        ));
+        img.save(&src).unwrap();
+        let src = src.to_string_lossy().into_owned();
+        let estimate = |format: &str, quality| {
+            estimate_export(src.clone(), format.into(), quality, None).unwrap()
+        };
+
+        let png = estimate("png", 100);
+        assert_eq!(png.psnr, Some(metrics::PSNR_IDENTICAL));
+        assert!((png.ssim.unwrap() - 1.0).abs() < 1e-9);
+        let (high, low) = (estimate("jpeg", 95), estimate("jpeg", 20));
+        assert!(high.bytes > low.bytes);
+        assert!(high.psnr.unwrap() > low.psnr.unwrap() && low.psnr.unwrap() > 15.0);
+        assert!(high.ssim.unwrap() > low.ssim.unwrap());
+        assert!(high.encode_ms >= 0.0);
+        assert_eq!(estimate("avif", 60).psnr, None);
+
+        let raw = |format| preview_bytes(&src, format, 80, &ExportOptions::default()).unwrap();
+        assert_eq!(&raw("jpeg")[..2], &[0xFF, 0xD8]);
+        assert_eq!(&raw("tiff")[..4], &[0x89, 0x50, 0x4E, 0x47]);
+
+        // Flattening onto the matte is part of the export, not a loss
+        let translucent = image::RgbaImage::from_fn(40, 30, |x, y| {
+            image::Rgba([
+                (x * 6) as u8,
+                (y * 8) as u8,
+                90,
+                if x < 20 { 0 } else { 255 },
+            ])
+        });
+        translucent.save(&src).unwrap();
+        let flattened = estimate("jpeg", 95);
+        assert!(flattened.psnr.unwrap() > 35.0, "{flattened:?}");
+
+        std::fs::remove_file(&src).ok();
+    }
+
+    #[test]
+    fn test_alpha_flattens_for_jpeg_and_premultiplies_tiff() {
+        // Opaque left half; the right half is transparent green
+        let img = image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(16, 8, |x, _| {
+            if x < 8 {
+                image::Rgba([40, 60, 200, 255])
+            } else {
+                image::Rgba([0, 255, 0, 0])
+            }
+        }));
+        let mut options = EncoderOptions::default();
+        options.alpha.color = [250, 250, 250];
+        let jpeg = encode(&img, image::ImageFormat::Jpeg, 95, &options).unwrap();
+        let decoded = image::load_from_memory(&jpeg).unwrap().to_rgb8();
+        let matte = decoded.get_pixel(13, 4);
+        assert!(matte.0.iter().all(|&v| v > 240), "{matte:?}");
+
+        let extra_samples = |bytes: &[u8]| {
+            let mut decoder = tiff::decoder::Decoder::new(std::io::Cursor::new(bytes)).unwrap();
+            decoder.get_tag_u32(tiff::tags::Tag::ExtraSamples).unwrap()
+        };
+        let half = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            2,
+            2,
+            image::Rgba([200, 100, 50, 128]),
+        ));
+        let straight = encode(&half, image::ImageFormat::Tiff, 100, &options).unwrap();
+        assert_eq!(extra_samples(&straight), 2);
+        options.alpha.premultiplied = true;
+        let associated = encode(&half, image::ImageFormat::Tiff, 100, &options).unwrap();
+        assert_eq!(extra_samples(&associated), 1);
+        let mut decoder = tiff::decoder::Decoder::new(std::io::Cursor::new(&associated)).unwrap();
+        match decoder.read_image().unwrap() {
+            tiff::decoder::DecodingResult::U8(samples) => {
+                assert_eq!(&samples[..4], &[100, 50, 25, 128])
+            }
+            _ => panic!("expected 8-bit samples"),
+        }
+
+        // EXR is premultiplied whatever the option says
+        options.alpha.premultiplied = false;
+        let exr = encode(&half, image::ImageFormat::OpenExr, 100, &options).unwrap();
+        let p = image::load_from_memory(&exr).unwrap().to_rgba32f();
+        let [r, _, _, a] = p.get_pixel(0, 0).0;
+        let linear = operations::srgb_to_linear(200.0 / 255.0);
+        assert!((a - 128.0 / 255.0).abs() < 1e-3);
+        assert!((r - linear * a).abs() < 1e-3, "{r}");
+    }
+
     #[test]
     fn test_save_unsupported() {
         let src = create_test_image();
         let dest = std::env::temp_dir().join("pixelforge_test_out.xyz");
-        let result = save_image(src, dest.to_string_lossy().into(), "xyz".into(), 100);
+        let result = save_image(src, dest.to_string_lossy().into(), "xyz".into(), 100, None);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_save_carries_metadata_from_original() {
+        let fields = [
+            exif::Field {
+                tag: exif::Tag::Orientation,
+                ifd_num: exif::In::PRIMARY,
+                value: exif::Value::Short(vec![6]),
+            },
+            exif::Field {
+                tag: exif::Tag::Artist,
+                ifd_num: exif::In::PRIMARY,
+                value: exif::Value::Ascii(vec![b"Ada".to_vec()]),
+            },
+        ];
+        let mut writer = exif::experimental::Writer::new();
+        for field in &fields {
+            writer.push_field(field);
+        }
+        let mut tiff = std::io::Cursor::new(Vec::new());
+        writer.write(&mut tiff, false).unwrap();
+        let original = std::env::temp_dir().join(format!(
+            "pixelforge_export_test_{}.jpg",
+            uuid::Uuid::new_v4()
+        ));
+        let mut file = std::fs::File::create(&original).unwrap();
+        let mut encoder = image::codecs::jpeg::JpegEncoder::new(&mut file);
+        image::ImageEncoder::set_exif_metadata(&mut encoder, tiff.into_inner()).unwrap();
+        image::DynamicImage::ImageRgb8(image::RgbImage::new(12, 8))
+            .write_with_encoder(encoder)
+            .unwrap();
+
+        // An edited copy, as operations produce it: upright and without metadata
+        let edited =
+            operations::save_temp_image(&operations::open_image(&original).unwrap()).unwrap();
+        let dest = std::env::temp_dir().join("pixelforge_test_meta_out.jpg");
+        let options = ExportOptions {
+            metadata_source: Some(original.to_string_lossy().into()),
+            software: Some("PixelForge".into()),
+            history: vec!["Rotate".into()],
+            ..ExportOptions::default()
+        };
+        save_image(
+            edited.clone(),
+            dest.to_string_lossy().into(),
+            "jpeg".into(),
+            90,
+            Some(options),
+        )
+        .unwrap();
+
+        let out = operations::open_image(&dest).unwrap();
+        assert_eq!((out.width(), out.height()), (8, 12));
+        let meta = metadata::read_metadata(&dest).unwrap();
+        let exif = meta.exif.unwrap();
+        assert_eq!(exif.orientation, Some(1));
+        assert_eq!(exif.artist.as_deref(), Some("Ada"));
+        assert_eq!(exif.software.as_deref(), Some("PixelForge"));
+        assert!(meta.xmp.unwrap().packet.contains("Rotate"));
+
+        let options = ExportOptions {
+            metadata_source: Some(original.to_string_lossy().into()),
+            metadata: MetadataMode::Strip,
+            ..ExportOptions::default()
+        };
+        save_image(
+            edited.clone(),
+            dest.to_string_lossy().into(),
+            "png".into(),
+            90,
+            Some(options),
+        )
+        .unwrap();
+        assert!(metadata::read_metadata(&dest).unwrap().exif.is_none());
+
+        for path in [original, dest, edited.into()] {
+            std::fs::remove_file(path).ok();
+        }
+    }
 }
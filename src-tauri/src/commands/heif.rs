@@ -0,0 +1,299 @@
+use crate::error::AppError;
+use image::DynamicImage;
+use serde::Serialize;
+use std::path::Path;
+
+// ── HEIF / HEIC ─────────────────────────────────────────────────────
+//
+// Decoding goes through libheif, enabled with the `heif` cargo feature since
+// it needs the system library. libheif applies the container's rotation and
+// mirror properties while decoding; HEIC files also carry an EXIF orientation
+// that mirrors those and must not be applied a second time.
+
+pub const HEIF_EXTENSIONS: &[&str] = &["heic", "heif", "hif"];
+
+/// HEIF brands; `mif1`/`msf1` are generic and also used by AVIF, which is
+/// told apart by its `avif`/`avis` compatible brand.
+const HEIF_BRANDS: &[&[u8]] = &[
+    b"heic", b"heix", b"hevc", b"hevx", b"heim", b"heis", b"hevm", b"hevs", b"mif1", b"msf1",
+];
+
+/// Whether the bytes start with a HEIF `ftyp` box.
+pub fn sniff(bytes: &[u8]) -> bool {
+    if bytes.get(4..8) != Some(b"ftyp") {
+        return false;
+    }
+    let size = bytes
+        .get(..4)
+        .map_or(0, |b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize);
+    let brands = bytes.get(8..size.min(bytes.len())).unwrap_or_default();
+    // Major brand, minor version, then compatible brands
+    let major = brands.get(..4).unwrap_or_default();
+    let mut compatible = brands.get(8..).unwrap_or_default().chunks_exact(4);
+    let heic = compatible.clone().any(|b| b == b"heic" || b == b"heix");
+    !compatible.any(|b| b == b"avif" || b == b"avis") && (heic || HEIF_BRANDS.contains(&major))
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeifAuxiliaryImage {
+    /// "alpha", "depth", or the auxiliary type URN (e.g. Apple's portrait matte)
+    pub kind: String,
+    pub width: u32,
+    pub height: u32,
+    /// Grayscale temp PNG
+    pub path: String,
+}
+
+#[cfg(feature = "heif")]
+mod decode {
+    use super::HeifAuxiliaryImage;
+    use crate::commands::{large, operations};
+    use crate::error::AppError;
+    use image::{DynamicImage, GrayImage, ImageBuffer};
+    use libheif_rs::{
+        AuxiliaryImagesFilter, ColorSpace, HeifContext, ImageHandle, LibHeif, RgbChroma,
+    };
+    use std::path::Path;
+
+    fn heif_error(e: libheif_rs::HeifError) -> AppError {
+        AppError::ImageDecode(e.message)
+    }
+
+    fn open(path: &Path) -> Result<HeifContext<'static>, AppError> {
+        let path = path
+            .to_str()
+            .ok_or_else(|| AppError::FileRead("Path is not valid UTF-8".into()))?;
+        HeifContext::read_from_file(path).map_err(heif_error)
+    }
+
+    /// Decode a handle to 8-bit RGB(A), or 16-bit for high bit depth images.
+    fn to_dynamic(lib: &LibHeif, handle: &ImageHandle) -> Result<DynamicImage, AppError> {
+        let bits = handle.luma_bits_per_pixel();
+        let alpha = handle.has_alpha_channel();
+        let chroma = match (bits > 8, alpha) {
+            (false, false) => RgbChroma::Rgb,
+            (false, true) => RgbChroma::Rgba,
+            (true, false) => RgbChroma::HdrRgbLe,
+            (true, true) => RgbChroma::HdrRgbaLe,
+        };
+        let image = lib
+            .decode(handle, ColorSpace::Rgb(chroma), None)
+            .map_err(heif_error)?;
+        let planes = image.planes();
+        let plane = planes
+            .interleaved
+            .ok_or_else(|| AppError::ImageDecode("HEIF image has no pixel data".into()))?;
+        let (width, height) = (plane.width, plane.height);
+        let row_len = width as usize * if alpha { 4 } else { 3 };
+
+        if bits <= 8 {
+            let data: Vec<u8> = plane
+                .data
+                .chunks(plane.stride)
+                .take(height as usize)
+                .flat_map(|row| row[..row_len].iter().copied())
+                .collect();
+            return if alpha {
+                ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba8)
+            } else {
+                ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb8)
+            }
+            .ok_or_else(|| AppError::ImageDecode("HEIF plane size mismatch".into()));
+        }
+
+        // Stretch 10/12-bit samples over the full 16-bit range
+        let shift = 16 - bits.min(16) as u32;
+        let data: Vec<u16> = plane
+            .data
+            .chunks(plane.stride)
+            .take(height as usize)
+            .flat_map(|row| {
+                row[..row_len * 2]
+                    .chunks_exact(2)
+                    .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            })
+            .map(|v| (v << shift) | (v >> (bits as u32).saturating_sub(shift)))
+            .collect();
+        if alpha {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba16)
+        } else {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb16)
+        }
+        .ok_or_else(|| AppError::ImageDecode("HEIF plane size mismatch".into()))
+    }
+
+    fn to_gray(lib: &LibHeif, handle: &ImageHandle) -> Result<GrayImage, AppError> {
+        let image = lib
+            .decode(handle, ColorSpace::Monochrome, None)
+            .map_err(heif_error)?;
+        let planes = image.planes();
+        let plane = planes
+            .y
+            .ok_or_else(|| AppError::ImageDecode("HEIF auxiliary image has no data".into()))?;
+        let (width, height) = (plane.width, plane.height);
+        let wide = plane.storage_bits_per_pixel > 8;
+        let shift = plane.bits_per_pixel.saturating_sub(8);
+        let data: Vec<u8> = plane
+            .data
+            .chunks(plane.stride)
+            .take(height as usize)
+            .flat_map(|row| {
+                (0..width as usize).map(move |x| {
+                    if wide {
+                        (u16::from_ne_bytes([row[x * 2], row[x * 2 + 1]]) >> shift) as u8
+                    } else {
+                        row[x]
+                    }
+                })
+            })
+            .collect();
+        GrayImage::from_raw(width, height, data)
+            .ok_or_else(|| AppError::ImageDecode("HEIF plane size mismatch".into()))
+    }
+
+    pub fn decode_file(path: &Path) -> Result<DynamicImage, AppError> {
+        let context = open(path)?;
+        let handle = context.primary_image_handle().map_err(heif_error)?;
+        let (width, height) = (handle.width(), handle.height());
+        let channels = if handle.has_alpha_channel() { 4 } else { 3 };
+        let sample_bytes = if handle.luma_bits_per_pixel() > 8 {
+            2
+        } else {
+            1
+        };
+        // libheif's interleaved plane and the copy `to_dynamic` makes of it
+        large::check_budget(
+            u64::from(width) * u64::from(height) * channels * sample_bytes * 2,
+            &format!("Decoding this {width}×{height} HEIF image"),
+        )?;
+        to_dynamic(&LibHeif::new(), &handle)
+    }
+
+    pub fn dimensions(path: &Path) -> Result<(u32, u32), AppError> {
+        let context = open(path)?;
+        let handle = context.primary_image_handle().map_err(heif_error)?;
+        // Handle dimensions already account for rotation
+        Ok((handle.width(), handle.height()))
+    }
+
+    pub fn auxiliary_images(path: &Path) -> Result<Vec<HeifAuxiliaryImage>, AppError> {
+        let lib = LibHeif::new();
+        let context = open(path)?;
+        let primary = context.primary_image_handle().map_err(heif_error)?;
+        let mut out = Vec::new();
+        let mut push = |kind: String, img: GrayImage| -> Result<(), AppError> {
+            let (width, height) = img.dimensions();
+            let path = operations::save_temp_image(&DynamicImage::ImageLuma8(img))?;
+            out.push(HeifAuxiliaryImage {
+                kind,
+                width,
+                height,
+                path,
+            });
+            Ok(())
+        };
+
+        if primary.has_alpha_channel() {
+            let alpha = to_dynamic(&lib, &primary)?.to_luma_alpha8();
+            let (w, h) = alpha.dimensions();
+            let mask = GrayImage::from_fn(w, h, |x, y| image::Luma([alpha.get_pixel(x, y).0[1]]));
+            push("alpha".into(), mask)?;
+        }
+
+        let mut ids = vec![0; primary.number_of_depth_images().max(0) as usize];
+        let count = primary.depth_image_ids(&mut ids);
+        for &id in &ids[..count] {
+            let depth = primary.depth_image_handle(id).map_err(heif_error)?;
+            push("depth".into(), to_gray(&lib, &depth)?)?;
+        }
+
+        let others = AuxiliaryImagesFilter::OMIT_ALPHA.omit_depth();
+        for aux in primary.auxiliary_images(others) {
+            let kind = aux.auxiliary_type().unwrap_or_default();
+            push(kind, to_gray(&lib, &aux)?)?;
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(not(feature = "heif"))]
+mod decode {
+    use super::HeifAuxiliaryImage;
+    use crate::error::AppError;
+    use image::DynamicImage;
+    use std::path::Path;
+
+    fn unavailable() -> AppError {
+        AppError::UnsupportedFormat(
+            "HEIC/HEIF import needs a build with the heif feature and libheif installed".into(),
+        )
+    }
+
+    pub fn decode_file(_path: &Path) -> Result<DynamicImage, AppError> {
+        Err(unavailable())
+    }
+
+    pub fn dimensions(_path: &Path) -> Result<(u32, u32), AppError> {
+        Err(unavailable())
+    }
+
+    pub fn auxiliary_images(_path: &Path) -> Result<Vec<HeifAuxiliaryImage>, AppError> {
+        Err(unavailable())
+    }
+}
+
+/// Decode the primary image, upright and with alpha when present.
+pub fn decode_file(path: &Path) -> Result<DynamicImage, AppError> {
+    decode::decode_file(path)
+}
+
+/// Displayed dimensions of the primary image.
+pub fn dimensions(path: &Path) -> Result<(u32, u32), AppError> {
+    decode::dimensions(path)
+}
+
+/// Whether this build can decode HEIF, i.e. was built with the `heif` feature.
+#[tauri::command]
+pub fn heif_supported() -> bool {
+    cfg!(feature = "heif")
+}
+
+/// Alpha, depth and other auxiliary images of the primary image, as temp PNGs.
+#[tauri::command]
+pub fn get_heif_auxiliary_images(path: String) -> Result<Vec<HeifAuxiliaryImage>, AppError> {
+    decode::auxiliary_images(Path::new(&path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ftyp(major: &[u8; 4], compatible: &[&[u8; 4]]) -> Vec<u8> {
+        let mut out = ((16 + compatible.len() * 4) as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(b"ftyp");
+        out.extend_from_slice(major);
+        out.extend_from_slice(&[0; 4]);
+        for brand in compatible {
+            out.extend_from_slice(*brand);
+        }
+        out
+    }
+
+    #[test]
+    fn sniffs_heif_brands_but_not_avif() {
+        assert!(sniff(&ftyp(b"heic", &[b"mif1", b"heic"])));
+        assert!(sniff(&ftyp(b"mif1", &[b"mif1", b"heic"])));
+        assert!(!sniff(&ftyp(b"avif", &[b"mif1", b"avif"])));
+        assert!(!sniff(&ftyp(b"mif1", &[b"mif1", b"avif"])));
+        assert!(!sniff(&ftyp(b"isom", &[b"mp41"])));
+    }
+
+    #[cfg(not(feature = "heif"))]
+    #[test]
+    fn reports_missing_heif_support() {
+        let err = decode_file(Path::new("/photos/IMG_0001.heic")).unwrap_err();
+        assert!(matches!(&err, AppError::UnsupportedFormat(m) if m.contains("heif feature")));
+        assert!(!heif_supported());
+    }
+}
@@ -0,0 +1,325 @@
+use crate::commands::alpha::{self, AlphaOptions};
+use crate::commands::operations::{self, ResizeMode, ResizeOptions};
+use crate::error::AppError;
+use image::codecs::ico::{IcoEncoder, IcoFrame};
+use image::DynamicImage;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+// ── Icon sets ───────────────────────────────────────────────────────
+//
+// One square source rendered at every size an app or site needs: a
+// multi-size ICO, the PNG sets that web manifests, iOS and Android expect,
+// and a macOS ICNS. Non-square sources are centered on a transparent square;
+// iOS icons, which Apple shows black wherever they're transparent, are then
+// flattened onto a solid background.
+
+/// Sizes below this get an unsharp pass after downscaling.
+const SHARPEN_BELOW: u32 = 64;
+
+/// Relative path, size and opacity of each PNG in the set.
+const PNG_ICONS: &[(&str, u32, bool)] = &[
+    ("web/favicon-16x16.png", 16, false),
+    ("web/favicon-32x32.png", 32, false),
+    ("web/favicon-48x48.png", 48, false),
+    ("web/apple-touch-icon.png", 180, true),
+    ("web/android-chrome-192x192.png", 192, false),
+    ("web/android-chrome-512x512.png", 512, false),
+    ("ios/AppIcon-40.png", 40, true),
+    ("ios/AppIcon-58.png", 58, true),
+    ("ios/AppIcon-60.png", 60, true),
+    ("ios/AppIcon-76.png", 76, true),
+    ("ios/AppIcon-80.png", 80, true),
+    ("ios/AppIcon-87.png", 87, true),
+    ("ios/AppIcon-120.png", 120, true),
+    ("ios/AppIcon-152.png", 152, true),
+    ("ios/AppIcon-167.png", 167, true),
+    ("ios/AppIcon-180.png", 180, true),
+    ("ios/AppIcon-1024.png", 1024, true),
+    ("android/mipmap-mdpi/ic_launcher.png", 48, false),
+    ("android/mipmap-hdpi/ic_launcher.png", 72, false),
+    ("android/mipmap-xhdpi/ic_launcher.png", 96, false),
+    ("android/mipmap-xxhdpi/ic_launcher.png", 144, false),
+    ("android/mipmap-xxxhdpi/ic_launcher.png", 192, false),
+    ("android/play-store-512.png", 512, false),
+];
+
+/// PNG-based ICNS entry types and their pixel sizes.
+const ICNS_TYPES: &[(&[u8; 4], u32)] = &[
+    (b"icp4", 16),
+    (b"ic11", 32),
+    (b"icp5", 32),
+    (b"ic12", 64),
+    (b"icp6", 64),
+    (b"ic07", 128),
+    (b"ic13", 256),
+    (b"ic08", 256),
+    (b"ic14", 512),
+    (b"ic09", 512),
+    (b"ic10", 1024),
+];
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct IconSetOptions {
+    /// Write `favicon.ico` holding `ico_sizes`
+    pub ico: bool,
+    /// 1–256 each
+    pub ico_sizes: Vec<u32>,
+    /// Write the web, iOS and Android PNGs and `web/site.webmanifest`
+    pub png: bool,
+    /// Write `icon.icns`
+    pub icns: bool,
+    /// Unsharp mask on sizes below 64 px, which downscaling softens most
+    pub sharpen: bool,
+    /// Fill behind the iOS icons and `apple-touch-icon.png`
+    pub ios_background: [u8; 3],
+}
+
+impl Default for IconSetOptions {
+    fn default() -> Self {
+        Self {
+            ico: true,
+            ico_sizes: vec![16, 24, 32, 48, 64, 128, 256],
+            png: true,
+            icns: true,
+            sharpen: true,
+            ios_background: [255, 255, 255],
+        }
+    }
+}
+
+/// Renders each size once and hands out its PNG bytes.
+struct Renderer<'a> {
+    source: &'a DynamicImage,
+    sharpen: bool,
+    background: [u8; 3],
+    rendered: BTreeMap<(u32, bool), Vec<u8>>,
+}
+
+impl Renderer<'_> {
+    /// `opaque` flattens the icon onto the background
+    fn png(&mut self, size: u32, opaque: bool) -> Result<&[u8], AppError> {
+        if !self.rendered.contains_key(&(size, opaque)) {
+            let options = ResizeOptions {
+                mode: ResizeMode::Pad,
+                ..ResizeOptions::default()
+            };
+            let mut img = operations::resize_dynamic(self.source, size, size, "lanczos", &options)?;
+            if self.sharpen && size < SHARPEN_BELOW {
+                img = img.unsharpen(0.6, 1);
+            }
+            let img = if opaque {
+                let matte = AlphaOptions {
+                    color: self.background,
+                    ..AlphaOptions::default()
+                };
+                DynamicImage::ImageRgb8(alpha::flatten(&img, &matte).to_rgb8())
+            } else {
+                DynamicImage::ImageRgba8(img.to_rgba8())
+            };
+            let mut bytes = Vec::new();
+            img.write_to(
+                &mut std::io::Cursor::new(&mut bytes),
+                image::ImageFormat::Png,
+            )
+            .map_err(|e| AppError::SaveFailed(e.to_string()))?;
+            self.rendered.insert((size, opaque), bytes);
+        }
+        Ok(&self.rendered[&(size, opaque)])
+    }
+}
+
+fn write(path: &Path, bytes: &[u8]) -> Result<String, AppError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, bytes).map_err(|e| AppError::SaveFailed(e.to_string()))?;
+    Ok(path.to_string_lossy().into_owned())
+}
+
+fn encode_ico(renderer: &mut Renderer, sizes: &[u32]) -> Result<Vec<u8>, AppError> {
+    let mut sizes = sizes.to_vec();
+    sizes.sort_unstable();
+    sizes.dedup();
+    if sizes.is_empty() || sizes.iter().any(|s| !(1..=256).contains(s)) {
+        return Err(AppError::General(
+            "ICO sizes must be between 1 and 256".into(),
+        ));
+    }
+    let mut frames = Vec::with_capacity(sizes.len());
+    for &size in &sizes {
+        let png = renderer.png(size, false)?.to_vec();
+        frames.push(
+            IcoFrame::with_encoded(png, size, size, image::ExtendedColorType::Rgba8)
+                .map_err(|e| AppError::SaveFailed(e.to_string()))?,
+        );
+    }
+    let mut bytes = Vec::new();
+    IcoEncoder::new(&mut bytes)
+        .encode_images(&frames)
+        .map_err(|e| AppError::SaveFailed(e.to_string()))?;
+    Ok(bytes)
+}
+
+/// ICNS with PNG entries: `icns`, total length, then type + length + data.
+fn encode_icns(renderer: &mut Renderer) -> Result<Vec<u8>, AppError> {
+    let mut entries = Vec::new();
+    for &(kind, size) in ICNS_TYPES {
+        let png = renderer.png(size, false)?;
+        entries.extend_from_slice(kind);
+        entries.extend_from_slice(&(png.len() as u32 + 8).to_be_bytes());
+        entries.extend_from_slice(png);
+    }
+    let mut bytes = Vec::with_capacity(entries.len() + 8);
+    bytes.extend_from_slice(b"icns");
+    bytes.extend_from_slice(&(entries.len() as u32 + 8).to_be_bytes());
+    bytes.extend_from_slice(&entries);
+    Ok(bytes)
+}
+
+fn web_manifest() -> String {
+    let icons: Vec<serde_json::Value> = [192, 512]
+        .iter()
+        .map(|size| {
+            serde_json::json!({
+                "src": format!("android-chrome-{size}x{size}.png"),
+                "sizes": format!("{size}x{size}"),
+                "type": "image/png",
+            })
+        })
+        .collect();
+    serde_json::to_string_pretty(&serde_json::json!({ "icons": icons })).unwrap_or_default()
+}
+
+/// Write the icon set into `output_dir` and return the files written.
+#[tauri::command]
+pub fn export_icon_set(
+    source_path: String,
+    output_dir: String,
+    options: Option<IconSetOptions>,
+) -> Result<Vec<String>, AppError> {
+    let options = options.unwrap_or_default();
+    let source = operations::open_image(&source_path)?;
+    let output_dir = Path::new(&output_dir);
+    std::fs::create_dir_all(output_dir)?;
+    let mut renderer = Renderer {
+        source: &source,
+        sharpen: options.sharpen,
+        background: options.ios_background,
+        rendered: BTreeMap::new(),
+    };
+
+    let mut written = Vec::new();
+    if options.ico {
+        let ico = encode_ico(&mut renderer, &options.ico_sizes)?;
+        written.push(write(&output_dir.join("favicon.ico"), &ico)?);
+    }
+    if options.png {
+        for &(name, size, opaque) in PNG_ICONS {
+            let png = renderer.png(size, opaque)?;
+            written.push(write(&output_dir.join(name), png)?);
+        }
+        written.push(write(
+            &output_dir.join("web/site.webmanifest"),
+            web_manifest().as_bytes(),
+        )?);
+    }
+    if options.icns {
+        let icns = encode_icns(&mut renderer)?;
+        written.push(write(&output_dir.join("icon.icns"), &icns)?);
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn icon_set_writes_ico_pngs_and_icns() {
+        // Wider than tall, so every icon is padded to a square
+        let img = image::RgbaImage::from_fn(80, 40, |x, y| {
+            image::Rgba([(x * 3) as u8, (y * 6) as u8, 120, 255])
+        });
+        let id = uuid::Uuid::new_v4();
+        let src = std::env::temp_dir().join(format!("pixelforge_icon_src_{id}.png"));
+        img.save(&src).unwrap();
+        let dir = std::env::temp_dir().join(format!("pixelforge_icons_{id}"));
+
+        let written = export_icon_set(
+            src.to_string_lossy().into_owned(),
+            dir.to_string_lossy().into_owned(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(written.len(), PNG_ICONS.len() + 3);
+
+        let ico = std::fs::read(dir.join("favicon.ico")).unwrap();
+        assert_eq!(&ico[..4], &[0, 0, 1, 0]);
+        assert_eq!(u16::from_le_bytes([ico[4], ico[5]]), 7);
+        let largest = image::load_from_memory(&ico).unwrap();
+        assert_eq!((largest.width(), largest.height()), (256, 256));
+
+        for &(name, size, opaque) in PNG_ICONS {
+            let icon = image::open(dir.join(name)).unwrap();
+            assert_eq!(icon.color().has_alpha(), !opaque, "{name}");
+            let icon = icon.to_rgba8();
+            assert_eq!(icon.dimensions(), (size, size), "{name}");
+            // The padding is transparent, or white behind iOS icons
+            let padding = if opaque { [255; 4] } else { [0; 4] };
+            assert_eq!(icon.get_pixel(size / 2, 0).0, padding, "{name}");
+            assert_eq!(icon.get_pixel(size / 2, size / 2)[3], 255, "{name}");
+        }
+        let manifest = std::fs::read_to_string(dir.join("web/site.webmanifest")).unwrap();
+        assert!(manifest.contains("android-chrome-512x512.png"));
+
+        let icns = std::fs::read(dir.join("icon.icns")).unwrap();
+        assert_eq!(&icns[..4], b"icns");
+        assert_eq!(
+            u32::from_be_bytes(icns[4..8].try_into().unwrap()) as usize,
+            icns.len()
+        );
+        let mut offset = 8;
+        let mut kinds = Vec::new();
+        while offset < icns.len() {
+            kinds.push(icns[offset..offset + 4].to_vec());
+            let length = u32::from_be_bytes(icns[offset + 4..offset + 8].try_into().unwrap());
+            let png = image::load_from_memory(&icns[offset + 8..offset + length as usize]).unwrap();
+            assert_eq!(png.width(), png.height());
+            offset += length as usize;
+        }
+        assert_eq!(offset, icns.len());
+        assert_eq!(kinds.len(), ICNS_TYPES.len());
+
+        let navy = IconSetOptions {
+            ico: false,
+            icns: false,
+            ios_background: [10, 20, 90],
+            ..IconSetOptions::default()
+        };
+        export_icon_set(
+            src.to_string_lossy().into_owned(),
+            dir.to_string_lossy().into_owned(),
+            Some(navy),
+        )
+        .unwrap();
+        let touch = image::open(dir.join("web/apple-touch-icon.png")).unwrap();
+        assert_eq!(touch.to_rgb8().get_pixel(90, 0).0, [10, 20, 90]);
+
+        let bad = IconSetOptions {
+            ico_sizes: vec![16, 512],
+            ..IconSetOptions::default()
+        };
+        assert!(export_icon_set(
+            src.to_string_lossy().into_owned(),
+            dir.to_string_lossy().into_owned(),
+            Some(bad),
+        )
+        .is_err());
+
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::remove_file(&src).ok();
+    }
+}
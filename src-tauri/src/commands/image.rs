@@ -1,5 +1,9 @@
+use crate::commands::{animation, heif, multipage, operations, raw};
 use crate::error::AppError;
+use image::metadata::Orientation;
+use image::{ImageDecoder, ImageFormat};
 use serde::Serialize;
+use std::io::Read;
 use std::path::Path;
 use tauri::ipc::{InvokeResponseBody, Response};
 
@@ -13,24 +17,147 @@ pub struct ImageInfo {
     pub file_name: String,
     pub file_path: String,
     pub needs_conversion: bool,
+    /// The file extension names a different format than the content
+    pub extension_mismatch: bool,
+    /// Frames of an animated GIF, APNG or WebP; 1 for stills
+    pub frame_count: u32,
+    /// Pages of a multi-page TIFF; 1 for everything else
+    pub page_count: u32,
 }
 
-fn detect_format(path: &Path) -> Result<(String, bool), AppError> {
-    let ext = path
-        .extension()
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    Image(ImageFormat),
+    /// Camera RAW, by container name (DNG, CR2, ...)
+    Raw(&'static str),
+    Heif,
+}
+
+impl FileFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            FileFormat::Image(format) => match format {
+                ImageFormat::Png => "PNG",
+                ImageFormat::Jpeg => "JPEG",
+                ImageFormat::Gif => "GIF",
+                ImageFormat::WebP => "WebP",
+                ImageFormat::Pnm => "PNM",
+                ImageFormat::Tiff => "TIFF",
+                ImageFormat::Tga => "TGA",
+                ImageFormat::Dds => "DDS",
+                ImageFormat::Bmp => "BMP",
+                ImageFormat::Ico => "ICO",
+                ImageFormat::Hdr => "HDR",
+                ImageFormat::OpenExr => "EXR",
+                ImageFormat::Farbfeld => "Farbfeld",
+                ImageFormat::Avif => "AVIF",
+                ImageFormat::Qoi => "QOI",
+                _ => "Unknown",
+            },
+            FileFormat::Raw(name) => name,
+            FileFormat::Heif => "HEIF",
+        }
+    }
+
+    /// Whether the webview can't display the file directly and needs a PNG.
+    pub fn needs_conversion(self) -> bool {
+        !matches!(
+            self,
+            FileFormat::Image(
+                ImageFormat::Png
+                    | ImageFormat::Jpeg
+                    | ImageFormat::Gif
+                    | ImageFormat::WebP
+                    | ImageFormat::Bmp
+                    | ImageFormat::Ico
+                    | ImageFormat::Avif
+            )
+        )
+    }
+
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        let ext = ext.to_lowercase();
+        if let Some(raw) = raw::RAW_EXTENSIONS.iter().find(|&&e| e == ext) {
+            return Some(FileFormat::Raw(raw::format_name(raw)));
+        }
+        if heif::HEIF_EXTENSIONS.contains(&ext.as_str()) {
+            return Some(FileFormat::Heif);
+        }
+        // `image` knows .ff for Farbfeld but not the long form
+        match ext.as_str() {
+            "farbfeld" => Some(FileFormat::Image(ImageFormat::Farbfeld)),
+            _ => ImageFormat::from_extension(&ext).map(FileFormat::Image),
+        }
+    }
+}
+
+/// Bytes read from the start of a file for sniffing; RAW containers need
+/// their first IFD, which cameras put near the header.
+const SNIFF_LEN: u64 = 64 * 1024;
+
+/// Identify a file from its leading bytes. `ext` only tells RAW flavours
+/// that share the plain TIFF layout apart from ordinary TIFFs.
+pub fn sniff_format(bytes: &[u8], ext: &str) -> Option<FileFormat> {
+    if let Some(name) = raw::sniff(bytes, ext) {
+        return Some(FileFormat::Raw(name));
+    }
+    if heif::sniff(bytes) {
+        return Some(FileFormat::Heif);
+    }
+    image::guess_format(bytes).ok().map(FileFormat::Image)
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
         .and_then(|e| e.to_str())
         .map(|e| e.to_lowercase())
-        .unwrap_or_default();
-
-    match ext.as_str() {
-        "jpg" | "jpeg" => Ok(("JPEG".into(), false)),
-        "png" => Ok(("PNG".into(), false)),
-        "webp" => Ok(("WebP".into(), false)),
-        "avif" => Ok(("AVIF".into(), false)),
-        "gif" => Ok(("GIF".into(), false)),
-        "bmp" => Ok(("BMP".into(), false)),
-        "tiff" | "tif" => Ok(("TIFF".into(), true)),
-        other => Err(AppError::UnsupportedFormat(other.to_string())),
+        .unwrap_or_default()
+}
+
+/// Format of a file by content. TGA has no signature, so it's the one format
+/// still taken from the extension.
+pub fn file_format(path: &Path) -> Result<FileFormat, AppError> {
+    let mut bytes = Vec::new();
+    std::fs::File::open(path)?
+        .take(SNIFF_LEN)
+        .read_to_end(&mut bytes)?;
+    let ext = extension(path);
+    if raw::is_cr3(&bytes) {
+        return Err(AppError::UnsupportedFormat(
+            "Canon CR3 RAW files can't be opened yet; convert them to DNG first".into(),
+        ));
+    }
+    sniff_format(&bytes, &ext)
+        .or_else(|| (ext == "tga").then_some(FileFormat::Image(ImageFormat::Tga)))
+        .ok_or_else(|| {
+            AppError::UnsupportedFormat(if ext.is_empty() {
+                "unrecognized image data".into()
+            } else {
+                ext
+            })
+        })
+}
+
+/// Dimensions as displayed, i.e. swapped when the EXIF orientation rotates
+/// the image by 90°.
+fn oriented_dimensions(path: &Path, format: FileFormat) -> Result<(u32, u32), AppError> {
+    let format = match format {
+        FileFormat::Raw(_) => return raw::dimensions(path),
+        FileFormat::Heif => return heif::dimensions(path),
+        FileFormat::Image(format) => format,
+    };
+    let mut reader = image::ImageReader::open(path)?;
+    reader.set_format(format);
+    let mut decoder = reader.into_decoder()?;
+    let (width, height) = decoder.dimensions();
+    match decoder.orientation() {
+        Ok(
+            Orientation::Rotate90
+            | Orientation::Rotate270
+            | Orientation::Rotate90FlipH
+            | Orientation::Rotate270FlipH,
+        ) => Ok((height, width)),
+        _ => Ok((width, height)),
     }
 }
 
@@ -39,9 +166,13 @@ pub fn get_image_info(path: String) -> Result<ImageInfo, AppError> {
     let file_path = Path::new(&path);
 
     let metadata = std::fs::metadata(file_path)?;
-    let (format, needs_conversion) = detect_format(file_path)?;
-    let (width, height) =
-        image::image_dimensions(file_path).map_err(|e| AppError::ImageDecode(e.to_string()))?;
+    let format = file_format(file_path)?;
+    let ext = extension(file_path);
+    let extension_mismatch = !ext.is_empty()
+        && FileFormat::from_extension(&ext).map(FileFormat::name) != Some(format.name());
+    let (width, height) = oriented_dimensions(file_path, format)?;
+    let frame_count = animation::frame_count(file_path)?;
+    let page_count = multipage::page_count(file_path)?;
 
     let file_name = file_path
         .file_name()
@@ -52,17 +183,21 @@ pub fn get_image_info(path: String) -> Result<ImageInfo, AppError> {
     Ok(ImageInfo {
         width,
         height,
-        format,
+        format: format.name().into(),
         file_size_bytes: metadata.len(),
         file_name,
         file_path: path,
-        needs_conversion,
+        needs_conversion: format.needs_conversion(),
+        extension_mismatch,
+        frame_count,
+        page_count,
     })
 }
 
 #[tauri::command]
 pub fn convert_image(path: String) -> Result<Response, AppError> {
-    let img = image::open(&path)?;
+    let img = operations::open_image(&path)?;
+    let img = operations::png_compatible(&img).unwrap_or(img);
     let mut bytes: Vec<u8> = Vec::new();
     img.write_to(
         &mut std::io::Cursor::new(&mut bytes),
@@ -84,29 +219,87 @@ mod tests {
     }
 
     #[test]
-    fn detect_format_known() {
+    fn extension_format_known() {
         let cases = vec![
-            ("test.jpg", "JPEG", false),
-            ("test.jpeg", "JPEG", false),
-            ("test.png", "PNG", false),
-            ("test.webp", "WebP", false),
-            ("test.gif", "GIF", false),
-            ("test.bmp", "BMP", false),
-            ("test.tiff", "TIFF", true),
-            ("test.tif", "TIFF", true),
-            ("test.avif", "AVIF", false),
+            ("jpg", "JPEG", false),
+            ("jpeg", "JPEG", false),
+            ("png", "PNG", false),
+            ("webp", "WebP", false),
+            ("gif", "GIF", false),
+            ("bmp", "BMP", false),
+            ("tiff", "TIFF", true),
+            ("tif", "TIFF", true),
+            ("avif", "AVIF", false),
+            ("dng", "DNG", true),
+            ("NEF", "NEF", true),
+            ("heic", "HEIF", true),
+            ("qoi", "QOI", true),
+            ("tga", "TGA", true),
+            ("ppm", "PNM", true),
+            ("dds", "DDS", true),
+            ("ico", "ICO", false),
+            ("hdr", "HDR", true),
+            ("exr", "EXR", true),
+            ("ff", "Farbfeld", true),
         ];
-        for (name, expected_fmt, expected_conv) in cases {
-            let (fmt, conv) = detect_format(Path::new(name)).unwrap();
-            assert_eq!(fmt, expected_fmt, "failed for {}", name);
-            assert_eq!(conv, expected_conv, "failed for {}", name);
+        for (ext, expected_fmt, expected_conv) in cases {
+            let format = FileFormat::from_extension(ext).unwrap();
+            assert_eq!(format.name(), expected_fmt, "failed for {}", ext);
+            assert_eq!(
+                format.needs_conversion(),
+                expected_conv,
+                "failed for {}",
+                ext
+            );
         }
     }
 
     #[test]
-    fn detect_format_unknown() {
-        let result = detect_format(Path::new("test.xyz"));
-        assert!(result.is_err());
+    fn extension_format_unknown() {
+        assert!(FileFormat::from_extension("xyz").is_none());
+    }
+
+    #[test]
+    fn get_image_info_sniffs_content() {
+        let dir = std::env::temp_dir();
+        let img = image::DynamicImage::ImageRgb8(image::RgbImage::new(3, 2));
+
+        // A PNG saved as .jpg, and the same bytes without an extension
+        let mut png = Vec::new();
+        img.write_to(&mut std::io::Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        for name in ["pixelforge_sniff_mislabeled.jpg", "pixelforge_sniff_noext"] {
+            let path = dir.join(name);
+            std::fs::write(&path, &png).unwrap();
+            let info = get_image_info(path.to_string_lossy().into()).unwrap();
+            assert_eq!(info.format, "PNG");
+            assert_eq!(info.extension_mismatch, name.ends_with(".jpg"));
+            assert_eq!((info.width, info.height), (3, 2));
+            std::fs::remove_file(&path).ok();
+        }
+
+        for (format, ext, name) in [
+            (ImageFormat::Qoi, "qoi", "QOI"),
+            (ImageFormat::Pnm, "ppm", "PNM"),
+            (ImageFormat::Farbfeld, "ff", "Farbfeld"),
+            (ImageFormat::Ico, "ico", "ICO"),
+            (ImageFormat::Tga, "tga", "TGA"),
+            (ImageFormat::OpenExr, "exr", "EXR"),
+        ] {
+            let path = dir.join(format!("pixelforge_sniff.{ext}"));
+            let img = if format == ImageFormat::OpenExr {
+                image::DynamicImage::ImageRgb32F(img.to_rgb32f())
+            } else if format == ImageFormat::Farbfeld {
+                image::DynamicImage::ImageRgba16(img.to_rgba16())
+            } else {
+                img.clone()
+            };
+            img.save_with_format(&path, format).unwrap();
+            let info = get_image_info(path.to_string_lossy().into()).unwrap();
+            assert_eq!(info.format, name);
+            assert!(!info.extension_mismatch, "{name}");
+            std::fs::remove_file(&path).ok();
+        }
     }
 
     #[test]
@@ -126,4 +319,30 @@ mod tests {
 
         std::fs::remove_file(&path).ok();
     }
+
+    #[test]
+    fn get_image_info_reports_oriented_dimensions() {
+        let field = exif::Field {
+            tag: exif::Tag::Orientation,
+            ifd_num: exif::In::PRIMARY,
+            value: exif::Value::Short(vec![8]),
+        };
+        let mut writer = exif::experimental::Writer::new();
+        writer.push_field(&field);
+        let mut tiff = std::io::Cursor::new(Vec::new());
+        writer.write(&mut tiff, true).unwrap();
+
+        let path = std::env::temp_dir().join("pixelforge_test_oriented.jpg");
+        let mut file = std::fs::File::create(&path).unwrap();
+        let mut encoder = image::codecs::jpeg::JpegEncoder::new(&mut file);
+        image::ImageEncoder::set_exif_metadata(&mut encoder, tiff.into_inner()).unwrap();
+        image::DynamicImage::ImageRgb8(image::RgbImage::new(6, 2))
+            .write_with_encoder(encoder)
+            .unwrap();
+
+        let info = get_image_info(path.to_string_lossy().into()).unwrap();
+        assert_eq!((info.width, info.height), (2, 6));
+
+        std::fs::remove_file(&path).ok();
+    }
 }
@@ -0,0 +1,159 @@
+use crate::error::AppError;
+use image::DynamicImage;
+use jpeg_encoder::{ColorType, Encoder, SamplingFactor};
+use serde::Deserialize;
+
+// ── JPEG encoding ───────────────────────────────────────────────────
+//
+// The image crate writes baseline 4:4:4 JPEGs with the standard Huffman
+// tables. jpeg-encoder adds chroma subsampling, Huffman tables built for the
+// image at hand, and progressive scans, which is what web pages want for
+// large photos.
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+pub enum ChromaSubsampling {
+    #[serde(rename = "444")]
+    Yuv444,
+    #[serde(rename = "422")]
+    Yuv422,
+    #[default]
+    #[serde(rename = "420")]
+    Yuv420,
+}
+
+impl ChromaSubsampling {
+    fn sampling_factor(self) -> SamplingFactor {
+        match self {
+            ChromaSubsampling::Yuv444 => SamplingFactor::R_4_4_4,
+            ChromaSubsampling::Yuv422 => SamplingFactor::R_4_2_2,
+            ChromaSubsampling::Yuv420 => SamplingFactor::R_4_2_0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct JpegOptions {
+    /// 1–100; `None` uses the export's quality
+    pub quality: Option<u8>,
+    pub progressive: bool,
+    pub chroma_subsampling: ChromaSubsampling,
+    /// Build Huffman tables from the image's own statistics. Progressive
+    /// files always do, since the standard tables lack end-of-band runs;
+    /// baseline 4:2:0 files never do (see `encode`).
+    pub optimize: bool,
+}
+
+impl Default for JpegOptions {
+    fn default() -> Self {
+        Self {
+            quality: None,
+            progressive: false,
+            chroma_subsampling: ChromaSubsampling::Yuv420,
+            optimize: true,
+        }
+    }
+}
+
+/// Encode as JPEG at `quality` (1–100). Alpha is dropped and gray images
+/// stay single-channel, as with the image crate's encoder.
+pub fn encode(img: &DynamicImage, quality: u8, options: &JpegOptions) -> Result<Vec<u8>, AppError> {
+    let (width, height) = (img.width(), img.height());
+    if width == 0 || height == 0 || width > 0xFFFF || height > 0xFFFF {
+        return Err(AppError::SaveFailed(format!(
+            "JPEG cannot hold a {width}x{height} image"
+        )));
+    }
+
+    let mut bytes = Vec::new();
+    let mut encoder = Encoder::new(&mut bytes, quality.clamp(1, 100));
+    encoder.set_progressive(options.progressive);
+    // Optimized baseline files are written one scan per component, which
+    // the image crate's decoder misreads when chroma is halved vertically
+    let baseline_420 =
+        !options.progressive && options.chroma_subsampling == ChromaSubsampling::Yuv420;
+    encoder
+        .set_optimized_huffman_tables(options.progressive || (options.optimize && !baseline_420));
+    encoder.set_sampling_factor(options.chroma_subsampling.sampling_factor());
+    if img.color().has_color() {
+        let rgb = img.to_rgb8();
+        encoder.encode(rgb.as_raw(), width as u16, height as u16, ColorType::Rgb)
+    } else {
+        let luma = img.to_luma8();
+        encoder.encode(luma.as_raw(), width as u16, height as u16, ColorType::Luma)
+    }
+    .map_err(AppError::save_failed)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn photo() -> DynamicImage {
+        // Smooth gradients with a hard edge, 37x29 so no dimension fills an MCU
+        DynamicImage::ImageRgb8(image::RgbImage::from_fn(37, 29, |x, y| {
+            let edge = if x > 20 { 120 } else { 0 };
+            image::Rgb([(x * 6) as u8, (y * 8) as u8, (edge + x * 3) as u8])
+        }))
+    }
+
+    fn psnr(a: &image::RgbImage, b: &image::RgbImage) -> f64 {
+        let mse = a
+            .as_raw()
+            .iter()
+            .zip(b.as_raw())
+            .map(|(&x, &y)| (f64::from(x) - f64::from(y)).powi(2))
+            .sum::<f64>()
+            / a.as_raw().len() as f64;
+        10.0 * (255.0 * 255.0 / mse.max(1e-9)).log10()
+    }
+
+    #[test]
+    fn every_mode_decodes_close_to_the_source() {
+        let img = photo();
+        for progressive in [false, true] {
+            for optimize in [false, true] {
+                for chroma_subsampling in [
+                    ChromaSubsampling::Yuv444,
+                    ChromaSubsampling::Yuv422,
+                    ChromaSubsampling::Yuv420,
+                ] {
+                    let options = JpegOptions {
+                        quality: None,
+                        progressive,
+                        chroma_subsampling,
+                        optimize,
+                    };
+                    let bytes = encode(&img, 90, &options).unwrap();
+                    let decoded =
+                        image::load_from_memory_with_format(&bytes, image::ImageFormat::Jpeg)
+                            .unwrap();
+                    assert_eq!((decoded.width(), decoded.height()), (37, 29));
+                    let score = psnr(&img.to_rgb8(), &decoded.to_rgb8());
+                    assert!(score > 28.0, "{options:?}: {score:.1} dB");
+                }
+            }
+        }
+
+        let gray = DynamicImage::ImageLuma8(img.to_luma8());
+        let bytes = encode(&gray, 90, &JpegOptions::default()).unwrap();
+        let decoded = image::load_from_memory(&bytes).unwrap();
+        assert_eq!(decoded.color(), image::ColorType::L8);
+    }
+
+    #[test]
+    fn optimized_tables_and_lower_quality_shrink_files() {
+        let img = photo();
+        let size = |quality, optimize| {
+            let options = JpegOptions {
+                optimize,
+                chroma_subsampling: ChromaSubsampling::Yuv444,
+                ..JpegOptions::default()
+            };
+            encode(&img, quality, &options).unwrap().len()
+        };
+        assert!(size(90, true) < size(90, false));
+        assert!(size(40, true) < size(90, true));
+    }
+}
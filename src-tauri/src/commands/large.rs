@@ -0,0 +1,1284 @@
+use crate::commands::image::{file_format, FileFormat};
+use crate::commands::operations::{linear_to_srgb, srgb_to_linear};
+use crate::error::AppError;
+use image::metadata::Orientation;
+use image::{imageops, ImageBuffer, ImageDecoder, ImageFormat, Rgba};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tauri::{AppHandle, Emitter};
+use tiff::decoder::{ChunkType, Decoder as TiffDecoder, DecodingResult};
+use tiff::encoder::{colortype, TiffEncoder};
+use tiff::tags::Tag;
+
+// ── Large images ────────────────────────────────────────────────────
+//
+// Scans and stitched panoramas can be larger than memory once decoded. PNG
+// and TIFF are streamed instead: a band of rows is read, run through the
+// steps and written before the next is read. Whole-image decodes elsewhere
+// check the same memory budget first, so they fail with a clear error
+// instead of an allocation failure.
+
+pub const DEFAULT_MEMORY_BUDGET: u64 = 4 << 30;
+/// Smallest budget `set_memory_budget` accepts
+const MIN_MEMORY_BUDGET: u64 = 64 << 20;
+
+static MEMORY_BUDGET: AtomicU64 = AtomicU64::new(DEFAULT_MEMORY_BUDGET);
+
+/// Rows of RGBA16 pixels, which every step works on whatever the file holds.
+type Strip = ImageBuffer<Rgba<u16>, Vec<u16>>;
+
+pub fn memory_budget() -> u64 {
+    MEMORY_BUDGET.load(Ordering::Relaxed)
+}
+
+fn mib(bytes: u64) -> String {
+    format!("{} MiB", bytes.div_ceil(1 << 20))
+}
+
+fn within(bytes: u64, budget: u64, what: &str) -> Result<(), AppError> {
+    if bytes > budget {
+        return Err(AppError::General(format!(
+            "{what} needs {}, over the {} memory budget",
+            mib(bytes),
+            mib(budget)
+        )));
+    }
+    Ok(())
+}
+
+/// Fail before allocating `bytes` when that would exceed the memory budget.
+pub fn check_budget(bytes: u64, what: &str) -> Result<(), AppError> {
+    within(bytes, memory_budget(), what)
+}
+
+#[tauri::command]
+pub fn get_memory_budget() -> u64 {
+    memory_budget()
+}
+
+#[tauri::command]
+pub fn set_memory_budget(bytes: u64) -> Result<(), AppError> {
+    if bytes < MIN_MEMORY_BUDGET {
+        return Err(AppError::General(format!(
+            "The memory budget must be at least {}",
+            mib(MIN_MEMORY_BUDGET)
+        )));
+    }
+    MEMORY_BUDGET.store(bytes, Ordering::Relaxed);
+    Ok(())
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "op")]
+pub enum LargeStep {
+    Crop {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    FlipHorizontal,
+    /// -255 to 255 on the 8-bit scale, as `apply_brightness`
+    Brightness {
+        value: i32,
+    },
+    /// -100 to 100, as `apply_contrast`
+    Contrast {
+        value: f32,
+    },
+    /// Average each `factor`×`factor` block in linear light
+    Downscale {
+        factor: u32,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LargeImageOptions {
+    /// Output rows processed and written at a time; also the TIFF strip height
+    pub strip_rows: u32,
+}
+
+impl Default for LargeImageOptions {
+    fn default() -> Self {
+        Self { strip_rows: 256 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LargeImageResult {
+    pub width: u32,
+    pub height: u32,
+    /// Estimated peak memory of the run
+    pub working_bytes: u64,
+}
+
+fn decode_failed(e: impl std::fmt::Display) -> AppError {
+    AppError::ImageDecode(e.to_string())
+}
+
+/// Widen gray, gray+alpha, RGB or RGBA samples to RGBA16.
+fn expand(width: u32, rows: u32, channels: usize, samples: &[u16]) -> Strip {
+    let mut out = Vec::with_capacity(width as usize * rows as usize * 4);
+    for px in samples.chunks_exact(channels) {
+        match *px {
+            [g] => out.extend_from_slice(&[g, g, g, u16::MAX]),
+            [g, a] => out.extend_from_slice(&[g, g, g, a]),
+            [r, g, b] => out.extend_from_slice(&[r, g, b, u16::MAX]),
+            _ => out.extend_from_slice(&px[..4]),
+        }
+    }
+    Strip::from_raw(width, rows, out).unwrap_or_else(|| Strip::new(width, 0))
+}
+
+/// RGBA16 samples narrowed back to `channels` (1–4).
+fn narrow_u16(samples: &[u16], channels: usize) -> Vec<u16> {
+    let mut out = Vec::with_capacity(samples.len() / 4 * channels);
+    for px in samples.chunks_exact(4) {
+        match channels {
+            1 => out.push(px[0]),
+            2 => out.extend_from_slice(&[px[0], px[3]]),
+            _ => out.extend_from_slice(&px[..channels]),
+        }
+    }
+    out
+}
+
+fn narrow_u8(samples: &[u16], channels: usize) -> Vec<u8> {
+    narrow_u16(samples, channels)
+        .into_iter()
+        .map(|v| ((u32::from(v) + 128) / 257) as u8)
+        .collect()
+}
+
+/// Decodes an image a band of rows at a time.
+trait StripSource {
+    fn dimensions(&self) -> (u32, u32);
+    /// 1–4: gray, gray+alpha, RGB, RGBA
+    fn channels(&self) -> usize;
+    fn sixteen_bit(&self) -> bool;
+    /// Most rows one `next_strip` returns when asked for `rows`
+    fn band_rows(&self, rows: u32) -> u32;
+    fn next_strip(&mut self, rows: u32) -> Result<Option<Strip>, AppError>;
+}
+
+/// Non-interlaced PNG, read row by row.
+struct PngSource {
+    reader: png::Reader<BufReader<File>>,
+    width: u32,
+    height: u32,
+    channels: usize,
+    sixteen: bool,
+    row: u32,
+}
+
+impl PngSource {
+    fn open(path: &Path) -> Result<Self, AppError> {
+        let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+        // Palette, low bit depths and tRNS become plain 8-bit samples
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let reader = decoder.read_info().map_err(decode_failed)?;
+        if reader.info().interlaced {
+            return Err(AppError::UnsupportedFormat(
+                "Interlaced PNGs can't be read in strips".into(),
+            ));
+        }
+        let (color, depth) = reader.output_color_type();
+        let (width, height) = (reader.info().width, reader.info().height);
+        Ok(Self {
+            reader,
+            width,
+            height,
+            channels: color.samples(),
+            sixteen: depth == png::BitDepth::Sixteen,
+            row: 0,
+        })
+    }
+}
+
+impl StripSource for PngSource {
+    fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn channels(&self) -> usize {
+        self.channels
+    }
+
+    fn sixteen_bit(&self) -> bool {
+        self.sixteen
+    }
+
+    fn band_rows(&self, rows: u32) -> u32 {
+        rows
+    }
+
+    fn next_strip(&mut self, rows: u32) -> Result<Option<Strip>, AppError> {
+        let rows = rows.min(self.height - self.row);
+        if rows == 0 {
+            return Ok(None);
+        }
+        let mut samples = Vec::with_capacity(self.width as usize * rows as usize * self.channels);
+        for _ in 0..rows {
+            let row = self
+                .reader
+                .next_row()
+                .map_err(decode_failed)?
+                .ok_or_else(|| AppError::ImageDecode("PNG ended early".into()))?;
+            if self.sixteen {
+                samples.extend(
+                    row.data()
+                        .chunks_exact(2)
+                        .map(|b| u16::from_be_bytes([b[0], b[1]])),
+                );
+            } else {
+                samples.extend(row.data().iter().map(|&v| u16::from(v) * 257));
+            }
+        }
+        self.row += rows;
+        Ok(Some(expand(self.width, rows, self.channels, &samples)))
+    }
+}
+
+/// Stripped or tiled TIFF, read a strip or a row of tiles at a time.
+struct TiffSource {
+    decoder: TiffDecoder<BufReader<File>>,
+    width: u32,
+    height: u32,
+    channels: usize,
+    sixteen: bool,
+    white_is_zero: bool,
+    chunk: (u32, u32),
+    /// Index of the next strip or row of tiles
+    band: u32,
+    /// Set when the pixels are one uncompressed strip
+    raw: Option<RawStrip>,
+}
+
+/// An uncompressed image stored as one strip, read by rows straight from
+/// the file instead of as a single chunk the size of the image.
+struct RawStrip {
+    file: BufReader<File>,
+    offset: u64,
+    big_endian: bool,
+    /// Next row to read
+    row: u32,
+}
+
+impl TiffSource {
+    fn open(path: &Path) -> Result<Self, AppError> {
+        let mut decoder =
+            TiffDecoder::new(BufReader::new(File::open(path)?)).map_err(decode_failed)?;
+        let (width, height) = decoder.dimensions().map_err(decode_failed)?;
+        let (channels, bits) = match decoder.colortype().map_err(decode_failed)? {
+            tiff::ColorType::Gray(bits) => (1, bits),
+            tiff::ColorType::GrayA(bits) => (2, bits),
+            tiff::ColorType::RGB(bits) => (3, bits),
+            tiff::ColorType::RGBA(bits) => (4, bits),
+            other => {
+                return Err(AppError::UnsupportedFormat(format!(
+                    "{other:?} TIFFs can't be read in strips"
+                )))
+            }
+        };
+        if !matches!(bits, 8 | 16) {
+            return Err(AppError::UnsupportedFormat(format!(
+                "{bits}-bit TIFFs can't be read in strips"
+            )));
+        }
+        let tag = |decoder: &mut TiffDecoder<_>, tag| {
+            decoder.find_tag_unsigned::<u16>(tag).map_err(decode_failed)
+        };
+        if tag(&mut decoder, Tag::PlanarConfiguration)? == Some(2) {
+            return Err(AppError::UnsupportedFormat(
+                "Planar TIFFs can't be read in strips".into(),
+            ));
+        }
+        let white_is_zero = tag(&mut decoder, Tag::PhotometricInterpretation)? == Some(0);
+        let chunk = decoder.chunk_dimensions();
+        let single_strip = decoder.get_chunk_type() == ChunkType::Strip && chunk.1 >= height;
+        let uncompressed = tag(&mut decoder, Tag::Compression)?.unwrap_or(1) == 1;
+        let raw = if single_strip && uncompressed {
+            let offset = decoder
+                .find_tag_unsigned::<u64>(Tag::StripOffsets)
+                .map_err(decode_failed)?
+                .ok_or_else(|| AppError::ImageDecode("TIFF has no strip offsets".into()))?;
+            let mut file = BufReader::new(File::open(path)?);
+            let mut order = [0; 2];
+            file.read_exact(&mut order)?;
+            Some(RawStrip {
+                file,
+                offset,
+                big_endian: &order == b"MM",
+                row: 0,
+            })
+        } else {
+            None
+        };
+        Ok(Self {
+            decoder,
+            width,
+            height,
+            channels,
+            sixteen: bits == 16,
+            white_is_zero,
+            chunk,
+            band: 0,
+            raw,
+        })
+    }
+
+    /// Up to `rows` rows of an uncompressed single strip.
+    fn read_rows(&mut self, rows: u32) -> Result<Option<(u32, Vec<u16>)>, AppError> {
+        let Some(raw) = &mut self.raw else {
+            return Ok(None);
+        };
+        let rows = rows.min(self.height - raw.row);
+        if rows == 0 {
+            return Ok(None);
+        }
+        let sample_bytes = if self.sixteen { 2 } else { 1 };
+        let row_bytes = u64::from(self.width) * self.channels as u64 * sample_bytes;
+        raw.file
+            .seek(SeekFrom::Start(raw.offset + u64::from(raw.row) * row_bytes))?;
+        let mut bytes = vec![0; (row_bytes * u64::from(rows)) as usize];
+        raw.file.read_exact(&mut bytes).map_err(decode_failed)?;
+        let samples = match (self.sixteen, raw.big_endian) {
+            (false, _) => bytes.iter().map(|&v| u16::from(v) * 257).collect(),
+            (true, true) => bytes
+                .chunks_exact(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]))
+                .collect(),
+            (true, false) => bytes
+                .chunks_exact(2)
+                .map(|b| u16::from_le_bytes([b[0], b[1]]))
+                .collect(),
+        };
+        raw.row += rows;
+        Ok(Some((rows, samples)))
+    }
+
+    /// The next strip or row of tiles, as decoded samples.
+    fn read_band(&mut self) -> Result<Option<(u32, Vec<u16>)>, AppError> {
+        let (chunk_w, chunk_h) = self.chunk;
+        let top = self.band * chunk_h;
+        if top >= self.height {
+            return Ok(None);
+        }
+        let rows = chunk_h.min(self.height - top);
+        // Strips span the full width, so this is one chunk for them
+        let across = match self.decoder.get_chunk_type() {
+            ChunkType::Strip => 1,
+            ChunkType::Tile => self.width.div_ceil(chunk_w),
+        };
+        let (width, ch) = (self.width as usize, self.channels);
+        let mut samples = vec![0_u16; width * rows as usize * ch];
+        for tx in 0..across {
+            let index = self.band * across + tx;
+            let (data_w, data_h) = self.decoder.chunk_data_dimensions(index);
+            let data: Vec<u16> = match self.decoder.read_chunk(index).map_err(decode_failed)? {
+                DecodingResult::U8(v) => v.into_iter().map(|s| u16::from(s) * 257).collect(),
+                DecodingResult::U16(v) => v,
+                _ => {
+                    return Err(AppError::UnsupportedFormat(
+                        "Only unsigned 8 and 16-bit TIFFs can be read in strips".into(),
+                    ))
+                }
+            };
+            let run = data_w as usize * ch;
+            let left = (tx * chunk_w) as usize * ch;
+            for (y, src) in data
+                .chunks_exact(run)
+                .take(data_h.min(rows) as usize)
+                .enumerate()
+            {
+                let at = y * width * ch + left;
+                samples[at..at + run].copy_from_slice(src);
+            }
+        }
+        self.band += 1;
+        Ok(Some((rows, samples)))
+    }
+}
+
+impl StripSource for TiffSource {
+    fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn channels(&self) -> usize {
+        self.channels
+    }
+
+    fn sixteen_bit(&self) -> bool {
+        self.sixteen
+    }
+
+    fn band_rows(&self, rows: u32) -> u32 {
+        match self.raw {
+            Some(_) => rows,
+            None => self.chunk.1.min(self.height),
+        }
+    }
+
+    fn next_strip(&mut self, rows: u32) -> Result<Option<Strip>, AppError> {
+        let read = match self.raw {
+            Some(_) => self.read_rows(rows)?,
+            None => self.read_band()?,
+        };
+        let Some((rows, mut samples)) = read else {
+            return Ok(None);
+        };
+        if self.white_is_zero {
+            for px in samples.chunks_exact_mut(self.channels) {
+                px[0] = u16::MAX - px[0];
+            }
+        }
+        Ok(Some(expand(self.width, rows, self.channels, &samples)))
+    }
+}
+
+/// Whether `orientation` turns the image on its side.
+fn swaps_sides(orientation: Orientation) -> bool {
+    matches!(
+        orientation,
+        Orientation::Rotate90
+            | Orientation::Rotate270
+            | Orientation::Rotate90FlipH
+            | Orientation::Rotate270FlipH
+    )
+}
+
+/// Where stored pixel `x`,`y` of a `stored`-sized image ends up once
+/// `orientation` is applied.
+fn place(orientation: Orientation, stored: (u32, u32), x: u32, y: u32) -> (u32, u32) {
+    let (w, h) = stored;
+    match orientation {
+        Orientation::Rotate180 => (w - 1 - x, h - 1 - y),
+        Orientation::FlipVertical => (x, h - 1 - y),
+        Orientation::Rotate90FlipH => (y, x),
+        Orientation::Rotate90 => (h - 1 - y, x),
+        Orientation::Rotate270FlipH => (h - 1 - y, w - 1 - x),
+        Orientation::Rotate270 => (y, w - 1 - x),
+        Orientation::FlipHorizontal => (w - 1 - x, y),
+        Orientation::NoTransforms => (x, y),
+    }
+}
+
+/// Peak bytes per stored pixel while spilling: the decoded band in its raw
+/// and RGBA16 forms, the turned copy and its narrowed samples.
+const SPILL_BYTES_PER_PIXEL: u64 = 32;
+
+/// A source turned upright by its EXIF orientation. The stored image is
+/// read once; each band is turned and written where it lands in a temp file
+/// of the source's own samples, which is then read back in order.
+struct Oriented {
+    spill: BufReader<File>,
+    path: PathBuf,
+    width: u32,
+    height: u32,
+    channels: usize,
+    sixteen: bool,
+    row: u32,
+}
+
+impl Oriented {
+    fn new(
+        mut stored: Box<dyn StripSource>,
+        orientation: Orientation,
+        read_rows: u32,
+    ) -> Result<Self, AppError> {
+        let size = stored.dimensions();
+        let (width, height) = if swaps_sides(orientation) {
+            (size.1, size.0)
+        } else {
+            size
+        };
+        let path = std::env::temp_dir().join(format!("pixelforge_{}.spill", uuid::Uuid::new_v4()));
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        // Built first so the temp file goes away on any error below
+        let mut oriented = Self {
+            spill: BufReader::new(file),
+            path,
+            width,
+            height,
+            channels: stored.channels(),
+            sixteen: stored.sixteen_bit(),
+            row: 0,
+        };
+        let pixel_bytes = oriented.pixel_bytes();
+        let file = oriented.spill.get_mut();
+        let mut y = 0;
+        while let Some(strip) = stored.next_strip(read_rows)? {
+            let rows = strip.height();
+            if rows == 0 {
+                break;
+            }
+            // The band's corners bound the rectangle it turns into
+            let (ax, ay) = place(orientation, size, 0, y);
+            let (bx, by) = place(orientation, size, size.0 - 1, y + rows - 1);
+            let (left, top) = (ax.min(bx), ay.min(by));
+            let mut block = Strip::new(ax.abs_diff(bx) + 1, ay.abs_diff(by) + 1);
+            for (sy, row) in (y..).zip(strip.rows()) {
+                for (sx, px) in (0..).zip(row) {
+                    let (ox, oy) = place(orientation, size, sx, sy);
+                    block.put_pixel(ox - left, oy - top, *px);
+                }
+            }
+            let bytes = if oriented.sixteen {
+                narrow_u16(block.as_raw(), oriented.channels)
+                    .iter()
+                    .flat_map(|v| v.to_le_bytes())
+                    .collect()
+            } else {
+                narrow_u8(block.as_raw(), oriented.channels)
+            };
+            let run = block.width() as usize * pixel_bytes as usize;
+            for (oy, bytes) in (u64::from(top)..).zip(bytes.chunks_exact(run)) {
+                let at = (oy * u64::from(width) + u64::from(left)) * pixel_bytes;
+                file.seek(SeekFrom::Start(at))?;
+                file.write_all(bytes)?;
+            }
+            y += rows;
+        }
+        oriented.spill.seek(SeekFrom::Start(0))?;
+        Ok(oriented)
+    }
+
+    fn pixel_bytes(&self) -> u64 {
+        self.channels as u64 * if self.sixteen { 2 } else { 1 }
+    }
+}
+
+impl Drop for Oriented {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.path).ok();
+    }
+}
+
+impl StripSource for Oriented {
+    fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn channels(&self) -> usize {
+        self.channels
+    }
+
+    fn sixteen_bit(&self) -> bool {
+        self.sixteen
+    }
+
+    fn band_rows(&self, rows: u32) -> u32 {
+        rows
+    }
+
+    fn next_strip(&mut self, rows: u32) -> Result<Option<Strip>, AppError> {
+        let rows = rows.min(self.height - self.row);
+        if rows == 0 {
+            return Ok(None);
+        }
+        let mut bytes =
+            vec![0; (u64::from(self.width) * u64::from(rows) * self.pixel_bytes()) as usize];
+        self.spill.read_exact(&mut bytes)?;
+        let samples: Vec<u16> = if self.sixteen {
+            bytes
+                .chunks_exact(2)
+                .map(|b| u16::from_le_bytes([b[0], b[1]]))
+                .collect()
+        } else {
+            bytes.iter().map(|&v| u16::from(v) * 257).collect()
+        };
+        self.row += rows;
+        Ok(Some(expand(self.width, rows, self.channels, &samples)))
+    }
+}
+
+/// Box average in linear light, weighted by alpha, carrying partial blocks
+/// over from one strip to the next.
+struct Downscale {
+    factor: u32,
+    in_width: u32,
+    out_width: u32,
+    /// 16-bit sRGB sample to linear
+    lut: Vec<f32>,
+    /// Premultiplied linear RGB and alpha per output column
+    sums: Vec<f32>,
+    rows: u32,
+}
+
+impl Downscale {
+    fn new(factor: u32, in_width: u32) -> Self {
+        let out_width = in_width.div_ceil(factor);
+        Self {
+            factor,
+            in_width,
+            out_width,
+            lut: (0..=u16::MAX)
+                .map(|v| srgb_to_linear(f32::from(v) / 65535.0))
+                .collect(),
+            sums: vec![0.0; out_width as usize * 4],
+            rows: 0,
+        }
+    }
+
+    fn emit(&mut self, out: &mut Vec<u16>) {
+        for ox in 0..self.out_width {
+            let cols = self.factor.min(self.in_width - ox * self.factor);
+            let n = (cols * self.rows) as f32;
+            let s = &mut self.sums[ox as usize * 4..ox as usize * 4 + 4];
+            for c in 0..3 {
+                let v = if s[3] > 0.0 {
+                    linear_to_srgb(s[c] / s[3])
+                } else {
+                    0.0
+                };
+                out.push((v * 65535.0).round() as u16);
+            }
+            out.push((s[3] / n * 65535.0).round() as u16);
+            s.fill(0.0);
+        }
+        self.rows = 0;
+    }
+
+    fn apply(&mut self, strip: &Strip, last: bool) -> Strip {
+        let mut out = Vec::new();
+        let mut out_rows = 0;
+        for row in strip.rows() {
+            for (x, p) in row.enumerate() {
+                let a = f32::from(p[3]) / 65535.0;
+                let i = x / self.factor as usize * 4;
+                for c in 0..3 {
+                    self.sums[i + c] += self.lut[p[c] as usize] * a;
+                }
+                self.sums[i + 3] += a;
+            }
+            self.rows += 1;
+            if self.rows == self.factor {
+                self.emit(&mut out);
+                out_rows += 1;
+            }
+        }
+        if last && self.rows > 0 {
+            self.emit(&mut out);
+            out_rows += 1;
+        }
+        Strip::from_raw(self.out_width, out_rows, out).unwrap_or_else(|| Strip::new(0, 0))
+    }
+}
+
+enum Stage {
+    Crop {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        /// Source row the next strip starts at
+        row: u32,
+    },
+    FlipHorizontal,
+    Brightness(i32),
+    Contrast(f32),
+    Downscale(Box<Downscale>),
+}
+
+impl Stage {
+    /// Run one strip through; `last` flushes rows held back for later.
+    fn apply(&mut self, strip: Strip, last: bool) -> Strip {
+        match self {
+            Stage::Crop {
+                x,
+                y,
+                width,
+                height,
+                row,
+            } => {
+                let top = *row;
+                *row += strip.height();
+                let start = (*y).max(top);
+                let end = (*y + *height).min(*row);
+                if start >= end {
+                    return Strip::new(*width, 0);
+                }
+                imageops::crop_imm(&strip, *x, start - top, *width, end - start).to_image()
+            }
+            Stage::FlipHorizontal => imageops::flip_horizontal(&strip),
+            Stage::Brightness(value) => imageops::brighten(&strip, *value),
+            Stage::Contrast(value) => imageops::contrast(&strip, *value),
+            Stage::Downscale(downscale) => downscale.apply(&strip, last),
+        }
+    }
+}
+
+/// Stages for `steps` and the output size, or an error for a step that
+/// doesn't fit the image it would get.
+fn plan(steps: &[LargeStep], width: u32, height: u32) -> Result<(Vec<Stage>, u32, u32), AppError> {
+    let (mut width, mut height) = (width, height);
+    let mut stages = Vec::with_capacity(steps.len());
+    for step in steps {
+        stages.push(match *step {
+            LargeStep::Crop {
+                x,
+                y,
+                width: w,
+                height: h,
+            } => {
+                if w == 0 || h == 0 || x.saturating_add(w) > width || y.saturating_add(h) > height {
+                    return Err(AppError::General(format!(
+                        "Crop {w}×{h} at {x},{y} is outside the {width}×{height} image"
+                    )));
+                }
+                (width, height) = (w, h);
+                Stage::Crop {
+                    x,
+                    y,
+                    width: w,
+                    height: h,
+                    row: 0,
+                }
+            }
+            LargeStep::FlipHorizontal => Stage::FlipHorizontal,
+            // Strips are 16-bit, so the 8-bit scale is stretched to match
+            LargeStep::Brightness { value } => Stage::Brightness(value.clamp(-255, 255) * 257),
+            LargeStep::Contrast { value } => Stage::Contrast(value.clamp(-100.0, 100.0)),
+            LargeStep::Downscale { factor } => {
+                if factor == 0 {
+                    return Err(AppError::General(
+                        "Downscale factor must be at least 1".into(),
+                    ));
+                }
+                let downscale = Downscale::new(factor, width);
+                (width, height) = (downscale.out_width, height.div_ceil(factor));
+                Stage::Downscale(Box::new(downscale))
+            }
+        });
+    }
+    Ok((stages, width, height))
+}
+
+/// Peak bytes of a run: a decoded band in its raw and RGBA16 forms plus one
+/// copy per stage, and the output rows waiting to fill a strip.
+fn working_set(
+    source_width: u32,
+    band_rows: u32,
+    steps: usize,
+    out_width: u32,
+    strip_rows: u32,
+) -> u64 {
+    let band = u64::from(band_rows) * u64::from(source_width) * 8;
+    let pending = (u64::from(strip_rows) + u64::from(band_rows)) * u64::from(out_width) * 8;
+    band * (2 + steps as u64) + pending * 2
+}
+
+/// Output strips of `strip_rows` RGBA16 rows, pulled through the stages.
+struct Pipeline<'a> {
+    source: Box<dyn StripSource>,
+    stages: Vec<Stage>,
+    strip_rows: u32,
+    width: u32,
+    height: u32,
+    pending: Vec<u16>,
+    read: u32,
+    done: bool,
+    progress: &'a mut dyn FnMut(u32),
+}
+
+impl Pipeline<'_> {
+    fn next_strip(&mut self) -> Result<Option<Vec<u16>>, AppError> {
+        let strip_len = self.strip_rows as usize * self.width as usize * 4;
+        while self.pending.len() < strip_len && !self.done {
+            let next = self.source.next_strip(self.strip_rows)?;
+            self.done = next.is_none();
+            let mut strip = next.unwrap_or_else(|| Strip::new(self.source.dimensions().0, 0));
+            self.read += strip.height();
+            for stage in &mut self.stages {
+                strip = stage.apply(strip, self.done);
+            }
+            self.pending.extend_from_slice(strip.as_raw());
+            let source_height = self.source.dimensions().1.max(1);
+            (self.progress)((u64::from(self.read) * 100 / u64::from(source_height)) as u32);
+        }
+        if self.pending.is_empty() {
+            return Ok(None);
+        }
+        let take = strip_len.min(self.pending.len());
+        Ok(Some(self.pending.drain(..take).collect()))
+    }
+}
+
+fn write_png(
+    file: File,
+    pipeline: &mut Pipeline,
+    channels: usize,
+    sixteen: bool,
+) -> Result<(), AppError> {
+    let mut encoder = png::Encoder::new(BufWriter::new(file), pipeline.width, pipeline.height);
+    encoder.set_color(match channels {
+        1 => png::ColorType::Grayscale,
+        2 => png::ColorType::GrayscaleAlpha,
+        3 => png::ColorType::Rgb,
+        _ => png::ColorType::Rgba,
+    });
+    encoder.set_depth(if sixteen {
+        png::BitDepth::Sixteen
+    } else {
+        png::BitDepth::Eight
+    });
+    let mut writer = encoder
+        .write_header()
+        .map_err(AppError::save_failed)?
+        .into_stream_writer()
+        .map_err(AppError::save_failed)?;
+    while let Some(samples) = pipeline.next_strip()? {
+        let bytes = if sixteen {
+            narrow_u16(&samples, channels)
+                .iter()
+                .flat_map(|v| v.to_be_bytes())
+                .collect()
+        } else {
+            narrow_u8(&samples, channels)
+        };
+        writer.write_all(&bytes).map_err(AppError::save_failed)?;
+    }
+    writer.finish().map_err(AppError::save_failed)
+}
+
+/// Uncompressed: tiff 0.10 only compresses images written whole.
+fn write_tiff<C: colortype::ColorType>(
+    file: File,
+    pipeline: &mut Pipeline,
+    channels: usize,
+    narrow: fn(&[u16], usize) -> Vec<C::Inner>,
+) -> Result<(), AppError>
+where
+    [C::Inner]: tiff::encoder::TiffValue,
+{
+    let mut encoder = TiffEncoder::new(BufWriter::new(file)).map_err(AppError::save_failed)?;
+    let mut image = encoder
+        .new_image::<C>(pipeline.width, pipeline.height)
+        .map_err(AppError::save_failed)?;
+    if channels == 4 {
+        // Unassociated alpha
+        image
+            .encoder()
+            .write_tag(Tag::ExtraSamples, 2_u16)
+            .map_err(AppError::save_failed)?;
+    }
+    image
+        .rows_per_strip(pipeline.strip_rows)
+        .map_err(AppError::save_failed)?;
+    while let Some(samples) = pipeline.next_strip()? {
+        image
+            .write_strip(&narrow(&samples, channels))
+            .map_err(AppError::save_failed)?;
+    }
+    image.finish().map_err(AppError::save_failed)
+}
+
+/// Stream `source` through `steps` into `dest` a strip at a time, keeping
+/// the source's bit depth and channels. Fails before reading any pixels if
+/// the run would need more than `budget` bytes.
+pub fn process_large(
+    source: &Path,
+    dest: &Path,
+    steps: &[LargeStep],
+    options: &LargeImageOptions,
+    budget: u64,
+    progress: &mut dyn FnMut(u32),
+) -> Result<LargeImageResult, AppError> {
+    let strip_rows = options.strip_rows.max(1);
+    let tiff_out = match dest
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase)
+        .as_deref()
+    {
+        Some("png") => false,
+        Some("tif" | "tiff") => true,
+        _ => {
+            return Err(AppError::UnsupportedFormat(
+                "Large images can be written as PNG or TIFF".into(),
+            ))
+        }
+    };
+    if dest.exists() && dest.canonicalize()? == source.canonicalize()? {
+        return Err(AppError::General(
+            "The output can't overwrite the image being read".into(),
+        ));
+    }
+    let format = match file_format(source)? {
+        FileFormat::Image(format @ (ImageFormat::Png | ImageFormat::Tiff)) => format,
+        _ => {
+            return Err(AppError::UnsupportedFormat(
+                "Only PNG and TIFF can be processed in strips".into(),
+            ))
+        }
+    };
+    let stored: Box<dyn StripSource> = match format {
+        ImageFormat::Png => Box::new(PngSource::open(source)?),
+        _ => Box::new(TiffSource::open(source)?),
+    };
+    let mut reader = image::ImageReader::open(source)?;
+    reader.set_format(format);
+    reader.no_limits();
+    let orientation = reader
+        .into_decoder()?
+        .orientation()
+        .unwrap_or(Orientation::NoTransforms);
+    // Crops and the rest are given on the upright image
+    let mut steps = steps.to_vec();
+    if orientation == Orientation::FlipHorizontal {
+        steps.insert(0, LargeStep::FlipHorizontal);
+    }
+    let turned = !matches!(
+        orientation,
+        Orientation::NoTransforms | Orientation::FlipHorizontal
+    );
+
+    let (stored_width, stored_height) = stored.dimensions();
+    let (source_width, source_height) = if swaps_sides(orientation) {
+        (stored_height, stored_width)
+    } else {
+        (stored_width, stored_height)
+    };
+    let (stages, width, height) = plan(&steps, source_width, source_height)?;
+    // Turned images are spilled in bands as tall as half the budget allows,
+    // then streamed back upright in strips
+    let read_rows = if turned {
+        (budget / 2 / (u64::from(stored_width) * SPILL_BYTES_PER_PIXEL).max(1))
+            .max(u64::from(strip_rows))
+            .min(u64::from(stored_height)) as u32
+    } else {
+        strip_rows
+    };
+    let stored_rows = stored.band_rows(read_rows);
+    let working_bytes = if turned {
+        let spill = u64::from(stored_rows) * u64::from(stored_width) * SPILL_BYTES_PER_PIXEL;
+        working_set(source_width, strip_rows, stages.len(), width, strip_rows).max(spill)
+    } else {
+        working_set(source_width, stored_rows, stages.len(), width, strip_rows)
+    };
+    let what = if stored_rows >= stored_height && stored_height > read_rows {
+        format!(
+            "This TIFF stores all {stored_height} rows as one compressed strip, so processing it"
+        )
+    } else {
+        format!("Processing {source_width}×{source_height} in strips of {strip_rows} rows")
+    };
+    within(working_bytes, budget, &what)?;
+    let source: Box<dyn StripSource> = if turned {
+        Box::new(Oriented::new(stored, orientation, read_rows)?)
+    } else {
+        stored
+    };
+
+    let sixteen = source.sixteen_bit();
+    // TIFF has no gray+alpha color type here
+    let channels = match source.channels() {
+        2 if tiff_out => 4,
+        channels => channels,
+    };
+    let mut pipeline = Pipeline {
+        source,
+        stages,
+        strip_rows,
+        width,
+        height,
+        pending: Vec::new(),
+        read: 0,
+        done: false,
+        progress,
+    };
+    let file = File::create(dest).map_err(AppError::save_failed)?;
+    let written = match (tiff_out, channels, sixteen) {
+        (false, ..) => write_png(file, &mut pipeline, channels, sixteen),
+        (true, 1, false) => write_tiff::<colortype::Gray8>(file, &mut pipeline, 1, narrow_u8),
+        (true, 1, true) => write_tiff::<colortype::Gray16>(file, &mut pipeline, 1, narrow_u16),
+        (true, 3, false) => write_tiff::<colortype::RGB8>(file, &mut pipeline, 3, narrow_u8),
+        (true, 3, true) => write_tiff::<colortype::RGB16>(file, &mut pipeline, 3, narrow_u16),
+        (true, _, false) => write_tiff::<colortype::RGBA8>(file, &mut pipeline, 4, narrow_u8),
+        (true, _, true) => write_tiff::<colortype::RGBA16>(file, &mut pipeline, 4, narrow_u16),
+    };
+    if let Err(e) = written {
+        std::fs::remove_file(dest).ok();
+        return Err(e);
+    }
+    Ok(LargeImageResult {
+        width,
+        height,
+        working_bytes,
+    })
+}
+
+/// Stream a PNG or TIFF through `steps` into `dest_path` without decoding
+/// it whole.
+#[tauri::command]
+pub fn process_large_image(
+    app: AppHandle,
+    source_path: String,
+    dest_path: String,
+    steps: Vec<LargeStep>,
+    options: Option<LargeImageOptions>,
+) -> Result<LargeImageResult, AppError> {
+    let mut reported = None;
+    process_large(
+        Path::new(&source_path),
+        Path::new(&dest_path),
+        &steps,
+        &options.unwrap_or_default(),
+        memory_budget(),
+        &mut |percent| {
+            if reported != Some(percent) {
+                reported = Some(percent);
+                app.emit(
+                    "operation-progress",
+                    serde_json::json!({ "stage": "processing", "percent": percent }),
+                )
+                .ok();
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::DynamicImage;
+
+    fn temp_path(ext: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "pixelforge_large_test_{}.{ext}",
+            uuid::Uuid::new_v4()
+        ))
+    }
+
+    fn gradient() -> image::RgbaImage {
+        image::RgbaImage::from_fn(150, 97, |x, y| {
+            image::Rgba([
+                (x + y) as u8,
+                (x * 3) as u8,
+                (y * 2) as u8,
+                200 + (x % 50) as u8,
+            ])
+        })
+    }
+
+    fn run(source: &Path, dest: &Path, steps: &[LargeStep], strip_rows: u32) -> LargeImageResult {
+        process_large(
+            source,
+            dest,
+            steps,
+            &LargeImageOptions { strip_rows },
+            DEFAULT_MEMORY_BUDGET,
+            &mut |_| {},
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn strips_match_whole_image_edits() {
+        let img = gradient();
+        let src = temp_path("png");
+        img.save(&src).unwrap();
+        let steps = [
+            LargeStep::Crop {
+                x: 10,
+                y: 5,
+                width: 120,
+                height: 83,
+            },
+            LargeStep::FlipHorizontal,
+            LargeStep::Brightness { value: 20 },
+            LargeStep::Contrast { value: 15.0 },
+        ];
+        let expected = DynamicImage::ImageRgba8(img)
+            .crop_imm(10, 5, 120, 83)
+            .fliph()
+            .brighten(20)
+            .adjust_contrast(15.0)
+            .to_rgba8();
+
+        for ext in ["png", "tiff"] {
+            let dest = temp_path(ext);
+            // Strips that don't divide the height, and the crop's edges
+            let result = run(&src, &dest, &steps, 16);
+            assert_eq!((result.width, result.height), (120, 83));
+            let out = image::open(&dest).unwrap();
+            assert_eq!(out.color(), image::ColorType::Rgba8, "{ext}");
+            let out = out.to_rgba8();
+            let worst = out
+                .as_raw()
+                .iter()
+                .zip(expected.as_raw())
+                .map(|(&a, &b)| a.abs_diff(b))
+                .max()
+                .unwrap();
+            // 16-bit intermediates round differently by at most one step
+            assert!(worst <= 1, "{ext}: {worst}");
+            std::fs::remove_file(&dest).ok();
+        }
+        std::fs::remove_file(&src).ok();
+    }
+
+    #[test]
+    fn downscale_averages_in_linear_light_across_strips() {
+        // Black and white columns, 16-bit gray with a ragged right edge
+        let img = image::ImageBuffer::<image::Luma<u16>, _>::from_fn(101, 60, |x, _| {
+            image::Luma([if x % 2 == 0 { 0 } else { u16::MAX }])
+        });
+        let src = temp_path("tiff");
+        img.save(&src).unwrap();
+        let dest = temp_path("png");
+        let result = run(&src, &dest, &[LargeStep::Downscale { factor: 4 }], 7);
+        assert_eq!((result.width, result.height), (26, 15));
+        let out = image::open(&dest).unwrap();
+        assert_eq!(out.color(), image::ColorType::L16);
+        let out = out.to_luma16();
+        // Half white in linear light is 0.735 in sRGB, not 0.5
+        let mid = f32::from(out.get_pixel(3, 7)[0]) / 65535.0;
+        assert!((mid - linear_to_srgb(0.5)).abs() < 1e-3, "{mid}");
+        // The last column averages only the one black column left over
+        assert_eq!(out.get_pixel(25, 14)[0], 0);
+        std::fs::remove_file(&dest).ok();
+        std::fs::remove_file(&src).ok();
+    }
+
+    #[test]
+    fn budget_is_checked_before_anything_is_written() {
+        let src = temp_path("png");
+        gradient().save(&src).unwrap();
+        let dest = temp_path("png");
+        let err = process_large(
+            &src,
+            &dest,
+            &[],
+            &LargeImageOptions::default(),
+            1024,
+            &mut |_| {},
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("memory budget"), "{err}");
+        assert!(!dest.exists());
+
+        let outside = [LargeStep::Crop {
+            x: 100,
+            y: 0,
+            width: 100,
+            height: 10,
+        }];
+        assert!(process_large(
+            &src,
+            &dest,
+            &outside,
+            &LargeImageOptions::default(),
+            DEFAULT_MEMORY_BUDGET,
+            &mut |_| {}
+        )
+        .is_err());
+        assert!(within(2 << 20, 1 << 20, "Decoding").is_err());
+        assert!(within(1 << 20, 1 << 20, "Decoding").is_ok());
+        std::fs::remove_file(&src).ok();
+    }
+
+    /// An RGBA8 TIFF of `img` in strips of `rows_per_strip`, with an
+    /// orientation tag unless it's 1.
+    fn save_tiff(
+        img: &image::RgbaImage,
+        rows_per_strip: u32,
+        orientation: u16,
+    ) -> std::path::PathBuf {
+        let path = temp_path("tiff");
+        let mut encoder = TiffEncoder::new(File::create(&path).unwrap()).unwrap();
+        let mut tiff = encoder
+            .new_image::<colortype::RGBA8>(img.width(), img.height())
+            .unwrap();
+        if orientation != 1 {
+            tiff.encoder()
+                .write_tag(Tag::Orientation, orientation)
+                .unwrap();
+        }
+        tiff.rows_per_strip(rows_per_strip).unwrap();
+        tiff.write_data(img.as_raw()).unwrap();
+        path
+    }
+
+    #[test]
+    fn exif_orientation_is_applied_through_a_spill_file() {
+        let img = gradient();
+        for orientation in 2..=8 {
+            let src = save_tiff(&img, 10, orientation);
+            let dest = temp_path("png");
+            // Small enough that the stored image is spilled in several bands
+            let result = process_large(
+                &src,
+                &dest,
+                &[],
+                &LargeImageOptions { strip_rows: 16 },
+                350_000,
+                &mut |_| {},
+            )
+            .unwrap();
+            let mut expected = DynamicImage::ImageRgba8(img.clone());
+            expected.apply_orientation(Orientation::from_exif(orientation as u8).unwrap());
+            assert_eq!(
+                (result.width, result.height),
+                (expected.width(), expected.height())
+            );
+            let out = image::open(&dest).unwrap().to_rgba8();
+            assert!(out == expected.to_rgba8(), "orientation {orientation}");
+            std::fs::remove_file(&dest).ok();
+            std::fs::remove_file(&src).ok();
+        }
+    }
+
+    #[test]
+    fn single_strip_tiffs_are_read_by_rows() {
+        let img = gradient();
+        let src = save_tiff(&img, img.height(), 1);
+        let dest = temp_path("png");
+        let result = run(&src, &dest, &[], 16);
+        // Bands of the requested rows, not the whole strip
+        assert_eq!(result.working_bytes, working_set(150, 16, 0, 150, 16));
+        assert!(image::open(&dest).unwrap().to_rgba8() == img);
+
+        let gray = image::ImageBuffer::<image::Luma<u16>, _>::from_fn(40, 30, |x, y| {
+            image::Luma([(x * 1000 + y * 7) as u16])
+        });
+        let src16 = temp_path("tiff");
+        let mut encoder = TiffEncoder::new(File::create(&src16).unwrap()).unwrap();
+        let mut tiff = encoder.new_image::<colortype::Gray16>(40, 30).unwrap();
+        tiff.rows_per_strip(30).unwrap();
+        tiff.write_data(gray.as_raw()).unwrap();
+        run(&src16, &dest, &[], 7);
+        assert!(image::open(&dest).unwrap().to_luma16() == gray);
+        for path in [src, src16, dest] {
+            std::fs::remove_file(path).ok();
+        }
+    }
+
+    #[test]
+    fn output_cannot_overwrite_the_source() {
+        let src = temp_path("png");
+        gradient().save(&src).unwrap();
+        let err = process_large(
+            &src,
+            &src,
+            &[LargeStep::FlipHorizontal],
+            &LargeImageOptions::default(),
+            DEFAULT_MEMORY_BUDGET,
+            &mut |_| {},
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("overwrite"), "{err}");
+        assert!(image::open(&src).unwrap().to_rgba8() == gradient());
+        std::fs::remove_file(&src).ok();
+    }
+}
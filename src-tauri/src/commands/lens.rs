@@ -0,0 +1,374 @@
+use crate::commands::{metadata, operations, tonemap};
+use crate::error::AppError;
+use image::{ColorType, DynamicImage, Rgba32FImage};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+// ── Lens profiles ───────────────────────────────────────────────────
+//
+// Radial model shared by distortion and vignetting, with `r` normalized so
+// the image corners sit at r = 1:
+//   distortion:  r_src = r * (1 + k1·r² + k2·r⁴ + k3·r⁶)
+//   vignetting:  gain  = 1 / (1 + v1·r² + v2·r⁴ + v3·r⁶)
+// Negative k1 corrects barrel distortion, positive k1 corrects pincushion.
+// Chromatic aberration scales the red and blue sampling radius relative to green.
+
+/// Approximate profiles for common phones and kit lenses.
+const BUNDLED_PROFILES: &str = include_str!("../data/lens-profiles.json");
+/// Profiles calibrated by the user, e.g. converted from lensfun, in the app
+/// data directory; they're matched before the bundled ones.
+const PROFILES_FILE: &str = "lens-profiles.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LensProfile {
+    pub make: String,
+    pub lens: String,
+    pub k1: f32,
+    pub k2: f32,
+    pub k3: f32,
+    pub vignetting: [f32; 3],
+    pub ca_red: f32,
+    pub ca_blue: f32,
+}
+
+fn bundled_profiles() -> Result<Vec<LensProfile>, AppError> {
+    serde_json::from_str(BUNDLED_PROFILES).map_err(|e| AppError::General(e.to_string()))
+}
+
+/// The user's profiles from the app data directory, if any, followed by the
+/// bundled ones, so a user entry overrides a bundled one for the same lens.
+pub fn load_profiles(app: &AppHandle) -> Result<Vec<LensProfile>, AppError> {
+    let path = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::General(e.to_string()))?
+        .join(PROFILES_FILE);
+    let mut profiles: Vec<LensProfile> = if path.exists() {
+        serde_json::from_slice(&std::fs::read(&path)?)
+            .map_err(|e| AppError::General(format!("Invalid {PROFILES_FILE}: {e}")))?
+    } else {
+        Vec::new()
+    };
+    profiles.extend(bundled_profiles()?);
+    Ok(profiles)
+}
+
+/// Match a profile by camera make and lens name (case-insensitive substring).
+/// Fixed-lens cameras such as phones report no lens model, so `lens` falls
+/// back to the camera model there.
+pub fn find_profile<'a>(
+    profiles: &'a [LensProfile],
+    make: &str,
+    lens: &str,
+) -> Option<&'a LensProfile> {
+    let make = make.to_lowercase();
+    let lens = lens.to_lowercase();
+    profiles
+        .iter()
+        .find(|p| make.contains(&p.make.to_lowercase()) && lens.contains(&p.lens.to_lowercase()))
+}
+
+fn profile_for_file<'a>(profiles: &'a [LensProfile], path: &str) -> Option<&'a LensProfile> {
+    let exif = metadata::read_exif(std::path::Path::new(path))?;
+    let make = metadata::exif_string(&exif, exif::Tag::Make)?;
+    let lens = metadata::exif_string(&exif, exif::Tag::LensModel)
+        .or_else(|| metadata::exif_string(&exif, exif::Tag::Model))?;
+    find_profile(profiles, &make, &lens)
+}
+
+// ── Correction ──────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LensCorrection {
+    /// Look up a profile from the EXIF Make/LensModel tags; the manual
+    /// coefficients below are then adjustments on top of it.
+    pub use_profile: bool,
+    pub k1: f32,
+    pub k2: f32,
+    pub k3: f32,
+    pub vignetting: [f32; 3],
+    pub ca_red: f32,
+    pub ca_blue: f32,
+}
+
+impl Default for LensCorrection {
+    fn default() -> Self {
+        Self {
+            use_profile: true,
+            k1: 0.0,
+            k2: 0.0,
+            k3: 0.0,
+            vignetting: [0.0; 3],
+            ca_red: 1.0,
+            ca_blue: 1.0,
+        }
+    }
+}
+
+impl LensCorrection {
+    /// The manual coefficients added to the profile's; CA scales multiply.
+    fn on_top_of(&self, p: &LensProfile) -> Self {
+        Self {
+            use_profile: true,
+            k1: p.k1 + self.k1,
+            k2: p.k2 + self.k2,
+            k3: p.k3 + self.k3,
+            vignetting: std::array::from_fn(|i| p.vignetting[i] + self.vignetting[i]),
+            ca_red: p.ca_red * self.ca_red,
+            ca_blue: p.ca_blue * self.ca_blue,
+        }
+    }
+}
+
+/// Bilinear sample of one channel; `None` outside the source image.
+fn sample_channel(src: &Rgba32FImage, fx: f32, fy: f32, channel: usize) -> Option<f32> {
+    let (w, h) = src.dimensions();
+    if fx < 0.0 || fy < 0.0 || fx > (w - 1) as f32 || fy > (h - 1) as f32 {
+        return None;
+    }
+    let x0 = fx.floor() as u32;
+    let y0 = fy.floor() as u32;
+    let x1 = (x0 + 1).min(w - 1);
+    let y1 = (y0 + 1).min(h - 1);
+    let tx = fx - x0 as f32;
+    let ty = fy - y0 as f32;
+    let p = |x: u32, y: u32| src.get_pixel(x, y)[channel];
+    let top = p(x0, y0) * (1.0 - tx) + p(x1, y0) * tx;
+    let bottom = p(x0, y1) * (1.0 - tx) + p(x1, y1) * tx;
+    Some(top * (1.0 - ty) + bottom * ty)
+}
+
+/// Remapped in float and returned at the source's bit depth, with alpha
+/// for the corners distortion correction can leave empty.
+pub fn correct_lens(img: &DynamicImage, params: &LensCorrection) -> DynamicImage {
+    let color = match img.color() {
+        c if tonemap::is_float(c) => ColorType::Rgba32F,
+        c if c.bytes_per_pixel() / c.channel_count() > 1 => ColorType::Rgba16,
+        _ => ColorType::Rgba8,
+    };
+    let src = img.to_rgba32f();
+    let (w, h) = src.dimensions();
+    if w == 0 || h == 0 {
+        return operations::with_color(src, color);
+    }
+
+    let cx = (w - 1) as f32 / 2.0;
+    let cy = (h - 1) as f32 / 2.0;
+    let norm = (cx * cx + cy * cy).sqrt().max(1.0);
+    let [v1, v2, v3] = params.vignetting;
+
+    let out = Rgba32FImage::from_fn(w, h, |x, y| {
+        let dx = (x as f32 - cx) / norm;
+        let dy = (y as f32 - cy) / norm;
+        let r2 = dx * dx + dy * dy;
+        let scale = 1.0 + params.k1 * r2 + params.k2 * r2 * r2 + params.k3 * r2 * r2 * r2;
+
+        // Vignetting is a property of the sensor position, so use the source radius
+        let rs2 = r2 * scale * scale;
+        let falloff = 1.0 + v1 * rs2 + v2 * rs2 * rs2 + v3 * rs2 * rs2 * rs2;
+        let gain = 1.0 / falloff.max(0.05);
+
+        let at = |s: f32, channel: usize| {
+            sample_channel(&src, cx + dx * s * norm, cy + dy * s * norm, channel)
+        };
+
+        let alpha = match at(scale, 3) {
+            Some(a) => a,
+            None => return image::Rgba([0.0; 4]),
+        };
+        let r = at(scale * params.ca_red, 0).unwrap_or(0.0);
+        let g = at(scale, 1).unwrap_or(0.0);
+        let b = at(scale * params.ca_blue, 2).unwrap_or(0.0);
+
+        image::Rgba([r * gain, g * gain, b * gain, alpha.clamp(0.0, 1.0)])
+    });
+
+    operations::with_color(out, color)
+}
+
+#[tauri::command]
+pub fn get_lens_profile(app: AppHandle, path: String) -> Result<Option<LensProfile>, AppError> {
+    if !std::path::Path::new(&path).exists() {
+        return Err(AppError::FileRead("Input file does not exist".into()));
+    }
+    Ok(profile_for_file(&load_profiles(&app)?, &path).cloned())
+}
+
+#[tauri::command]
+pub fn apply_lens_correction(
+    app: AppHandle,
+    path: String,
+    params: LensCorrection,
+) -> Result<String, AppError> {
+    let profiles = if params.use_profile {
+        load_profiles(&app)?
+    } else {
+        Vec::new()
+    };
+    correct_file(&path, &params, &profiles)
+}
+
+fn correct_file(
+    path: &str,
+    params: &LensCorrection,
+    profiles: &[LensProfile],
+) -> Result<String, AppError> {
+    let img = operations::open_image(path)?;
+    let params = match profile_for_file(profiles, path) {
+        Some(profile) if params.use_profile => params.on_top_of(profile),
+        _ => params.clone(),
+    };
+    let corrected = correct_lens(&img, &params);
+    operations::save_temp_image(&corrected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbaImage;
+
+    fn gradient(w: u32, h: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(w, h, |x, y| {
+            image::Rgba([(x * 4 % 256) as u8, (y * 4 % 256) as u8, 128, 255])
+        }))
+    }
+
+    #[test]
+    fn identity_params_leave_image_unchanged() {
+        let img = gradient(40, 30);
+        let params = LensCorrection {
+            use_profile: false,
+            ..LensCorrection::default()
+        };
+        let out = correct_lens(&img, &params);
+        assert_eq!(out.to_rgba8(), img.to_rgba8());
+    }
+
+    #[test]
+    fn vignetting_brightens_corners_only() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+            41,
+            41,
+            image::Rgba([100, 100, 100, 255]),
+        ));
+        let params = LensCorrection {
+            vignetting: [-0.5, 0.0, 0.0],
+            ..LensCorrection::default()
+        };
+        let out = correct_lens(&img, &params).to_rgba8();
+        assert_eq!(out.get_pixel(20, 20)[0], 100);
+        assert!(out.get_pixel(0, 0)[0] > 150);
+    }
+
+    #[test]
+    fn pincushion_correction_leaves_transparent_corners() {
+        let img = gradient(40, 40);
+        let params = LensCorrection {
+            k1: 0.2,
+            ..LensCorrection::default()
+        };
+        let out = correct_lens(&img, &params).to_rgba8();
+        assert_eq!(out.get_pixel(0, 0)[3], 0);
+        assert_eq!(out.get_pixel(20, 20)[3], 255);
+    }
+
+    fn profile(make: &str, lens: &str) -> LensProfile {
+        LensProfile {
+            make: make.into(),
+            lens: lens.into(),
+            k1: -0.05,
+            k2: 0.01,
+            k3: 0.0,
+            vignetting: [-0.4, 0.1, 0.0],
+            ca_red: 1.0004,
+            ca_blue: 0.9996,
+        }
+    }
+
+    #[test]
+    fn profile_lookup_is_case_insensitive() {
+        let profiles = [profile("Canon", "EF-S18-55mm"), profile("Apple", "iPhone")];
+        let found = find_profile(&profiles, "CANON", "EF-S18-55mm f/3.5-5.6 IS STM").unwrap();
+        assert_eq!(found.make, "Canon");
+        assert!(find_profile(&profiles, "Apple", "iPhone 15 Pro").is_some());
+        assert!(find_profile(&profiles, "Leica", "Summilux 50").is_none());
+        let parsed: Vec<LensProfile> = serde_json::from_str(
+            r#"[{"make":"Sony","lens":"FE 50mm","k1":-0.02,"k2":0,"k3":0,
+                "vignetting":[-0.3,0,0],"caRed":1,"caBlue":1}]"#,
+        )
+        .unwrap();
+        assert_eq!(parsed[0].lens, "FE 50mm");
+    }
+
+    #[test]
+    fn bundled_profiles_match_common_lenses() {
+        let profiles = bundled_profiles().unwrap();
+        assert!(find_profile(&profiles, "Canon", "EF-S18-55mm f/3.5-5.6 IS II").is_some());
+        assert!(find_profile(&profiles, "Apple", "iPhone 15 Pro").is_some());
+        // A user profile listed first wins over the bundled one
+        let mut custom = profile("Canon", "EF-S18-55mm");
+        custom.k1 = 0.5;
+        let merged: Vec<_> = std::iter::once(custom).chain(profiles).collect();
+        assert_eq!(
+            find_profile(&merged, "Canon", "EF-S18-55mm").unwrap().k1,
+            0.5
+        );
+    }
+
+    #[test]
+    fn correction_keeps_bit_depth() {
+        let img = DynamicImage::ImageRgb16(image::ImageBuffer::from_fn(30, 20, |x, y| {
+            image::Rgb([x as u16 * 2000 + 7, y as u16 * 3000 + 1, 40_001])
+        }));
+        let params = LensCorrection {
+            use_profile: false,
+            ..LensCorrection::default()
+        };
+        let out = correct_lens(&img, &params);
+        assert_eq!(out.color(), ColorType::Rgba16);
+        assert_eq!(out.to_rgb16(), img.to_rgb16());
+
+        let hdr = DynamicImage::ImageRgb32F(image::ImageBuffer::from_pixel(
+            9,
+            9,
+            image::Rgb([4.0, 2.0, 0.5]),
+        ));
+        let out = correct_lens(&hdr, &params);
+        assert_eq!(out.color(), ColorType::Rgba32F);
+        assert_eq!(out.to_rgba32f().get_pixel(4, 4).0, [4.0, 2.0, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn manual_params_adjust_the_profile() {
+        let manual = LensCorrection {
+            k1: 0.02,
+            vignetting: [0.1, 0.0, 0.0],
+            ca_red: 1.001,
+            ..LensCorrection::default()
+        };
+        let combined = manual.on_top_of(&profile("Canon", "EF-S18-55mm"));
+        assert!((combined.k1 - -0.03).abs() < 1e-6);
+        assert!((combined.k2 - 0.01).abs() < 1e-6);
+        assert!((combined.vignetting[0] - -0.3).abs() < 1e-6);
+        assert!((combined.ca_red - 1.0004 * 1.001).abs() < 1e-6);
+    }
+
+    #[test]
+    fn apply_without_exif_uses_manual_params() {
+        let img = gradient(20, 20);
+        let path = operations::save_temp_image(&img).unwrap();
+        let params = LensCorrection {
+            k1: -0.1,
+            ..LensCorrection::default()
+        };
+        let profiles = [profile("Canon", "EF-S18-55mm")];
+        let result = correct_file(&path, &params, &profiles).unwrap();
+        let out = image::open(&result).unwrap();
+        assert_eq!(out.width(), 20);
+        assert_ne!(out.to_rgba8(), img.to_rgba8());
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_file(&result).ok();
+    }
+}
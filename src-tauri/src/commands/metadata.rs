@@ -0,0 +1,2788 @@
+use crate::commands::webp::{push_riff_chunk, push_u24};
+use crate::error::AppError;
+use image::ImageDecoder;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// ── Types ───────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageMetadata {
+    pub exif: Option<ExifMetadata>,
+    pub xmp: Option<XmpMetadata>,
+    pub iptc: Option<IptcMetadata>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExifMetadata {
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub lens_model: Option<String>,
+    pub software: Option<String>,
+    pub artist: Option<String>,
+    pub copyright: Option<String>,
+    pub description: Option<String>,
+    /// ISO 8601 local time, from DateTimeOriginal or DateTime
+    pub date_taken: Option<String>,
+    /// Shutter speed as shown on cameras, e.g. "1/250" or "2"
+    pub exposure_time: Option<String>,
+    pub f_number: Option<f64>,
+    pub iso: Option<u32>,
+    pub focal_length: Option<f64>,
+    pub focal_length_35mm: Option<u32>,
+    pub orientation: Option<u16>,
+    pub gps: Option<GpsPosition>,
+    /// Every primary-image field, for display as a raw table
+    pub fields: Vec<MetadataField>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GpsPosition {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataField {
+    pub tag: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XmpMetadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub creators: Vec<String>,
+    pub rights: Option<String>,
+    pub keywords: Vec<String>,
+    pub rating: Option<i32>,
+    pub creator_tool: Option<String>,
+    pub create_date: Option<String>,
+    /// The raw `<x:xmpmeta>` packet
+    pub packet: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IptcMetadata {
+    pub object_name: Option<String>,
+    pub headline: Option<String>,
+    pub caption: Option<String>,
+    pub keywords: Vec<String>,
+    pub by_line: Option<String>,
+    pub credit: Option<String>,
+    pub copyright: Option<String>,
+    pub city: Option<String>,
+    pub country: Option<String>,
+    pub date_created: Option<String>,
+}
+
+// ── EXIF ────────────────────────────────────────────────────────────
+
+/// Parse EXIF from any container kamadak-exif understands (JPEG, TIFF, PNG,
+/// WebP and HEIF/AVIF).
+pub fn read_exif(path: &Path) -> Option<exif::Exif> {
+    let file = std::fs::File::open(path).ok()?;
+    exif::Reader::new()
+        .read_from_container(&mut std::io::BufReader::new(file))
+        .ok()
+}
+
+pub fn exif_string(exif: &exif::Exif, tag: exif::Tag) -> Option<String> {
+    let field = exif.get_field(tag, exif::In::PRIMARY)?;
+    match &field.value {
+        exif::Value::Ascii(parts) => parts
+            .first()
+            .map(|s| String::from_utf8_lossy(s).trim().to_string())
+            .filter(|s| !s.is_empty()),
+        _ => None,
+    }
+}
+
+fn exif_uint(exif: &exif::Exif, tag: exif::Tag) -> Option<u32> {
+    exif.get_field(tag, exif::In::PRIMARY)?.value.get_uint(0)
+}
+
+fn exif_rationals(exif: &exif::Exif, tag: exif::Tag) -> Option<Vec<f64>> {
+    match &exif.get_field(tag, exif::In::PRIMARY)?.value {
+        exif::Value::Rational(v) if !v.is_empty() => Some(v.iter().map(|r| r.to_f64()).collect()),
+        exif::Value::SRational(v) if !v.is_empty() => Some(v.iter().map(|r| r.to_f64()).collect()),
+        _ => None,
+    }
+}
+
+fn exif_rational(exif: &exif::Exif, tag: exif::Tag) -> Option<f64> {
+    exif_rationals(exif, tag)?
+        .first()
+        .copied()
+        .filter(|v| v.is_finite())
+}
+
+fn exif_date(exif: &exif::Exif, tag: exif::Tag) -> Option<String> {
+    let field = exif.get_field(tag, exif::In::PRIMARY)?;
+    let exif::Value::Ascii(parts) = &field.value else {
+        return None;
+    };
+    let d = exif::DateTime::from_ascii(parts.first()?).ok()?;
+    Some(format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        d.year, d.month, d.day, d.hour, d.minute, d.second
+    ))
+}
+
+fn exposure_time(exif: &exif::Exif) -> Option<String> {
+    let field = exif.get_field(exif::Tag::ExposureTime, exif::In::PRIMARY)?;
+    let exif::Value::Rational(v) = &field.value else {
+        return None;
+    };
+    let r = v.first()?;
+    if r.num == 0 || r.denom == 0 {
+        return None;
+    }
+    if r.num < r.denom {
+        Some(format!("1/{}", (r.denom as f64 / r.num as f64).round()))
+    } else {
+        Some(format!("{}", (r.to_f64() * 10.0).round() / 10.0))
+    }
+}
+
+/// Degrees/minutes/seconds plus an N/S or E/W reference into signed degrees.
+fn gps_coordinate(exif: &exif::Exif, tag: exif::Tag, ref_tag: exif::Tag) -> Option<f64> {
+    let dms = exif_rationals(exif, tag)?;
+    let degrees =
+        dms.first()? + dms.get(1).unwrap_or(&0.0) / 60.0 + dms.get(2).unwrap_or(&0.0) / 3600.0;
+    let negative = matches!(exif_string(exif, ref_tag).as_deref(), Some("S") | Some("W"));
+    Some(if negative { -degrees } else { degrees })
+}
+
+fn gps_position(exif: &exif::Exif) -> Option<GpsPosition> {
+    let latitude = gps_coordinate(exif, exif::Tag::GPSLatitude, exif::Tag::GPSLatitudeRef)?;
+    let longitude = gps_coordinate(exif, exif::Tag::GPSLongitude, exif::Tag::GPSLongitudeRef)?;
+    let altitude = exif_rational(exif, exif::Tag::GPSAltitude).map(|alt| {
+        // AltitudeRef 1 means below sea level
+        if exif_uint(exif, exif::Tag::GPSAltitudeRef) == Some(1) {
+            -alt
+        } else {
+            alt
+        }
+    });
+    Some(GpsPosition {
+        latitude,
+        longitude,
+        altitude,
+    })
+}
+
+pub fn exif_metadata(exif: &exif::Exif) -> ExifMetadata {
+    use exif::Tag;
+    let fields = exif
+        .fields()
+        .filter(|f| f.ifd_num == exif::In::PRIMARY)
+        .map(|f| MetadataField {
+            tag: f.tag.to_string(),
+            value: f.display_value().with_unit(exif).to_string(),
+        })
+        .collect();
+
+    ExifMetadata {
+        make: exif_string(exif, Tag::Make),
+        model: exif_string(exif, Tag::Model),
+        lens_model: exif_string(exif, Tag::LensModel),
+        software: exif_string(exif, Tag::Software),
+        artist: exif_string(exif, Tag::Artist),
+        copyright: exif_string(exif, Tag::Copyright),
+        description: exif_string(exif, Tag::ImageDescription),
+        date_taken: exif_date(exif, Tag::DateTimeOriginal)
+            .or_else(|| exif_date(exif, Tag::DateTime)),
+        exposure_time: exposure_time(exif),
+        f_number: exif_rational(exif, Tag::FNumber),
+        iso: exif_uint(exif, Tag::PhotographicSensitivity),
+        focal_length: exif_rational(exif, Tag::FocalLength),
+        focal_length_35mm: exif_uint(exif, Tag::FocalLengthIn35mmFilm),
+        orientation: exif_uint(exif, Tag::Orientation).map(|o| o as u16),
+        gps: gps_position(exif),
+        fields,
+    }
+}
+
+// ── XMP ─────────────────────────────────────────────────────────────
+
+/// Locate a raw XMP packet anywhere in the file. Used for containers the
+/// `image` decoders don't expose XMP for (AVIF, HEIF).
+fn find_xmp_packet(bytes: &[u8]) -> Option<String> {
+    let start = find_bytes(bytes, b"<x:xmpmeta")?;
+    let end_tag = b"</x:xmpmeta>";
+    let end = start + find_bytes(&bytes[start..], end_tag)? + end_tag.len();
+    Some(String::from_utf8_lossy(&bytes[start..end]).into_owned())
+}
+
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn xml_unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Values of a qualified XMP property, whether written as an attribute
+/// (`xmp:Rating="5"`), a simple element or an `rdf:Alt`/`Seq`/`Bag` list.
+fn xmp_values(packet: &str, name: &str) -> Vec<String> {
+    let open = format!("<{name}");
+    let close = format!("</{name}>");
+    if let Some(start) = packet.find(&open) {
+        let after = &packet[start + open.len()..];
+        // Guard against prefixes of longer names, e.g. dc:creator vs dc:creatorX
+        if after.starts_with(['>', ' ', '\n', '\r', '\t']) {
+            if let (Some(gt), Some(end)) = (after.find('>'), after.find(&close)) {
+                if gt < end {
+                    let body = &after[gt + 1..end];
+                    if body.contains("<rdf:li") {
+                        return body
+                            .split("<rdf:li")
+                            .skip(1)
+                            .filter_map(|li| {
+                                // The close tag is searched after the `>` so
+                                // malformed input can't invert the range
+                                let rest = &li[li.find('>')? + 1..];
+                                let text = &rest[..rest.find("</rdf:li>")?];
+                                Some(xml_unescape(text.trim()))
+                            })
+                            .filter(|v| !v.is_empty())
+                            .collect();
+                    }
+                    let text = xml_unescape(body.trim());
+                    return if text.is_empty() { vec![] } else { vec![text] };
+                }
+            }
+        }
+    }
+    let attr = format!("{name}=\"");
+    packet
+        .match_indices(&attr)
+        .filter(|(i, _)| {
+            packet[..*i]
+                .chars()
+                .next_back()
+                .is_some_and(char::is_whitespace)
+        })
+        .find_map(|(i, _)| {
+            let rest = &packet[i + attr.len()..];
+            rest.find('"').map(|end| vec![xml_unescape(&rest[..end])])
+        })
+        .unwrap_or_default()
+}
+
+fn xmp_value(packet: &str, name: &str) -> Option<String> {
+    xmp_values(packet, name).into_iter().next()
+}
+
+pub fn parse_xmp(packet: &str) -> XmpMetadata {
+    XmpMetadata {
+        title: xmp_value(packet, "dc:title"),
+        description: xmp_value(packet, "dc:description"),
+        creators: xmp_values(packet, "dc:creator"),
+        rights: xmp_value(packet, "dc:rights"),
+        keywords: xmp_values(packet, "dc:subject"),
+        rating: xmp_value(packet, "xmp:Rating").and_then(|r| r.trim().parse().ok()),
+        creator_tool: xmp_value(packet, "xmp:CreatorTool"),
+        create_date: xmp_value(packet, "xmp:CreateDate")
+            .or_else(|| xmp_value(packet, "photoshop:DateCreated")),
+        packet: packet.to_string(),
+    }
+}
+
+// ── IPTC ────────────────────────────────────────────────────────────
+
+const PHOTOSHOP_HEADER: &[u8] = b"Photoshop 3.0\0";
+const IPTC_RESOURCE_ID: u16 = 0x0404;
+
+/// Extract the IPTC-IIM block from a Photoshop image resource list (JPEG
+/// APP13). The list is a sequence of `8BIM` + id + padded Pascal name +
+/// size + padded data.
+fn iim_from_photoshop(mut data: &[u8]) -> Option<&[u8]> {
+    if data.starts_with(PHOTOSHOP_HEADER) {
+        data = &data[PHOTOSHOP_HEADER.len()..];
+    }
+    while data.len() >= 12 && &data[..4] == b"8BIM" {
+        let id = u16::from_be_bytes([data[4], data[5]]);
+        let name_len = data[6] as usize;
+        // Length byte plus name, padded to an even size
+        let name_total = (1 + name_len).next_multiple_of(2);
+        let size_at = 6 + name_total;
+        let size = u32::from_be_bytes(data.get(size_at..size_at + 4)?.try_into().ok()?) as usize;
+        let body_at = size_at + 4;
+        let body = data.get(body_at..body_at + size)?;
+        if id == IPTC_RESOURCE_ID {
+            return Some(body);
+        }
+        data = data.get(body_at + size.next_multiple_of(2)..)?;
+    }
+    None
+}
+
+/// ImageMagick/exiftool store IPTC in PNG as a "Raw profile type iptc" text
+/// chunk: a name line, a length line, then the payload in hex.
+fn decode_raw_profile(text: &[u8]) -> Option<Vec<u8>> {
+    let text = std::str::from_utf8(text).ok()?;
+    let mut lines = text.trim_start().lines();
+    lines.next()?;
+    let len: usize = lines.next()?.trim().parse().ok()?;
+    let hex: Vec<u8> = lines
+        .flat_map(|l| l.trim().bytes())
+        .filter(u8::is_ascii_hexdigit)
+        .collect();
+    let digit = |b: u8| (b as char).to_digit(16).unwrap_or(0) as u8;
+    let bytes: Vec<u8> = hex
+        .chunks_exact(2)
+        .map(|pair| digit(pair[0]) << 4 | digit(pair[1]))
+        .collect();
+    (bytes.len() >= len).then(|| bytes[..len].to_vec())
+}
+
+/// Normalize an IPTC payload (raw IIM stream, Photoshop resource block or
+/// PNG raw profile) to the bare IIM datasets.
+fn iim_block(data: &[u8]) -> Option<Vec<u8>> {
+    let decoded;
+    let mut iim = data;
+    if data.starts_with(b"\n") || data.starts_with(b"iptc") {
+        decoded = decode_raw_profile(data)?;
+        iim = &decoded;
+    }
+    if iim.first() != Some(&0x1C) {
+        iim = iim_from_photoshop(iim)?;
+    }
+    Some(iim.to_vec())
+}
+
+/// Parse IPTC-IIM datasets (record 2, Application) from any payload form
+/// accepted by `iim_block`.
+pub fn parse_iptc(data: &[u8]) -> Option<IptcMetadata> {
+    let iim = iim_block(data)?;
+    let mut meta = IptcMetadata::default();
+    let mut found = false;
+    let mut pos = 0;
+    while pos + 5 <= iim.len() && iim[pos] == 0x1C {
+        let record = iim[pos + 1];
+        let dataset = iim[pos + 2];
+        let len = u16::from_be_bytes([iim[pos + 3], iim[pos + 4]]) as usize;
+        // Extended-length datasets (high bit set) only occur for binary
+        // payloads; stop rather than misparse them.
+        if len & 0x8000 != 0 {
+            break;
+        }
+        let Some(value) = iim.get(pos + 5..pos + 5 + len) else {
+            break;
+        };
+        pos += 5 + len;
+        if record != 2 {
+            continue;
+        }
+        let text = String::from_utf8_lossy(value).trim().to_string();
+        if text.is_empty() {
+            continue;
+        }
+        found = true;
+        match dataset {
+            5 => meta.object_name = Some(text),
+            25 => meta.keywords.push(text),
+            55 => meta.date_created = Some(text),
+            80 => meta.by_line = Some(text),
+            90 => meta.city = Some(text),
+            101 => meta.country = Some(text),
+            105 => meta.headline = Some(text),
+            110 => meta.credit = Some(text),
+            116 => meta.copyright = Some(text),
+            120 => meta.caption = Some(text),
+            _ => {}
+        }
+    }
+    found.then_some(meta)
+}
+
+/// Raw bytes of a binary IFD0 tag. TIFF keeps IPTC (33723) and the ICC
+/// profile (34675) there, typed as LONG or UNDEFINED by different writers.
+fn tiff_tag_bytes(exif: &exif::Exif, number: u16) -> Option<Vec<u8>> {
+    let field = exif.get_field(exif::Tag(exif::Context::Tiff, number), exif::In::PRIMARY)?;
+    match &field.value {
+        exif::Value::Undefined(bytes, _) | exif::Value::Byte(bytes) => Some(bytes.clone()),
+        exif::Value::Long(words) => Some(
+            words
+                .iter()
+                .flat_map(|w| {
+                    if exif.little_endian() {
+                        w.to_le_bytes()
+                    } else {
+                        w.to_be_bytes()
+                    }
+                })
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+// ── Reading ─────────────────────────────────────────────────────────
+
+/// Metadata payloads exactly as stored in a file, ready to be re-embedded.
+#[derive(Debug, Clone, Default)]
+pub struct MetadataBlobs {
+    pub icc: Option<Vec<u8>>,
+    /// TIFF-structured EXIF, without the JPEG `Exif\0\0` prefix
+    pub exif: Option<Vec<u8>>,
+    pub xmp: Option<String>,
+    /// Bare IPTC-IIM datasets
+    pub iptc: Option<Vec<u8>>,
+    /// Leave a file's own JPEG comments and PNG author, comment and
+    /// description text in place when embedding
+    pub keep_comments: bool,
+    /// Leave a PNG's `tIME` chunk and "Creation Time" text in place
+    pub keep_times: bool,
+}
+
+pub fn read_blobs(path: &Path) -> Result<MetadataBlobs, AppError> {
+    let bytes = std::fs::read(path)?;
+    let exif = read_exif(path);
+
+    let mut blobs = MetadataBlobs::default();
+    // Formats without an `image` decoder here (AVIF) still get the raw scans below
+    if let Ok(mut decoder) = image::ImageReader::new(std::io::Cursor::new(&bytes))
+        .with_guessed_format()
+        .map_err(AppError::from)
+        .and_then(|r| r.into_decoder().map_err(AppError::from))
+    {
+        blobs.icc = decoder.icc_profile().ok().flatten();
+        blobs.xmp = decoder
+            .xmp_metadata()
+            .ok()
+            .flatten()
+            .map(|x| String::from_utf8_lossy(&x).into_owned());
+        blobs.iptc = decoder
+            .iptc_metadata()
+            .ok()
+            .flatten()
+            .and_then(|b| iim_block(&b));
+    }
+    if blobs.icc.is_none() {
+        blobs.icc = exif.as_ref().and_then(|e| tiff_tag_bytes(e, 34675));
+    }
+    if blobs.xmp.is_none() {
+        blobs.xmp = find_xmp_packet(&bytes);
+    }
+    if blobs.iptc.is_none() {
+        blobs.iptc = exif
+            .as_ref()
+            .and_then(|e| tiff_tag_bytes(e, 33723))
+            .and_then(|b| iim_block(&b));
+    }
+    blobs.exif = exif.map(|e| e.buf().to_vec());
+    Ok(blobs)
+}
+
+fn parse_exif_blob(blob: &[u8]) -> Option<exif::Exif> {
+    exif::Reader::new().read_raw(blob.to_vec()).ok()
+}
+
+pub fn read_metadata(path: &Path) -> Result<ImageMetadata, AppError> {
+    let blobs = read_blobs(path)?;
+    Ok(ImageMetadata {
+        exif: blobs
+            .exif
+            .as_deref()
+            .and_then(parse_exif_blob)
+            .as_ref()
+            .map(exif_metadata),
+        xmp: blobs.xmp.as_deref().map(parse_xmp),
+        iptc: blobs.iptc.as_deref().and_then(parse_iptc),
+    })
+}
+
+#[tauri::command]
+pub fn get_image_metadata(path: String) -> Result<ImageMetadata, AppError> {
+    read_metadata(Path::new(&path))
+}
+
+// ── Export ──────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MetadataMode {
+    /// Carry EXIF, XMP, IPTC and the ICC profile over
+    #[default]
+    Keep,
+    /// Only author and copyright fields, plus the ICC profile
+    CopyrightOnly,
+    Strip,
+}
+
+/// How the source metadata changes on its way into an export.
+pub struct MetadataUpdate<'a> {
+    pub mode: MetadataMode,
+    pub width: u32,
+    pub height: u32,
+    /// Written to EXIF Software and as the history agent
+    pub software: Option<&'a str>,
+    /// Edit steps, appended to the XMP `xmpMM:History`
+    pub history: &'a [String],
+}
+
+/// IFD0 tags that describe the photo rather than the pixel layout of the
+/// file they were read from.
+const DESCRIPTIVE_TIFF_TAGS: &[u16] = &[
+    270,   // ImageDescription
+    271,   // Make
+    272,   // Model
+    282,   // XResolution
+    283,   // YResolution
+    296,   // ResolutionUnit
+    305,   // Software
+    306,   // DateTime
+    315,   // Artist
+    318,   // WhitePoint
+    319,   // PrimaryChromaticities
+    529,   // YCbCrCoefficients
+    33432, // Copyright
+];
+
+fn ascii_field(tag: exif::Tag, text: &str) -> exif::Field {
+    exif::Field {
+        tag,
+        ifd_num: exif::In::PRIMARY,
+        value: exif::Value::Ascii(vec![text.as_bytes().to_vec()]),
+    }
+}
+
+fn rebuild_exif(source: Option<&[u8]>, update: &MetadataUpdate) -> Option<Vec<u8>> {
+    use exif::{Context, Tag, Value};
+    let parsed = source.and_then(parse_exif_blob);
+    let mut fields: Vec<exif::Field> = parsed
+        .iter()
+        .flat_map(|exif| exif.fields())
+        .filter(|f| f.ifd_num == exif::In::PRIMARY && !matches!(f.value, Value::Unknown(..)))
+        .filter(|f| match update.mode {
+            MetadataMode::Keep => match f.tag.context() {
+                Context::Tiff => DESCRIPTIVE_TIFF_TAGS.contains(&f.tag.number()),
+                _ => !matches!(f.tag, Tag::PixelXDimension | Tag::PixelYDimension),
+            },
+            MetadataMode::CopyrightOnly => matches!(f.tag, Tag::Artist | Tag::Copyright),
+            MetadataMode::Strip => false,
+        })
+        .cloned()
+        .collect();
+
+    if update.mode == MetadataMode::Keep && parsed.is_some() {
+        // Pixels are written upright, so the orientation must not be applied again
+        fields.push(exif::Field {
+            tag: Tag::Orientation,
+            ifd_num: exif::In::PRIMARY,
+            value: Value::Short(vec![1]),
+        });
+        for (tag, value) in [
+            (Tag::PixelXDimension, update.width),
+            (Tag::PixelYDimension, update.height),
+        ] {
+            fields.push(exif::Field {
+                tag,
+                ifd_num: exif::In::PRIMARY,
+                value: Value::Long(vec![value]),
+            });
+        }
+    }
+    if let Some(software) = update.software {
+        fields.retain(|f| f.tag != Tag::Software);
+        fields.push(ascii_field(Tag::Software, software));
+    }
+    let little_endian = parsed.as_ref().is_some_and(|e| e.little_endian());
+    write_exif(&fields, None, little_endian)
+}
+
+/// Serialize fields (and an optional IFD1 JPEG thumbnail) as an EXIF blob.
+fn write_exif(
+    fields: &[exif::Field],
+    thumbnail: Option<&[u8]>,
+    little_endian: bool,
+) -> Option<Vec<u8>> {
+    if fields.is_empty() {
+        return None;
+    }
+    let mut writer = exif::experimental::Writer::new();
+    for field in fields {
+        writer.push_field(field);
+    }
+    if let Some(jpeg) = thumbnail {
+        writer.set_jpeg(jpeg, exif::In::THUMBNAIL);
+    }
+    let mut buf = std::io::Cursor::new(Vec::new());
+    writer.write(&mut buf, little_endian).ok()?;
+    Some(buf.into_inner())
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Replace the value of an existing simple XMP property, in attribute or
+/// element form. Missing properties are left alone.
+fn xmp_set(packet: &str, name: &str, value: &str) -> String {
+    let attr = format!("{name}=\"");
+    if let Some(i) = packet
+        .match_indices(&attr)
+        .map(|(i, _)| i)
+        .find(|&i| packet[..i].ends_with(char::is_whitespace))
+    {
+        let start = i + attr.len();
+        if let Some(len) = packet[start..].find('"') {
+            return format!("{}{}{}", &packet[..start], value, &packet[start + len..]);
+        }
+    }
+    let open = format!("<{name}>");
+    let close = format!("</{name}>");
+    if let (Some(i), Some(j)) = (packet.find(&open), packet.find(&close)) {
+        if i < j {
+            let start = i + open.len();
+            return format!("{}{}{}", &packet[..start], value, &packet[j..]);
+        }
+    }
+    packet.to_string()
+}
+
+fn xmp_packet(descriptions: &str) -> String {
+    format!(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+         <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n\
+         <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n\
+         {descriptions}\
+         </rdf:RDF>\n\
+         </x:xmpmeta>\n\
+         <?xpacket end=\"w\"?>"
+    )
+}
+
+fn copyright_description(xmp: &XmpMetadata) -> Option<String> {
+    if xmp.creators.is_empty() && xmp.rights.is_none() {
+        return None;
+    }
+    let mut body = String::new();
+    if !xmp.creators.is_empty() {
+        let items: String = xmp
+            .creators
+            .iter()
+            .map(|c| format!("<rdf:li>{}</rdf:li>", xml_escape(c)))
+            .collect();
+        body.push_str(&format!(
+            "<dc:creator><rdf:Seq>{items}</rdf:Seq></dc:creator>\n"
+        ));
+    }
+    if let Some(rights) = &xmp.rights {
+        body.push_str(&format!(
+            "<dc:rights><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:rights>\n",
+            xml_escape(rights)
+        ));
+    }
+    Some(format!(
+        "<rdf:Description rdf:about=\"\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n{body}</rdf:Description>\n"
+    ))
+}
+
+/// Seconds since the epoch as an ISO 8601 UTC timestamp.
+fn utc_timestamp(secs: u64) -> String {
+    // Civil-from-days conversion (Howard Hinnant's algorithm)
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    let rem = secs % 86_400;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60
+    )
+}
+
+fn history_items(update: &MetadataUpdate) -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let when = utc_timestamp(secs);
+    let agent = update
+        .software
+        .map(|s| {
+            format!(
+                "<stEvt:softwareAgent>{}</stEvt:softwareAgent>",
+                xml_escape(s)
+            )
+        })
+        .unwrap_or_default();
+    update
+        .history
+        .iter()
+        .map(|entry| {
+            format!(
+                "<rdf:li rdf:parseType=\"Resource\"><stEvt:action>edited</stEvt:action>\
+                 <stEvt:parameters>{}</stEvt:parameters>{agent}<stEvt:when>{when}</stEvt:when></rdf:li>\n",
+                xml_escape(entry)
+            )
+        })
+        .collect()
+}
+
+/// Append history events to an existing `xmpMM:History` sequence, or add one
+/// in a new description.
+fn add_history(packet: Option<String>, items: &str) -> String {
+    if let Some(packet) = &packet {
+        if let Some(start) = packet.find("<xmpMM:History>") {
+            if let Some(end) = packet[start..].find("</rdf:Seq>") {
+                let at = start + end;
+                return format!("{}{}{}", &packet[..at], items, &packet[at..]);
+            }
+        }
+    }
+    let description = format!(
+        "<rdf:Description rdf:about=\"\" xmlns:xmpMM=\"http://ns.adobe.com/xap/1.0/mm/\" \
+         xmlns:stEvt=\"http://ns.adobe.com/xap/1.0/sType/ResourceEvent#\">\n\
+         <xmpMM:History><rdf:Seq>\n{items}</rdf:Seq></xmpMM:History>\n</rdf:Description>\n"
+    );
+    match packet {
+        Some(packet) if packet.contains("</rdf:RDF>") => {
+            packet.replacen("</rdf:RDF>", &format!("{description}</rdf:RDF>"), 1)
+        }
+        _ => xmp_packet(&description),
+    }
+}
+
+fn rebuild_xmp(source: Option<&str>, update: &MetadataUpdate) -> Option<String> {
+    let packet = match update.mode {
+        MetadataMode::Keep => source.map(|packet| {
+            let width = update.width.to_string();
+            let height = update.height.to_string();
+            [
+                ("tiff:Orientation", "1"),
+                ("tiff:ImageWidth", width.as_str()),
+                ("tiff:ImageLength", height.as_str()),
+                ("exif:PixelXDimension", width.as_str()),
+                ("exif:PixelYDimension", height.as_str()),
+            ]
+            .iter()
+            .fold(packet.to_string(), |p, (name, value)| {
+                xmp_set(&p, name, value)
+            })
+        }),
+        MetadataMode::CopyrightOnly => source
+            .map(parse_xmp)
+            .as_ref()
+            .and_then(copyright_description)
+            .map(|d| xmp_packet(&d)),
+        MetadataMode::Strip => None,
+    };
+    if update.history.is_empty() {
+        packet
+    } else {
+        Some(add_history(packet, &history_items(update)))
+    }
+}
+
+/// Serialize record-2 datasets, preceded by the mandatory record version.
+fn build_iim(datasets: &[(u8, &str)]) -> Vec<u8> {
+    let mut iim = vec![0x1C, 2, 0, 0, 2, 0, 4];
+    for (dataset, text) in datasets {
+        let bytes = &text.as_bytes()[..text.len().min(0x7FFF)];
+        iim.extend_from_slice(&[0x1C, 2, *dataset]);
+        iim.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+        iim.extend_from_slice(bytes);
+    }
+    iim
+}
+
+fn rebuild_iptc(source: Option<&[u8]>, mode: MetadataMode) -> Option<Vec<u8>> {
+    match mode {
+        MetadataMode::Keep => source.map(<[u8]>::to_vec),
+        MetadataMode::CopyrightOnly => {
+            let iptc = parse_iptc(source?)?;
+            let datasets: Vec<(u8, &str)> = [
+                (80, iptc.by_line.as_deref()),
+                (110, iptc.credit.as_deref()),
+                (116, iptc.copyright.as_deref()),
+            ]
+            .into_iter()
+            .filter_map(|(d, v)| Some((d, v?)))
+            .collect();
+            (!datasets.is_empty()).then(|| build_iim(&datasets))
+        }
+        MetadataMode::Strip => None,
+    }
+}
+
+/// Apply an export's metadata mode and edits to the blobs read from its source.
+pub fn prepare_blobs(source: &MetadataBlobs, update: &MetadataUpdate) -> MetadataBlobs {
+    MetadataBlobs {
+        icc: match update.mode {
+            MetadataMode::Strip => None,
+            _ => source.icc.clone(),
+        },
+        exif: rebuild_exif(source.exif.as_deref(), update),
+        xmp: rebuild_xmp(source.xmp.as_deref(), update),
+        iptc: rebuild_iptc(source.iptc.as_deref(), update.mode),
+        ..MetadataBlobs::default()
+    }
+}
+
+// ── Containers ──────────────────────────────────────────────────────
+//
+// Embedding rewrites only the metadata segments/chunks of an encoded file
+// and copies the compressed image data through untouched. Existing
+// metadata is always dropped first, so an empty `MetadataBlobs` strips it.
+
+const JPEG_EXIF_HEADER: &[u8] = b"Exif\0\0";
+const JPEG_XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const JPEG_XMP_EXTENSION_HEADER: &[u8] = b"http://ns.adobe.com/xmp/extension/\0";
+const JPEG_ICC_HEADER: &[u8] = b"ICC_PROFILE\0";
+/// Largest JPEG segment payload (the 16-bit length includes itself)
+const JPEG_MAX_PAYLOAD: usize = 65533;
+
+fn malformed(format: &str) -> AppError {
+    AppError::ImageDecode(format!("Malformed {format} file"))
+}
+
+fn jpeg_segment(
+    out: &mut Vec<u8>,
+    marker: u8,
+    parts: &[&[u8]],
+    what: &str,
+) -> Result<(), AppError> {
+    let len: usize = parts.iter().map(|p| p.len()).sum();
+    if len > JPEG_MAX_PAYLOAD {
+        return Err(AppError::SaveFailed(format!(
+            "The {what} metadata is {len} bytes, more than a JPEG segment can hold; \
+             export with less metadata or to another format"
+        )));
+    }
+    out.extend_from_slice(&[0xFF, marker]);
+    out.extend_from_slice(&((len + 2) as u16).to_be_bytes());
+    for part in parts {
+        out.extend_from_slice(part);
+    }
+    Ok(())
+}
+
+fn jpeg_metadata_segments(blobs: &MetadataBlobs) -> Result<Vec<u8>, AppError> {
+    let mut out = Vec::new();
+    if let Some(exif) = &blobs.exif {
+        jpeg_segment(&mut out, 0xE1, &[JPEG_EXIF_HEADER, exif], "EXIF")?;
+    }
+    if let Some(xmp) = &blobs.xmp {
+        jpeg_segment(&mut out, 0xE1, &[JPEG_XMP_HEADER, xmp.as_bytes()], "XMP")?;
+    }
+    if let Some(icc) = &blobs.icc {
+        // Split over APP2 segments of at most 65519 bytes, numbered from 1
+        let chunks: Vec<&[u8]> = icc
+            .chunks(JPEG_MAX_PAYLOAD - JPEG_ICC_HEADER.len() - 2)
+            .collect();
+        if chunks.len() > 255 {
+            return Err(AppError::SaveFailed(format!(
+                "The ICC profile is {} bytes, more than 255 JPEG segments can hold",
+                icc.len()
+            )));
+        }
+        for (i, chunk) in chunks.iter().enumerate() {
+            let seq = [i as u8 + 1, chunks.len() as u8];
+            jpeg_segment(&mut out, 0xE2, &[JPEG_ICC_HEADER, &seq, chunk], "ICC")?;
+        }
+    }
+    if let Some(iim) = &blobs.iptc {
+        let mut resource = b"8BIM".to_vec();
+        resource.extend_from_slice(&IPTC_RESOURCE_ID.to_be_bytes());
+        resource.extend_from_slice(&[0, 0]);
+        resource.extend_from_slice(&(iim.len() as u32).to_be_bytes());
+        resource.extend_from_slice(iim);
+        if iim.len() % 2 == 1 {
+            resource.push(0);
+        }
+        jpeg_segment(&mut out, 0xED, &[PHOTOSHOP_HEADER, &resource], "IPTC")?;
+    }
+    Ok(out)
+}
+
+fn is_jpeg_metadata(marker: u8, payload: &[u8], blobs: &MetadataBlobs) -> bool {
+    match marker {
+        0xE1 => {
+            payload.starts_with(JPEG_EXIF_HEADER)
+                || payload.starts_with(JPEG_XMP_HEADER)
+                || payload.starts_with(JPEG_XMP_EXTENSION_HEADER)
+        }
+        0xE2 => payload.starts_with(JPEG_ICC_HEADER),
+        0xED => payload.starts_with(PHOTOSHOP_HEADER),
+        // Comments
+        0xFE => !blobs.keep_comments,
+        _ => false,
+    }
+}
+
+fn embed_jpeg(bytes: &[u8], blobs: &MetadataBlobs) -> Result<Vec<u8>, AppError> {
+    if !bytes.starts_with(&[0xFF, 0xD8]) {
+        return Err(malformed("JPEG"));
+    }
+    let segments = jpeg_metadata_segments(blobs)?;
+    let mut out = Vec::with_capacity(bytes.len() + segments.len());
+    out.extend_from_slice(&bytes[..2]);
+    let mut inserted = false;
+    let mut pos = 2;
+    loop {
+        if pos + 2 > bytes.len() || bytes[pos] != 0xFF {
+            return Err(malformed("JPEG"));
+        }
+        let marker = bytes[pos + 1];
+        if marker == 0xFF {
+            // Fill byte
+            pos += 1;
+            continue;
+        }
+        // JFIF requires its APP0 to come first; everything else follows ours
+        if !inserted && marker != 0xE0 {
+            out.extend_from_slice(&segments);
+            inserted = true;
+        }
+        // Entropy-coded data starts at SOS; copy the rest verbatim
+        if marker == 0xDA || marker == 0xD9 {
+            out.extend_from_slice(&bytes[pos..]);
+            return Ok(out);
+        }
+        let len = bytes
+            .get(pos + 2..pos + 4)
+            .map(|l| u16::from_be_bytes([l[0], l[1]]) as usize)
+            .ok_or_else(|| malformed("JPEG"))?;
+        let end = pos + 2 + len;
+        let payload = bytes.get(pos + 4..end).ok_or_else(|| malformed("JPEG"))?;
+        if !is_jpeg_metadata(marker, payload, blobs) {
+            out.extend_from_slice(&bytes[pos..end]);
+        }
+        pos = end;
+    }
+}
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+fn crc32(parts: &[&[u8]]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in parts.iter().flat_map(|p| p.iter()) {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Wrap data in a zlib stream of stored (uncompressed) blocks, which is all
+/// iCCP and zTXt need to be valid.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xFFFF).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        out.push(u8::from(last));
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    out.extend_from_slice(&((b << 16) | a).to_be_bytes());
+    out
+}
+
+fn png_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    out.extend_from_slice(&crc32(&[kind, data]).to_be_bytes());
+}
+
+fn png_metadata_chunks(blobs: &MetadataBlobs) -> Vec<u8> {
+    let mut out = Vec::new();
+    if let Some(icc) = &blobs.icc {
+        let mut data = b"ICC Profile\0\0".to_vec();
+        data.extend_from_slice(&zlib_stored(icc));
+        png_chunk(&mut out, b"iCCP", &data);
+    }
+    if let Some(exif) = &blobs.exif {
+        png_chunk(&mut out, b"eXIf", exif);
+    }
+    if let Some(xmp) = &blobs.xmp {
+        // Keyword, no compression, empty language and translated keyword
+        let mut data = b"XML:com.adobe.xmp\0\0\0\0\0".to_vec();
+        data.extend_from_slice(xmp.as_bytes());
+        png_chunk(&mut out, b"iTXt", &data);
+    }
+    if let Some(iim) = &blobs.iptc {
+        // ImageMagick's raw profile layout: name, length, then hex lines
+        let hex: Vec<String> = iim
+            .chunks(36)
+            .map(|line| line.iter().map(|b| format!("{b:02x}")).collect())
+            .collect();
+        let profile = format!("\niptc\n{:8}\n{}\n", iim.len(), hex.join("\n"));
+        let mut data = b"Raw profile type iptc\0\0".to_vec();
+        data.extend_from_slice(&zlib_stored(profile.as_bytes()));
+        png_chunk(&mut out, b"zTXt", &data);
+    }
+    out
+}
+
+fn embed_png(bytes: &[u8], blobs: &MetadataBlobs) -> Result<Vec<u8>, AppError> {
+    if !bytes.starts_with(PNG_SIGNATURE) {
+        return Err(malformed("PNG"));
+    }
+    let mut out = PNG_SIGNATURE.to_vec();
+    let mut pos = PNG_SIGNATURE.len();
+    while pos < bytes.len() {
+        let header = bytes.get(pos..pos + 8).ok_or_else(|| malformed("PNG"))?;
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let kind = &header[4..8];
+        let end = pos + 12 + len;
+        let chunk = bytes.get(pos..end).ok_or_else(|| malformed("PNG"))?;
+        let drop = match kind {
+            b"iCCP" | b"eXIf" => true,
+            b"tEXt" | b"zTXt" | b"iTXt" => {
+                let data = &chunk[8..chunk.len() - 4];
+                let keyword = &data[..data.iter().position(|&b| b == 0).unwrap_or(0)];
+                match keyword {
+                    b"Author" | b"Comment" | b"Description" => !blobs.keep_comments,
+                    b"Creation Time" => !blobs.keep_times,
+                    // Title, Copyright, Software and the like stay
+                    _ => {
+                        keyword == b"XML:com.adobe.xmp" || keyword.starts_with(b"Raw profile type")
+                    }
+                }
+            }
+            b"tIME" => !blobs.keep_times,
+            // sRGB and an embedded profile are mutually exclusive
+            b"sRGB" => blobs.icc.is_some(),
+            _ => false,
+        };
+        if !drop {
+            out.extend_from_slice(chunk);
+        }
+        if kind == b"IHDR" {
+            out.extend_from_slice(&png_metadata_chunks(blobs));
+        }
+        pos = end;
+    }
+    Ok(out)
+}
+
+const WEBP_FLAG_ICC: u8 = 0x20;
+const WEBP_FLAG_ALPHA: u8 = 0x10;
+const WEBP_FLAG_EXIF: u8 = 0x08;
+const WEBP_FLAG_XMP: u8 = 0x04;
+const WEBP_FLAG_ANIMATION: u8 = 0x02;
+
+/// Canvas size and alpha flag from a simple-format VP8 or VP8L bitstream.
+fn webp_bitstream_info(kind: &[u8], data: &[u8]) -> Option<(u32, u32, bool)> {
+    match kind {
+        b"VP8L" if data.len() >= 5 && data[0] == 0x2F => {
+            let bits = u32::from_le_bytes([data[1], data[2], data[3], data[4]]);
+            Some((
+                (bits & 0x3FFF) + 1,
+                ((bits >> 14) & 0x3FFF) + 1,
+                (bits >> 28) & 1 == 1,
+            ))
+        }
+        b"VP8 " if data.len() >= 10 && data[3..6] == [0x9D, 0x01, 0x2A] => Some((
+            u16::from_le_bytes([data[6], data[7]]) as u32 & 0x3FFF,
+            u16::from_le_bytes([data[8], data[9]]) as u32 & 0x3FFF,
+            false,
+        )),
+        _ => None,
+    }
+}
+
+fn embed_webp(bytes: &[u8], blobs: &MetadataBlobs) -> Result<Vec<u8>, AppError> {
+    if bytes.len() < 12 || &bytes[..4] != b"RIFF" || &bytes[8..12] != b"WEBP" {
+        return Err(malformed("WebP"));
+    }
+    let mut vp8x: Option<&[u8]> = None;
+    let mut image_chunks: Vec<(&[u8], &[u8])> = Vec::new();
+    let mut pos = 12;
+    while pos + 8 <= bytes.len() {
+        let kind = &bytes[pos..pos + 4];
+        let len = u32::from_le_bytes([
+            bytes[pos + 4],
+            bytes[pos + 5],
+            bytes[pos + 6],
+            bytes[pos + 7],
+        ]) as usize;
+        let data = bytes
+            .get(pos + 8..pos + 8 + len)
+            .ok_or_else(|| malformed("WebP"))?;
+        match kind {
+            b"VP8X" => vp8x = Some(data),
+            b"ICCP" | b"EXIF" | b"XMP " => {}
+            _ => image_chunks.push((kind, data)),
+        }
+        pos += 8 + len + len % 2;
+    }
+
+    let (mut flags, canvas) = match vp8x.filter(|x| x.len() >= 10) {
+        Some(x) => (
+            x[0] & (WEBP_FLAG_ALPHA | WEBP_FLAG_ANIMATION),
+            x[4..10].to_vec(),
+        ),
+        None => {
+            let (kind, data) = image_chunks.first().ok_or_else(|| malformed("WebP"))?;
+            let (w, h, alpha) = webp_bitstream_info(kind, data).ok_or_else(|| malformed("WebP"))?;
+            let mut canvas = Vec::new();
+            push_u24(&mut canvas, w - 1);
+            push_u24(&mut canvas, h - 1);
+            (if alpha { WEBP_FLAG_ALPHA } else { 0 }, canvas)
+        }
+    };
+    if image_chunks.iter().any(|(kind, _)| *kind == b"ALPH") {
+        flags |= WEBP_FLAG_ALPHA;
+    }
+    for (present, flag) in [
+        (blobs.icc.is_some(), WEBP_FLAG_ICC),
+        (blobs.exif.is_some(), WEBP_FLAG_EXIF),
+        (blobs.xmp.is_some(), WEBP_FLAG_XMP),
+    ] {
+        if present {
+            flags |= flag;
+        }
+    }
+
+    let mut body = b"WEBP".to_vec();
+    // The simple format (a lone VP8/VP8L chunk) can't carry anything else
+    let extended = flags & !WEBP_FLAG_ALPHA != 0
+        || image_chunks.iter().any(|(kind, _)| *kind == b"ALPH")
+        || image_chunks.len() > 1;
+    if extended {
+        let mut header = vec![flags, 0, 0, 0];
+        header.extend_from_slice(&canvas);
+        push_riff_chunk(&mut body, b"VP8X", &header);
+    }
+    if let Some(icc) = &blobs.icc {
+        push_riff_chunk(&mut body, b"ICCP", icc);
+    }
+    for (kind, data) in &image_chunks {
+        push_riff_chunk(&mut body, kind, data);
+    }
+    if let Some(exif) = &blobs.exif {
+        push_riff_chunk(&mut body, b"EXIF", exif);
+    }
+    if let Some(xmp) = &blobs.xmp {
+        push_riff_chunk(&mut body, b"XMP ", xmp.as_bytes());
+    }
+
+    let mut out = b"RIFF".to_vec();
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(&body);
+    Ok(out)
+}
+
+/// IFD0 tags holding metadata rather than image structure. Orientation is
+/// deliberately absent: it describes how the stored pixels are displayed.
+const TIFF_METADATA_TAGS: &[u16] = &[
+    270,   // ImageDescription
+    271,   // Make
+    272,   // Model
+    305,   // Software
+    306,   // DateTime
+    315,   // Artist
+    316,   // HostComputer
+    700,   // XMP
+    33432, // Copyright
+    33723, // IPTC
+    34377, // Photoshop resources
+    34665, // Exif IFD
+    34675, // ICC profile
+    34853, // GPS IFD
+    37724, // Photoshop layer data
+    40965, // Interoperability IFD
+];
+const TIFF_SUB_IFD_TAGS: &[u16] = &[34665, 34853, 40965];
+const TIFF_TEXT_TAGS: &[u16] = &[270, 271, 272, 305, 306, 315, 316, 33432];
+
+pub(crate) struct TiffReader<'a> {
+    pub bytes: &'a [u8],
+    pub little_endian: bool,
+}
+
+impl TiffReader<'_> {
+    pub fn u16(&self, at: usize) -> Option<u16> {
+        let b: [u8; 2] = self.bytes.get(at..at + 2)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        })
+    }
+
+    pub fn u32(&self, at: usize) -> Option<u32> {
+        let b: [u8; 4] = self.bytes.get(at..at + 4)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    }
+
+    pub fn u64(&self, at: usize) -> Option<u64> {
+        let b: [u8; 8] = self.bytes.get(at..at + 8)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u64::from_le_bytes(b)
+        } else {
+            u64::from_be_bytes(b)
+        })
+    }
+
+    /// Byte range of an entry's value when it doesn't fit inline.
+    fn out_of_line(&self, entry: usize) -> Option<std::ops::Range<usize>> {
+        let unit = match self.u16(entry + 2)? {
+            1 | 2 | 6 | 7 => 1,
+            3 | 8 => 2,
+            4 | 9 | 11 | 13 => 4,
+            5 | 10 | 12 => 8,
+            _ => return None,
+        };
+        let size = unit * self.u32(entry + 4)? as usize;
+        if size <= 4 {
+            return None;
+        }
+        let offset = self.u32(entry + 8)? as usize;
+        (offset + size <= self.bytes.len()).then_some(offset..offset + size)
+    }
+}
+
+/// Ranges to blank out when dropping an entry: its value and, for sub-IFD
+/// pointers, the whole sub-directory, so stripped GPS data can't be recovered.
+fn tiff_dropped_ranges(
+    reader: &TiffReader,
+    entry: usize,
+    depth: u8,
+    out: &mut Vec<std::ops::Range<usize>>,
+) {
+    if let Some(range) = reader.out_of_line(entry) {
+        out.push(range);
+    }
+    let Some(tag) = reader.u16(entry) else {
+        return;
+    };
+    if depth > 0 || !TIFF_SUB_IFD_TAGS.contains(&tag) {
+        return;
+    }
+    let Some(ifd) = reader.u32(entry + 8).map(|o| o as usize) else {
+        return;
+    };
+    let Some(count) = reader.u16(ifd).map(|c| c as usize) else {
+        return;
+    };
+    let end = ifd + 2 + count * 12 + 4;
+    if end > reader.bytes.len() {
+        return;
+    }
+    for i in 0..count {
+        tiff_dropped_ranges(reader, ifd + 2 + i * 12, depth + 1, out);
+    }
+    out.push(ifd..end);
+}
+
+/// Offsets of the value fields in `ifd` that point elsewhere in the file,
+/// including those of a nested Interoperability IFD.
+fn tiff_offset_fields(reader: &TiffReader, ifd: usize, depth: u8, out: &mut Vec<usize>) {
+    let count = reader.u16(ifd).unwrap_or(0) as usize;
+    for i in 0..count {
+        let at = ifd + 2 + i * 12;
+        if reader.out_of_line(at).is_some() {
+            out.push(at + 8);
+        } else if depth == 0 && reader.u16(at) == Some(40965) {
+            if let Some(interop) = reader.u32(at + 8) {
+                tiff_offset_fields(reader, interop as usize, depth + 1, out);
+            }
+            out.push(at + 8);
+        }
+    }
+}
+
+/// Append the Exif and GPS sub-IFDs of an EXIF blob to `out`, serialized in
+/// its byte order, with their offsets moved to where they land. Returns the
+/// IFD0 pointer entries to add for them.
+fn append_tiff_sub_ifds(out: &mut Vec<u8>, blob: &[u8], little_endian: bool) -> Vec<(u16, u32)> {
+    use exif::Context;
+    let fields: Vec<exif::Field> = parse_exif_blob(blob)
+        .iter()
+        .flat_map(|exif| exif.fields())
+        .filter(|f| {
+            f.ifd_num == exif::In::PRIMARY
+                && matches!(
+                    f.tag.context(),
+                    Context::Exif | Context::Gps | Context::Interop
+                )
+                && !matches!(f.value, exif::Value::Unknown(..))
+        })
+        .cloned()
+        .collect();
+    // The writer lays out a TIFF whose IFD0 holds only the sub-IFD pointers
+    let Some(sub) = write_exif(&fields, None, little_endian) else {
+        return vec![];
+    };
+    let reader = TiffReader {
+        bytes: &sub,
+        little_endian,
+    };
+    let Some(ifd0) = reader.u32(4).map(|o| o as usize) else {
+        return vec![];
+    };
+    let count = reader.u16(ifd0).unwrap_or(0) as usize;
+
+    if out.len() % 2 == 1 {
+        out.push(0);
+    }
+    let base = out.len();
+    let mut pointers = Vec::new();
+    let mut offsets = Vec::new();
+    for i in 0..count {
+        let at = ifd0 + 2 + i * 12;
+        if let (Some(tag @ (34665 | 34853)), Some(ifd)) = (reader.u16(at), reader.u32(at + 8)) {
+            tiff_offset_fields(&reader, ifd as usize, 0, &mut offsets);
+            pointers.push((tag, base as u32 + ifd));
+        }
+    }
+
+    let mut moved = sub.clone();
+    for at in offsets {
+        let Some(offset) = reader.u32(at) else {
+            continue;
+        };
+        let offset = base as u32 + offset;
+        moved[at..at + 4].copy_from_slice(&if little_endian {
+            offset.to_le_bytes()
+        } else {
+            offset.to_be_bytes()
+        });
+    }
+    // The blob's own header and IFD0 are left behind as padding
+    moved[..8].fill(0);
+    moved[ifd0..ifd0 + 2 + count * 12 + 4].fill(0);
+    out.extend_from_slice(&moved);
+    pointers
+}
+
+fn embed_tiff(bytes: &[u8], blobs: &MetadataBlobs) -> Result<Vec<u8>, AppError> {
+    let little_endian = match bytes.get(..4) {
+        Some(b"II*\0") => true,
+        Some(b"MM\0*") => false,
+        Some(b"II+\0") | Some(b"MM\0+") => {
+            return Err(AppError::UnsupportedFormat("BigTIFF metadata".into()))
+        }
+        _ => return Err(malformed("TIFF")),
+    };
+    let reader = TiffReader {
+        bytes,
+        little_endian,
+    };
+    let ifd = reader.u32(4).ok_or_else(|| malformed("TIFF"))? as usize;
+    let count = reader.u16(ifd).ok_or_else(|| malformed("TIFF"))? as usize;
+    let next_ifd = reader
+        .u32(ifd + 2 + count * 12)
+        .ok_or_else(|| malformed("TIFF"))?;
+
+    let mut entries: Vec<(u16, Vec<u8>)> = Vec::new();
+    let mut blank = Vec::new();
+    for i in 0..count {
+        let at = ifd + 2 + i * 12;
+        let tag = reader.u16(at).ok_or_else(|| malformed("TIFF"))?;
+        if TIFF_METADATA_TAGS.contains(&tag) {
+            tiff_dropped_ranges(&reader, at, 0, &mut blank);
+        } else {
+            entries.push((tag, bytes[at..at + 12].to_vec()));
+        }
+    }
+
+    let mut out = bytes.to_vec();
+    for range in blank {
+        out[range].fill(0);
+    }
+
+    let u16_bytes = |v: u16| {
+        if little_endian {
+            v.to_le_bytes()
+        } else {
+            v.to_be_bytes()
+        }
+    };
+    let u32_bytes = |v: u32| {
+        if little_endian {
+            v.to_le_bytes()
+        } else {
+            v.to_be_bytes()
+        }
+    };
+
+    // (tag, type, payload)
+    let mut added: Vec<(u16, u16, Vec<u8>)> = Vec::new();
+    if let Some(exif) = blobs.exif.as_deref().and_then(parse_exif_blob) {
+        for field in exif.fields().filter(|f| {
+            f.ifd_num == exif::In::PRIMARY
+                && f.tag.context() == exif::Context::Tiff
+                && TIFF_TEXT_TAGS.contains(&f.tag.number())
+        }) {
+            if let exif::Value::Ascii(parts) = &field.value {
+                let mut text = parts.first().cloned().unwrap_or_default();
+                text.push(0);
+                added.push((field.tag.number(), 2, text));
+            }
+        }
+    }
+    if let Some(xmp) = &blobs.xmp {
+        added.push((700, 1, xmp.as_bytes().to_vec()));
+    }
+    if let Some(iim) = &blobs.iptc {
+        added.push((33723, 7, iim.clone()));
+    }
+    if let Some(icc) = &blobs.icc {
+        added.push((34675, 7, icc.clone()));
+    }
+
+    for (tag, kind, payload) in added {
+        let mut entry = Vec::with_capacity(12);
+        entry.extend_from_slice(&u16_bytes(tag));
+        entry.extend_from_slice(&u16_bytes(kind));
+        entry.extend_from_slice(&u32_bytes(payload.len() as u32));
+        if payload.len() <= 4 {
+            let mut inline = payload.clone();
+            inline.resize(4, 0);
+            entry.extend_from_slice(&inline);
+        } else {
+            if out.len() % 2 == 1 {
+                out.push(0);
+            }
+            entry.extend_from_slice(&u32_bytes(out.len() as u32));
+            out.extend_from_slice(&payload);
+        }
+        entries.push((tag, entry));
+    }
+    if let Some(exif) = &blobs.exif {
+        for (tag, ifd) in append_tiff_sub_ifds(&mut out, exif, little_endian) {
+            let mut entry = Vec::with_capacity(12);
+            entry.extend_from_slice(&u16_bytes(tag));
+            entry.extend_from_slice(&u16_bytes(4));
+            entry.extend_from_slice(&u32_bytes(1));
+            entry.extend_from_slice(&u32_bytes(ifd));
+            entries.push((tag, entry));
+        }
+    }
+    entries.sort_by_key(|(tag, _)| *tag);
+
+    // Write the new IFD0 at the end and point the header at it; the old one
+    // is blanked so it doesn't linger as a stale copy
+    if out.len() % 2 == 1 {
+        out.push(0);
+    }
+    let new_ifd = out.len() as u32;
+    out.extend_from_slice(&u16_bytes(entries.len() as u16));
+    for (_, entry) in &entries {
+        out.extend_from_slice(entry);
+    }
+    out.extend_from_slice(&u32_bytes(next_ifd));
+    out[ifd..ifd + 2 + count * 12 + 4].fill(0);
+    out[4..8].copy_from_slice(&u32_bytes(new_ifd));
+    Ok(out)
+}
+
+// AVIF stores EXIF and XMP as items in its `meta` box: each described in
+// `iinf`, located by `iloc` and tied to the image by a `cdsc` reference.
+
+const AVIF_XMP_CONTENT_TYPE: &[u8] = b"application/rdf+xml";
+
+/// Big-endian unsigned integer of 0, 4 or 8 bytes (or 2, for fixed fields).
+fn be_uint(bytes: &[u8], at: usize, size: usize) -> Option<u64> {
+    if size > 8 {
+        return None;
+    }
+    let b = bytes.get(at..at.checked_add(size)?)?;
+    Some(b.iter().fold(0, |v, &byte| (v << 8) | u64::from(byte)))
+}
+
+fn put_be(out: &mut Vec<u8>, value: u64, size: usize) {
+    out.extend_from_slice(&value.to_be_bytes()[8 - size..]);
+}
+
+/// A box of an ISO base media file, as positions in it.
+struct IsoBox {
+    kind: [u8; 4],
+    start: usize,
+    body: usize,
+    end: usize,
+}
+
+fn iso_boxes(bytes: &[u8], range: std::ops::Range<usize>) -> Option<Vec<IsoBox>> {
+    let mut boxes = Vec::new();
+    let mut pos = range.start;
+    while pos < range.end {
+        let kind = bytes.get(pos + 4..pos + 8)?.try_into().ok()?;
+        let (body, end) = match be_uint(bytes, pos, 4)? {
+            // Runs to the end of the enclosing box
+            0 => (pos + 8, range.end),
+            1 => (
+                pos + 16,
+                pos.checked_add(usize::try_from(be_uint(bytes, pos + 8, 8)?).ok()?)?,
+            ),
+            size => (pos + 8, pos.checked_add(size as usize)?),
+        };
+        if body > end || end > range.end {
+            return None;
+        }
+        boxes.push(IsoBox {
+            kind,
+            start: pos,
+            body,
+            end,
+        });
+        pos = end;
+    }
+    Some(boxes)
+}
+
+fn iso_box(out: &mut Vec<u8>, kind: &[u8; 4], body: &[u8]) {
+    out.extend_from_slice(&((body.len() + 8) as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(body);
+}
+
+/// Where an item's data lives, from `iloc`.
+#[derive(Clone)]
+struct ItemLocation {
+    id: u32,
+    construction_method: u8,
+    data_reference: u16,
+    /// (index, offset, length), with the base offset already added in
+    extents: Vec<(u64, u64, u64)>,
+}
+
+fn parse_iloc(bytes: &[u8], iloc: &IsoBox) -> Option<Vec<ItemLocation>> {
+    let version = *bytes.get(iloc.body)?;
+    let sizes = be_uint(bytes, iloc.body + 4, 2)? as usize;
+    let (offset_size, length_size, base_size) = (sizes >> 12, (sizes >> 8) & 15, (sizes >> 4) & 15);
+    let index_size = if version > 0 { sizes & 15 } else { 0 };
+    if [offset_size, length_size, base_size, index_size]
+        .iter()
+        .any(|s| ![0, 4, 8].contains(s))
+    {
+        return None;
+    }
+    let id_size = if version < 2 { 2 } else { 4 };
+    let count = be_uint(bytes, iloc.body + 6, id_size)?;
+    let mut pos = iloc.body + 6 + id_size;
+    let mut items = Vec::new();
+    for _ in 0..count {
+        let id = be_uint(bytes, pos, id_size)? as u32;
+        pos += id_size;
+        let mut construction_method = 0;
+        if version > 0 {
+            construction_method = (be_uint(bytes, pos, 2)? & 15) as u8;
+            pos += 2;
+        }
+        let data_reference = be_uint(bytes, pos, 2)? as u16;
+        let base = be_uint(bytes, pos + 2, base_size)?;
+        let extent_count = be_uint(bytes, pos + 2 + base_size, 2)?;
+        pos += 4 + base_size;
+        let mut extents = Vec::new();
+        for _ in 0..extent_count {
+            let index = be_uint(bytes, pos, index_size)?;
+            let offset = be_uint(bytes, pos + index_size, offset_size)?;
+            let length = be_uint(bytes, pos + index_size + offset_size, length_size)?;
+            pos += index_size + offset_size + length_size;
+            extents.push((index, base.checked_add(offset)?, length));
+        }
+        items.push(ItemLocation {
+            id,
+            construction_method,
+            data_reference,
+            extents,
+        });
+    }
+    (pos <= iloc.end).then_some(items)
+}
+
+/// `iloc` body with 4- or 8-byte (`wide`) offsets and lengths, so its size
+/// doesn't depend on the values in it.
+fn write_iloc(items: &[ItemLocation], wide: bool) -> Vec<u8> {
+    let version = if items.iter().any(|i| i.id > 0xFFFF) {
+        2
+    } else {
+        1
+    };
+    let id_size = if version == 2 { 4 } else { 2 };
+    let size = if wide { 8 } else { 4 };
+    let index_size = match items.iter().flat_map(|i| &i.extents).map(|e| e.0).max() {
+        None | Some(0) => 0,
+        Some(i) if i <= u64::from(u32::MAX) => 4,
+        Some(_) => 8,
+    };
+    let mut out = vec![version, 0, 0, 0];
+    put_be(
+        &mut out,
+        ((size << 12) | (size << 8) | index_size) as u64,
+        2,
+    );
+    put_be(&mut out, items.len() as u64, id_size);
+    for item in items {
+        put_be(&mut out, item.id.into(), id_size);
+        put_be(&mut out, item.construction_method.into(), 2);
+        put_be(&mut out, item.data_reference.into(), 2);
+        put_be(&mut out, item.extents.len() as u64, 2);
+        for &(index, offset, length) in &item.extents {
+            put_be(&mut out, index, index_size);
+            put_be(&mut out, offset, size);
+            put_be(&mut out, length, size);
+        }
+    }
+    out
+}
+
+/// Item ids from `iinf`, whether each holds EXIF or XMP, and where its
+/// `infe` box is.
+fn parse_iinf(bytes: &[u8], iinf: &IsoBox) -> Option<Vec<(u32, bool, std::ops::Range<usize>)>> {
+    let count_size = if *bytes.get(iinf.body)? == 0 { 2 } else { 4 };
+    let mut items = Vec::new();
+    for infe in iso_boxes(bytes, iinf.body + 4 + count_size..iinf.end)? {
+        if &infe.kind != b"infe" {
+            continue;
+        }
+        let at = infe.body + 4;
+        let (id, item_type) = match *bytes.get(infe.body)? {
+            0 | 1 => (be_uint(bytes, at, 2)?, None),
+            2 => (be_uint(bytes, at, 2)?, Some(at + 4)),
+            _ => (be_uint(bytes, at, 4)?, Some(at + 6)),
+        };
+        let metadata = item_type.is_some_and(|t| match bytes.get(t..t + 4) {
+            Some(b"Exif") => true,
+            // The item name and content type follow, NUL-terminated
+            Some(b"mime") => {
+                bytes
+                    .get(t + 4..infe.end)
+                    .and_then(|s| s.split(|&c| c == 0).nth(1))
+                    == Some(AVIF_XMP_CONTENT_TYPE)
+            }
+            _ => false,
+        });
+        items.push((id as u32, metadata, infe.start..infe.end));
+    }
+    Some(items)
+}
+
+fn avif_infe(id: u32, item_type: &[u8; 4], content_type: &[u8]) -> Vec<u8> {
+    let mut body = Vec::new();
+    if id > 0xFFFF {
+        body.extend_from_slice(&[3, 0, 0, 0]);
+        put_be(&mut body, id.into(), 4);
+    } else {
+        body.extend_from_slice(&[2, 0, 0, 0]);
+        put_be(&mut body, id.into(), 2);
+    }
+    // Protection index, type and an empty name
+    body.extend_from_slice(&[0, 0]);
+    body.extend_from_slice(item_type);
+    body.push(0);
+    if !content_type.is_empty() {
+        body.extend_from_slice(content_type);
+        body.push(0);
+    }
+    let mut out = Vec::new();
+    iso_box(&mut out, b"infe", &body);
+    out
+}
+
+/// (reference type, from item, to items), from `iref`.
+type ItemReference = ([u8; 4], u32, Vec<u32>);
+
+fn parse_iref(bytes: &[u8], iref: &IsoBox) -> Option<Vec<ItemReference>> {
+    let id_size = if *bytes.get(iref.body)? == 0 { 2 } else { 4 };
+    let mut refs = Vec::new();
+    for r in iso_boxes(bytes, iref.body + 4..iref.end)? {
+        let from = be_uint(bytes, r.body, id_size)? as u32;
+        let count = be_uint(bytes, r.body + id_size, 2)? as usize;
+        let to = (0..count)
+            .map(|i| be_uint(bytes, r.body + id_size + 2 + i * id_size, id_size).map(|v| v as u32))
+            .collect::<Option<Vec<_>>>()?;
+        refs.push((r.kind, from, to));
+    }
+    Some(refs)
+}
+
+fn write_iref(refs: &[ItemReference]) -> Vec<u8> {
+    let wide = refs
+        .iter()
+        .any(|(_, from, to)| *from > 0xFFFF || to.iter().any(|&t| t > 0xFFFF));
+    let id_size = if wide { 4 } else { 2 };
+    let mut out = vec![u8::from(wide), 0, 0, 0];
+    for (kind, from, to) in refs {
+        let mut body = Vec::new();
+        put_be(&mut body, (*from).into(), id_size);
+        put_be(&mut body, to.len() as u64, 2);
+        for &t in to {
+            put_be(&mut body, t.into(), id_size);
+        }
+        iso_box(&mut out, kind, &body);
+    }
+    out
+}
+
+fn embed_avif(bytes: &[u8], blobs: &MetadataBlobs) -> Result<Vec<u8>, AppError> {
+    let top = iso_boxes(bytes, 0..bytes.len()).ok_or_else(|| malformed("AVIF"))?;
+    // Sequences keep sample offsets in `moov` too, which this doesn't move
+    if top.iter().any(|b| &b.kind == b"moov") {
+        return Err(AppError::UnsupportedFormat(
+            "Metadata in AVIF image sequences".into(),
+        ));
+    }
+    let meta = top
+        .iter()
+        .find(|b| &b.kind == b"meta")
+        .ok_or_else(|| malformed("AVIF"))?;
+    let children = iso_boxes(bytes, meta.body + 4..meta.end).ok_or_else(|| malformed("AVIF"))?;
+    let child = |kind: &[u8; 4]| children.iter().find(|b| &b.kind == kind);
+    let primary = child(b"pitm")
+        .and_then(|b| match *bytes.get(b.body)? {
+            0 => be_uint(bytes, b.body + 4, 2),
+            _ => be_uint(bytes, b.body + 4, 4),
+        })
+        .ok_or_else(|| malformed("AVIF"))? as u32;
+    let items = child(b"iinf")
+        .and_then(|b| parse_iinf(bytes, b))
+        .ok_or_else(|| malformed("AVIF"))?;
+    let mut locations = child(b"iloc")
+        .and_then(|b| parse_iloc(bytes, b))
+        .ok_or_else(|| malformed("AVIF"))?;
+    let mut refs = match child(b"iref") {
+        Some(b) => parse_iref(bytes, b).ok_or_else(|| malformed("AVIF"))?,
+        None => vec![],
+    };
+
+    // Drop the existing EXIF and XMP items, blanking their data so stripped
+    // metadata can't be recovered
+    let dropped: Vec<u32> = items.iter().filter(|i| i.1).map(|i| i.0).collect();
+    let mut src = bytes.to_vec();
+    let idat = child(b"idat").map(|b| b.body);
+    for location in locations
+        .iter()
+        .filter(|l| dropped.contains(&l.id) && l.data_reference == 0)
+    {
+        let origin = match (location.construction_method, idat) {
+            (0, _) => 0,
+            (1, Some(idat)) => idat as u64,
+            _ => continue,
+        };
+        for &(_, offset, length) in &location.extents {
+            let start = (origin + offset).min(src.len() as u64) as usize;
+            let end = (origin + offset + length).min(src.len() as u64) as usize;
+            src[start..end].fill(0);
+        }
+    }
+    locations.retain(|l| !dropped.contains(&l.id));
+    refs.retain_mut(|(_, from, to)| {
+        to.retain(|t| !dropped.contains(t));
+        !dropped.contains(from) && !to.is_empty()
+    });
+
+    let mut next_id = items
+        .iter()
+        .map(|i| i.0)
+        .chain(locations.iter().map(|l| l.id))
+        .max()
+        .unwrap_or(0)
+        + 1;
+    let mut added: Vec<(u32, Vec<u8>, Vec<u8>)> = Vec::new();
+    if let Some(exif) = &blobs.exif {
+        // Prefixed with the offset of the TIFF header within the item
+        let mut data = vec![0; 4];
+        data.extend_from_slice(exif);
+        added.push((next_id, avif_infe(next_id, b"Exif", b""), data));
+        next_id += 1;
+    }
+    if let Some(xmp) = &blobs.xmp {
+        added.push((
+            next_id,
+            avif_infe(next_id, b"mime", AVIF_XMP_CONTENT_TYPE),
+            xmp.as_bytes().to_vec(),
+        ));
+    }
+
+    let mut iinf = Vec::new();
+    let entry_count = items.len() - dropped.len() + added.len();
+    if entry_count > 0xFFFF {
+        iinf.extend_from_slice(&[1, 0, 0, 0]);
+        put_be(&mut iinf, entry_count as u64, 4);
+    } else {
+        iinf.extend_from_slice(&[0, 0, 0, 0]);
+        put_be(&mut iinf, entry_count as u64, 2);
+    }
+    for (_, _, range) in items.iter().filter(|i| !i.1) {
+        iinf.extend_from_slice(&src[range.clone()]);
+    }
+    for (id, infe, _) in &added {
+        iinf.extend_from_slice(infe);
+        refs.push((*b"cdsc", *id, vec![primary]));
+    }
+
+    // Data stored "to the end of the file" gets an explicit length, since
+    // the new items are appended after it
+    for location in locations
+        .iter_mut()
+        .filter(|l| l.construction_method == 0 && l.data_reference == 0)
+    {
+        for extent in location.extents.iter_mut().filter(|e| e.2 == 0) {
+            extent.2 = (bytes.len() as u64).saturating_sub(extent.1);
+        }
+    }
+    let added_len: usize = added.iter().map(|a| a.2.len()).sum();
+    let wide = bytes.len() + 2 * (meta.end - meta.start) + added_len + 4096 > u32::MAX as usize;
+    let build_meta = |locations: &[ItemLocation]| {
+        let mut body = src[meta.body..meta.body + 4].to_vec();
+        for c in &children {
+            match &c.kind {
+                b"iloc" => iso_box(&mut body, b"iloc", &write_iloc(locations, wide)),
+                b"iref" => {}
+                b"iinf" => {
+                    iso_box(&mut body, b"iinf", &iinf);
+                    if !refs.is_empty() {
+                        iso_box(&mut body, b"iref", &write_iref(&refs));
+                    }
+                }
+                _ => body.extend_from_slice(&src[c.start..c.end]),
+            }
+        }
+        let mut out = Vec::new();
+        iso_box(&mut out, b"meta", &body);
+        out
+    };
+    let with_added = |mut locations: Vec<ItemLocation>, at: u64| {
+        let mut offset = at;
+        for (id, _, data) in &added {
+            locations.push(ItemLocation {
+                id: *id,
+                construction_method: 0,
+                data_reference: 0,
+                extents: vec![(0, offset, data.len() as u64)],
+            });
+            offset += data.len() as u64;
+        }
+        locations
+    };
+
+    // Everything after `meta` moves by however much it grows or shrinks
+    let delta =
+        build_meta(&with_added(locations.clone(), 0)).len() as i64 - (meta.end - meta.start) as i64;
+    for location in locations
+        .iter_mut()
+        .filter(|l| l.construction_method == 0 && l.data_reference == 0)
+    {
+        for extent in &mut location.extents {
+            if extent.1 >= meta.end as u64 {
+                extent.1 = (extent.1 as i64 + delta) as u64;
+            } else if extent.1 >= meta.start as u64 {
+                return Err(AppError::UnsupportedFormat(
+                    "AVIF with image data inside its meta box".into(),
+                ));
+            }
+        }
+    }
+    let mdat_body = (bytes.len() as i64 + delta) as u64 + 8;
+    let new_meta = build_meta(&with_added(locations, mdat_body));
+
+    let mut out = src[..meta.start].to_vec();
+    out.extend_from_slice(&new_meta);
+    out.extend_from_slice(&src[meta.end..]);
+    if !added.is_empty() {
+        // A last box sized "to the end of the file" must now say where it stops
+        if let Some(last) = top
+            .last()
+            .filter(|b| b.start > meta.start && be_uint(bytes, b.start, 4) == Some(0))
+        {
+            let size = u32::try_from(last.end - last.start)
+                .map_err(|_| AppError::UnsupportedFormat("AVIF over 4 GiB".into()))?;
+            let at = (last.start as i64 + delta) as usize;
+            out[at..at + 4].copy_from_slice(&size.to_be_bytes());
+        }
+        let mut data = Vec::with_capacity(added_len);
+        for (_, _, item) in &added {
+            data.extend_from_slice(item);
+        }
+        iso_box(&mut out, b"mdat", &data);
+    }
+    Ok(out)
+}
+
+/// Replace the metadata of an encoded image with `blobs`. AVIF takes only
+/// the EXIF and XMP; formats without metadata support (BMP here) are
+/// returned unchanged.
+pub fn embed_metadata(
+    bytes: Vec<u8>,
+    format: image::ImageFormat,
+    blobs: &MetadataBlobs,
+) -> Result<Vec<u8>, AppError> {
+    match format {
+        image::ImageFormat::Jpeg => embed_jpeg(&bytes, blobs),
+        image::ImageFormat::Png => embed_png(&bytes, blobs),
+        image::ImageFormat::WebP => embed_webp(&bytes, blobs),
+        image::ImageFormat::Tiff => embed_tiff(&bytes, blobs),
+        image::ImageFormat::Avif => embed_avif(&bytes, blobs),
+        _ => Ok(bytes),
+    }
+}
+
+// ── Privacy scrub ───────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ScrubOptions {
+    /// GPS coordinates and place names
+    pub location: bool,
+    /// Body and lens serial numbers, unique image IDs
+    pub serials: bool,
+    /// Artist, camera owner and contact names, user comments
+    pub owner: bool,
+    /// The EXIF preview thumbnail, which keeps the uncropped original
+    pub thumbnail: bool,
+    /// Vendor maker notes, which often repeat serials in a private format
+    pub maker_notes: bool,
+    /// Capture and modification dates
+    pub timestamps: bool,
+}
+
+impl Default for ScrubOptions {
+    fn default() -> Self {
+        Self {
+            location: true,
+            serials: true,
+            owner: true,
+            thumbnail: true,
+            maker_notes: true,
+            timestamps: false,
+        }
+    }
+}
+
+const SERIAL_EXIF_TAGS: &[exif::Tag] = &[
+    exif::Tag::BodySerialNumber,
+    exif::Tag::LensSerialNumber,
+    exif::Tag::ImageUniqueID,
+];
+const OWNER_EXIF_TAGS: &[exif::Tag] = &[
+    exif::Tag::Artist,
+    exif::Tag(exif::Context::Tiff, 316), // HostComputer
+    exif::Tag::CameraOwnerName,
+    exif::Tag::UserComment,
+];
+const TIMESTAMP_EXIF_TAGS: &[exif::Tag] = &[
+    exif::Tag::DateTime,
+    exif::Tag::DateTimeOriginal,
+    exif::Tag::DateTimeDigitized,
+    exif::Tag::OffsetTime,
+    exif::Tag::OffsetTimeOriginal,
+    exif::Tag::OffsetTimeDigitized,
+    exif::Tag::SubSecTime,
+    exif::Tag::SubSecTimeOriginal,
+    exif::Tag::SubSecTimeDigitized,
+];
+
+/// XMP property names, or `prefix*` for a whole family.
+const LOCATION_XMP: &[&str] = &[
+    "exif:GPS*",
+    "photoshop:City",
+    "photoshop:State",
+    "photoshop:Country",
+    "Iptc4xmpCore:Location",
+    "Iptc4xmpCore:CountryCode",
+    "Iptc4xmpExt:LocationCreated",
+    "Iptc4xmpExt:LocationShown",
+];
+const SERIAL_XMP: &[&str] = &[
+    "aux:SerialNumber",
+    "aux:LensSerialNumber",
+    "exifEX:BodySerialNumber",
+    "exifEX:LensSerialNumber",
+    "exif:ImageUniqueID",
+];
+const OWNER_XMP: &[&str] = &[
+    "dc:creator",
+    "aux:OwnerName",
+    "exifEX:CameraOwnerName",
+    "photoshop:AuthorsPosition",
+    "Iptc4xmpCore:CreatorContactInfo",
+    "exif:UserComment",
+];
+const TIMESTAMP_XMP: &[&str] = &[
+    "xmp:CreateDate",
+    "xmp:ModifyDate",
+    "xmp:MetadataDate",
+    "exif:DateTimeOriginal",
+    "exif:DateTimeDigitized",
+    "photoshop:DateCreated",
+    "tiff:DateTime",
+];
+
+/// IPTC record-2 dataset numbers.
+const LOCATION_IPTC: &[u8] = &[90, 92, 95, 100, 101];
+const OWNER_IPTC: &[u8] = &[80, 85, 118, 122];
+const TIMESTAMP_IPTC: &[u8] = &[55, 60, 62, 63];
+
+fn exif_thumbnail(exif: &exif::Exif) -> Option<&[u8]> {
+    let at = |tag| exif.get_field(tag, exif::In::THUMBNAIL)?.value.get_uint(0);
+    let offset = at(exif::Tag::JPEGInterchangeFormat)? as usize;
+    let len = at(exif::Tag::JPEGInterchangeFormatLength)? as usize;
+    exif.buf().get(offset..offset + len)
+}
+
+fn scrub_exif(blob: &[u8], options: &ScrubOptions) -> Option<Vec<u8>> {
+    use exif::{Context, In, Tag};
+    let exif = parse_exif_blob(blob)?;
+    let thumbnail = if options.thumbnail {
+        None
+    } else {
+        exif_thumbnail(&exif)
+    };
+    let dropped = |tag: Tag| {
+        (options.location && tag.context() == Context::Gps)
+            || (options.serials && SERIAL_EXIF_TAGS.contains(&tag))
+            || (options.owner && OWNER_EXIF_TAGS.contains(&tag))
+            || (options.maker_notes && tag == Tag::MakerNote)
+            || (options.timestamps && TIMESTAMP_EXIF_TAGS.contains(&tag))
+    };
+    let fields: Vec<exif::Field> = exif
+        .fields()
+        .filter(|f| f.ifd_num == In::PRIMARY || (f.ifd_num == In::THUMBNAIL && thumbnail.is_some()))
+        .filter(|f| !matches!(f.value, exif::Value::Unknown(..)) && !dropped(f.tag))
+        .cloned()
+        .collect();
+    write_exif(&fields, thumbnail, exif.little_endian())
+}
+
+/// Byte range of the next XMP property matching `name` (or `prefix*`), in
+/// element form including its content, or attribute form including the
+/// leading whitespace.
+fn find_xmp_property(packet: &str, name: &str) -> Option<std::ops::Range<usize>> {
+    let prefix = name.strip_suffix('*').unwrap_or(name);
+    let is_name_end = |c: char| c.is_whitespace() || matches!(c, '>' | '/' | '=');
+    let mut from = 0;
+    while let Some(found) = packet[from..].find(prefix) {
+        let at = from + found;
+        from = at + prefix.len();
+        let rest = &packet[at..];
+        let full_len = rest.find(is_name_end).unwrap_or(rest.len());
+        let full = &rest[..full_len];
+        if full != name && !name.ends_with('*') {
+            continue;
+        }
+        let before = packet[..at].chars().next_back();
+        if before == Some('<') {
+            let start = at - 1;
+            let tail = &packet[at + full_len..];
+            let gt = tail.find('>')?;
+            if tail[..gt].ends_with('/') {
+                return Some(start..at + full_len + gt + 1);
+            }
+            let close = format!("</{full}>");
+            let end = packet[at..].find(&close)? + at + close.len();
+            return Some(start..end);
+        }
+        if before.is_some_and(char::is_whitespace) && rest[full_len..].starts_with("=\"") {
+            let value_start = at + full_len + 2;
+            let end = packet[value_start..].find('"')? + value_start + 1;
+            let start = packet[..at].trim_end().len();
+            return Some(start..end);
+        }
+    }
+    None
+}
+
+fn xmp_remove(packet: &str, names: &[&str]) -> String {
+    let mut packet = packet.to_string();
+    for name in names {
+        while let Some(range) = find_xmp_property(&packet, name) {
+            packet.replace_range(range, "");
+        }
+    }
+    packet
+}
+
+fn scrub_xmp(packet: &str, options: &ScrubOptions) -> String {
+    let mut names: Vec<&str> = Vec::new();
+    for (enabled, group) in [
+        (options.location, LOCATION_XMP),
+        (options.serials, SERIAL_XMP),
+        (options.owner, OWNER_XMP),
+        (options.timestamps, TIMESTAMP_XMP),
+        (options.thumbnail, &["xmp:Thumbnails"][..]),
+    ] {
+        if enabled {
+            names.extend_from_slice(group);
+        }
+    }
+    xmp_remove(packet, &names)
+}
+
+/// Drop record-2 datasets from a bare IIM stream, keeping everything else.
+fn scrub_iim(iim: &[u8], options: &ScrubOptions) -> Option<Vec<u8>> {
+    let mut dropped: Vec<u8> = Vec::new();
+    for (enabled, group) in [
+        (options.location, LOCATION_IPTC),
+        (options.owner, OWNER_IPTC),
+        (options.timestamps, TIMESTAMP_IPTC),
+    ] {
+        if enabled {
+            dropped.extend_from_slice(group);
+        }
+    }
+    let mut out = Vec::with_capacity(iim.len());
+    let mut pos = 0;
+    while pos + 5 <= iim.len() && iim[pos] == 0x1C {
+        let len = u16::from_be_bytes([iim[pos + 3], iim[pos + 4]]) as usize;
+        if len & 0x8000 != 0 {
+            break;
+        }
+        let end = (pos + 5 + len).min(iim.len());
+        if !(iim[pos + 1] == 2 && dropped.contains(&iim[pos + 2])) {
+            out.extend_from_slice(&iim[pos..end]);
+        }
+        pos = end;
+    }
+    // A lone record version dataset carries nothing
+    (out.len() > 7).then_some(out)
+}
+
+pub fn scrub_blobs(source: &MetadataBlobs, options: &ScrubOptions) -> MetadataBlobs {
+    MetadataBlobs {
+        icc: source.icc.clone(),
+        exif: source.exif.as_deref().and_then(|b| scrub_exif(b, options)),
+        xmp: source.xmp.as_deref().map(|p| scrub_xmp(p, options)),
+        iptc: source.iptc.as_deref().and_then(|b| scrub_iim(b, options)),
+        keep_comments: !options.owner,
+        keep_times: !options.timestamps,
+    }
+}
+
+/// Remove the selected metadata groups from a JPEG, PNG, WebP or TIFF
+/// without re-encoding its pixels.
+pub fn scrub_file(path: &Path, dest: &Path, options: &ScrubOptions) -> Result<(), AppError> {
+    let bytes = std::fs::read(path)?;
+    let format = image::guess_format(&bytes)?;
+    if !matches!(
+        format,
+        image::ImageFormat::Jpeg
+            | image::ImageFormat::Png
+            | image::ImageFormat::WebP
+            | image::ImageFormat::Tiff
+    ) {
+        return Err(AppError::UnsupportedFormat(format!("{format:?}")));
+    }
+    let blobs = scrub_blobs(&read_blobs(path)?, options);
+    let scrubbed = embed_metadata(bytes, format, &blobs)?;
+
+    // Write beside the destination and rename over it, so scrubbing in place
+    // never leaves a half-written original behind
+    let tmp = dest.with_extension("pixelforge-scrub");
+    std::fs::write(&tmp, scrubbed).map_err(|e| AppError::SaveFailed(e.to_string()))?;
+    std::fs::rename(&tmp, dest).map_err(|e| {
+        std::fs::remove_file(&tmp).ok();
+        AppError::SaveFailed(e.to_string())
+    })
+}
+
+/// Scrub `path` into `dest_path`, or in place when no destination is given.
+#[tauri::command]
+pub fn scrub_metadata(
+    path: String,
+    dest_path: Option<String>,
+    options: Option<ScrubOptions>,
+) -> Result<String, AppError> {
+    let dest = dest_path.unwrap_or_else(|| path.clone());
+    scrub_file(
+        Path::new(&path),
+        Path::new(&dest),
+        &options.unwrap_or_default(),
+    )?;
+    Ok(dest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exif::{Field, In, Tag, Value};
+
+    fn ascii(tag: Tag, s: &str) -> Field {
+        Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value: Value::Ascii(vec![s.as_bytes().to_vec()]),
+        }
+    }
+
+    fn rationals(tag: Tag, v: &[(u32, u32)]) -> Field {
+        Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value: Value::Rational(v.iter().map(|&r| r.into()).collect()),
+        }
+    }
+
+    fn exif_blob(fields: &[Field]) -> Vec<u8> {
+        let mut writer = exif::experimental::Writer::new();
+        for f in fields {
+            writer.push_field(f);
+        }
+        let mut tiff = std::io::Cursor::new(Vec::new());
+        writer.write(&mut tiff, false).unwrap();
+        tiff.into_inner()
+    }
+
+    fn jpeg_with_exif(fields: &[Field]) -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("pixelforge_meta_{}.jpg", uuid::Uuid::new_v4()));
+        let mut file = std::fs::File::create(&path).unwrap();
+        let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut file, 90);
+        image::ImageEncoder::set_exif_metadata(&mut encoder, exif_blob(fields)).unwrap();
+        image::DynamicImage::ImageRgb8(image::RgbImage::new(8, 8))
+            .write_with_encoder(encoder)
+            .unwrap();
+        path
+    }
+
+    #[test]
+    fn reads_exif_summary_from_jpeg() {
+        let fields = vec![
+            ascii(Tag::Make, "Canon"),
+            ascii(Tag::Model, "EOS R6"),
+            ascii(Tag::DateTimeOriginal, "2024:05:01 14:30:05"),
+            rationals(Tag::ExposureTime, &[(1, 250)]),
+            rationals(Tag::FNumber, &[(28, 10)]),
+            Field {
+                tag: Tag::PhotographicSensitivity,
+                ifd_num: In::PRIMARY,
+                value: Value::Short(vec![400]),
+            },
+            Field {
+                tag: Tag::Orientation,
+                ifd_num: In::PRIMARY,
+                value: Value::Short(vec![6]),
+            },
+            rationals(Tag::GPSLatitude, &[(48, 1), (51, 1), (2952, 100)]),
+            ascii(Tag::GPSLatitudeRef, "N"),
+            rationals(Tag::GPSLongitude, &[(2, 1), (17, 1), (4020, 100)]),
+            ascii(Tag::GPSLongitudeRef, "E"),
+        ];
+        let path = jpeg_with_exif(&fields);
+        let meta = read_metadata(&path).unwrap();
+        let exif = meta.exif.unwrap();
+        assert_eq!(exif.make.as_deref(), Some("Canon"));
+        assert_eq!(exif.model.as_deref(), Some("EOS R6"));
+        assert_eq!(exif.date_taken.as_deref(), Some("2024-05-01T14:30:05"));
+        assert_eq!(exif.exposure_time.as_deref(), Some("1/250"));
+        assert_eq!(exif.f_number, Some(2.8));
+        assert_eq!(exif.iso, Some(400));
+        assert_eq!(exif.orientation, Some(6));
+        let gps = exif.gps.unwrap();
+        assert!((gps.latitude - 48.8582).abs() < 1e-3);
+        assert!((gps.longitude - 2.2945).abs() < 1e-3);
+        assert!(exif.fields.iter().any(|f| f.tag == "Make"));
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn png_without_metadata_is_empty() {
+        let path =
+            std::env::temp_dir().join(format!("pixelforge_meta_{}.png", uuid::Uuid::new_v4()));
+        image::RgbImage::new(4, 4).save(&path).unwrap();
+        let meta = read_metadata(&path).unwrap();
+        assert!(meta.exif.is_none() && meta.xmp.is_none() && meta.iptc.is_none());
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn parses_xmp_attributes_and_lists() {
+        let packet = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF>
+            <rdf:Description xmp:Rating="4" xmp:CreatorTool="Darktable">
+              <dc:title><rdf:Alt><rdf:li xml:lang="x-default">Harbour &amp; boats</rdf:li></rdf:Alt></dc:title>
+              <dc:creator><rdf:Seq><rdf:li>Ada</rdf:li></rdf:Seq></dc:creator>
+              <dc:subject><rdf:Bag><rdf:li>sea</rdf:li><rdf:li>dusk</rdf:li></rdf:Bag></dc:subject>
+            </rdf:Description></rdf:RDF></x:xmpmeta>"#;
+        let xmp = parse_xmp(packet);
+        assert_eq!(xmp.title.as_deref(), Some("Harbour & boats"));
+        assert_eq!(xmp.creators, vec!["Ada"]);
+        assert_eq!(xmp.keywords, vec!["sea", "dusk"]);
+        assert_eq!(xmp.rating, Some(4));
+        assert_eq!(xmp.creator_tool.as_deref(), Some("Darktable"));
+        assert!(xmp.rights.is_none());
+
+        let malformed = "<dc:subject><rdf:Bag><rdf:li</rdf:li>x></rdf:Bag></dc:subject>";
+        assert!(xmp_values(malformed, "dc:subject").is_empty());
+    }
+
+    #[test]
+    fn parses_iptc_from_photoshop_resource() {
+        let mut iim = Vec::new();
+        for (dataset, text) in [
+            (25u8, "sea"),
+            (25, "dusk"),
+            (116, "(c) Ada"),
+            (120, "Caption"),
+        ] {
+            iim.extend_from_slice(&[0x1C, 2, dataset]);
+            iim.extend_from_slice(&(text.len() as u16).to_be_bytes());
+            iim.extend_from_slice(text.as_bytes());
+        }
+        let mut block = PHOTOSHOP_HEADER.to_vec();
+        // A resource to skip first, with an odd-sized body
+        block.extend_from_slice(b"8BIM\x04\x25\0\0\0\0\0\x03abc\0");
+        block.extend_from_slice(b"8BIM\x04\x04\0\0");
+        block.extend_from_slice(&(iim.len() as u32).to_be_bytes());
+        block.extend_from_slice(&iim);
+
+        let iptc = parse_iptc(&block).unwrap();
+        assert_eq!(iptc.keywords, vec!["sea", "dusk"]);
+        assert_eq!(iptc.copyright.as_deref(), Some("(c) Ada"));
+        assert_eq!(iptc.caption.as_deref(), Some("Caption"));
+        assert!(parse_iptc(b"8BIM\x04\x25\0\0\0\0\0\0").is_none());
+    }
+
+    #[test]
+    fn raw_profile_skips_non_hex_text() {
+        let text = "\niptc\n       3\n1c 02\n19é\n".as_bytes();
+        assert_eq!(decode_raw_profile(text), Some(vec![0x1C, 0x02, 0x19]));
+        assert!(decode_raw_profile("\niptc\n4\né1c02".as_bytes()).is_none());
+    }
+
+    fn sample_blobs() -> MetadataBlobs {
+        MetadataBlobs {
+            icc: Some(vec![7; 300]),
+            exif: Some(exif_blob(&[
+                ascii(Tag::Artist, "Ada"),
+                ascii(Tag::Model, "EOS R6"),
+                ascii(Tag::DateTimeOriginal, "2024:05:01 14:30:05"),
+                ascii(Tag::GPSLatitudeRef, "N"),
+            ])),
+            xmp: Some(parse_xmp_fixture()),
+            iptc: Some(build_iim(&[(116, "(c) Ada"), (90, "Lisbon")])),
+            ..MetadataBlobs::default()
+        }
+    }
+
+    fn parse_xmp_fixture() -> String {
+        xmp_packet(
+            "<rdf:Description rdf:about=\"\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\" \
+             xmlns:tiff=\"http://ns.adobe.com/tiff/1.0/\" tiff:Orientation=\"6\">\n\
+             <dc:rights><rdf:Alt><rdf:li xml:lang=\"x-default\">(c) Ada</rdf:li></rdf:Alt></dc:rights>\n\
+             </rdf:Description>\n",
+        )
+    }
+
+    /// Encode a small image, embed `blobs` and read everything back.
+    fn roundtrip(format: image::ImageFormat, blobs: &MetadataBlobs) -> (MetadataBlobs, Vec<u8>) {
+        let img = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            6,
+            4,
+            image::Rgba([10, 200, 30, 128]),
+        ));
+        let img = if format == image::ImageFormat::Jpeg {
+            image::DynamicImage::ImageRgb8(img.to_rgb8())
+        } else {
+            img
+        };
+        let mut bytes = Vec::new();
+        img.write_to(&mut std::io::Cursor::new(&mut bytes), format)
+            .unwrap();
+        let bytes = embed_metadata(bytes, format, blobs).unwrap();
+        let path = std::env::temp_dir().join(format!(
+            "pixelforge_meta_{}.{}",
+            uuid::Uuid::new_v4(),
+            format.extensions_str()[0]
+        ));
+        std::fs::write(&path, &bytes).unwrap();
+        let decoded = image::open(&path).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (6, 4));
+        let read = read_blobs(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        (read, bytes)
+    }
+
+    #[test]
+    fn embeds_and_reads_back_in_every_container() {
+        let blobs = sample_blobs();
+        for format in [
+            image::ImageFormat::Jpeg,
+            image::ImageFormat::Png,
+            image::ImageFormat::WebP,
+            image::ImageFormat::Tiff,
+        ] {
+            let (read, _) = roundtrip(format, &blobs);
+            let exif = parse_exif_blob(read.exif.as_deref().unwrap()).unwrap();
+            assert_eq!(
+                exif_string(&exif, Tag::Artist).as_deref(),
+                Some("Ada"),
+                "{format:?}"
+            );
+            // TIFF keeps these in sub-IFDs rather than in the blob as a whole
+            assert!(
+                exif_date(&exif, Tag::DateTimeOriginal).is_some(),
+                "{format:?}"
+            );
+            assert_eq!(
+                exif_string(&exif, Tag::GPSLatitudeRef).as_deref(),
+                Some("N"),
+                "{format:?}"
+            );
+            assert_eq!(read.icc, blobs.icc, "{format:?}");
+            assert_eq!(
+                parse_xmp(read.xmp.as_deref().unwrap()).rights.as_deref(),
+                Some("(c) Ada"),
+                "{format:?}"
+            );
+            if format != image::ImageFormat::WebP {
+                let iptc = parse_iptc(read.iptc.as_deref().unwrap()).unwrap();
+                assert_eq!(iptc.city.as_deref(), Some("Lisbon"), "{format:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn empty_blobs_strip_existing_metadata() {
+        for format in [
+            image::ImageFormat::Jpeg,
+            image::ImageFormat::Png,
+            image::ImageFormat::WebP,
+            image::ImageFormat::Tiff,
+        ] {
+            let (_, bytes) = roundtrip(format, &sample_blobs());
+            let stripped = embed_metadata(bytes, format, &MetadataBlobs::default()).unwrap();
+            assert!(find_bytes(&stripped, b"Lisbon").is_none(), "{format:?}");
+            assert!(find_bytes(&stripped, b"EOS R6").is_none(), "{format:?}");
+            assert!(find_bytes(&stripped, b"x:xmpmeta").is_none(), "{format:?}");
+            image::load_from_memory_with_format(&stripped, format).unwrap();
+        }
+    }
+
+    /// The bytes of the primary item, which must survive the rewrite intact.
+    fn avif_primary_data(bytes: &[u8]) -> Vec<u8> {
+        let top = iso_boxes(bytes, 0..bytes.len()).unwrap();
+        let meta = top.iter().find(|b| &b.kind == b"meta").unwrap();
+        let children = iso_boxes(bytes, meta.body + 4..meta.end).unwrap();
+        let pitm = children.iter().find(|b| &b.kind == b"pitm").unwrap();
+        let primary = be_uint(bytes, pitm.body + 4, 2).unwrap() as u32;
+        let iloc = children.iter().find(|b| &b.kind == b"iloc").unwrap();
+        let location = parse_iloc(bytes, iloc)
+            .unwrap()
+            .into_iter()
+            .find(|l| l.id == primary)
+            .unwrap();
+        location
+            .extents
+            .iter()
+            .flat_map(|&(_, offset, length)| &bytes[offset as usize..(offset + length) as usize])
+            .copied()
+            .collect()
+    }
+
+    #[test]
+    fn avif_items_carry_exif_and_xmp() {
+        let img = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            6,
+            4,
+            image::Rgba([10, 200, 30, 128]),
+        ));
+        let mut bytes = Vec::new();
+        img.write_to(
+            &mut std::io::Cursor::new(&mut bytes),
+            image::ImageFormat::Avif,
+        )
+        .unwrap();
+        let image_data = avif_primary_data(&bytes);
+
+        let embedded = embed_metadata(bytes, image::ImageFormat::Avif, &sample_blobs()).unwrap();
+        assert_eq!(avif_primary_data(&embedded), image_data);
+        let path =
+            std::env::temp_dir().join(format!("pixelforge_meta_{}.avif", uuid::Uuid::new_v4()));
+        std::fs::write(&path, &embedded).unwrap();
+        let read = read_blobs(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        let exif = parse_exif_blob(read.exif.as_deref().unwrap()).unwrap();
+        assert_eq!(exif_string(&exif, Tag::Artist).as_deref(), Some("Ada"));
+        assert_eq!(
+            parse_xmp(read.xmp.as_deref().unwrap()).rights.as_deref(),
+            Some("(c) Ada")
+        );
+
+        // Replacing drops the old items rather than stacking new ones
+        let blobs = MetadataBlobs {
+            exif: Some(exif_blob(&[ascii(Tag::Artist, "Grace")])),
+            ..MetadataBlobs::default()
+        };
+        let replaced = embed_metadata(embedded, image::ImageFormat::Avif, &blobs).unwrap();
+        assert_eq!(avif_primary_data(&replaced), image_data);
+        assert!(find_bytes(&replaced, b"EOS R6").is_none());
+        assert!(find_bytes(&replaced, b"x:xmpmeta").is_none());
+        let exif = exif::Reader::new()
+            .read_from_container(&mut std::io::Cursor::new(&replaced))
+            .unwrap();
+        assert_eq!(exif_string(&exif, Tag::Artist).as_deref(), Some("Grace"));
+
+        let stripped = embed_metadata(
+            replaced,
+            image::ImageFormat::Avif,
+            &MetadataBlobs::default(),
+        )
+        .unwrap();
+        assert_eq!(avif_primary_data(&stripped), image_data);
+        assert!(find_bytes(&stripped, b"Grace").is_none());
+    }
+
+    #[test]
+    fn oversized_jpeg_segments_are_an_error() {
+        let blobs = MetadataBlobs {
+            xmp: Some(xmp_packet(&" ".repeat(70_000))),
+            ..MetadataBlobs::default()
+        };
+        let mut bytes = Vec::new();
+        image::DynamicImage::ImageRgb8(image::RgbImage::new(4, 4))
+            .write_to(
+                &mut std::io::Cursor::new(&mut bytes),
+                image::ImageFormat::Jpeg,
+            )
+            .unwrap();
+        assert!(embed_metadata(bytes.clone(), image::ImageFormat::Jpeg, &blobs).is_err());
+        // ICC profiles are split over numbered segments instead
+        let blobs = MetadataBlobs {
+            icc: Some(vec![7; 200_000]),
+            ..MetadataBlobs::default()
+        };
+        let path =
+            std::env::temp_dir().join(format!("pixelforge_meta_{}.jpg", uuid::Uuid::new_v4()));
+        std::fs::write(
+            &path,
+            embed_metadata(bytes, image::ImageFormat::Jpeg, &blobs).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(read_blobs(&path).unwrap().icc, blobs.icc);
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn prepare_blobs_applies_mode_and_edits() {
+        let mut source = sample_blobs();
+        source.exif = Some(exif_blob(&[
+            ascii(Tag::Artist, "Ada"),
+            ascii(Tag::Model, "EOS R6"),
+            Field {
+                tag: Tag::Orientation,
+                ifd_num: In::PRIMARY,
+                value: Value::Short(vec![6]),
+            },
+            rationals(Tag::GPSLatitude, &[(48, 1), (51, 1), (0, 1)]),
+            ascii(Tag::GPSLatitudeRef, "N"),
+            rationals(Tag::GPSLongitude, &[(2, 1), (17, 1), (0, 1)]),
+            ascii(Tag::GPSLongitudeRef, "E"),
+        ]));
+        let history = vec!["Resize 800x600".to_string()];
+        let update = |mode| MetadataUpdate {
+            mode,
+            width: 800,
+            height: 600,
+            software: Some("PixelForge"),
+            history: &history,
+        };
+
+        let kept = prepare_blobs(&source, &update(MetadataMode::Keep));
+        let exif = exif_metadata(&parse_exif_blob(kept.exif.as_deref().unwrap()).unwrap());
+        assert_eq!(exif.orientation, Some(1));
+        assert_eq!(exif.model.as_deref(), Some("EOS R6"));
+        assert_eq!(exif.software.as_deref(), Some("PixelForge"));
+        assert!(exif.gps.is_some());
+        let xmp = kept.xmp.unwrap();
+        assert!(xmp.contains("tiff:Orientation=\"1\""));
+        assert!(xmp.contains("<stEvt:parameters>Resize 800x600</stEvt:parameters>"));
+        assert!(kept.iptc.is_some() && kept.icc.is_some());
+
+        let copyright = prepare_blobs(&source, &update(MetadataMode::CopyrightOnly));
+        let exif = exif_metadata(&parse_exif_blob(copyright.exif.as_deref().unwrap()).unwrap());
+        assert_eq!(exif.artist.as_deref(), Some("Ada"));
+        assert!(exif.model.is_none() && exif.gps.is_none());
+        let iptc = parse_iptc(copyright.iptc.as_deref().unwrap()).unwrap();
+        assert_eq!(iptc.copyright.as_deref(), Some("(c) Ada"));
+        assert!(iptc.city.is_none());
+        assert_eq!(
+            parse_xmp(copyright.xmp.as_deref().unwrap())
+                .rights
+                .as_deref(),
+            Some("(c) Ada")
+        );
+
+        let stripped = prepare_blobs(
+            &source,
+            &MetadataUpdate {
+                software: None,
+                history: &[],
+                ..update(MetadataMode::Strip)
+            },
+        );
+        assert!(stripped.exif.is_none() && stripped.xmp.is_none());
+        assert!(stripped.iptc.is_none() && stripped.icc.is_none());
+    }
+
+    #[test]
+    fn utc_timestamp_formats_epoch_seconds() {
+        assert_eq!(utc_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(utc_timestamp(1_700_000_000), "2023-11-14T22:13:20Z");
+        assert_eq!(utc_timestamp(951_825_600), "2000-02-29T12:00:00Z");
+    }
+
+    #[test]
+    fn scrub_removes_selected_groups_without_reencoding() {
+        let path = jpeg_with_exif(&[
+            ascii(Tag::Model, "EOS R6"),
+            ascii(Tag::Artist, "Ada"),
+            ascii(Tag::BodySerialNumber, "SN-0042"),
+            ascii(Tag::DateTimeOriginal, "2024:05:01 14:30:05"),
+            Field {
+                tag: Tag::Orientation,
+                ifd_num: In::PRIMARY,
+                value: Value::Short(vec![6]),
+            },
+            rationals(Tag::GPSLatitude, &[(48, 1), (51, 1), (0, 1)]),
+            ascii(Tag::GPSLatitudeRef, "N"),
+        ]);
+        let before = image::open(&path).unwrap().to_rgb8();
+        scrub_file(&path, &path, &ScrubOptions::default()).unwrap();
+
+        let after = image::open(&path).unwrap().to_rgb8();
+        assert_eq!(before.as_raw(), after.as_raw());
+        let exif = read_exif(&path).unwrap();
+        assert!(exif.fields().all(|f| f.tag.context() != exif::Context::Gps));
+        assert!(exif.get_field(Tag::BodySerialNumber, In::PRIMARY).is_none());
+        assert!(exif.get_field(Tag::Artist, In::PRIMARY).is_none());
+        assert_eq!(exif_string(&exif, Tag::Model).as_deref(), Some("EOS R6"));
+        assert_eq!(exif_uint(&exif, Tag::Orientation), Some(6));
+        assert!(exif.get_field(Tag::DateTimeOriginal, In::PRIMARY).is_some());
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn scrub_keeps_fields_whose_flag_is_off() {
+        let exif = exif_blob(&[
+            ascii(Tag::DateTimeOriginal, "2024:05:01 14:30:05"),
+            ascii(Tag::GPSLatitudeRef, "N"),
+        ]);
+        let mut png = Vec::new();
+        image::RgbImage::new(4, 4)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        let blobs = MetadataBlobs {
+            exif: Some(exif.clone()),
+            ..MetadataBlobs::default()
+        };
+        let mut png = embed_metadata(png, image::ImageFormat::Png, &blobs).unwrap();
+        let mut extra = Vec::new();
+        png_chunk(&mut extra, b"tIME", &[0x07, 0xE8, 5, 1, 14, 30, 5]);
+        png_chunk(&mut extra, b"tEXt", b"Comment\0shot by Ada");
+        png_chunk(&mut extra, b"tEXt", b"Title\0Harbour");
+        png.splice(33..33, extra);
+
+        let mut tiff = Vec::new();
+        image::RgbImage::new(4, 4)
+            .write_to(
+                &mut std::io::Cursor::new(&mut tiff),
+                image::ImageFormat::Tiff,
+            )
+            .unwrap();
+        let tiff = embed_metadata(tiff, image::ImageFormat::Tiff, &blobs).unwrap();
+
+        for (bytes, ext) in [(png, "png"), (tiff, "tiff")] {
+            let path = std::env::temp_dir()
+                .join(format!("pixelforge_scrub_{}.{ext}", uuid::Uuid::new_v4()));
+            std::fs::write(&path, &bytes).unwrap();
+            scrub_file(&path, &path, &ScrubOptions::default()).unwrap();
+            let scrubbed = std::fs::read(&path).unwrap();
+            let exif = read_exif(&path).unwrap();
+            assert!(
+                exif.get_field(Tag::DateTimeOriginal, In::PRIMARY).is_some(),
+                "{ext}"
+            );
+            assert!(
+                exif.get_field(Tag::GPSLatitudeRef, In::PRIMARY).is_none(),
+                "{ext}"
+            );
+            if ext == "png" {
+                assert!(find_bytes(&scrubbed, b"tIME").is_some());
+                assert!(find_bytes(&scrubbed, b"Harbour").is_some());
+                assert!(find_bytes(&scrubbed, b"shot by Ada").is_none());
+
+                std::fs::write(&path, &bytes).unwrap();
+                let keep_owner = ScrubOptions {
+                    owner: false,
+                    timestamps: true,
+                    ..ScrubOptions::default()
+                };
+                scrub_file(&path, &path, &keep_owner).unwrap();
+                let scrubbed = std::fs::read(&path).unwrap();
+                assert!(find_bytes(&scrubbed, b"shot by Ada").is_some());
+                assert!(find_bytes(&scrubbed, b"tIME").is_none());
+            }
+            std::fs::remove_file(&path).ok();
+        }
+    }
+
+    #[test]
+    fn scrub_filters_xmp_and_iptc_by_group() {
+        let packet = xmp_packet(
+            "<rdf:Description rdf:about=\"\" exif:GPSLatitude=\"48,51N\" \
+             exif:GPSLongitude=\"2,17E\" aux:SerialNumber=\"SN-0042\" xmp:Rating=\"4\">\n\
+             <dc:creator><rdf:Seq><rdf:li>Ada</rdf:li></rdf:Seq></dc:creator>\n\
+             <photoshop:City>Lisbon</photoshop:City>\n\
+             </rdf:Description>\n",
+        );
+        let scrubbed = scrub_xmp(&packet, &ScrubOptions::default());
+        for gone in ["GPSLatitude", "GPSLongitude", "SN-0042", "Ada", "Lisbon"] {
+            assert!(!scrubbed.contains(gone), "{gone}");
+        }
+        assert_eq!(parse_xmp(&scrubbed).rating, Some(4));
+
+        let iim = build_iim(&[(116, "(c) Ada"), (90, "Lisbon"), (80, "Ada")]);
+        let scrubbed = scrub_iim(&iim, &ScrubOptions::default()).unwrap();
+        let iptc = parse_iptc(&scrubbed).unwrap();
+        assert_eq!(iptc.copyright.as_deref(), Some("(c) Ada"));
+        assert!(iptc.city.is_none() && iptc.by_line.is_none());
+
+        let location_only = ScrubOptions {
+            owner: false,
+            ..ScrubOptions::default()
+        };
+        let iptc = parse_iptc(&scrub_iim(&iim, &location_only).unwrap()).unwrap();
+        assert_eq!(iptc.by_line.as_deref(), Some("Ada"));
+    }
+}
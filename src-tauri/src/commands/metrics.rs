@@ -0,0 +1,107 @@
+use image::{GrayImage, RgbaImage};
+
+// ── Image quality metrics ───────────────────────────────────────────
+//
+// Full-reference comparisons of an encoded result against its source, used
+// to preview what an export loses before it is written.
+
+/// Reported for identical images, whose PSNR is infinite.
+pub const PSNR_IDENTICAL: f64 = 100.0;
+
+/// Peak signal-to-noise ratio in dB over all RGBA channels.
+pub fn psnr(a: &RgbaImage, b: &RgbaImage) -> f64 {
+    debug_assert_eq!(a.dimensions(), b.dimensions());
+    let samples = a.as_raw().len().max(1) as f64;
+    let sse: f64 = a
+        .as_raw()
+        .iter()
+        .zip(b.as_raw())
+        .map(|(&x, &y)| (f64::from(x) - f64::from(y)).powi(2))
+        .sum();
+    if sse == 0.0 {
+        return PSNR_IDENTICAL;
+    }
+    (10.0 * (255.0_f64.powi(2) / (sse / samples)).log10()).min(PSNR_IDENTICAL)
+}
+
+fn luma(img: &RgbaImage) -> GrayImage {
+    GrayImage::from_fn(img.width(), img.height(), |x, y| {
+        let p = img.get_pixel(x, y);
+        let y = 0.299 * f32::from(p[0]) + 0.587 * f32::from(p[1]) + 0.114 * f32::from(p[2]);
+        image::Luma([y.round() as u8])
+    })
+}
+
+/// Mean structural similarity of the luma planes, over 8×8 windows with a
+/// stride of 4. Images smaller than a window are compared whole.
+pub fn ssim(a: &RgbaImage, b: &RgbaImage) -> f64 {
+    debug_assert_eq!(a.dimensions(), b.dimensions());
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+    let (la, lb) = (luma(a), luma(b));
+    let (width, height) = la.dimensions();
+    let window_w = width.min(8);
+    let window_h = height.min(8);
+    if window_w == 0 || window_h == 0 {
+        return 1.0;
+    }
+
+    let mut total = 0.0;
+    let mut windows = 0_u32;
+    for y0 in (0..=height - window_h).step_by(4) {
+        for x0 in (0..=width - window_w).step_by(4) {
+            let n = f64::from(window_w * window_h);
+            let (mut sa, mut sb, mut saa, mut sbb, mut sab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+            for y in y0..y0 + window_h {
+                for x in x0..x0 + window_w {
+                    let pa = f64::from(la.get_pixel(x, y)[0]);
+                    let pb = f64::from(lb.get_pixel(x, y)[0]);
+                    sa += pa;
+                    sb += pb;
+                    saa += pa * pa;
+                    sbb += pb * pb;
+                    sab += pa * pb;
+                }
+            }
+            let (ma, mb) = (sa / n, sb / n);
+            let va = saa / n - ma * ma;
+            let vb = sbb / n - mb * mb;
+            let cov = sab / n - ma * mb;
+            total += ((2.0 * ma * mb + C1) * (2.0 * cov + C2))
+                / ((ma * ma + mb * mb + C1) * (va + vb + C2));
+            windows += 1;
+        }
+    }
+    total / f64::from(windows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metrics_rank_distortion() {
+        let source = RgbaImage::from_fn(32, 24, |x, y| {
+            image::Rgba([(x * 8) as u8, (y * 10) as u8, ((x + y) * 4) as u8, 255])
+        });
+        let shift = |amount: u8| {
+            let mut img = source.clone();
+            for (i, p) in img.pixels_mut().enumerate() {
+                let noise = if i % 2 == 0 { amount } else { 0 };
+                p[0] = p[0].saturating_add(noise);
+            }
+            img
+        };
+        let (slight, heavy) = (shift(4), shift(60));
+
+        assert_eq!(psnr(&source, &source), PSNR_IDENTICAL);
+        assert!((ssim(&source, &source) - 1.0).abs() < 1e-9);
+        assert!(psnr(&source, &slight) > psnr(&source, &heavy));
+        assert!(ssim(&source, &slight) > ssim(&source, &heavy));
+        assert!(ssim(&source, &heavy) < 0.99);
+
+        // Smaller than a window
+        let tiny = RgbaImage::from_pixel(3, 2, image::Rgba([9, 9, 9, 255]));
+        assert!((ssim(&tiny, &tiny) - 1.0).abs() < 1e-9);
+    }
+}
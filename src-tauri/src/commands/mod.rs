@@ -1,6 +1,23 @@
 pub mod ai;
+pub mod alpha;
+pub mod animation;
+pub mod canvas;
 pub mod export;
+pub mod heif;
+pub mod icons;
 pub mod image;
+pub mod jpeg;
+pub mod large;
+pub mod lens;
+pub mod metadata;
+pub mod metrics;
+pub mod multipage;
 pub mod operations;
+pub mod quantize;
+pub mod raw;
+pub mod responsive;
+pub mod retarget;
+pub mod tonemap;
+pub mod webp;
 
 pub mod batch;
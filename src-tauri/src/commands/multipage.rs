@@ -0,0 +1,387 @@
+use crate::commands::image::{file_format, FileFormat};
+use crate::commands::metadata::TiffReader;
+use crate::commands::{alpha, operations};
+use crate::error::AppError;
+use image::{DynamicImage, ImageDecoder, ImageFormat};
+use serde::Deserialize;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+use tiff::encoder::{colortype, Compression, DeflateLevel, TiffEncoder};
+
+// ── Multi-page TIFF ─────────────────────────────────────────────────
+//
+// Scanners and fax software chain one IFD per page. The image crate only
+// decodes the first, so a page is read by pointing the header at its IFD and
+// decoding that as if it were a single-page file, which keeps every sample
+// layout the image crate supports. Only the header and IFDs are read up
+// front; the page itself is decoded straight from the file.
+
+const TAG_NEW_SUBFILE_TYPE: u16 = 254;
+/// NewSubfileType bit for reduced-resolution copies (thumbnails) of a page
+const SUBFILE_REDUCED: u32 = 1;
+
+fn malformed() -> AppError {
+    AppError::ImageDecode("Malformed TIFF".into())
+}
+
+/// Byte order and BigTIFF-ness from a TIFF header.
+fn tiff_kind(header: &[u8]) -> Result<(bool, bool), AppError> {
+    match header.get(..4) {
+        Some(b"II*\0") => Ok((true, false)),
+        Some(b"MM\0*") => Ok((false, false)),
+        Some(b"II+\0") => Ok((true, true)),
+        Some(b"MM\0+") => Ok((false, true)),
+        _ => Err(malformed()),
+    }
+}
+
+/// Offsets of the page IFDs in the top-level chain, skipping thumbnails.
+/// Only the header and the IFDs themselves are read.
+fn page_offsets<R: Read + Seek>(file: &mut R) -> Result<Vec<u64>, AppError> {
+    let mut header = [0u8; 16];
+    file.rewind()?;
+    file.read_exact(&mut header[..8])?;
+    let (little_endian, big) = tiff_kind(&header)?;
+    if big {
+        file.read_exact(&mut header[8..])?;
+    }
+    let header = TiffReader {
+        bytes: &header,
+        little_endian,
+    };
+    // BigTIFF widens counts and offsets to 8 bytes and entries to 20
+    let (entry_len, count_len, offset_len) = if big { (20, 8, 8) } else { (12, 2, 4) };
+    let offset_at = |reader: &TiffReader, at: usize| {
+        if big {
+            reader.u64(at)
+        } else {
+            reader.u32(at).map(u64::from)
+        }
+    };
+
+    let mut pages = Vec::new();
+    let mut visited = Vec::new();
+    let mut ifd = offset_at(&header, if big { 8 } else { 4 }).ok_or_else(malformed)?;
+    while ifd != 0 && !visited.contains(&ifd) {
+        visited.push(ifd);
+        file.seek(SeekFrom::Start(ifd))?;
+        let mut count = [0u8; 8];
+        file.read_exact(&mut count[..count_len])?;
+        let count = TiffReader {
+            bytes: &count,
+            little_endian,
+        };
+        let count = if big {
+            count.u64(0).and_then(|n| usize::try_from(n).ok())
+        } else {
+            count.u16(0).map(usize::from)
+        }
+        // No real IFD has anywhere near this many entries
+        .filter(|&n| n <= u16::MAX as usize)
+        .ok_or_else(malformed)?;
+
+        let mut entries = vec![0u8; count * entry_len + offset_len];
+        file.read_exact(&mut entries).map_err(|_| malformed())?;
+        let reader = TiffReader {
+            bytes: &entries,
+            little_endian,
+        };
+        // A SHORT sits in the first two bytes of the value field, a LONG in four
+        let value_at = if big { 12 } else { 8 };
+        let subfile_type = (0..count)
+            .map(|i| i * entry_len)
+            .find(|&e| reader.u16(e) == Some(TAG_NEW_SUBFILE_TYPE))
+            .and_then(|e| match reader.u16(e + 2)? {
+                3 => reader.u16(e + value_at).map(u32::from),
+                4 | 13 => reader.u32(e + value_at),
+                _ => None,
+            })
+            .unwrap_or(0);
+        if subfile_type & SUBFILE_REDUCED == 0 {
+            pages.push(ifd);
+        }
+        ifd = offset_at(&reader, count * entry_len).ok_or_else(malformed)?;
+    }
+    Ok(pages)
+}
+
+/// Number of pages; 1 for anything that isn't a TIFF.
+pub fn page_count(path: &Path) -> Result<u32, AppError> {
+    match file_format(path)? {
+        FileFormat::Image(ImageFormat::Tiff) => {
+            let mut file = BufReader::new(File::open(path)?);
+            Ok(page_offsets(&mut file)?.len().max(1) as u32)
+        }
+        _ => Ok(1),
+    }
+}
+
+/// A file read through a replacement header, which points it at a later IFD.
+struct PatchedHeader<R> {
+    inner: R,
+    header: Vec<u8>,
+    pos: u64,
+}
+
+impl<R: Read + Seek> Read for PatchedHeader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = match usize::try_from(self.pos) {
+            Ok(start) if start < self.header.len() => {
+                let n = buf.len().min(self.header.len() - start);
+                buf[..n].copy_from_slice(&self.header[start..start + n]);
+                self.inner.seek(SeekFrom::Start(self.pos + n as u64))?;
+                n
+            }
+            _ => self.inner.read(buf)?,
+        };
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Seek> Seek for PatchedHeader<R> {
+    fn seek(&mut self, to: SeekFrom) -> std::io::Result<u64> {
+        self.pos = self.inner.seek(to)?;
+        Ok(self.pos)
+    }
+}
+
+/// Decode one page (zero-based) with its orientation applied.
+pub fn decode_page(path: &Path, page: u32) -> Result<DynamicImage, AppError> {
+    if !matches!(file_format(path)?, FileFormat::Image(ImageFormat::Tiff)) {
+        return match page {
+            0 => operations::open_image(path),
+            _ => Err(AppError::General("Only TIFF files have more pages".into())),
+        };
+    }
+    let mut file = BufReader::new(File::open(path)?);
+    let pages = page_offsets(&mut file)?;
+    let offset = *pages.get(page as usize).ok_or_else(|| {
+        AppError::General(format!("Page {} of {} requested", page + 1, pages.len()))
+    })?;
+
+    let mut header = [0u8; 16];
+    file.rewind()?;
+    file.read_exact(&mut header[..8])?;
+    let (little_endian, big) = tiff_kind(&header)?;
+    let header = if big {
+        file.read_exact(&mut header[8..])?;
+        let offset = if little_endian {
+            offset.to_le_bytes()
+        } else {
+            offset.to_be_bytes()
+        };
+        header[8..16].copy_from_slice(&offset);
+        header.to_vec()
+    } else {
+        let offset = u32::try_from(offset).map_err(|_| malformed())?;
+        let offset = if little_endian {
+            offset.to_le_bytes()
+        } else {
+            offset.to_be_bytes()
+        };
+        header[4..8].copy_from_slice(&offset);
+        header[..8].to_vec()
+    };
+
+    let patched = PatchedHeader {
+        inner: file.into_inner(),
+        header,
+        pos: 0,
+    };
+    let mut decoder = image::ImageReader::with_format(BufReader::new(patched), ImageFormat::Tiff)
+        .into_decoder()?;
+    let orientation = decoder
+        .orientation()
+        .unwrap_or(image::metadata::Orientation::NoTransforms);
+    let mut img = DynamicImage::from_decoder(decoder)?;
+    img.apply_orientation(orientation);
+    Ok(img)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TiffCompression {
+    None,
+    #[default]
+    Lzw,
+    Deflate,
+}
+
+impl TiffCompression {
+    fn codec(self) -> Compression {
+        match self {
+            TiffCompression::None => Compression::Uncompressed,
+            TiffCompression::Lzw => Compression::Lzw,
+            TiffCompression::Deflate => Compression::Deflate(DeflateLevel::Balanced),
+        }
+    }
+}
+
+/// Encode the images as pages of one TIFF, keeping float samples, 16-bit
+/// depth and alpha where present.
+pub fn encode_pages(
+    pages: &[DynamicImage],
+    compression: TiffCompression,
+    premultiplied: bool,
+) -> Result<Vec<u8>, AppError> {
+    if pages.is_empty() {
+        return Err(AppError::General("No pages to write".into()));
+    }
+    // ExtraSamples: 1 = associated (premultiplied) alpha, 2 = unassociated
+    let extra_samples: u16 = if premultiplied { 1 } else { 2 };
+    let mut bytes = Vec::new();
+    {
+        let mut encoder = TiffEncoder::new(Cursor::new(&mut bytes))
+            .map_err(AppError::save_failed)?
+            .with_compression(compression.codec());
+        for page in pages {
+            let premultiplied_page;
+            let page = if premultiplied && page.color().has_alpha() {
+                premultiplied_page = alpha::premultiply(page);
+                &premultiplied_page
+            } else {
+                page
+            };
+            let (w, h) = (page.width(), page.height());
+            match page {
+                DynamicImage::ImageLuma8(img) => {
+                    write_page::<colortype::Gray8>(&mut encoder, w, h, img, None)
+                }
+                DynamicImage::ImageLuma16(img) => {
+                    write_page::<colortype::Gray16>(&mut encoder, w, h, img, None)
+                }
+                DynamicImage::ImageRgb8(img) => {
+                    write_page::<colortype::RGB8>(&mut encoder, w, h, img, None)
+                }
+                DynamicImage::ImageRgb16(img) => {
+                    write_page::<colortype::RGB16>(&mut encoder, w, h, img, None)
+                }
+                DynamicImage::ImageRgb32F(img) => {
+                    write_page::<colortype::RGB32Float>(&mut encoder, w, h, img, None)
+                }
+                DynamicImage::ImageRgba32F(img) => write_page::<colortype::RGBA32Float>(
+                    &mut encoder,
+                    w,
+                    h,
+                    img,
+                    Some(extra_samples),
+                ),
+                // Gray+alpha widens to RGBA; 16-bit alpha images stay 16-bit
+                img if img.color().bytes_per_pixel() > img.color().channel_count() => {
+                    write_page::<colortype::RGBA16>(
+                        &mut encoder,
+                        w,
+                        h,
+                        &img.to_rgba16(),
+                        Some(extra_samples),
+                    )
+                }
+                img => write_page::<colortype::RGBA8>(
+                    &mut encoder,
+                    w,
+                    h,
+                    &img.to_rgba8(),
+                    Some(extra_samples),
+                ),
+            }
+            .map_err(AppError::save_failed)?;
+        }
+    }
+    Ok(bytes)
+}
+
+/// Write one page, tagging what its fourth sample is when it has one.
+fn write_page<C: colortype::ColorType>(
+    encoder: &mut TiffEncoder<Cursor<&mut Vec<u8>>>,
+    width: u32,
+    height: u32,
+    data: &[C::Inner],
+    extra_samples: Option<u16>,
+) -> tiff::TiffResult<()>
+where
+    [C::Inner]: tiff::encoder::TiffValue,
+{
+    let mut image = encoder.new_image::<C>(width, height)?;
+    if let Some(kind) = extra_samples {
+        image
+            .encoder()
+            .write_tag(tiff::tags::Tag::ExtraSamples, kind)?;
+    }
+    image.write_data(data)
+}
+
+/// Decode a page into a temp PNG that the other commands can work on.
+#[tauri::command]
+pub fn open_tiff_page(path: String, page: u32) -> Result<String, AppError> {
+    operations::save_temp_image(&decode_page(Path::new(&path), page)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(ext: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "pixelforge_pages_test_{}.{ext}",
+            uuid::Uuid::new_v4()
+        ))
+    }
+
+    #[test]
+    fn pages_roundtrip_with_their_own_sizes_and_depths() {
+        let pages = [
+            DynamicImage::ImageRgb8(image::RgbImage::from_pixel(8, 6, image::Rgb([200, 10, 10]))),
+            DynamicImage::ImageLuma16(image::ImageBuffer::from_pixel(5, 9, image::Luma([40000]))),
+            DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+                4,
+                4,
+                image::Rgba([0, 0, 255, 100]),
+            )),
+        ];
+        let path = temp_path("tif");
+        for compression in [
+            TiffCompression::None,
+            TiffCompression::Deflate,
+            TiffCompression::Lzw,
+        ] {
+            std::fs::write(&path, encode_pages(&pages, compression, false).unwrap()).unwrap();
+
+            assert_eq!(page_count(&path).unwrap(), 3);
+            for (i, expected) in pages.iter().enumerate() {
+                let page = decode_page(&path, i as u32).unwrap();
+                assert_eq!(
+                    (page.width(), page.height()),
+                    (expected.width(), expected.height())
+                );
+                assert_eq!(page.color(), expected.color());
+                assert_eq!(page.as_bytes(), expected.as_bytes());
+            }
+            assert!(decode_page(&path, 3).is_err());
+        }
+        // The first page still decodes the ordinary way
+        let first = operations::open_image(&path).unwrap();
+        assert_eq!(first.to_rgb8().get_pixel(0, 0).0, [200, 10, 10]);
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn short_subfile_types_mark_thumbnails() {
+        // Big-endian: a thumbnail IFD whose NewSubfileType is a SHORT, then a page
+        let mut bytes = b"MM\0*\0\0\0\x08".to_vec();
+        bytes.extend_from_slice(&[0, 1, 0, 254, 0, 3, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 26]);
+        bytes.extend_from_slice(&[0, 1, 0, 254, 0, 4, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(page_offsets(&mut Cursor::new(bytes)).unwrap(), [26]);
+    }
+
+    #[test]
+    fn non_tiff_files_have_one_page() {
+        let path = temp_path("png");
+        image::RgbImage::new(2, 2).save(&path).unwrap();
+        assert_eq!(page_count(&path).unwrap(), 1);
+        assert_eq!(decode_page(&path, 0).unwrap().width(), 2);
+        assert!(decode_page(&path, 1).is_err());
+        std::fs::remove_file(path).ok();
+    }
+}
@@ -1,10 +1,61 @@
+use crate::commands::image::FileFormat;
+use crate::commands::tonemap::{self, ToneMapOptions};
+use crate::commands::{animation, heif, large, raw};
 use crate::error::AppError;
-use image::DynamicImage;
-use serde::Serialize;
+use image::{DynamicImage, ImageDecoder, Rgba32FImage};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Decode an image with its EXIF orientation applied, so phone photos come
+/// out upright. Commands load through this rather than `image::open`; the
+/// temp PNGs they write carry no orientation tag, so re-opening is a no-op.
+/// Camera RAW files are developed with default settings; HEIF files come
+/// out of libheif already upright.
+pub fn open_image(path: impl AsRef<Path>) -> Result<DynamicImage, AppError> {
+    let path = path.as_ref();
+    let format = match crate::commands::image::file_format(path)? {
+        FileFormat::Raw(_) => return raw::develop_file(path, &raw::RawOptions::default()),
+        FileFormat::Heif => return heif::decode_file(path),
+        FileFormat::Image(format) => format,
+    };
+    let mut reader = image::ImageReader::open(path)?;
+    reader.set_format(format);
+    // Decoders hold the budget for their own buffers too, not the crate's
+    // 512 MiB default
+    let mut limits = image::Limits::default();
+    limits.max_alloc = Some(large::memory_budget());
+    reader.limits(limits);
+    let mut decoder = reader.into_decoder()?;
+    let (width, height) = decoder.dimensions();
+    large::check_budget(
+        decoder.total_bytes(),
+        &format!("Decoding this {width}×{height} image whole"),
+    )?;
+    let orientation = decoder
+        .orientation()
+        .unwrap_or(image::metadata::Orientation::NoTransforms);
+    let mut img = DynamicImage::from_decoder(decoder)?;
+    img.apply_orientation(orientation);
+    Ok(img)
+}
+
+/// PNG has no float samples, so HDR/EXR images are shown as 16-bit sRGB
+/// through the default display transform.
+pub fn png_compatible(img: &DynamicImage) -> Option<DynamicImage> {
+    tonemap::is_float(img.color()).then(|| tonemap::tone_map(img, &ToneMapOptions::display()))
+}
 
-fn save_temp_image(img: &DynamicImage) -> Result<String, AppError> {
+/// Save an operation's result as a temp file. Float results go to an EXR so
+/// edits keep values above 1.0, which the webview can't show (see
+/// `png_compatible`); everything else to a PNG.
+pub fn save_temp_image(img: &DynamicImage) -> Result<String, AppError> {
     let id = uuid::Uuid::new_v4();
-    let path = std::env::temp_dir().join(format!("pixelforge_{}.png", id));
+    let ext = if tonemap::is_float(img.color()) {
+        "exr"
+    } else {
+        "png"
+    };
+    let path = std::env::temp_dir().join(format!("pixelforge_{}.{}", id, ext));
     img.save(&path)
         .map_err(|e| AppError::SaveFailed(e.to_string()))?;
     Ok(path.to_string_lossy().into_owned())
@@ -18,12 +69,256 @@ pub fn apply_crop(
     width: u32,
     height: u32,
 ) -> Result<String, AppError> {
-    let img = image::open(&path)?;
-    if x + width > img.width() || y + height > img.height() {
-        return Err(AppError::General("Crop region exceeds image bounds".into()));
+    crop_image(&path, x, y, width, height)
+}
+
+// --- Resize modes ---
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ResizeMode {
+    /// Stretch to exactly `width` x `height`
+    #[default]
+    Exact,
+    /// Scale to fit inside the box, keeping aspect ratio
+    Fit,
+    /// Scale to cover the box, then center-crop the overflow
+    #[serde(alias = "cover")]
+    Fill,
+    /// Fit inside the box, then center on a `width` x `height` canvas
+    Pad,
+    /// Scale to `width`, height follows aspect ratio
+    Width,
+    /// Scale to `height`, width follows aspect ratio
+    Height,
+    /// Scale so the longer side equals `width`
+    LongestEdge,
+    /// Scale both sides by `percentage`
+    Percentage,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ResizeOptions {
+    pub mode: ResizeMode,
+    /// RGBA fill for the canvas in `pad` mode
+    pub pad_color: [u8; 4],
+    pub percentage: f32,
+    /// Never scale above the source size
+    pub no_upscale: bool,
+    /// Resample in linear light; `None` decides per image (on for photos)
+    pub linear_light: Option<bool>,
+}
+
+impl Default for ResizeOptions {
+    fn default() -> Self {
+        Self {
+            mode: ResizeMode::Exact,
+            pad_color: [0, 0, 0, 0],
+            percentage: 100.0,
+            no_upscale: false,
+            linear_light: None,
+        }
+    }
+}
+
+pub fn parse_filter(filter: &str) -> Result<image::imageops::FilterType, AppError> {
+    match filter {
+        "lanczos" => Ok(image::imageops::FilterType::Lanczos3),
+        "catmullrom" => Ok(image::imageops::FilterType::CatmullRom),
+        "gaussian" => Ok(image::imageops::FilterType::Gaussian),
+        "bilinear" => Ok(image::imageops::FilterType::Triangle),
+        "nearest" => Ok(image::imageops::FilterType::Nearest),
+        other => Err(AppError::General(format!("Unknown filter: {}", other))),
+    }
+}
+
+/// Scaled size of the image content plus the final output size.
+/// The two differ for `fill` (crop) and `pad` (canvas).
+#[derive(Debug, PartialEq)]
+pub struct ResizePlan {
+    pub scaled_w: u32,
+    pub scaled_h: u32,
+    pub out_w: u32,
+    pub out_h: u32,
+}
+
+/// Check that the target box carries the dimensions the mode actually uses.
+pub fn validate_resize_target(
+    width: u32,
+    height: u32,
+    options: &ResizeOptions,
+) -> Result<(), AppError> {
+    let needs_width = !matches!(options.mode, ResizeMode::Height | ResizeMode::Percentage);
+    let needs_height = matches!(
+        options.mode,
+        ResizeMode::Exact
+            | ResizeMode::Fit
+            | ResizeMode::Fill
+            | ResizeMode::Pad
+            | ResizeMode::Height
+    );
+    if (needs_width && width == 0) || (needs_height && height == 0) {
+        return Err(AppError::General(
+            "Width and height must be greater than 0".into(),
+        ));
+    }
+    if options.mode == ResizeMode::Percentage && options.percentage <= 0.0 {
+        return Err(AppError::General(
+            "Percentage must be greater than 0".into(),
+        ));
+    }
+    Ok(())
+}
+
+pub fn plan_resize(
+    src_w: u32,
+    src_h: u32,
+    width: u32,
+    height: u32,
+    options: &ResizeOptions,
+) -> Result<ResizePlan, AppError> {
+    validate_resize_target(width, height, options)?;
+
+    let (sw, sh) = (src_w.max(1) as f64, src_h.max(1) as f64);
+    let limit = |scale: f64| {
+        if options.no_upscale {
+            scale.min(1.0)
+        } else {
+            scale
+        }
+    };
+    let scaled = |scale: f64| {
+        (
+            ((sw * scale).round() as u32).max(1),
+            ((sh * scale).round() as u32).max(1),
+        )
+    };
+
+    let plan = match options.mode {
+        ResizeMode::Exact => {
+            // One factor for both axes, so the requested shape survives
+            let (w, h) = if options.no_upscale {
+                let scale = (sw / width as f64).min(sh / height as f64).min(1.0);
+                (
+                    ((width as f64 * scale).round() as u32).max(1),
+                    ((height as f64 * scale).round() as u32).max(1),
+                )
+            } else {
+                (width, height)
+            };
+            ResizePlan {
+                scaled_w: w,
+                scaled_h: h,
+                out_w: w,
+                out_h: h,
+            }
+        }
+        ResizeMode::Fit | ResizeMode::Pad => {
+            let (w, h) = scaled(limit((width as f64 / sw).min(height as f64 / sh)));
+            let (w, h) = (w.min(width), h.min(height));
+            let (out_w, out_h) = if options.mode == ResizeMode::Pad {
+                (width, height)
+            } else {
+                (w, h)
+            };
+            ResizePlan {
+                scaled_w: w,
+                scaled_h: h,
+                out_w,
+                out_h,
+            }
+        }
+        ResizeMode::Fill => {
+            let (w, h) = scaled(limit((width as f64 / sw).max(height as f64 / sh)));
+            ResizePlan {
+                scaled_w: w,
+                scaled_h: h,
+                out_w: w.min(width),
+                out_h: h.min(height),
+            }
+        }
+        ResizeMode::Width
+        | ResizeMode::Height
+        | ResizeMode::LongestEdge
+        | ResizeMode::Percentage => {
+            let scale = match options.mode {
+                ResizeMode::Width => width as f64 / sw,
+                ResizeMode::Height => height as f64 / sh,
+                ResizeMode::LongestEdge => width as f64 / sw.max(sh),
+                _ => options.percentage as f64 / 100.0,
+            };
+            let (w, h) = scaled(limit(scale));
+            ResizePlan {
+                scaled_w: w,
+                scaled_h: h,
+                out_w: w,
+                out_h: h,
+            }
+        }
+    };
+    Ok(plan)
+}
+
+pub fn resize_dynamic(
+    img: &DynamicImage,
+    width: u32,
+    height: u32,
+    filter: &str,
+    options: &ResizeOptions,
+) -> Result<DynamicImage, AppError> {
+    let filter_type = parse_filter(filter)?;
+    let plan = plan_resize(img.width(), img.height(), width, height, options)?;
+    let linear = options.linear_light.unwrap_or_else(|| {
+        filter_type != image::imageops::FilterType::Nearest && is_photographic(img)
+    });
+    // The linear path holds float copies of the source and the result
+    let scaled = u64::from(plan.scaled_w) * u64::from(plan.scaled_h);
+    let bytes = if linear {
+        (u64::from(img.width()) * u64::from(img.height()) + scaled) * 16
+    } else {
+        0
+    } + scaled * u64::from(img.color().bytes_per_pixel())
+        + u64::from(plan.out_w) * u64::from(plan.out_h) * 16;
+    large::check_budget(
+        bytes,
+        &format!("Resizing to {}×{}", plan.scaled_w, plan.scaled_h),
+    )?;
+    let resized = if (plan.scaled_w, plan.scaled_h) == (img.width(), img.height()) {
+        img.clone()
+    } else if linear {
+        resize_linear(img, plan.scaled_w, plan.scaled_h, filter_type)
+    } else {
+        img.resize_exact(plan.scaled_w, plan.scaled_h, filter_type)
+    };
+
+    match options.mode {
+        ResizeMode::Fill => {
+            let x = (plan.scaled_w - plan.out_w) / 2;
+            let y = (plan.scaled_h - plan.out_h) / 2;
+            Ok(resized.crop_imm(x, y, plan.out_w, plan.out_h))
+        }
+        ResizeMode::Pad => {
+            // Padded in float, then stored at the source's depth; alpha is
+            // added only when the source or the pad color needs it
+            let mut canvas = Rgba32FImage::from_pixel(
+                plan.out_w,
+                plan.out_h,
+                image::Rgba(options.pad_color.map(|v| f32::from(v) / 255.0)),
+            );
+            let x = (plan.out_w - plan.scaled_w) / 2;
+            let y = (plan.out_h - plan.scaled_h) / 2;
+            image::imageops::overlay(&mut canvas, &resized.to_rgba32f(), x as i64, y as i64);
+            let color = match img.color() {
+                c if c.has_alpha() || options.pad_color[3] == 255 => c,
+                c if tonemap::is_float(c) => image::ColorType::Rgba32F,
+                c if c.bytes_per_pixel() / c.channel_count() > 1 => image::ColorType::Rgba16,
+                _ => image::ColorType::Rgba8,
+            };
+            Ok(with_color(canvas, color))
+        }
+        _ => Ok(resized),
     }
-    let cropped = img.crop_imm(x, y, width, height);
-    save_temp_image(&cropped)
 }
 
 #[tauri::command]
@@ -32,78 +327,205 @@ pub fn apply_resize(
     width: u32,
     height: u32,
     filter: String,
+    options: Option<ResizeOptions>,
 ) -> Result<String, AppError> {
-    let img = image::open(&path)?;
-    let filter_type = match filter.as_str() {
-        "lanczos" => image::imageops::FilterType::Lanczos3,
-        "bilinear" => image::imageops::FilterType::Triangle,
-        "nearest" => image::imageops::FilterType::Nearest,
-        other => return Err(AppError::General(format!("Unknown filter: {}", other))),
-    };
-    let resized = img.resize_exact(width, height, filter_type);
-    save_temp_image(&resized)
+    resize_image(&path, width, height, &filter, &options.unwrap_or_default())
 }
 
 #[tauri::command]
 pub fn apply_rotate(path: String, degrees: i32) -> Result<String, AppError> {
-    let img = image::open(&path)?;
-    let rotated = match degrees {
-        90 => img.rotate90(),
-        180 => img.rotate180(),
-        270 | -90 => img.rotate270(),
-        _ => {
-            return Err(AppError::General(
-                "Only 90, 180, 270 degree rotations supported".into(),
-            ))
-        }
-    };
-    save_temp_image(&rotated)
+    rotate_image(&path, degrees)
 }
 
 #[tauri::command]
 pub fn apply_flip(path: String, direction: String) -> Result<String, AppError> {
-    let img = image::open(&path)?;
-    let flipped = match direction.as_str() {
-        "horizontal" => img.fliph(),
-        "vertical" => img.flipv(),
-        _ => {
-            return Err(AppError::General(
-                "Direction must be 'horizontal' or 'vertical'".into(),
-            ))
-        }
-    };
-    save_temp_image(&flipped)
+    flip_image(&path, &direction)
 }
 
 #[tauri::command]
 pub fn apply_brightness(path: String, value: i32) -> Result<String, AppError> {
-    let img = image::open(&path)?;
-    let value = value.clamp(-255, 255);
-    let result = img.brighten(value);
-    save_temp_image(&result)
+    brightness_image(&path, value)
 }
 
 #[tauri::command]
 pub fn apply_contrast(path: String, value: f32) -> Result<String, AppError> {
-    let img = image::open(&path)?;
-    let value = value.clamp(-100.0, 100.0);
-    let result = img.adjust_contrast(value);
-    save_temp_image(&result)
+    contrast_image(&path, value)
 }
 
 #[tauri::command]
 pub fn apply_hue(path: String, degrees: i32) -> Result<String, AppError> {
-    let img = image::open(&path)?;
-    let result = img.huerotate(degrees);
-    save_temp_image(&result)
+    hue_image(&path, degrees)
+}
+
+// --- Linear-light helpers ---
+//
+// Averaging sRGB-encoded values darkens high-contrast detail, so resampling,
+// blurring and blending convert to premultiplied linear light first.
+
+pub fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(v: f32) -> f32 {
+    let v = v.clamp(0.0, 1.0);
+    if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Premultiplied linear RGBA; float images are linear already.
+pub fn to_linear(img: &DynamicImage) -> Rgba32FImage {
+    let float = tonemap::is_float(img.color());
+    let mut out = img.to_rgba32f();
+    for p in out.pixels_mut() {
+        let a = p[3];
+        for c in 0..3 {
+            p[c] = if float { p[c] } else { srgb_to_linear(p[c]) } * a;
+        }
+    }
+    out
+}
+
+/// Convert back to sRGB with the channel layout and bit depth of `color`.
+pub fn from_linear(mut lin: Rgba32FImage, color: image::ColorType) -> DynamicImage {
+    let float = tonemap::is_float(color);
+    for p in lin.pixels_mut() {
+        let a = p[3].clamp(0.0, 1.0);
+        for c in 0..3 {
+            p[c] = match (a > 0.0, float) {
+                (false, _) => 0.0,
+                (true, true) => p[c] / a,
+                (true, false) => linear_to_srgb(p[c] / a),
+            };
+        }
+        p[3] = a;
+    }
+    with_color(lin, color)
+}
+
+/// RGBA float samples stored with the channel layout and bit depth of `color`.
+pub fn with_color(img: Rgba32FImage, color: image::ColorType) -> DynamicImage {
+    let img = DynamicImage::ImageRgba32F(img);
+    match color {
+        image::ColorType::Rgba32F => return img,
+        image::ColorType::Rgb32F => return DynamicImage::ImageRgb32F(img.to_rgb32f()),
+        _ => {}
+    }
+    let has_alpha = color.has_alpha();
+    let wide = color.bytes_per_pixel() / color.channel_count() > 1;
+    match (wide, has_alpha) {
+        (false, true) => DynamicImage::ImageRgba8(img.to_rgba8()),
+        (false, false) => DynamicImage::ImageRgb8(img.to_rgb8()),
+        (true, true) => DynamicImage::ImageRgba16(img.to_rgba16()),
+        (true, false) => DynamicImage::ImageRgb16(img.to_rgb16()),
+    }
+}
+
+/// The sRGB curve continued past 1.0, so HDR values stay apart once encoded.
+fn encode_unclipped(v: f32) -> f32 {
+    if v > 1.0 {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    } else {
+        linear_to_srgb(v)
+    }
+}
+
+/// Apply `f` to the sRGB-encoded colour of every pixel, keeping the image's
+/// bit depth. Float images are encoded without clipping, so values above
+/// 1.0 reach `f` and survive it.
+fn map_encoded(img: &DynamicImage, f: impl Fn([f32; 3]) -> [f32; 3]) -> DynamicImage {
+    let float = tonemap::is_float(img.color());
+    let mut rgba = img.to_rgba32f();
+    for p in rgba.pixels_mut() {
+        let rgb = [p[0], p[1], p[2]];
+        let out = f(if float {
+            rgb.map(encode_unclipped)
+        } else {
+            rgb
+        });
+        for c in 0..3 {
+            p[c] = if float {
+                srgb_to_linear(out[c].max(0.0))
+            } else {
+                out[c].clamp(0.0, 1.0)
+            };
+        }
+    }
+    with_color(rgba, img.color())
+}
+
+/// The resamplers clamp float samples to [0, 1], so HDR values are scaled
+/// into that range around `op` and back afterwards.
+fn with_headroom(
+    mut lin: Rgba32FImage,
+    op: impl FnOnce(&Rgba32FImage) -> Rgba32FImage,
+) -> Rgba32FImage {
+    let peak = lin
+        .pixels()
+        .flat_map(|p| [p[0], p[1], p[2]])
+        .fold(1.0f32, f32::max);
+    if peak <= 1.0 {
+        return op(&lin);
+    }
+    for p in lin.pixels_mut() {
+        for c in 0..3 {
+            p[c] /= peak;
+        }
+    }
+    let mut out = op(&lin);
+    for p in out.pixels_mut() {
+        for c in 0..3 {
+            p[c] *= peak;
+        }
+    }
+    out
+}
+
+pub fn resize_linear(
+    img: &DynamicImage,
+    width: u32,
+    height: u32,
+    filter: image::imageops::FilterType,
+) -> DynamicImage {
+    let resized = with_headroom(to_linear(img), |lin| {
+        image::imageops::resize(lin, width, height, filter)
+    });
+    from_linear(resized, img.color())
+}
+
+pub fn blur_linear(img: &DynamicImage, sigma: f32) -> DynamicImage {
+    let blurred = with_headroom(to_linear(img), |lin| image::imageops::blur(lin, sigma));
+    from_linear(blurred, img.color())
+}
+
+/// Heuristic for "photo vs. graphic": flat artwork and pixel art use few
+/// distinct colors and should keep their exact sRGB values. Float images are
+/// scene-linear renders or captures, and only the linear path keeps values
+/// above 1.0.
+pub fn is_photographic(img: &DynamicImage) -> bool {
+    if tonemap::is_float(img.color()) {
+        return true;
+    }
+    let thumb = img.thumbnail(64, 64).to_rgb8();
+    let mut seen = std::collections::HashSet::new();
+    for p in thumb.pixels() {
+        seen.insert(p.0);
+        if seen.len() > 256 {
+            return true;
+        }
+    }
+    false
 }
 
 // --- HSL helpers (image crate lacks saturation/lightness) ---
 
-fn rgb_to_hsl(r: u8, g: u8, b: u8) -> (f32, f32, f32) {
-    let r = r as f32 / 255.0;
-    let g = g as f32 / 255.0;
-    let b = b as f32 / 255.0;
+fn rgb_to_hsl([r, g, b]: [f32; 3]) -> (f32, f32, f32) {
     let max = r.max(g).max(b);
     let min = r.min(g).min(b);
     let l = (max + min) / 2.0;
@@ -145,10 +567,9 @@ fn hue_to_rgb(p: f32, q: f32, mut t: f32) -> f32 {
     p
 }
 
-fn hsl_to_rgb(h: f32, s: f32, l: f32) -> (u8, u8, u8) {
+fn hsl_to_rgb(h: f32, s: f32, l: f32) -> [f32; 3] {
     if s.abs() < f32::EPSILON {
-        let v = (l * 255.0).round() as u8;
-        return (v, v, v);
+        return [l; 3];
     }
     let q = if l < 0.5 {
         l * (1.0 + s)
@@ -156,64 +577,35 @@ fn hsl_to_rgb(h: f32, s: f32, l: f32) -> (u8, u8, u8) {
         l + s - l * s
     };
     let p = 2.0 * l - q;
-    let r = hue_to_rgb(p, q, h + 1.0 / 3.0);
-    let g = hue_to_rgb(p, q, h);
-    let b = hue_to_rgb(p, q, h - 1.0 / 3.0);
-    (
-        (r * 255.0).round() as u8,
-        (g * 255.0).round() as u8,
-        (b * 255.0).round() as u8,
-    )
+    [
+        hue_to_rgb(p, q, h + 1.0 / 3.0),
+        hue_to_rgb(p, q, h),
+        hue_to_rgb(p, q, h - 1.0 / 3.0),
+    ]
 }
 
 #[tauri::command]
 pub fn apply_saturation(path: String, value: f32) -> Result<String, AppError> {
-    let img = image::open(&path)?;
-    let mut rgba = img.to_rgba8();
-    let factor = 1.0 + value / 100.0;
-
-    for pixel in rgba.pixels_mut() {
-        let (h, s, l) = rgb_to_hsl(pixel[0], pixel[1], pixel[2]);
-        let new_s = (s * factor).clamp(0.0, 1.0);
-        let (r, g, b) = hsl_to_rgb(h, new_s, l);
-        pixel[0] = r;
-        pixel[1] = g;
-        pixel[2] = b;
-    }
-
-    save_temp_image(&DynamicImage::ImageRgba8(rgba))
+    saturation_image(&path, value)
 }
 
 #[tauri::command]
 pub fn apply_lightness(path: String, value: f32) -> Result<String, AppError> {
-    let img = image::open(&path)?;
-    let mut rgba = img.to_rgba8();
-
-    for pixel in rgba.pixels_mut() {
-        let (h, s, l) = rgb_to_hsl(pixel[0], pixel[1], pixel[2]);
-        let new_l = (l + value / 100.0).clamp(0.0, 1.0);
-        let (r, g, b) = hsl_to_rgb(h, s, new_l);
-        pixel[0] = r;
-        pixel[1] = g;
-        pixel[2] = b;
-    }
-
-    save_temp_image(&DynamicImage::ImageRgba8(rgba))
+    lightness_image(&path, value)
 }
 
 #[tauri::command]
-pub fn apply_blur(path: String, sigma: f32) -> Result<String, AppError> {
-    let img = image::open(&path)?;
-    let sigma = sigma.clamp(0.1, 50.0);
-    let blurred = img.blur(sigma);
-    save_temp_image(&blurred)
+pub fn apply_blur(
+    path: String,
+    sigma: f32,
+    linear_light: Option<bool>,
+) -> Result<String, AppError> {
+    blur_image(&path, sigma, linear_light)
 }
 
 #[tauri::command]
 pub fn apply_sharpen(path: String, sigma: f32, threshold: i32) -> Result<String, AppError> {
-    let img = image::open(&path)?;
-    let sharpened = img.unsharpen(sigma, threshold);
-    save_temp_image(&sharpened)
+    sharpen_image(&path, sigma, threshold)
 }
 
 // --- Color Palette Extraction (K-Means) ---
@@ -229,7 +621,7 @@ pub struct PaletteColor {
 
 #[tauri::command]
 pub fn extract_palette(path: String, num_colors: u32) -> Result<Vec<PaletteColor>, AppError> {
-    let img = image::open(&path)?;
+    let img = open_image(&path)?;
     // Downsample for speed
     let thumb = img.resize(100, 100, image::imageops::FilterType::Nearest);
     let rgb = thumb.to_rgb8();
@@ -337,121 +729,144 @@ pub fn extract_palette(path: String, num_colors: u32) -> Result<Vec<PaletteColor
 
 // --- Public core functions for batch processing (Phase 5) ---
 
-pub fn crop_image(path: &str, x: u32, y: u32, width: u32, height: u32) -> Result<String, AppError> {
-    let img = image::open(path)?;
-    if x + width > img.width() || y + height > img.height() {
-        return Err(AppError::General("Crop region exceeds image bounds".into()));
+/// Apply `op` to the image, or to every frame of an animated file so the
+/// animation survives the edit.
+fn edit(
+    path: &str,
+    op: impl Fn(DynamicImage) -> Result<DynamicImage, AppError>,
+) -> Result<String, AppError> {
+    if let Some(frames) = animation::decode(Path::new(path))? {
+        return animation::save_temp_animation(&frames.map_frames(op)?);
     }
-    let cropped = img.crop_imm(x, y, width, height);
-    save_temp_image(&cropped)
+    save_temp_image(&op(open_image(path)?)?)
 }
 
-pub fn resize_image(path: &str, width: u32, height: u32, filter: &str) -> Result<String, AppError> {
-    let img = image::open(path)?;
-    let filter_type = match filter {
-        "lanczos" => image::imageops::FilterType::Lanczos3,
-        "bilinear" => image::imageops::FilterType::Triangle,
-        "nearest" => image::imageops::FilterType::Nearest,
-        other => return Err(AppError::General(format!("Unknown filter: {}", other))),
-    };
-    let resized = img.resize_exact(width, height, filter_type);
-    save_temp_image(&resized)
+pub fn crop_image(path: &str, x: u32, y: u32, width: u32, height: u32) -> Result<String, AppError> {
+    edit(path, |img| {
+        if x + width > img.width() || y + height > img.height() {
+            return Err(AppError::General("Crop region exceeds image bounds".into()));
+        }
+        Ok(img.crop_imm(x, y, width, height))
+    })
+}
+
+pub fn resize_image(
+    path: &str,
+    width: u32,
+    height: u32,
+    filter: &str,
+    options: &ResizeOptions,
+) -> Result<String, AppError> {
+    edit(path, |img| {
+        resize_dynamic(&img, width, height, filter, options)
+    })
 }
 
 pub fn rotate_image(path: &str, degrees: i32) -> Result<String, AppError> {
-    let img = image::open(path)?;
-    let rotated = match degrees {
-        90 => img.rotate90(),
-        180 => img.rotate180(),
-        270 | -90 => img.rotate270(),
-        _ => {
-            return Err(AppError::General(
-                "Only 90, 180, 270 degree rotations supported".into(),
-            ))
-        }
-    };
-    save_temp_image(&rotated)
+    if !matches!(degrees, 90 | 180 | 270 | -90) {
+        return Err(AppError::General(
+            "Only 90, 180, 270 degree rotations supported".into(),
+        ));
+    }
+    edit(path, |img| {
+        Ok(match degrees {
+            90 => img.rotate90(),
+            180 => img.rotate180(),
+            _ => img.rotate270(),
+        })
+    })
 }
 
 pub fn flip_image(path: &str, direction: &str) -> Result<String, AppError> {
-    let img = image::open(path)?;
-    let flipped = match direction {
-        "horizontal" => img.fliph(),
-        "vertical" => img.flipv(),
-        _ => {
-            return Err(AppError::General(
-                "Direction must be 'horizontal' or 'vertical'".into(),
-            ))
-        }
-    };
-    save_temp_image(&flipped)
+    if !matches!(direction, "horizontal" | "vertical") {
+        return Err(AppError::General(
+            "Direction must be 'horizontal' or 'vertical'".into(),
+        ));
+    }
+    edit(path, |img| {
+        Ok(match direction {
+            "horizontal" => img.fliph(),
+            _ => img.flipv(),
+        })
+    })
 }
 
+/// The image crate clips float samples at 1.0, so float images take the
+/// same steps on unclipped encoded values instead.
 pub fn brightness_image(path: &str, value: i32) -> Result<String, AppError> {
-    let img = image::open(path)?;
     let value = value.clamp(-255, 255);
-    let result = img.brighten(value);
-    save_temp_image(&result)
+    let offset = value as f32 / 255.0;
+    edit(path, |img| {
+        Ok(if tonemap::is_float(img.color()) {
+            map_encoded(&img, |rgb| rgb.map(|v| v + offset))
+        } else {
+            img.brighten(value)
+        })
+    })
 }
 
 pub fn contrast_image(path: &str, value: f32) -> Result<String, AppError> {
-    let img = image::open(path)?;
     let value = value.clamp(-100.0, 100.0);
-    let result = img.adjust_contrast(value);
-    save_temp_image(&result)
+    let factor = ((100.0 + value) / 100.0).powi(2);
+    edit(path, |img| {
+        Ok(if tonemap::is_float(img.color()) {
+            map_encoded(&img, |rgb| rgb.map(|v| (v - 0.5) * factor + 0.5))
+        } else {
+            img.adjust_contrast(value)
+        })
+    })
 }
 
 pub fn hue_image(path: &str, degrees: i32) -> Result<String, AppError> {
-    let img = image::open(path)?;
-    let result = img.huerotate(degrees);
-    save_temp_image(&result)
+    edit(path, |img| Ok(img.huerotate(degrees)))
 }
 
+/// HSL on encoded values at the image's own depth. Float highlights past
+/// 1.0 have no HSL form, so their chroma or level is shifted directly.
 pub fn saturation_image(path: &str, value: f32) -> Result<String, AppError> {
-    let img = image::open(path)?;
-    let mut rgba = img.to_rgba8();
     let factor = 1.0 + value / 100.0;
-    for pixel in rgba.pixels_mut() {
-        let (h, s, l) = rgb_to_hsl(pixel[0], pixel[1], pixel[2]);
-        let new_s = (s * factor).clamp(0.0, 1.0);
-        let (r, g, b) = hsl_to_rgb(h, new_s, l);
-        pixel[0] = r;
-        pixel[1] = g;
-        pixel[2] = b;
-    }
-    save_temp_image(&DynamicImage::ImageRgba8(rgba))
+    edit(path, |img| {
+        Ok(map_encoded(&img, |rgb| {
+            let (max, min) = (
+                rgb[0].max(rgb[1]).max(rgb[2]),
+                rgb[0].min(rgb[1]).min(rgb[2]),
+            );
+            if max > 1.0 {
+                let l = (max + min) / 2.0;
+                return rgb.map(|v| l + (v - l) * factor.max(0.0));
+            }
+            let (h, s, l) = rgb_to_hsl(rgb);
+            hsl_to_rgb(h, (s * factor).clamp(0.0, 1.0), l)
+        }))
+    })
 }
 
 pub fn lightness_image(path: &str, value: f32) -> Result<String, AppError> {
-    let img = image::open(path)?;
-    let mut rgba = img.to_rgba8();
-    for pixel in rgba.pixels_mut() {
-        let (h, s, l) = rgb_to_hsl(pixel[0], pixel[1], pixel[2]);
-        let new_l = (l + value / 100.0).clamp(0.0, 1.0);
-        let (r, g, b) = hsl_to_rgb(h, s, new_l);
-        pixel[0] = r;
-        pixel[1] = g;
-        pixel[2] = b;
-    }
-    save_temp_image(&DynamicImage::ImageRgba8(rgba))
+    let shift = value / 100.0;
+    edit(path, |img| {
+        Ok(map_encoded(&img, |rgb| {
+            if rgb.iter().any(|&v| v > 1.0) {
+                return rgb.map(|v| v + shift);
+            }
+            let (h, s, l) = rgb_to_hsl(rgb);
+            hsl_to_rgb(h, s, (l + shift).clamp(0.0, 1.0))
+        }))
+    })
 }
 
-pub fn blur_image(path: &str, sigma: f32) -> Result<String, AppError> {
-    let img = image::open(path)?;
+pub fn blur_image(path: &str, sigma: f32, linear_light: Option<bool>) -> Result<String, AppError> {
     let sigma = sigma.clamp(0.1, 50.0);
-    let blurred = img.blur(sigma);
-    save_temp_image(&blurred)
+    edit(path, |img| {
+        Ok(if linear_light.unwrap_or_else(|| is_photographic(&img)) {
+            blur_linear(&img, sigma)
+        } else {
+            img.blur(sigma)
+        })
+    })
 }
 
 pub fn sharpen_image(path: &str, sigma: f32, threshold: i32) -> Result<String, AppError> {
-    let img = image::open(path)?;
-    let sharpened = img.unsharpen(sigma, threshold);
-    save_temp_image(&sharpened)
-}
-
-/// Save a temp image (public for use by other modules like batch/ai)
-pub fn save_temp_png(img: &DynamicImage) -> Result<String, AppError> {
-    save_temp_image(img)
+    edit(path, |img| Ok(img.unsharpen(sigma, threshold)))
 }
 
 #[cfg(test)]
@@ -488,20 +903,159 @@ mod tests {
     #[test]
     fn test_resize() {
         let path = create_test_image(100, 100);
-        let result = apply_resize(path, 50, 50, "lanczos".into()).unwrap();
+        let result = apply_resize(path, 50, 50, "lanczos".into(), None).unwrap();
         let img = image::open(&result).unwrap();
         assert_eq!(img.width(), 50);
         assert_eq!(img.height(), 50);
         std::fs::remove_file(&result).ok();
     }
 
+    #[test]
+    fn test_edits_apply_to_every_frame() {
+        let frames = (0..3)
+            .map(|i| animation::AnimationFrame {
+                image: image::RgbaImage::from_pixel(40, 20, image::Rgba([i * 100, 0, 0, 255])),
+                delay_ms: 50,
+            })
+            .collect();
+        let original =
+            animation::save_temp_animation(&animation::Animation { frames, plays: 0 }).unwrap();
+
+        let resized = apply_resize(original.clone(), 20, 10, "bilinear".into(), None).unwrap();
+        let cropped = apply_crop(resized.clone(), 0, 0, 10, 10).unwrap();
+        let result = animation::decode(Path::new(&cropped)).unwrap().unwrap();
+        assert_eq!(result.frames.len(), 3);
+        assert_eq!(result.dimensions(), (10, 10));
+        assert_eq!(result.frames[2].image.get_pixel(5, 5).0, [200, 0, 0, 255]);
+        assert_eq!(result.frames[2].delay_ms, 50);
+        for path in [original, resized, cropped] {
+            std::fs::remove_file(path).ok();
+        }
+    }
+
     #[test]
     fn test_resize_invalid_filter() {
         let path = create_test_image(100, 100);
-        let result = apply_resize(path, 50, 50, "unknown".into());
+        let result = apply_resize(path, 50, 50, "unknown".into(), None);
         assert!(result.is_err());
     }
 
+    fn mode(mode: ResizeMode) -> ResizeOptions {
+        ResizeOptions {
+            mode,
+            ..ResizeOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_plan_fit_keeps_aspect() {
+        let portrait = plan_resize(800, 1200, 1200, 800, &mode(ResizeMode::Fit)).unwrap();
+        assert_eq!((portrait.out_w, portrait.out_h), (533, 800));
+        let landscape = plan_resize(1600, 1000, 1200, 800, &mode(ResizeMode::Fit)).unwrap();
+        assert_eq!((landscape.out_w, landscape.out_h), (1200, 750));
+    }
+
+    #[test]
+    fn test_plan_fill_and_pad_hit_box() {
+        let fill = plan_resize(800, 1200, 1200, 800, &mode(ResizeMode::Fill)).unwrap();
+        assert_eq!((fill.scaled_w, fill.scaled_h), (1200, 1800));
+        assert_eq!((fill.out_w, fill.out_h), (1200, 800));
+        let pad = plan_resize(800, 1200, 1200, 800, &mode(ResizeMode::Pad)).unwrap();
+        assert_eq!((pad.scaled_w, pad.scaled_h), (533, 800));
+        assert_eq!((pad.out_w, pad.out_h), (1200, 800));
+    }
+
+    #[test]
+    fn test_plan_single_dimension_modes() {
+        let width = plan_resize(1000, 500, 200, 0, &mode(ResizeMode::Width)).unwrap();
+        assert_eq!((width.out_w, width.out_h), (200, 100));
+        let height = plan_resize(1000, 500, 0, 100, &mode(ResizeMode::Height)).unwrap();
+        assert_eq!((height.out_w, height.out_h), (200, 100));
+        let edge = plan_resize(500, 1000, 400, 0, &mode(ResizeMode::LongestEdge)).unwrap();
+        assert_eq!((edge.out_w, edge.out_h), (200, 400));
+        let pct = ResizeOptions {
+            mode: ResizeMode::Percentage,
+            percentage: 25.0,
+            ..ResizeOptions::default()
+        };
+        let pct = plan_resize(1000, 500, 0, 0, &pct).unwrap();
+        assert_eq!((pct.out_w, pct.out_h), (250, 125));
+        assert!(plan_resize(1000, 500, 0, 100, &mode(ResizeMode::Width)).is_err());
+    }
+
+    #[test]
+    fn test_resize_over_budget_fails_before_allocating() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::new(100, 100));
+        let options = ResizeOptions {
+            mode: ResizeMode::Percentage,
+            percentage: 100_000.0,
+            ..ResizeOptions::default()
+        };
+        let err = resize_dynamic(&img, 0, 0, "lanczos", &options).unwrap_err();
+        assert!(err.to_string().contains("memory budget"), "{err}");
+    }
+
+    #[test]
+    fn test_plan_no_upscale() {
+        let options = ResizeOptions {
+            mode: ResizeMode::Fit,
+            no_upscale: true,
+            ..ResizeOptions::default()
+        };
+        let plan = plan_resize(300, 200, 1200, 800, &options).unwrap();
+        assert_eq!((plan.out_w, plan.out_h), (300, 200));
+    }
+
+    #[test]
+    fn test_resize_pad_fills_canvas() {
+        let path = create_test_image(100, 50);
+        let options = ResizeOptions {
+            mode: ResizeMode::Pad,
+            pad_color: [255, 255, 255, 255],
+            ..ResizeOptions::default()
+        };
+        let result = apply_resize(path, 80, 80, "catmullrom".into(), Some(options)).unwrap();
+        let img = image::open(&result).unwrap().to_rgba8();
+        assert_eq!(img.dimensions(), (80, 80));
+        assert_eq!(img.get_pixel(0, 0), &image::Rgba([255, 255, 255, 255]));
+        assert_eq!(img.get_pixel(40, 40)[2], 128);
+        std::fs::remove_file(&result).ok();
+    }
+
+    #[test]
+    fn pad_keeps_depth_and_exact_keeps_shape() {
+        let img = DynamicImage::ImageRgb16(image::ImageBuffer::from_pixel(
+            100,
+            50,
+            image::Rgb([1000, 20_000, 40_001]),
+        ));
+        let pad = |pad_color| ResizeOptions {
+            mode: ResizeMode::Pad,
+            pad_color,
+            ..ResizeOptions::default()
+        };
+        let out = resize_dynamic(&img, 80, 80, "nearest", &pad([0, 0, 0, 255])).unwrap();
+        assert_eq!(out.color(), image::ColorType::Rgb16);
+        assert_eq!(
+            out.as_rgb16().unwrap().get_pixel(40, 40).0,
+            [1000, 20_000, 40_001]
+        );
+        let out = resize_dynamic(&img, 80, 80, "nearest", &pad([0, 0, 0, 0])).unwrap();
+        assert_eq!(out.color(), image::ColorType::Rgba16);
+        assert_eq!(out.as_rgba16().unwrap().get_pixel(0, 0)[3], 0);
+
+        let exact = ResizeOptions {
+            mode: ResizeMode::Exact,
+            no_upscale: true,
+            ..ResizeOptions::default()
+        };
+        // 400×400 would stretch past the 300×200 source: the square shrinks
+        let plan = plan_resize(300, 200, 400, 400, &exact).unwrap();
+        assert_eq!((plan.out_w, plan.out_h), (200, 200));
+        let plan = plan_resize(300, 200, 150, 300, &exact).unwrap();
+        assert_eq!((plan.out_w, plan.out_h), (100, 200));
+    }
+
     #[test]
     fn test_rotate_90() {
         let path = create_test_image(100, 200);
@@ -558,7 +1112,7 @@ mod tests {
     #[test]
     fn test_blur() {
         let path = create_test_image(50, 50);
-        let result = apply_blur(path, 2.0).unwrap();
+        let result = apply_blur(path, 2.0, None).unwrap();
         assert!(std::path::Path::new(&result).exists());
         std::fs::remove_file(&result).ok();
     }
@@ -605,21 +1159,133 @@ mod tests {
         std::fs::remove_file(&path).ok();
     }
 
+    #[test]
+    fn test_linear_downscale_keeps_thin_lines_bright() {
+        // One white column in four: the sRGB average is 64, the linear-light one ~137
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_fn(4, 1, |x, _| {
+            let v = if x == 1 { 255 } else { 0 };
+            image::Rgb([v, v, v])
+        }));
+        let filter = image::imageops::FilterType::Triangle;
+        let gamma = img.resize_exact(1, 1, filter).to_rgb8();
+        let linear = resize_linear(&img, 1, 1, filter).to_rgb8();
+        assert!(gamma.get_pixel(0, 0)[0] < 80);
+        assert!(linear.get_pixel(0, 0)[0] > 120);
+    }
+
+    #[test]
+    fn test_linear_roundtrip_preserves_layout() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_fn(8, 8, |x, y| {
+            image::Rgb([(x * 30) as u8, (y * 30) as u8, 77])
+        }));
+        let back = from_linear(to_linear(&img), img.color());
+        assert_eq!(back.color(), image::ColorType::Rgb8);
+        assert_eq!(back.to_rgb8(), img.to_rgb8());
+    }
+
+    #[test]
+    fn test_photographic_heuristic() {
+        let flat = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            100,
+            100,
+            image::Rgba([10, 20, 30, 255]),
+        ));
+        assert!(!is_photographic(&flat));
+        let noisy = DynamicImage::ImageRgb8(image::RgbImage::from_fn(100, 100, |x, y| {
+            image::Rgb([(x * 7 + y * 3) as u8, (x * y) as u8, (y * 11) as u8])
+        }));
+        assert!(is_photographic(&noisy));
+    }
+
+    #[test]
+    fn adjustments_keep_float_and_16_bit_precision() {
+        let hdr = DynamicImage::ImageRgba32F(image::ImageBuffer::from_pixel(
+            4,
+            4,
+            image::Rgba([4.0, 0.5, 0.1, 1.0]),
+        ));
+        let path = save_temp_image(&hdr).unwrap();
+        for result in [
+            brightness_image(&path, 20).unwrap(),
+            contrast_image(&path, 20.0).unwrap(),
+            saturation_image(&path, 30.0).unwrap(),
+            lightness_image(&path, 10.0).unwrap(),
+            crop_image(&path, 1, 1, 2, 2).unwrap(),
+        ] {
+            let out = open_image(&result).unwrap();
+            assert!(tonemap::is_float(out.color()), "{result}");
+            let px = out.to_rgba32f().get_pixel(0, 0).0;
+            assert!(px[0] > 3.0, "{result}: {px:?}");
+            std::fs::remove_file(result).ok();
+        }
+        std::fs::remove_file(path).ok();
+
+        let deep = DynamicImage::ImageRgb16(image::ImageBuffer::from_pixel(
+            4,
+            4,
+            image::Rgb([40000u16, 20000, 10001]),
+        ));
+        let path = save_temp_image(&deep).unwrap();
+        let result = saturation_image(&path, 0.0).unwrap();
+        let out = open_image(&result).unwrap();
+        assert_eq!(out.color(), image::ColorType::Rgb16);
+        let [r, g, b] = out.to_rgb16().get_pixel(0, 0).0;
+        assert!(r.abs_diff(40000) <= 2 && g.abs_diff(20000) <= 2 && b.abs_diff(10001) <= 2);
+        for path in [path, result] {
+            std::fs::remove_file(path).ok();
+        }
+    }
+
     #[test]
     fn test_hsl_roundtrip() {
-        // White
-        let (h, s, l) = rgb_to_hsl(255, 255, 255);
-        let (r, g, b) = hsl_to_rgb(h, s, l);
-        assert_eq!((r, g, b), (255, 255, 255));
-
-        // Black
-        let (h, s, l) = rgb_to_hsl(0, 0, 0);
-        let (r, g, b) = hsl_to_rgb(h, s, l);
-        assert_eq!((r, g, b), (0, 0, 0));
-
-        // Red
-        let (h, s, l) = rgb_to_hsl(255, 0, 0);
-        let (r, g, b) = hsl_to_rgb(h, s, l);
-        assert_eq!((r, g, b), (255, 0, 0));
+        let roundtrip = |rgb: [f32; 3]| {
+            let (h, s, l) = rgb_to_hsl(rgb);
+            hsl_to_rgb(h, s, l).map(|v| (v * 255.0).round() as u8)
+        };
+        // White, black, red
+        assert_eq!(roundtrip([1.0, 1.0, 1.0]), [255, 255, 255]);
+        assert_eq!(roundtrip([0.0, 0.0, 0.0]), [0, 0, 0]);
+        assert_eq!(roundtrip([1.0, 0.0, 0.0]), [255, 0, 0]);
+    }
+
+    #[test]
+    fn open_image_applies_exif_orientation() {
+        // Orientation 6: stored landscape, displayed rotated 90° clockwise
+        let field = exif::Field {
+            tag: exif::Tag::Orientation,
+            ifd_num: exif::In::PRIMARY,
+            value: exif::Value::Short(vec![6]),
+        };
+        let mut writer = exif::experimental::Writer::new();
+        writer.push_field(&field);
+        let mut tiff = std::io::Cursor::new(Vec::new());
+        writer.write(&mut tiff, false).unwrap();
+
+        let path =
+            std::env::temp_dir().join(format!("pixelforge_test_{}.jpg", uuid::Uuid::new_v4()));
+        let mut file = std::fs::File::create(&path).unwrap();
+        let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut file, 95);
+        image::ImageEncoder::set_exif_metadata(&mut encoder, tiff.into_inner()).unwrap();
+        let stored = image::RgbImage::from_fn(16, 8, |x, _| {
+            if x < 8 {
+                image::Rgb([255, 0, 0])
+            } else {
+                image::Rgb([0, 0, 255])
+            }
+        });
+        DynamicImage::ImageRgb8(stored)
+            .write_with_encoder(encoder)
+            .unwrap();
+
+        let img = open_image(&path).unwrap().to_rgb8();
+        assert_eq!(img.dimensions(), (8, 16));
+        // The stored left (red) half ends up on top
+        assert!(img.get_pixel(4, 2)[0] > 200);
+        assert!(img.get_pixel(4, 13)[2] > 200);
+
+        let rotated = apply_rotate(path.to_string_lossy().into(), 180).unwrap();
+        assert_eq!(image::open(&rotated).unwrap().width(), 8);
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_file(&rotated).ok();
     }
 }
@@ -0,0 +1,593 @@
+use crate::commands::operations;
+use crate::error::AppError;
+use image::{DynamicImage, RgbaImage};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+// ── Palette quantization ────────────────────────────────────────────
+//
+// Reduces an image to at most 256 colors for indexed PNG and GIF. Colors
+// are compared premultiplied, so fully transparent pixels are one color
+// whatever their RGB. Full transparency always gets its own entry when
+// present, so cut-out edges stay exact.
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum QuantizeMethod {
+    #[default]
+    MedianCut,
+    /// Median cut refined by k-means; slower, with lower error
+    KMeans,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Dither {
+    None,
+    #[default]
+    FloydSteinberg,
+    /// 8×8 Bayer matrix; no error travels, so small edits stay local
+    Ordered,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct QuantizeOptions {
+    /// Palette size, 2–256
+    pub colors: u16,
+    pub method: QuantizeMethod,
+    pub dither: Dither,
+    /// Quantize alpha along with color. Otherwise every pixel ends up opaque
+    /// or fully transparent, the only kind GIF can store.
+    pub alpha_aware: bool,
+}
+
+impl Default for QuantizeOptions {
+    fn default() -> Self {
+        Self {
+            colors: 256,
+            method: QuantizeMethod::MedianCut,
+            dither: Dither::FloydSteinberg,
+            alpha_aware: true,
+        }
+    }
+}
+
+/// A palette image; translucent entries come first, as PNG's tRNS expects.
+pub struct Indexed {
+    pub width: u32,
+    pub height: u32,
+    pub palette: Vec<[u8; 4]>,
+    pub indices: Vec<u8>,
+}
+
+type Color = [f32; 4];
+
+fn premultiply(p: [u8; 4]) -> Color {
+    let a = f32::from(p[3]) / 255.0;
+    [
+        f32::from(p[0]) * a,
+        f32::from(p[1]) * a,
+        f32::from(p[2]) * a,
+        f32::from(p[3]),
+    ]
+}
+
+fn unpremultiply(c: Color) -> [u8; 4] {
+    let a = c[3].round().clamp(0.0, 255.0);
+    if a == 0.0 {
+        return [0; 4];
+    }
+    let channel = |v: f32| (v * 255.0 / a).round().clamp(0.0, 255.0) as u8;
+    [channel(c[0]), channel(c[1]), channel(c[2]), a as u8]
+}
+
+fn distance(a: &Color, b: &Color) -> f32 {
+    (0..4).map(|i| (a[i] - b[i]) * (a[i] - b[i])).sum()
+}
+
+fn nearest(palette: &[Color], c: &Color) -> usize {
+    let mut best = (0, f32::MAX);
+    for (i, p) in palette.iter().enumerate() {
+        let d = distance(p, c);
+        if d < best.1 {
+            best = (i, d);
+        }
+    }
+    best.0
+}
+
+fn weighted_mean(entries: &[(Color, u32)], members: &[usize]) -> Color {
+    let mut sum = [0.0_f64; 4];
+    let mut weight = 0.0_f64;
+    for &i in members {
+        let (c, count) = entries[i];
+        for ch in 0..4 {
+            sum[ch] += f64::from(c[ch]) * f64::from(count);
+        }
+        weight += f64::from(count);
+    }
+    sum.map(|s| (s / weight.max(1.0)) as f32)
+}
+
+/// Split the box with the widest channel at its weighted median until there
+/// are `colors` boxes or none can split.
+fn median_cut(entries: &[(Color, u32)], colors: usize) -> Vec<Color> {
+    let mut boxes: Vec<Vec<usize>> = vec![(0..entries.len()).collect()];
+    while boxes.len() < colors {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| {
+                let (channel, range) = (0..4)
+                    .map(|ch| {
+                        let values = b.iter().map(|&e| entries[e].0[ch]);
+                        let min = values.clone().fold(f32::MAX, f32::min);
+                        let max = values.fold(f32::MIN, f32::max);
+                        (ch, max - min)
+                    })
+                    .max_by(|a, b| a.1.total_cmp(&b.1))
+                    .unwrap();
+                (i, channel, range)
+            })
+            .max_by(|a, b| a.2.total_cmp(&b.2));
+        let Some((index, channel, _)) = widest else {
+            break;
+        };
+
+        let mut members = boxes.swap_remove(index);
+        members.sort_by(|&a, &b| entries[a].0[channel].total_cmp(&entries[b].0[channel]));
+        let total: u64 = members.iter().map(|&e| u64::from(entries[e].1)).sum();
+        let mut running = 0_u64;
+        let mut split = members.len() - 1;
+        for (i, &e) in members.iter().enumerate() {
+            running += u64::from(entries[e].1);
+            if running * 2 >= total {
+                split = i + 1;
+                break;
+            }
+        }
+        let upper = members.split_off(split.clamp(1, members.len() - 1));
+        boxes.push(members);
+        boxes.push(upper);
+    }
+    boxes.iter().map(|b| weighted_mean(entries, b)).collect()
+}
+
+/// Lloyd iterations over the color histogram, starting from `palette`.
+fn k_means(entries: &[(Color, u32)], mut palette: Vec<Color>) -> Vec<Color> {
+    let mut assignments = vec![0_usize; entries.len()];
+    for _ in 0..16 {
+        for (i, (c, _)) in entries.iter().enumerate() {
+            assignments[i] = nearest(&palette, c);
+        }
+        let mut clusters = vec![Vec::new(); palette.len()];
+        for (i, &cluster) in assignments.iter().enumerate() {
+            clusters[cluster].push(i);
+        }
+        let mut moved = 0.0_f32;
+        for (centroid, members) in palette.iter_mut().zip(&clusters) {
+            if members.is_empty() {
+                continue;
+            }
+            let updated = weighted_mean(entries, members);
+            moved = moved.max(distance(centroid, &updated));
+            *centroid = updated;
+        }
+        if moved < 0.25 {
+            break;
+        }
+    }
+    palette
+}
+
+const BAYER_8X8: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+/// Map every pixel to a palette entry, diffusing or patterning the error.
+/// Fully transparent pixels neither take nor pass on error.
+fn remap(pixels: &RgbaImage, palette: &[Color], dither: Dither) -> Vec<u8> {
+    let (width, height) = (pixels.width() as usize, pixels.height() as usize);
+    let mut indices = Vec::with_capacity(width * height);
+    // Step between neighbouring palette colors, roughly
+    let spread = 255.0 / (palette.len() as f32).cbrt();
+    let mut errors = vec![[0.0_f32; 4]; width + 2];
+    let mut next_errors = vec![[0.0_f32; 4]; width + 2];
+    let mut cache: HashMap<[u8; 4], u8> = HashMap::new();
+
+    for y in 0..height {
+        for x in 0..width {
+            let p = pixels.get_pixel(x as u32, y as u32).0;
+            let mut c = premultiply(p);
+            if p[3] > 0 {
+                match dither {
+                    Dither::None => {}
+                    Dither::FloydSteinberg => {
+                        for ch in 0..4 {
+                            c[ch] += errors[x + 1][ch];
+                        }
+                    }
+                    Dither::Ordered => {
+                        let t = (f32::from(BAYER_8X8[y % 8][x % 8]) + 0.5) / 64.0 - 0.5;
+                        let a = c[3] / 255.0;
+                        for v in &mut c[..3] {
+                            *v += t * spread * a;
+                        }
+                    }
+                }
+                c[3] = c[3].clamp(1.0, 255.0);
+                for ch in 0..3 {
+                    c[ch] = c[ch].clamp(0.0, c[3]);
+                }
+            }
+
+            // Dithered colors share a lookup once rounded; the error still
+            // carries the exact difference
+            let key = if dither == Dither::None {
+                p
+            } else {
+                c.map(|v| v.round() as u8)
+            };
+            let index = *cache
+                .entry(key)
+                .or_insert_with(|| nearest(palette, &c) as u8);
+            indices.push(index);
+
+            if dither == Dither::FloydSteinberg && p[3] > 0 {
+                let chosen = palette[usize::from(index)];
+                let error: Color = std::array::from_fn(|ch| c[ch] - chosen[ch]);
+                for ch in 0..4 {
+                    errors[x + 2][ch] += error[ch] * 7.0 / 16.0;
+                    next_errors[x][ch] += error[ch] * 3.0 / 16.0;
+                    next_errors[x + 1][ch] += error[ch] * 5.0 / 16.0;
+                    next_errors[x + 2][ch] += error[ch] / 16.0;
+                }
+            }
+        }
+        std::mem::swap(&mut errors, &mut next_errors);
+        next_errors.iter_mut().for_each(|e| *e = [0.0; 4]);
+    }
+    indices
+}
+
+/// Above this many distinct colors the histogram is bucketed, which keeps
+/// median cut and k-means fast on photos.
+const EXACT_HISTOGRAM_COLORS: usize = 4096;
+
+/// Merge sorted histogram entries into buckets of 5 bits per channel, each
+/// standing in at its members' weighted mean.
+fn bucketed(entries: &[([u8; 4], u32)]) -> Vec<(Color, u32)> {
+    let mut buckets: HashMap<[u8; 4], ([f64; 4], u32)> = HashMap::new();
+    for &(p, count) in entries {
+        let (sum, total) = buckets.entry(p.map(|v| v >> 3)).or_default();
+        let c = premultiply(p);
+        for ch in 0..4 {
+            sum[ch] += f64::from(c[ch]) * f64::from(count);
+        }
+        *total += count;
+    }
+    let mut buckets: Vec<_> = buckets.into_iter().collect();
+    buckets.sort_unstable_by_key(|&(key, _)| key);
+    buckets
+        .into_iter()
+        .map(|(_, (sum, total))| (sum.map(|s| (s / f64::from(total)) as f32), total))
+        .collect()
+}
+
+pub fn quantize(img: &DynamicImage, options: &QuantizeOptions) -> Result<Indexed, AppError> {
+    if !(2..=256).contains(&options.colors) {
+        return Err(AppError::General(format!(
+            "Palette size must be between 2 and 256, not {}",
+            options.colors
+        )));
+    }
+    let mut pixels = img.to_rgba8();
+    for p in pixels.pixels_mut() {
+        if !options.alpha_aware {
+            p[3] = if p[3] >= 128 { 255 } else { 0 };
+        }
+        if p[3] == 0 {
+            p.0 = [0; 4];
+        }
+    }
+
+    let mut histogram: HashMap<[u8; 4], u32> = HashMap::new();
+    for p in pixels.pixels() {
+        *histogram.entry(p.0).or_default() += 1;
+    }
+    let transparent = histogram.remove(&[0; 4]).is_some();
+    let mut entries: Vec<([u8; 4], u32)> = histogram.into_iter().collect();
+    // Deterministic regardless of hashing
+    entries.sort_unstable();
+    let entries = if entries.len() > EXACT_HISTOGRAM_COLORS {
+        bucketed(&entries)
+    } else {
+        entries
+            .into_iter()
+            .map(|(p, count)| (premultiply(p), count))
+            .collect()
+    };
+
+    let budget = usize::from(options.colors) - usize::from(transparent);
+    let mut palette = if entries.is_empty() {
+        Vec::new()
+    } else {
+        median_cut(&entries, budget)
+    };
+    if options.method == QuantizeMethod::KMeans && !entries.is_empty() {
+        palette = k_means(&entries, palette);
+    }
+    let mut palette: Vec<[u8; 4]> = palette.into_iter().map(unpremultiply).collect();
+    if !options.alpha_aware {
+        for p in &mut palette {
+            p[3] = 255;
+        }
+    }
+    if transparent {
+        palette.push([0; 4]);
+    }
+    palette.sort_by_key(|p| p[3] == 255);
+
+    let search: Vec<Color> = palette.iter().map(|&p| premultiply(p)).collect();
+    let indices = remap(&pixels, &search, options.dither);
+    Ok(Indexed {
+        width: pixels.width(),
+        height: pixels.height(),
+        palette,
+        indices,
+    })
+}
+
+impl Indexed {
+    pub fn to_rgba(&self) -> RgbaImage {
+        RgbaImage::from_fn(self.width, self.height, |x, y| {
+            let index = self.indices[(y * self.width + x) as usize];
+            image::Rgba(self.palette[usize::from(index)])
+        })
+    }
+
+    /// 8-bit indexed PNG, with tRNS when any entry is translucent.
+    /// `PLTE` entries and the `tRNS` alphas up to the last translucent one.
+    pub fn png_palette(&self) -> (Vec<u8>, Vec<u8>) {
+        let plte = self
+            .palette
+            .iter()
+            .flat_map(|p| [p[0], p[1], p[2]])
+            .collect();
+        let trns = self
+            .palette
+            .iter()
+            .map(|p| p[3])
+            .take_while(|&a| a < 255)
+            .collect();
+        (plte, trns)
+    }
+
+    pub fn to_png(
+        &self,
+        compression: png::DeflateCompression,
+        filter: png::Filter,
+    ) -> Result<Vec<u8>, AppError> {
+        let failed = |e: png::EncodingError| AppError::SaveFailed(e.to_string());
+        let (plte, trns) = self.png_palette();
+
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, self.width, self.height);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_palette(plte);
+        if !trns.is_empty() {
+            encoder.set_trns(trns);
+        }
+        encoder.set_deflate_compression(compression);
+        encoder.set_filter(filter);
+        let mut writer = encoder.write_header().map_err(failed)?;
+        writer.write_image_data(&self.indices).map_err(failed)?;
+        writer.finish().map_err(failed)?;
+        Ok(bytes)
+    }
+
+    /// A GIF frame with its own palette. GIF keeps one fully transparent
+    /// entry; quantize without `alpha_aware` so there is nothing in between.
+    pub fn gif_frame(&self) -> Result<gif::Frame<'_>, AppError> {
+        let (Ok(width), Ok(height)) = (u16::try_from(self.width), u16::try_from(self.height))
+        else {
+            return Err(AppError::SaveFailed(
+                "GIF dimensions are limited to 65535 pixels".into(),
+            ));
+        };
+        if self.palette.iter().any(|p| p[3] != 0 && p[3] != 255) {
+            return Err(AppError::SaveFailed(
+                "GIF can't store partial transparency".into(),
+            ));
+        }
+        Ok(gif::Frame {
+            width,
+            height,
+            buffer: std::borrow::Cow::Borrowed(&self.indices),
+            transparent: self.palette.iter().position(|p| p[3] == 0).map(|i| i as u8),
+            palette: Some(
+                self.palette
+                    .iter()
+                    .flat_map(|p| [p[0], p[1], p[2]])
+                    .collect(),
+            ),
+            ..gif::Frame::default()
+        })
+    }
+
+    pub fn to_gif(&self) -> Result<Vec<u8>, AppError> {
+        let failed = |e: gif::EncodingError| AppError::SaveFailed(e.to_string());
+        let frame = self.gif_frame()?;
+        let mut bytes = Vec::new();
+        {
+            let mut encoder =
+                gif::Encoder::new(&mut bytes, frame.width, frame.height, &[]).map_err(failed)?;
+            encoder.write_frame(&frame).map_err(failed)?;
+        }
+        Ok(bytes)
+    }
+}
+
+#[tauri::command]
+pub fn quantize_image(path: String, options: Option<QuantizeOptions>) -> Result<String, AppError> {
+    let img = operations::open_image(&path)?;
+    let indexed = quantize(&img, &options.unwrap_or_default())?;
+    operations::save_temp_image(&DynamicImage::ImageRgba8(indexed.to_rgba()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient() -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(48, 32, |x, y| {
+            let alpha = if x < 4 {
+                0
+            } else if x < 8 {
+                128
+            } else {
+                255
+            };
+            image::Rgba([(x * 5) as u8, (y * 8) as u8, 200 - (x * 2) as u8, alpha])
+        }))
+    }
+
+    /// Mean absolute difference of premultiplied samples
+    fn mean_error(a: &RgbaImage, b: &RgbaImage) -> f32 {
+        let sum: f32 = a
+            .pixels()
+            .zip(b.pixels())
+            .map(|(x, y)| {
+                let (x, y) = (premultiply(x.0), premultiply(y.0));
+                (0..4).map(|ch| (x[ch] - y[ch]).abs()).sum::<f32>()
+            })
+            .sum();
+        sum / (a.len() as f32)
+    }
+
+    #[test]
+    fn every_method_and_dither_fits_the_palette() {
+        let source = gradient();
+        let mut undithered = Vec::new();
+        for method in [QuantizeMethod::MedianCut, QuantizeMethod::KMeans] {
+            for dither in [Dither::None, Dither::FloydSteinberg, Dither::Ordered] {
+                let options = QuantizeOptions {
+                    colors: 16,
+                    method,
+                    dither,
+                    ..QuantizeOptions::default()
+                };
+                let indexed = quantize(&source, &options).unwrap();
+                assert!(indexed.palette.len() <= 16, "{method:?} {dither:?}");
+                assert!(indexed
+                    .indices
+                    .iter()
+                    .all(|&i| usize::from(i) < indexed.palette.len()));
+                let out = indexed.to_rgba();
+                let error = mean_error(&source.to_rgba8(), &out);
+                assert!(error < 16.0, "{method:?} {dither:?}: {error}");
+                if dither == Dither::None {
+                    undithered.push(error);
+                }
+                // Full transparency stays exact; half-transparent pixels keep their alpha
+                assert_eq!(out.get_pixel(0, 0)[3], 0);
+                assert!((i32::from(out.get_pixel(5, 0)[3]) - 128).abs() < 16);
+            }
+        }
+        // k-means refines the median cut palette
+        assert!(undithered[1] < undithered[0], "{undithered:?}");
+        assert!(quantize(
+            &source,
+            &QuantizeOptions {
+                colors: 1,
+                ..QuantizeOptions::default()
+            }
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn few_colors_survive_exactly() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(9, 9, |x, y| match (x + y) % 3 {
+            0 => image::Rgba([255, 0, 0, 255]),
+            1 => image::Rgba([0, 0, 255, 255]),
+            _ => image::Rgba([0, 0, 0, 0]),
+        }));
+        let indexed = quantize(&img, &QuantizeOptions::default()).unwrap();
+        assert_eq!(indexed.palette.len(), 3);
+        assert_eq!(indexed.to_rgba(), img.to_rgba8());
+    }
+
+    #[test]
+    fn many_colors_are_bucketed_before_clustering() {
+        let photo = DynamicImage::ImageRgba8(RgbaImage::from_fn(128, 96, |x, y| {
+            let n = (x * 7919 + y * 104_729) % 17;
+            image::Rgba([(x + n) as u8, (y * 2 + n) as u8, (90 + n) as u8, 255])
+        }));
+        let mut entries: Vec<([u8; 4], u32)> = photo
+            .to_rgba8()
+            .pixels()
+            .fold(HashMap::new(), |mut histogram, p| {
+                *histogram.entry(p.0).or_insert(0) += 1;
+                histogram
+            })
+            .into_iter()
+            .collect();
+        entries.sort_unstable();
+        assert!(entries.len() > EXACT_HISTOGRAM_COLORS);
+        let buckets = bucketed(&entries);
+        assert!(buckets.len() < entries.len() / 2);
+        assert_eq!(buckets.iter().map(|b| b.1).sum::<u32>(), 128 * 96);
+
+        let options = QuantizeOptions {
+            colors: 32,
+            method: QuantizeMethod::KMeans,
+            ..QuantizeOptions::default()
+        };
+        let indexed = quantize(&photo, &options).unwrap();
+        let error = mean_error(&photo.to_rgba8(), &indexed.to_rgba());
+        assert!(error < 8.0, "{error}");
+    }
+
+    #[test]
+    fn indexed_png_and_gif_round_trip() {
+        let source = gradient();
+        let indexed = quantize(&source, &QuantizeOptions::default()).unwrap();
+        let png = indexed
+            .to_png(png::DeflateCompression::Level(6), png::Filter::Adaptive)
+            .unwrap();
+        let mut decoder = png::Decoder::new(std::io::Cursor::new(&png));
+        decoder.set_transformations(png::Transformations::IDENTITY);
+        let reader = decoder.read_info().unwrap();
+        assert_eq!(reader.info().color_type, png::ColorType::Indexed);
+        let decoded = image::load_from_memory(&png).unwrap().to_rgba8();
+        assert_eq!(decoded, indexed.to_rgba());
+        assert!(png.len() < source.as_bytes().len() / 2);
+
+        // Translucent pixels can't go to GIF
+        assert!(indexed.to_gif().is_err());
+        let binary = quantize(
+            &source,
+            &QuantizeOptions {
+                alpha_aware: false,
+                ..QuantizeOptions::default()
+            },
+        )
+        .unwrap();
+        let gif = binary.to_gif().unwrap();
+        let decoded = image::load_from_memory(&gif).unwrap().to_rgba8();
+        assert_eq!(decoded, binary.to_rgba());
+        assert_eq!(decoded.get_pixel(5, 0)[3], 255);
+        assert_eq!(decoded.get_pixel(0, 0)[3], 0);
+    }
+}
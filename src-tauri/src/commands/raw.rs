@@ -0,0 +1,2016 @@
+use crate::commands::{large, operations};
+use crate::error::AppError;
+use image::{DynamicImage, ImageBuffer, Rgb};
+use serde::Deserialize;
+use std::path::Path;
+
+// ── Camera RAW ──────────────────────────────────────────────────────
+//
+// TIFF-based RAW containers (DNG, CR2, NEF, ARW) are parsed directly. Sensor
+// data may be uncompressed, lossless JPEG (ITU T.81 process 14, used by DNG
+// and CR2), Nikon's Huffman-coded NEF or Sony's ARW2; the Nikon and Sony
+// decoders follow dcraw. Canon's CR3 is an ISO media file rather than a
+// TIFF and isn't decoded; `is_cr3` spots it so opening one says so.
+//
+// Development: linearize → black/white level → white balance on the mosaic →
+// bilinear demosaic → camera-to-sRGB matrix (DNG only) → exposure → tone
+// curve → 16-bit RGB. Values stay unclipped until the tone curve, so
+// negative exposure can bring back what white balance pushed past white.
+
+pub const RAW_EXTENSIONS: &[&str] = &["dng", "cr2", "nef", "arw"];
+
+/// Whether `bytes` start a Canon CR3, which can't be developed.
+pub fn is_cr3(bytes: &[u8]) -> bool {
+    bytes.get(4..12) == Some(b"ftypcrx ")
+}
+
+/// Display name for a RAW extension from `RAW_EXTENSIONS`.
+pub fn format_name(ext: &str) -> &'static str {
+    match ext {
+        "dng" => "DNG",
+        "cr2" => "CR2",
+        "nef" => "NEF",
+        _ => "ARW",
+    }
+}
+
+/// Identify a RAW file from its leading bytes. DNG and CR2 have their own
+/// markers; NEF and ARW are plain TIFFs told apart by extension.
+pub fn sniff(bytes: &[u8], ext: &str) -> Option<&'static str> {
+    let tiff = Tiff::new(bytes).ok()?;
+    if bytes.get(8..10) == Some(b"CR") {
+        return Some("CR2");
+    }
+    let is_dng = tiff
+        .ifd(tiff.u32(4)? as usize)
+        .is_some_and(|(ifd0, _)| find(&ifd0, TAG_DNG_VERSION).is_some());
+    match ext {
+        _ if is_dng => Some("DNG"),
+        "nef" => Some("NEF"),
+        "arw" => Some("ARW"),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RawWhiteBalance {
+    /// Camera's recorded white balance, or gray world when it isn't readable
+    #[default]
+    AsShot,
+    /// Gray-world estimate from the sensor data
+    Auto,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RawOptions {
+    /// Exposure compensation in stops, applied to linear sensor values
+    pub exposure: f32,
+    pub white_balance: RawWhiteBalance,
+    /// Extra multipliers on the red and blue channels, on top of `white_balance`
+    pub red_gain: f32,
+    pub blue_gain: f32,
+    /// Film-like contrast curve on top of the sRGB transfer function
+    pub tone_curve: bool,
+}
+
+impl Default for RawOptions {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            white_balance: RawWhiteBalance::AsShot,
+            red_gain: 1.0,
+            blue_gain: 1.0,
+            tone_curve: true,
+        }
+    }
+}
+
+// ── TIFF structure ──────────────────────────────────────────────────
+
+const TAG_NEW_SUBFILE_TYPE: u16 = 254;
+const TAG_WIDTH: u16 = 256;
+const TAG_HEIGHT: u16 = 257;
+const TAG_BITS_PER_SAMPLE: u16 = 258;
+const TAG_COMPRESSION: u16 = 259;
+const TAG_PHOTOMETRIC: u16 = 262;
+const TAG_STRIP_OFFSETS: u16 = 273;
+const TAG_ORIENTATION: u16 = 274;
+const TAG_SAMPLES_PER_PIXEL: u16 = 277;
+const TAG_ROWS_PER_STRIP: u16 = 278;
+const TAG_STRIP_BYTE_COUNTS: u16 = 279;
+const TAG_TILE_WIDTH: u16 = 322;
+const TAG_TILE_LENGTH: u16 = 323;
+const TAG_TILE_OFFSETS: u16 = 324;
+const TAG_TILE_BYTE_COUNTS: u16 = 325;
+const TAG_SUB_IFDS: u16 = 330;
+const TAG_CFA_REPEAT_DIM: u16 = 33421;
+const TAG_CFA_PATTERN: u16 = 33422;
+const TAG_EXIF_IFD: u16 = 34665;
+const TAG_MAKER_NOTE: u16 = 37500;
+const TAG_DNG_VERSION: u16 = 50706;
+const TAG_LINEARIZATION: u16 = 50712;
+const TAG_BLACK_LEVEL: u16 = 50714;
+const TAG_WHITE_LEVEL: u16 = 50717;
+const TAG_COLOR_MATRIX_1: u16 = 50721;
+const TAG_COLOR_MATRIX_2: u16 = 50722;
+const TAG_AS_SHOT_NEUTRAL: u16 = 50728;
+const TAG_CR2_SLICES: u16 = 50752;
+const TAG_SONY_CURVE: u16 = 28688;
+const TAG_ACTIVE_AREA: u16 = 50829;
+
+const NIKON_WB_LEVELS: u16 = 0x000C;
+const NIKON_BLACK_LEVEL: u16 = 0x003D;
+const NIKON_DECODE_TABLE: u16 = 0x0096;
+
+const PHOTOMETRIC_CFA: u32 = 32803;
+const PHOTOMETRIC_LINEAR_RAW: u32 = 34892;
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    tag: u16,
+    kind: u16,
+    count: usize,
+    /// Offset of the value, inline or out of line
+    at: usize,
+}
+
+type Ifd = Vec<Entry>;
+
+struct Tiff<'a> {
+    bytes: &'a [u8],
+    little_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(bytes: &'a [u8]) -> Result<Self, AppError> {
+        let little_endian = match bytes.get(..4) {
+            Some(b"II*\0") => true,
+            Some(b"MM\0*") => false,
+            _ => {
+                return Err(AppError::UnsupportedFormat(
+                    "Not a TIFF-based RAW file".into(),
+                ))
+            }
+        };
+        Ok(Self {
+            bytes,
+            little_endian,
+        })
+    }
+
+    fn u16(&self, at: usize) -> Option<u16> {
+        let b: [u8; 2] = self.bytes.get(at..at + 2)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        })
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        let b: [u8; 4] = self.bytes.get(at..at + 4)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    }
+
+    /// Entries of the IFD at `offset` and the offset of the next one.
+    fn ifd(&self, offset: usize) -> Option<(Ifd, usize)> {
+        let count = self.u16(offset)? as usize;
+        let mut entries = Vec::with_capacity(count);
+        for i in 0..count {
+            let entry = offset + 2 + i * 12;
+            let kind = self.u16(entry + 2)?;
+            let count = self.u32(entry + 4)? as usize;
+            let size = count.saturating_mul(type_size(kind));
+            let at = if size <= 4 {
+                entry + 8
+            } else {
+                self.u32(entry + 8)? as usize
+            };
+            entries.push(Entry {
+                tag: self.u16(entry)?,
+                kind,
+                count,
+                at,
+            });
+        }
+        let next = self.u32(offset + 2 + count * 12)? as usize;
+        Some((entries, next))
+    }
+
+    /// Every IFD reachable from the header: the main chain plus SubIFDs.
+    fn all_ifds(&self) -> Vec<Ifd> {
+        let mut out = Vec::new();
+        let mut pending: Vec<(usize, u8)> = Vec::new();
+        let mut offset = self.u32(4).unwrap_or(0) as usize;
+        while offset != 0 && out.len() < 16 {
+            let Some((ifd, next)) = self.ifd(offset) else {
+                break;
+            };
+            for e in ifd.iter().filter(|e| e.tag == TAG_SUB_IFDS) {
+                pending.extend(self.uints(e).into_iter().map(|o| (o as usize, 1)));
+            }
+            out.push(ifd);
+            offset = next;
+        }
+        while let Some((offset, depth)) = pending.pop() {
+            if out.len() >= 32 {
+                break;
+            }
+            let Some((ifd, _)) = self.ifd(offset) else {
+                continue;
+            };
+            if depth < 2 {
+                for e in ifd.iter().filter(|e| e.tag == TAG_SUB_IFDS) {
+                    pending.extend(self.uints(e).into_iter().map(|o| (o as usize, depth + 1)));
+                }
+            }
+            out.push(ifd);
+        }
+        out
+    }
+
+    fn values(&self, e: &Entry) -> Vec<f64> {
+        let size = type_size(e.kind);
+        let count = e
+            .count
+            .min(self.bytes.len().saturating_sub(e.at) / size.max(1));
+        (0..count)
+            .filter_map(|i| {
+                let at = e.at + i * size;
+                Some(match e.kind {
+                    1 | 7 => *self.bytes.get(at)? as f64,
+                    6 => *self.bytes.get(at)? as i8 as f64,
+                    3 => self.u16(at)? as f64,
+                    8 => self.u16(at)? as i16 as f64,
+                    4 | 13 => self.u32(at)? as f64,
+                    9 => self.u32(at)? as i32 as f64,
+                    5 => {
+                        let den = self.u32(at + 4)?;
+                        self.u32(at)? as f64 / den.max(1) as f64
+                    }
+                    10 => {
+                        let den = self.u32(at + 4)? as i32;
+                        self.u32(at)? as i32 as f64 / if den == 0 { 1.0 } else { den as f64 }
+                    }
+                    11 => f32::from_bits(self.u32(at)?) as f64,
+                    _ => return None,
+                })
+            })
+            .collect()
+    }
+
+    fn uints(&self, e: &Entry) -> Vec<u32> {
+        self.values(e).into_iter().map(|v| v as u32).collect()
+    }
+
+    fn get(&self, ifd: &Ifd, tag: u16) -> Vec<f64> {
+        find(ifd, tag).map(|e| self.values(e)).unwrap_or_default()
+    }
+
+    fn uint(&self, ifd: &Ifd, tag: u16) -> Option<u32> {
+        self.get(ifd, tag).first().map(|&v| v as u32)
+    }
+}
+
+fn type_size(kind: u16) -> usize {
+    match kind {
+        3 | 8 => 2,
+        4 | 9 | 11 | 13 => 4,
+        5 | 10 | 12 => 8,
+        _ => 1,
+    }
+}
+
+fn find(ifd: &Ifd, tag: u16) -> Option<&Entry> {
+    ifd.iter().find(|e| e.tag == tag)
+}
+
+// ── Lossless JPEG ───────────────────────────────────────────────────
+
+struct Huffman {
+    /// (max code, index of first symbol minus min code) per code length
+    max_code: [i32; 17],
+    offset: [i32; 17],
+    symbols: Vec<u8>,
+}
+
+impl Huffman {
+    fn new(counts: &[u8; 16], symbols: Vec<u8>) -> Self {
+        let mut max_code = [-1; 17];
+        let mut offset = [0; 17];
+        let mut code = 0i32;
+        let mut index = 0i32;
+        for len in 1..=16 {
+            let n = counts[len - 1] as i32;
+            if n > 0 {
+                offset[len] = index - code;
+                code += n;
+                index += n;
+                max_code[len] = code - 1;
+            }
+            code <<= 1;
+        }
+        Self {
+            max_code,
+            offset,
+            symbols,
+        }
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    /// JPEG entropy data: 0xFF00 is a stuffed 0xFF and other 0xFF starts a marker
+    jpeg: bool,
+    pos: usize,
+    acc: u64,
+    bits: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8], jpeg: bool) -> Self {
+        Self {
+            data,
+            jpeg,
+            pos: 0,
+            acc: 0,
+            bits: 0,
+        }
+    }
+
+    fn fill(&mut self) {
+        while self.bits <= 56 {
+            let byte = match self.data.get(self.pos) {
+                Some(&b) if !self.jpeg => {
+                    self.pos += 1;
+                    b
+                }
+                Some(0xFF) if self.data.get(self.pos + 1) == Some(&0) => {
+                    self.pos += 2;
+                    0xFF
+                }
+                // A marker ends the entropy-coded data; pad with zeros
+                Some(0xFF) | None => 0,
+                Some(&b) => {
+                    self.pos += 1;
+                    b
+                }
+            };
+            self.acc |= (byte as u64) << (56 - self.bits);
+            self.bits += 8;
+        }
+    }
+
+    fn bits(&mut self, n: u32) -> u32 {
+        if n == 0 {
+            return 0;
+        }
+        self.fill();
+        let v = (self.acc >> (64 - n)) as u32;
+        self.acc <<= n;
+        self.bits -= n;
+        v
+    }
+
+    fn symbol(&mut self, table: &Huffman) -> Result<u8, AppError> {
+        let mut code = 0i32;
+        for len in 1..=16 {
+            code = (code << 1) | self.bits(1) as i32;
+            if code <= table.max_code[len] {
+                let index = (code + table.offset[len]) as usize;
+                return table
+                    .symbols
+                    .get(index)
+                    .copied()
+                    .ok_or_else(|| AppError::ImageDecode("Corrupt lossless JPEG".into()));
+            }
+        }
+        Err(AppError::ImageDecode("Corrupt lossless JPEG".into()))
+    }
+
+    /// Skip to the byte after the next RSTn marker.
+    fn restart(&mut self) {
+        self.acc = 0;
+        self.bits = 0;
+        while self.pos + 1 < self.data.len() {
+            if self.data[self.pos] == 0xFF && (0xD0..=0xD7).contains(&self.data[self.pos + 1]) {
+                self.pos += 2;
+                return;
+            }
+            self.pos += 1;
+        }
+    }
+}
+
+/// Decoded lossless JPEG: `width` samples per row (frame width × components).
+struct Ljpeg {
+    width: usize,
+    height: usize,
+    samples: Vec<u16>,
+}
+
+fn decode_ljpeg(data: &[u8]) -> Result<Ljpeg, AppError> {
+    let corrupt = || AppError::ImageDecode("Corrupt lossless JPEG".into());
+    if data.get(..2) != Some(&[0xFF, 0xD8]) {
+        return Err(corrupt());
+    }
+    let mut tables: [Option<Huffman>; 4] = [None, None, None, None];
+    let mut frame: Option<(u32, usize, usize, Vec<u8>)> = None;
+    let mut restart_interval = 0usize;
+    let mut pos = 2;
+    loop {
+        while data.get(pos) == Some(&0xFF) && data.get(pos + 1) == Some(&0xFF) {
+            pos += 1;
+        }
+        if data.get(pos) != Some(&0xFF) {
+            return Err(corrupt());
+        }
+        let marker = *data.get(pos + 1).ok_or_else(corrupt)?;
+        let len = u16::from_be_bytes([
+            *data.get(pos + 2).ok_or_else(corrupt)?,
+            *data.get(pos + 3).ok_or_else(corrupt)?,
+        ]) as usize;
+        let segment = data.get(pos + 4..pos + 2 + len).ok_or_else(corrupt)?;
+        match marker {
+            0xC4 => {
+                let mut s = segment;
+                while s.len() >= 17 {
+                    let id = (s[0] & 0x03) as usize;
+                    let counts: [u8; 16] = s[1..17].try_into().unwrap();
+                    let n: usize = counts.iter().map(|&c| c as usize).sum();
+                    let symbols = s.get(17..17 + n).ok_or_else(corrupt)?.to_vec();
+                    tables[id] = Some(Huffman::new(&counts, symbols));
+                    s = &s[17 + n..];
+                }
+            }
+            0xC3 => {
+                let sof = segment.get(..6).ok_or_else(corrupt)?;
+                let precision = sof[0] as u32;
+                let height = u16::from_be_bytes([sof[1], sof[2]]) as usize;
+                let width = u16::from_be_bytes([sof[3], sof[4]]) as usize;
+                let comps = sof[5] as usize;
+                let ids = (0..comps)
+                    .map(|i| segment.get(6 + i * 3).copied().ok_or_else(corrupt))
+                    .collect::<Result<Vec<_>, _>>()?;
+                frame = Some((precision, width, height, ids));
+            }
+            0xC0..=0xCF => {
+                return Err(AppError::UnsupportedFormat(
+                    "Only lossless JPEG RAW data is supported".into(),
+                ))
+            }
+            0xDD => {
+                let dri = segment.get(..2).ok_or_else(corrupt)?;
+                restart_interval = u16::from_be_bytes([dri[0], dri[1]]) as usize;
+            }
+            0xDA => {
+                let (precision, width, height, ids) = frame.take().ok_or_else(corrupt)?;
+                let comps = *segment.first().ok_or_else(corrupt)? as usize;
+                let mut comp_tables = Vec::with_capacity(comps);
+                for i in 0..comps {
+                    let id = *segment.get(1 + i * 2).ok_or_else(corrupt)?;
+                    if !ids.contains(&id) {
+                        return Err(corrupt());
+                    }
+                    let td = (segment.get(2 + i * 2).ok_or_else(corrupt)? >> 4) as usize & 0x03;
+                    comp_tables.push(tables[td].as_ref().ok_or_else(corrupt)?);
+                }
+                let predictor = *segment.get(1 + comps * 2).ok_or_else(corrupt)?;
+                let transform = segment.get(3 + comps * 2).ok_or_else(corrupt)? & 0x0F;
+                if !(2..=16).contains(&precision) || u32::from(transform) >= precision {
+                    return Err(corrupt());
+                }
+                let entropy = &data[pos + 2 + len..];
+                return decode_scan(
+                    entropy,
+                    &comp_tables,
+                    ScanParams {
+                        width,
+                        height,
+                        precision,
+                        predictor,
+                        transform,
+                        restart_interval,
+                    },
+                );
+            }
+            0xD9 => return Err(corrupt()),
+            _ => {}
+        }
+        pos += 2 + len;
+    }
+}
+
+struct ScanParams {
+    width: usize,
+    height: usize,
+    precision: u32,
+    predictor: u8,
+    transform: u8,
+    restart_interval: usize,
+}
+
+fn decode_scan(data: &[u8], tables: &[&Huffman], p: ScanParams) -> Result<Ljpeg, AppError> {
+    let comps = tables.len();
+    let row_len = p.width * comps;
+    let mut samples = vec![0u16; sample_count(row_len, p.height, 1)?];
+    let mut reader = BitReader::new(data, true);
+    let initial = 1i32 << (p.precision - u32::from(p.transform) - 1);
+    let mut first_row = 0;
+    let mut mcus = 0;
+    for y in 0..p.height {
+        for x in 0..p.width {
+            if p.restart_interval > 0 && mcus > 0 && mcus % p.restart_interval == 0 {
+                reader.restart();
+                first_row = y;
+                if x != 0 {
+                    // Restarts mid-row aren't used by any camera we read
+                    return Err(AppError::ImageDecode("Unsupported restart layout".into()));
+                }
+            }
+            mcus += 1;
+            for (c, table) in tables.iter().enumerate() {
+                let ssss = reader.symbol(table)? as u32;
+                let diff = match ssss {
+                    17.. => return Err(AppError::ImageDecode("Corrupt lossless JPEG".into())),
+                    0 => 0,
+                    16 => 32768,
+                    n => {
+                        let v = reader.bits(n) as i32;
+                        if v < 1 << (n - 1) {
+                            v - (1 << n) + 1
+                        } else {
+                            v
+                        }
+                    }
+                };
+                let at = y * row_len + x * comps + c;
+                let left = || samples[at - comps] as i32;
+                let up = || samples[at - row_len] as i32;
+                let pred = if y == first_row {
+                    if x == 0 {
+                        initial
+                    } else {
+                        left()
+                    }
+                } else if x == 0 {
+                    up()
+                } else {
+                    let (a, b, c) = (left(), up(), samples[at - row_len - comps] as i32);
+                    match p.predictor {
+                        1 => a,
+                        2 => b,
+                        3 => c,
+                        4 => a + b - c,
+                        5 => a + ((b - c) >> 1),
+                        6 => b + ((a - c) >> 1),
+                        7 => (a + b) >> 1,
+                        _ => a,
+                    }
+                };
+                samples[at] = (pred + diff) as u16;
+            }
+        }
+    }
+    if p.transform > 0 {
+        for s in &mut samples {
+            *s <<= p.transform;
+        }
+    }
+    Ok(Ljpeg {
+        width: row_len,
+        height: p.height,
+        samples,
+    })
+}
+
+// ── Sensor data ─────────────────────────────────────────────────────
+
+/// Undeveloped sensor data with everything needed to develop it.
+struct RawImage {
+    width: usize,
+    height: usize,
+    /// 1 for a colour filter array, 3 for linear (already demosaiced) DNG
+    channels: usize,
+    data: Vec<u16>,
+    /// Colour (0 = R, 1 = G, 2 = B) at each position of the 2×2 pattern
+    cfa: [[usize; 2]; 2],
+    black: f32,
+    white: f32,
+    /// As-shot white balance multipliers
+    wb: Option<[f32; 3]>,
+    /// DNG colour matrix, XYZ → camera
+    cam_xyz: Option<[[f32; 3]; 3]>,
+    orientation: u8,
+}
+
+fn unsupported(what: &str) -> AppError {
+    AppError::UnsupportedFormat(format!("{what} RAW files are not supported"))
+}
+
+/// `width × height × channels`, once it's known not to overflow or go over
+/// the memory budget as 16-bit samples.
+fn sample_count(width: usize, height: usize, channels: usize) -> Result<usize, AppError> {
+    let count = width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(channels))
+        .ok_or_else(|| AppError::ImageDecode("Corrupt RAW image size".into()))?;
+    large::check_budget(
+        count as u64 * 2,
+        &format!("Reading this {width}×{height} RAW image"),
+    )?;
+    Ok(count)
+}
+
+/// Copy strips or tiles of `ifd` into a `width × height × channels` buffer.
+fn read_image_data(
+    tiff: &Tiff,
+    ifd: &Ifd,
+    slices: Option<&[u32]>,
+) -> Result<(usize, usize, usize, Vec<u16>), AppError> {
+    let corrupt = || AppError::ImageDecode("Corrupt RAW image data".into());
+    let (width, height) = stored_size(tiff, ifd).ok_or_else(corrupt)?;
+    let bits = tiff
+        .uint(ifd, TAG_BITS_PER_SAMPLE)
+        .unwrap_or(16)
+        .clamp(1, 16);
+    let channels = tiff.uint(ifd, TAG_SAMPLES_PER_PIXEL).unwrap_or(1) as usize;
+    let compression = tiff.uint(ifd, TAG_COMPRESSION).unwrap_or(1);
+    if width == 0 || height == 0 || !(1..=4).contains(&channels) {
+        return Err(corrupt());
+    }
+
+    let (seg_w, seg_h, offsets, counts) =
+        match (find(ifd, TAG_TILE_OFFSETS), find(ifd, TAG_TILE_BYTE_COUNTS)) {
+            (Some(o), Some(c)) => (
+                tiff.uint(ifd, TAG_TILE_WIDTH).ok_or_else(corrupt)? as usize,
+                tiff.uint(ifd, TAG_TILE_LENGTH).ok_or_else(corrupt)? as usize,
+                tiff.uints(o),
+                tiff.uints(c),
+            ),
+            _ => (
+                width,
+                tiff.uint(ifd, TAG_ROWS_PER_STRIP)
+                    .map_or(height, |r| (r as usize).min(height)),
+                tiff.get(ifd, TAG_STRIP_OFFSETS)
+                    .into_iter()
+                    .map(|v| v as u32)
+                    .collect(),
+                tiff.get(ifd, TAG_STRIP_BYTE_COUNTS)
+                    .into_iter()
+                    .map(|v| v as u32)
+                    .collect(),
+            ),
+        };
+    if seg_w == 0 || seg_h == 0 || offsets.is_empty() {
+        return Err(corrupt());
+    }
+    sample_count(seg_w, seg_h, channels)?;
+
+    let row_len = width * channels;
+    let mut data = vec![0u16; sample_count(width, height, channels)?];
+    let across = width.div_ceil(seg_w);
+    for (i, (&offset, &count)) in offsets.iter().zip(&counts).enumerate() {
+        let bytes = tiff
+            .bytes
+            .get(offset as usize..offset as usize + count as usize)
+            .ok_or_else(corrupt)?;
+        let (x0, y0) = ((i % across) * seg_w, (i / across) * seg_h);
+        let seg_len = seg_w * channels;
+        let samples = match compression {
+            1 => unpack(bytes, bits, seg_len, seg_h, tiff.little_endian),
+            7 | 6 => decode_ljpeg(bytes)?.samples,
+            other => {
+                return Err(AppError::UnsupportedFormat(format!(
+                    "RAW compression {other}"
+                )))
+            }
+        };
+
+        if let Some(&[n, slice_w, last_w]) = slices {
+            // CR2 stores the sensor as vertical slices, each decoded top to bottom
+            let (n, slice_w, last_w) = (n as usize, slice_w as usize, last_w as usize);
+            if slice_w == 0 || last_w == 0 || n * slice_w + last_w != width {
+                return Err(corrupt());
+            }
+            for (j, &v) in samples.iter().enumerate().take(width * height) {
+                let slice = (j / (slice_w * height)).min(n);
+                let w = if slice == n { last_w } else { slice_w };
+                let j = j - slice * slice_w * height;
+                data[(j / w) * width + slice * slice_w + j % w] = v;
+            }
+            continue;
+        }
+
+        for (j, &v) in samples.iter().enumerate() {
+            let (x, y) = (x0 * channels + j % seg_len, y0 + j / seg_len);
+            if x < row_len && y < height {
+                data[y * row_len + x] = v;
+            }
+        }
+    }
+    Ok((width, height, channels, data))
+}
+
+/// Stored size of `ifd`'s image: from its tags, or for CR2's sensor IFD,
+/// which has none, from the lossless JPEG frame header.
+fn stored_size(tiff: &Tiff, ifd: &Ifd) -> Option<(usize, usize)> {
+    if let (Some(w), Some(h)) = (tiff.uint(ifd, TAG_WIDTH), tiff.uint(ifd, TAG_HEIGHT)) {
+        return Some((w as usize, h as usize));
+    }
+    let offset = tiff.uint(ifd, TAG_STRIP_OFFSETS)? as usize;
+    let data = tiff.bytes.get(offset..)?;
+    let mut pos = 2;
+    while data.get(pos) == Some(&0xFF) {
+        let marker = *data.get(pos + 1)?;
+        let len = u16::from_be_bytes([*data.get(pos + 2)?, *data.get(pos + 3)?]) as usize;
+        if marker == 0xC3 {
+            // Precision, height, width, components
+            let sof = data.get(pos + 4..pos + 10)?;
+            let height = u16::from_be_bytes([sof[1], sof[2]]) as usize;
+            let width = u16::from_be_bytes([sof[3], sof[4]]) as usize;
+            return Some((width * sof[5] as usize, height));
+        }
+        pos += 2 + len;
+    }
+    None
+}
+
+/// Uncompressed samples: 8 or 16 bits in file byte order, anything else
+/// packed MSB-first with each row starting on a byte boundary.
+fn unpack(bytes: &[u8], bits: u32, row_len: usize, rows: usize, little_endian: bool) -> Vec<u16> {
+    if bits == 8 {
+        return bytes.iter().map(|&b| b as u16).collect();
+    }
+    // Cameras often store 12- or 14-bit data in 16-bit words
+    if bits == 16 || bytes.len() >= row_len * rows * 2 {
+        return bytes
+            .chunks_exact(2)
+            .map(|b| {
+                if little_endian {
+                    u16::from_le_bytes([b[0], b[1]])
+                } else {
+                    u16::from_be_bytes([b[0], b[1]])
+                }
+            })
+            .collect();
+    }
+    let row_bytes = (row_len * bits as usize).div_ceil(8);
+    let mut out = Vec::with_capacity(row_len * rows);
+    for row in bytes.chunks(row_bytes).take(rows) {
+        let mut reader = BitReader::new(row, false);
+        for _ in 0..row_len {
+            out.push(reader.bits(bits) as u16);
+        }
+    }
+    out
+}
+
+fn cfa_pattern(tiff: &Tiff, ifd: &Ifd) -> Result<[[usize; 2]; 2], AppError> {
+    let dims = tiff.get(ifd, TAG_CFA_REPEAT_DIM);
+    let pattern = tiff.get(ifd, TAG_CFA_PATTERN);
+    if pattern.is_empty() {
+        return Ok([[0, 1], [1, 2]]);
+    }
+    if (!dims.is_empty() && dims != [2.0, 2.0])
+        || pattern.len() != 4
+        || pattern.iter().any(|&c| c > 2.0)
+    {
+        return Err(unsupported("Non-Bayer"));
+    }
+    Ok([
+        [pattern[0] as usize, pattern[1] as usize],
+        [pattern[2] as usize, pattern[3] as usize],
+    ])
+}
+
+/// Canon maker note: sensor crop and as-shot white balance.
+struct CanonInfo {
+    crop: Option<[usize; 4]>,
+    wb: Option<[f32; 3]>,
+}
+
+fn canon_info(tiff: &Tiff, ifd0: &Ifd) -> CanonInfo {
+    let mut info = CanonInfo {
+        crop: None,
+        wb: None,
+    };
+    let Some(note) = tiff
+        .uint(ifd0, TAG_EXIF_IFD)
+        .and_then(|o| tiff.ifd(o as usize))
+        .and_then(|(exif, _)| find(&exif, TAG_MAKER_NOTE).copied())
+        .and_then(|e| tiff.ifd(e.at))
+        .map(|(note, _)| note)
+    else {
+        return info;
+    };
+
+    // SensorInfo: 1 width, 2 height, 5..=8 crop left/top/right/bottom
+    let sensor = tiff.get(&note, 0x00E0);
+    if sensor.len() > 8 {
+        let [l, t, r, b] = [sensor[5], sensor[6], sensor[7], sensor[8]].map(|v| v as usize);
+        if r > l && b > t {
+            info.crop = Some([l, t, r + 1, b + 1]);
+        }
+    }
+
+    // ColorData: the as-shot RGGB levels sit at a version-dependent index
+    if let Some(e) = find(&note, 0x4001) {
+        let index = match e.count {
+            582 => 25,
+            653 => 34,
+            5120 => 71,
+            1816 | 1820 | 1824 => 71,
+            2024 | 3656 => 85,
+            3973 | 3778 => 105,
+            _ => 63,
+        };
+        let levels = tiff.get(&note, 0x4001);
+        if let Some(l) = levels.get(index..index + 4) {
+            let g = (l[1] + l[2]) / 2.0;
+            if l[0] > 0.0 && l[3] > 0.0 {
+                info.wb = Some([(g / l[0]) as f32, 1.0, (g / l[3]) as f32]);
+            }
+        }
+    }
+    info
+}
+
+/// Nikon maker note: a TIFF of its own after a 10-byte `Nikon\0` header,
+/// with offsets counted from that TIFF.
+fn nikon_note<'a>(tiff: &Tiff<'a>, ifd0: &Ifd) -> Option<(Tiff<'a>, Ifd)> {
+    let entry = tiff
+        .uint(ifd0, TAG_EXIF_IFD)
+        .and_then(|o| tiff.ifd(o as usize))
+        .and_then(|(exif, _)| find(&exif, TAG_MAKER_NOTE).copied())?;
+    let note = tiff
+        .bytes
+        .get(entry.at..entry.at.checked_add(entry.count)?)?;
+    if !note.starts_with(b"Nikon\0") {
+        return None;
+    }
+    let inner = Tiff::new(note.get(10..)?).ok()?;
+    let (ifd, _) = inner.ifd(inner.u32(4)? as usize)?;
+    Some((inner, ifd))
+}
+
+/// Huffman tables of compressed NEF as (code counts per length, symbols).
+/// A symbol's low nibble is the difference's bit length and its high
+/// nibble the low bits dropped by lossy compression.
+const NIKON_TREES: [([u8; 16], &[u8]); 6] = [
+    // 12-bit lossy
+    (
+        [0, 1, 5, 1, 1, 1, 1, 1, 1, 2, 0, 0, 0, 0, 0, 0],
+        &[5, 4, 3, 6, 2, 7, 1, 0, 8, 9, 11, 10, 12, 0],
+    ),
+    // 12-bit lossy after split
+    (
+        [0, 1, 5, 1, 1, 1, 1, 1, 1, 2, 0, 0, 0, 0, 0, 0],
+        &[0x39, 0x5a, 0x38, 0x27, 0x16, 5, 4, 3, 2, 1, 0, 11, 12, 12],
+    ),
+    // 12-bit lossless
+    (
+        [0, 1, 4, 2, 3, 1, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        &[5, 4, 6, 3, 7, 2, 8, 1, 9, 0, 10, 11, 12],
+    ),
+    // 14-bit lossy
+    (
+        [0, 1, 4, 3, 1, 1, 1, 1, 1, 2, 0, 0, 0, 0, 0, 0],
+        &[5, 6, 4, 7, 8, 3, 9, 2, 1, 0, 10, 11, 12, 13, 14],
+    ),
+    // 14-bit lossy after split
+    (
+        [0, 1, 5, 1, 1, 1, 1, 1, 1, 1, 2, 0, 0, 0, 0, 0],
+        &[8, 0x5c, 0x4b, 0x3a, 0x29, 7, 6, 5, 4, 3, 2, 1, 0, 13, 14],
+    ),
+    // 14-bit lossless
+    (
+        [0, 1, 4, 2, 2, 3, 1, 2, 0, 0, 0, 0, 0, 0, 0, 0],
+        &[7, 6, 8, 5, 9, 4, 10, 3, 11, 12, 2, 0, 1, 13, 14],
+    ),
+];
+
+/// Nikon's compressed NEF (compression 34713): Huffman-coded differences
+/// from per-column-parity predictors, mapped through the linearization
+/// curve in the maker note. Returns the samples and the white level.
+fn decode_nef(
+    data: &[u8],
+    note: &Tiff,
+    note_ifd: &Ifd,
+    width: usize,
+    height: usize,
+    bits: u32,
+) -> Result<(Vec<u16>, f32), AppError> {
+    let corrupt = || AppError::ImageDecode("Corrupt NEF image data".into());
+    let meta = find(note_ifd, NIKON_DECODE_TABLE)
+        .ok_or_else(|| unsupported("NEF files without a decoding table"))?
+        .at;
+    let byte = |at: usize| note.bytes.get(at).copied().ok_or_else(corrupt);
+    let short = |at: usize| note.u16(at).ok_or_else(corrupt);
+    let (ver0, ver1) = (byte(meta)?, byte(meta + 1)?);
+    let mut pos = meta + 2;
+    if ver0 == 0x49 || ver1 == 0x58 {
+        pos += 2110;
+    }
+    let tree = if ver0 == 0x46 { 2 } else { 0 } + if bits == 14 { 3 } else { 0 };
+    let mut vpred = [[0u16; 2]; 2];
+    for (i, v) in vpred.iter_mut().flatten().enumerate() {
+        *v = short(pos + i * 2)?;
+    }
+    let csize = short(pos + 8)? as usize;
+    pos += 10;
+
+    let mut curve: Vec<u16> = (0..=u16::MAX).collect();
+    let mut max = (1usize << bits) & 0x7fff;
+    let mut split = 0;
+    let step = if csize > 1 { max / (csize - 1) } else { 0 };
+    if ver0 == 0x44 && ver1 == 0x20 && step > 0 {
+        // Lossy type 2: a sparse curve, linearly interpolated
+        for i in 0..csize {
+            curve[i * step] = short(pos + i * 2)?;
+        }
+        for i in 0..max {
+            let (knot, frac) = (i - i % step, i % step);
+            curve[i] = ((curve[knot] as usize * (step - frac) + curve[knot + step] as usize * frac)
+                / step) as u16;
+        }
+        split = short(meta + 562)? as usize;
+    } else if ver0 != 0x46 && csize <= 0x4001 {
+        for (i, v) in curve.iter_mut().take(csize).enumerate() {
+            *v = short(pos + i * 2)?;
+        }
+        max = csize;
+    }
+    while max > 2 && curve[max - 2] == curve[max - 1] {
+        max -= 1;
+    }
+    let white = curve[max.max(1) - 1] as f32;
+
+    let table = |t: usize| Huffman::new(&NIKON_TREES[t].0, NIKON_TREES[t].1.to_vec());
+    let mut huff = table(tree);
+    let mut reader = BitReader::new(data, false);
+    let mut samples = Vec::with_capacity(sample_count(width, height, 1)?);
+    let mut hpred = [0u16; 2];
+    for row in 0..height {
+        if split > 0 && row == split {
+            huff = table(tree + 1);
+        }
+        for col in 0..width {
+            let symbol = reader.symbol(&huff)?;
+            let (len, shl) = ((symbol & 15) as u32, (symbol >> 4) as u32);
+            let diff = if len == 0 {
+                0
+            } else {
+                let v = reader.bits(len.checked_sub(shl).ok_or_else(corrupt)?) as i32;
+                let diff = (((v << 1) + 1) << shl) >> 1;
+                if diff & (1 << (len - 1)) == 0 {
+                    diff - ((1 << len) - i32::from(shl == 0))
+                } else {
+                    diff
+                }
+            };
+            if col < 2 {
+                let v = &mut vpred[row & 1][col];
+                *v = v.wrapping_add(diff as u16);
+                hpred[col] = *v;
+            } else {
+                hpred[col & 1] = hpred[col & 1].wrapping_add(diff as u16);
+            }
+            samples.push(curve[(hpred[col & 1] as i16).clamp(0, 0x3fff) as usize]);
+        }
+    }
+    Ok((samples, white))
+}
+
+/// Sony's ARW2 (compression 32767, one byte per pixel): each 16-byte block
+/// holds 16 same-colour pixels two columns apart as a maximum, a minimum,
+/// their positions and 7-bit steps between them, all through the file's
+/// tone curve. Returns 12-bit samples.
+fn decode_arw2(data: &[u8], width: usize, height: usize, curve: &[u32]) -> Vec<u16> {
+    let mut samples = vec![0u16; width * height];
+    for (row, line) in data.chunks(width).take(height).enumerate() {
+        let mut col = 0;
+        for block in line.chunks(16) {
+            if col + 30 >= width {
+                break;
+            }
+            let at = |i: usize| block.get(i).copied().unwrap_or(0);
+            let head = u32::from_le_bytes([at(0), at(1), at(2), at(3)]);
+            let max = head & 0x7ff;
+            let min = (head >> 11) & 0x7ff;
+            let (imax, imin) = ((head >> 22) & 15, (head >> 26) & 15);
+            let mut shift = 0;
+            while shift < 4 && 0x80 << shift <= max.saturating_sub(min) {
+                shift += 1;
+            }
+            let mut bit = 30;
+            for i in 0..16 {
+                let pixel = if i == imax {
+                    max
+                } else if i == imin {
+                    min
+                } else {
+                    let word = u16::from_le_bytes([at(bit >> 3), at((bit >> 3) + 1)]) as u32;
+                    let step = (word >> (bit & 7)) & 0x7f;
+                    bit += 7;
+                    ((step << shift) + min).min(0x7ff)
+                };
+                samples[row * width + col] = (curve[pixel as usize * 2] >> 2) as u16;
+                col += 2;
+            }
+            // Even columns of 32, then the odd ones
+            col -= if col & 1 == 1 { 1 } else { 31 };
+        }
+    }
+    samples
+}
+
+/// Sony's tone curve from its four knots: slope 1, 2, 4, 8 then 16 between
+/// them, over 12-bit input.
+fn sony_curve(knots: &[f64]) -> Vec<u32> {
+    let mut curve: Vec<u32> = (0..0x1000).collect();
+    if let Some(knots) = knots.get(..4) {
+        let mut ends = [0usize, 0, 0, 0, 0, 0xfff];
+        for (end, &k) in ends[1..5].iter_mut().zip(knots) {
+            *end = (k as usize >> 2) & 0xfff;
+        }
+        for i in 0..5 {
+            for j in ends[i] + 1..=ends[i + 1] {
+                curve[j] = curve[j - 1] + (1 << i);
+            }
+        }
+    }
+    curve
+}
+
+/// The sensor IFD of a RAW file and the area of it that develops into the
+/// image, found from tags without decoding anything.
+struct RawLayout<'a> {
+    tiff: Tiff<'a>,
+    ifd0: Ifd,
+    raw_ifd: Ifd,
+    is_dng: bool,
+    /// Stored width and height of the sensor data
+    size: (usize, usize),
+    /// Left, top, right, bottom
+    crop: Option<[usize; 4]>,
+    canon: Option<CanonInfo>,
+}
+
+impl RawLayout<'_> {
+    /// Width and height after cropping, before orientation.
+    fn output_size(&self) -> (usize, usize) {
+        let (width, height) = self.size;
+        match self.crop {
+            Some([left, top, right, bottom])
+                if left < right.min(width) && top < bottom.min(height) =>
+            {
+                (right.min(width) - left, bottom.min(height) - top)
+            }
+            _ => (width, height),
+        }
+    }
+
+    fn orientation(&self) -> u8 {
+        self.tiff.uint(&self.ifd0, TAG_ORIENTATION).unwrap_or(1) as u8
+    }
+}
+
+fn raw_layout(bytes: &[u8]) -> Result<RawLayout<'_>, AppError> {
+    let tiff = Tiff::new(bytes)?;
+    let mut ifds = tiff.all_ifds();
+    if ifds.is_empty() {
+        return Err(unsupported("Empty"));
+    }
+    let is_cr2 = bytes.get(8..10) == Some(b"CR");
+    let is_dng = find(&ifds[0], TAG_DNG_VERSION).is_some();
+
+    let raw_index = if is_cr2 {
+        (ifds.len() > 3)
+            .then_some(3)
+            .ok_or_else(|| unsupported("This CR2 variant"))?
+    } else {
+        (0..ifds.len())
+            .filter(|&i| {
+                matches!(
+                    tiff.uint(&ifds[i], TAG_PHOTOMETRIC),
+                    Some(PHOTOMETRIC_CFA | PHOTOMETRIC_LINEAR_RAW)
+                ) && tiff.uint(&ifds[i], TAG_NEW_SUBFILE_TYPE).unwrap_or(0) & 1 == 0
+            })
+            .max_by_key(|&i| {
+                tiff.uint(&ifds[i], TAG_WIDTH).unwrap_or(0) as u64
+                    * tiff.uint(&ifds[i], TAG_HEIGHT).unwrap_or(0) as u64
+            })
+            .ok_or_else(|| unsupported("RAW files without sensor data"))?
+    };
+    let raw_ifd = ifds.swap_remove(raw_index);
+    let ifd0 = if raw_index == 0 {
+        raw_ifd.clone()
+    } else {
+        ifds.swap_remove(0)
+    };
+    let size = stored_size(&tiff, &raw_ifd)
+        .ok_or_else(|| AppError::ImageDecode("Corrupt RAW image data".into()))?;
+
+    let canon = is_cr2.then(|| canon_info(&tiff, &ifd0));
+    let crop = canon.as_ref().and_then(|c| c.crop).or_else(|| {
+        tiff.get(&raw_ifd, TAG_ACTIVE_AREA)
+            .get(..4)
+            .map(|a| [a[1] as usize, a[0] as usize, a[3] as usize, a[2] as usize])
+    });
+    Ok(RawLayout {
+        tiff,
+        ifd0,
+        raw_ifd,
+        is_dng,
+        size,
+        crop,
+        canon,
+    })
+}
+
+fn read_raw(bytes: &[u8]) -> Result<RawImage, AppError> {
+    let layout = raw_layout(bytes)?;
+    let RawLayout {
+        tiff,
+        ifd0,
+        raw_ifd,
+        ..
+    } = &layout;
+    let corrupt = || AppError::ImageDecode("Corrupt RAW image data".into());
+    let mut bits = tiff
+        .uint(raw_ifd, TAG_BITS_PER_SAMPLE)
+        .unwrap_or(16)
+        .min(16);
+    let mut white = tiff.uint(raw_ifd, TAG_WHITE_LEVEL).map(|w| w as f32);
+    let mut black = tiff.get(raw_ifd, TAG_BLACK_LEVEL);
+    let mut wb = None;
+
+    let (mut width, mut height) = layout.size;
+    let strip = || {
+        let offset = tiff.uint(raw_ifd, TAG_STRIP_OFFSETS).ok_or_else(corrupt)? as usize;
+        let count: f64 = tiff.get(raw_ifd, TAG_STRIP_BYTE_COUNTS).iter().sum();
+        tiff.bytes
+            .get(offset..(offset + count as usize).min(tiff.bytes.len()))
+            .ok_or_else(corrupt)
+    };
+    let (channels, mut data) = match tiff.uint(raw_ifd, TAG_COMPRESSION).unwrap_or(1) {
+        34713 => {
+            let (note, note_ifd) = nikon_note(tiff, ifd0)
+                .ok_or_else(|| unsupported("NEF files without a maker note"))?;
+            let (samples, curve_white) =
+                decode_nef(strip()?, &note, &note_ifd, width, height, bits)?;
+            white = white.or(Some(curve_white));
+            // Given at 14 bits whatever the file's depth
+            let levels = note.get(&note_ifd, NIKON_BLACK_LEVEL);
+            if black.is_empty() && !levels.is_empty() {
+                let mean = levels.iter().sum::<f64>() / levels.len() as f64;
+                black = vec![mean / f64::from(1 << (14 - bits.min(14)))];
+            }
+            let levels = note.get(&note_ifd, NIKON_WB_LEVELS);
+            if let [r, b, ..] = levels[..] {
+                if r > 0.0 && b > 0.0 {
+                    wb = Some([r as f32, 1.0, b as f32]);
+                }
+            }
+            (1, samples)
+        }
+        32767 => {
+            let data = strip()?;
+            let pixels = sample_count(width, height, 1)?;
+            if data.len() == pixels {
+                let knots = tiff
+                    .all_ifds()
+                    .iter()
+                    .map(|ifd| tiff.get(ifd, TAG_SONY_CURVE))
+                    .find(|k| k.len() >= 4)
+                    .unwrap_or_default();
+                let curve = sony_curve(&knots);
+                bits = 12;
+                white = white.or(Some((curve[0xffe] >> 2) as f32));
+                (1, decode_arw2(data, width, height, &curve))
+            } else if data.len() / 2 == pixels && data.len() % 2 == 0 {
+                bits = 14;
+                (1, unpack(data, 16, width, height, tiff.little_endian))
+            } else {
+                return Err(unsupported("Sony ARW 1.0"));
+            }
+        }
+        _ => {
+            let slices: Vec<u32> = find(raw_ifd, TAG_CR2_SLICES)
+                .map(|e| tiff.uints(e))
+                .unwrap_or_default();
+            let (w, h, channels, data) = read_image_data(
+                tiff,
+                raw_ifd,
+                (slices.len() == 3 && slices[0] > 0).then_some(&slices[..]),
+            )?;
+            (width, height) = (w, h);
+            (channels, data)
+        }
+    };
+    let mut cfa = cfa_pattern(tiff, raw_ifd)?;
+
+    let linearization = tiff.get(raw_ifd, TAG_LINEARIZATION);
+    if !linearization.is_empty() {
+        let last = linearization.len() - 1;
+        for v in &mut data {
+            *v = linearization[(*v as usize).min(last)] as u16;
+        }
+    }
+
+    if layout.is_dng {
+        let neutral = tiff.get(raw_ifd, TAG_AS_SHOT_NEUTRAL);
+        let neutral = if neutral.is_empty() {
+            tiff.get(ifd0, TAG_AS_SHOT_NEUTRAL)
+        } else {
+            neutral
+        };
+        if neutral.len() == 3 && neutral.iter().all(|&n| n > 0.0) {
+            wb = Some([
+                (neutral[1] / neutral[0]) as f32,
+                1.0,
+                (neutral[1] / neutral[2]) as f32,
+            ]);
+        }
+    } else if let Some(canon) = &layout.canon {
+        wb = canon.wb;
+        if let Some([left, top, _, _]) = canon.crop {
+            // Masked columns left of the active area give the black level
+            if left > 4 {
+                let (sum, n) = (0..height)
+                    .flat_map(|y| (2..left - 2).map(move |x| y * width + x))
+                    .filter_map(|i| data.get(i))
+                    .fold((0u64, 0u64), |(s, n), &v| (s + v as u64, n + 1));
+                if let Some(mean) = sum.checked_div(n) {
+                    black = vec![mean as f64];
+                }
+            }
+            // The CFA pattern is anchored at the sensor origin
+            cfa = [
+                [cfa[top % 2][left % 2], cfa[top % 2][(left + 1) % 2]],
+                [
+                    cfa[(top + 1) % 2][left % 2],
+                    cfa[(top + 1) % 2][(left + 1) % 2],
+                ],
+            ];
+        }
+    } else if tiff.uint(raw_ifd, TAG_COMPRESSION) == Some(32767) && black.is_empty() {
+        // Sony's black level sits in encrypted maker data; dcraw's constant
+        black = vec![f64::from(128u32 << (bits - 12))];
+    }
+
+    let (out_w, out_h) = layout.output_size();
+    if let (Some([left, top, ..]), true) = (layout.crop, (out_w, out_h) != (width, height)) {
+        let row_len = width * channels;
+        data = (top..top + out_h)
+            .flat_map(|y| {
+                let start = y * row_len + left * channels;
+                data[start..start + out_w * channels].iter().copied()
+            })
+            .collect();
+        (width, height) = (out_w, out_h);
+    }
+
+    let black = if black.is_empty() {
+        0.0
+    } else {
+        (black.iter().sum::<f64>() / black.len() as f64) as f32
+    };
+    let mut white = white.unwrap_or(((1u32 << bits) - 1) as f32);
+    if white <= black {
+        white = black + 1.0;
+    }
+
+    let matrix = tiff.get(ifd0, TAG_COLOR_MATRIX_2);
+    let matrix = if matrix.len() == 9 {
+        matrix
+    } else {
+        tiff.get(ifd0, TAG_COLOR_MATRIX_1)
+    };
+    let cam_xyz = (matrix.len() == 9).then(|| {
+        let m = |i: usize| matrix[i] as f32;
+        [[m(0), m(1), m(2)], [m(3), m(4), m(5)], [m(6), m(7), m(8)]]
+    });
+
+    Ok(RawImage {
+        width,
+        height,
+        channels,
+        data,
+        cfa,
+        black,
+        white,
+        wb,
+        cam_xyz,
+        orientation: layout.orientation(),
+    })
+}
+
+// ── Development ─────────────────────────────────────────────────────
+
+/// sRGB (D65) → XYZ
+const XYZ_FROM_SRGB: [[f32; 3]; 3] = [
+    [0.412_453, 0.357_580, 0.180_423],
+    [0.212_671, 0.715_160, 0.072_169],
+    [0.019_334, 0.119_193, 0.950_227],
+];
+
+fn mat_mul(a: &[[f32; 3]; 3], b: &[[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    let mut out = [[0.0; 3]; 3];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, v) in row.iter_mut().enumerate() {
+            *v = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    out
+}
+
+fn mat_inverse(m: &[[f32; 3]; 3]) -> Option<[[f32; 3]; 3]> {
+    let det = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+    if det.abs() < 1e-9 {
+        return None;
+    }
+    let c =
+        |r0: usize, c0: usize, r1: usize, c1: usize| m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0];
+    Some([
+        [
+            c(1, 1, 2, 2) / det,
+            -c(0, 1, 2, 2) / det,
+            c(0, 1, 1, 2) / det,
+        ],
+        [
+            -c(1, 0, 2, 2) / det,
+            c(0, 0, 2, 2) / det,
+            -c(0, 0, 1, 2) / det,
+        ],
+        [
+            c(1, 0, 2, 1) / det,
+            -c(0, 0, 2, 1) / det,
+            c(0, 0, 1, 1) / det,
+        ],
+    ])
+}
+
+/// White-balanced camera RGB → linear sRGB, with rows of the camera-from-sRGB
+/// matrix normalized so white stays white (the dcraw convention).
+fn srgb_from_camera(cam_xyz: &[[f32; 3]; 3]) -> Option<[[f32; 3]; 3]> {
+    let mut cam_rgb = mat_mul(cam_xyz, &XYZ_FROM_SRGB);
+    for row in &mut cam_rgb {
+        let sum: f32 = row.iter().sum();
+        if sum.abs() < 1e-6 {
+            return None;
+        }
+        row.iter_mut().for_each(|v| *v /= sum);
+    }
+    mat_inverse(&cam_rgb)
+}
+
+/// Gray-world multipliers from unclipped sensor values.
+fn gray_world(raw: &RawImage) -> [f32; 3] {
+    let range = raw.white - raw.black;
+    let mut sum = [0f64; 3];
+    let mut count = [0u64; 3];
+    for y in (0..raw.height).step_by(2) {
+        for x in (0..raw.width).step_by(2) {
+            for (dy, dx) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+                let (x, y) = (x + dx, y + dy);
+                if x >= raw.width || y >= raw.height {
+                    continue;
+                }
+                for ch in 0..raw.channels {
+                    let v = (raw.data[(y * raw.width + x) * raw.channels + ch] as f32 - raw.black)
+                        / range;
+                    if v > 0.0 && v < 0.95 {
+                        let color = if raw.channels == 1 {
+                            raw.cfa[y % 2][x % 2]
+                        } else {
+                            ch
+                        };
+                        sum[color] += v as f64;
+                        count[color] += 1;
+                    }
+                }
+            }
+        }
+    }
+    let mean = |c: usize| (sum[c] / count[c].max(1) as f64).max(1e-6) as f32;
+    [mean(1) / mean(0), 1.0, mean(1) / mean(2)]
+}
+
+fn tone(v: f32, curve: bool) -> f32 {
+    let v = operations::linear_to_srgb(v.clamp(0.0, 1.0));
+    if curve {
+        // Gentle S-curve: more midtone contrast, black and white stay fixed
+        v + 0.25 * (v * v * (3.0 - 2.0 * v) - v)
+    } else {
+        v
+    }
+}
+
+fn develop(raw: &RawImage, options: &RawOptions) -> DynamicImage {
+    let mut mult = match options.white_balance {
+        RawWhiteBalance::AsShot => raw.wb.unwrap_or_else(|| gray_world(raw)),
+        RawWhiteBalance::Auto => gray_world(raw),
+    };
+    mult[0] *= options.red_gain.max(0.01);
+    mult[2] *= options.blue_gain.max(0.01);
+    // Scale so the weakest channel is 1 and clipping happens at the same
+    // level in every channel, which keeps blown highlights neutral
+    let min = mult.iter().copied().fold(f32::MAX, f32::min);
+    mult.iter_mut().for_each(|m| *m /= min);
+
+    let range = raw.white - raw.black;
+    let (w, h) = (raw.width, raw.height);
+    let scaled: Vec<f32> = raw
+        .data
+        .iter()
+        .enumerate()
+        .map(|(i, &v)| {
+            let color = if raw.channels == 1 {
+                let (x, y) = (i % w, i / w);
+                raw.cfa[y % 2][x % 2]
+            } else {
+                i % raw.channels
+            };
+            // Unclipped: values over 1 come back under negative exposure
+            (v as f32 - raw.black) / range * mult[color]
+        })
+        .collect();
+
+    let matrix = raw.cam_xyz.as_ref().and_then(srgb_from_camera);
+    let gain = 2f32.powf(options.exposure);
+    let mut out = ImageBuffer::<Rgb<u16>, Vec<u16>>::new(w as u32, h as u32);
+    for (x, y, px) in out.enumerate_pixels_mut() {
+        let (x, y) = (x as usize, y as usize);
+        let mut rgb = if raw.channels >= 3 {
+            let i = (y * w + x) * raw.channels;
+            [scaled[i], scaled[i + 1], scaled[i + 2]]
+        } else {
+            demosaic_at(&scaled, raw, x, y)
+        };
+        if let Some(m) = &matrix {
+            rgb = [0, 1, 2].map(|i| m[i][0] * rgb[0] + m[i][1] * rgb[1] + m[i][2] * rgb[2]);
+        }
+        *px = Rgb(rgb.map(|v| (tone(v * gain, options.tone_curve) * 65535.0).round() as u16));
+    }
+
+    let mut img = DynamicImage::ImageRgb16(out);
+    if let Some(orientation) = image::metadata::Orientation::from_exif(raw.orientation) {
+        img.apply_orientation(orientation);
+    }
+    img
+}
+
+/// Bilinear demosaic: each missing colour is the mean of the neighbours in
+/// the 3×3 window that carry it.
+fn demosaic_at(scaled: &[f32], raw: &RawImage, x: usize, y: usize) -> [f32; 3] {
+    let (w, h) = (raw.width, raw.height);
+    let own = raw.cfa[y % 2][x % 2];
+    let mut sum = [0f32; 3];
+    let mut count = [0u32; 3];
+    for ny in y.saturating_sub(1)..(y + 2).min(h) {
+        for nx in x.saturating_sub(1)..(x + 2).min(w) {
+            let color = raw.cfa[ny % 2][nx % 2];
+            if color != own {
+                sum[color] += scaled[ny * w + nx];
+                count[color] += 1;
+            }
+        }
+    }
+    let mut rgb = [0f32; 3];
+    for c in 0..3 {
+        rgb[c] = if c == own {
+            scaled[y * w + x]
+        } else {
+            sum[c] / count[c].max(1) as f32
+        };
+    }
+    rgb
+}
+
+/// Peak bytes per sensor pixel while developing: the 16-bit mosaic, its
+/// scaled float copy and the 16-bit RGB result.
+const DEVELOP_BYTES_PER_PIXEL: u64 = 12;
+
+/// Decode and develop a RAW file into a 16-bit RGB working image.
+pub fn develop_file(path: &Path, options: &RawOptions) -> Result<DynamicImage, AppError> {
+    let bytes = std::fs::read(path)?;
+    let (width, height) = raw_layout(&bytes)?.size;
+    large::check_budget(
+        (width as u64)
+            .saturating_mul(height as u64)
+            .saturating_mul(DEVELOP_BYTES_PER_PIXEL),
+        &format!("Developing this {width}×{height} RAW image"),
+    )?;
+    let raw = read_raw(&bytes)?;
+    Ok(develop(&raw, options))
+}
+
+/// Displayed dimensions of a RAW file, after cropping and orientation, read
+/// from its tags without decoding the sensor data.
+pub fn dimensions(path: &Path) -> Result<(u32, u32), AppError> {
+    let bytes = std::fs::read(path)?;
+    let layout = raw_layout(&bytes)?;
+    let (w, h) = layout.output_size();
+    let (w, h) = (w as u32, h as u32);
+    Ok(if (5..=8).contains(&layout.orientation()) {
+        (h, w)
+    } else {
+        (w, h)
+    })
+}
+
+/// Re-develop a RAW file with exposure and white balance applied to the
+/// sensor data, producing the working image for the operation pipeline.
+#[tauri::command]
+pub fn develop_raw(path: String, options: Option<RawOptions>) -> Result<String, AppError> {
+    let img = develop_file(Path::new(&path), &options.unwrap_or_default())?;
+    operations::save_temp_image(&img)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    enum Value {
+        Short(Vec<u16>),
+        Long(Vec<u32>),
+        Byte(Vec<u8>),
+        Rational(Vec<(u32, u32)>),
+    }
+
+    /// Little-endian single-IFD TIFF with `data` as its only strip.
+    fn build_tiff(mut entries: Vec<(u16, Value)>, data: &[u8]) -> Vec<u8> {
+        entries.push((TAG_STRIP_OFFSETS, Value::Long(vec![8])));
+        entries.push((TAG_STRIP_BYTE_COUNTS, Value::Long(vec![data.len() as u32])));
+        entries.sort_by_key(|(tag, _)| *tag);
+
+        let mut out = b"II*\0".to_vec();
+        let ifd = (8 + data.len()).next_multiple_of(2);
+        out.extend_from_slice(&(ifd as u32).to_le_bytes());
+        out.extend_from_slice(data);
+        out.resize(ifd, 0);
+
+        let mut extra = Vec::new();
+        let extra_start = ifd + 2 + entries.len() * 12 + 4;
+        out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        for (tag, value) in &entries {
+            let (kind, count, bytes): (u16, usize, Vec<u8>) = match value {
+                Value::Short(v) => (3, v.len(), v.iter().flat_map(|x| x.to_le_bytes()).collect()),
+                Value::Long(v) => (4, v.len(), v.iter().flat_map(|x| x.to_le_bytes()).collect()),
+                Value::Byte(v) => (1, v.len(), v.clone()),
+                Value::Rational(v) => (
+                    5,
+                    v.len(),
+                    v.iter()
+                        .flat_map(|(n, d)| [n.to_le_bytes(), d.to_le_bytes()].concat())
+                        .collect(),
+                ),
+            };
+            out.extend_from_slice(&tag.to_le_bytes());
+            out.extend_from_slice(&kind.to_le_bytes());
+            out.extend_from_slice(&(count as u32).to_le_bytes());
+            if bytes.len() <= 4 {
+                let mut inline = bytes.clone();
+                inline.resize(4, 0);
+                out.extend_from_slice(&inline);
+            } else {
+                out.extend_from_slice(&((extra_start + extra.len()) as u32).to_le_bytes());
+                extra.extend_from_slice(&bytes);
+            }
+        }
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&extra);
+        out
+    }
+
+    /// Minimal lossless JPEG encoder (predictor 1, one 5-bit-code table).
+    fn encode_ljpeg(width: usize, height: usize, comps: usize, samples: &[u16]) -> Vec<u8> {
+        let mut out = vec![0xFF, 0xD8];
+        let mut dht = vec![0x00];
+        let mut counts = [0u8; 16];
+        counts[4] = 17;
+        dht.extend_from_slice(&counts);
+        dht.extend(0..=16u8);
+        out.extend_from_slice(&[0xFF, 0xC4]);
+        out.extend_from_slice(&(dht.len() as u16 + 2).to_be_bytes());
+        out.extend_from_slice(&dht);
+
+        let mut sof = vec![12];
+        sof.extend_from_slice(&(height as u16).to_be_bytes());
+        sof.extend_from_slice(&(width as u16).to_be_bytes());
+        sof.push(comps as u8);
+        for c in 0..comps {
+            sof.extend_from_slice(&[c as u8 + 1, 0x11, 0]);
+        }
+        out.extend_from_slice(&[0xFF, 0xC3]);
+        out.extend_from_slice(&(sof.len() as u16 + 2).to_be_bytes());
+        out.extend_from_slice(&sof);
+
+        let mut sos = vec![comps as u8];
+        for c in 0..comps {
+            sos.extend_from_slice(&[c as u8 + 1, 0x00]);
+        }
+        sos.extend_from_slice(&[1, 0, 0]);
+        out.extend_from_slice(&[0xFF, 0xDA]);
+        out.extend_from_slice(&(sos.len() as u16 + 2).to_be_bytes());
+        out.extend_from_slice(&sos);
+
+        let mut bits: Vec<bool> = Vec::new();
+        let mut put = |value: u32, n: u32| {
+            for i in (0..n).rev() {
+                bits.push(value >> i & 1 == 1);
+            }
+        };
+        let row = width * comps;
+        for (i, &s) in samples.iter().enumerate() {
+            let (x, y) = ((i % row) / comps, i / row);
+            let pred = match (x, y) {
+                (0, 0) => 1 << 11,
+                (0, _) => samples[i - row] as i32,
+                _ => samples[i - comps] as i32,
+            };
+            let diff = s as i32 - pred;
+            let ssss = 32 - diff.unsigned_abs().leading_zeros();
+            put(ssss, 5);
+            let extra = if diff < 0 { diff - 1 } else { diff };
+            put(extra as u32 & ((1 << ssss) - 1), ssss);
+        }
+        while !bits.len().is_multiple_of(8) {
+            bits.push(true);
+        }
+        for byte in bits.chunks(8) {
+            let b = byte.iter().fold(0u8, |acc, &bit| acc << 1 | bit as u8);
+            out.push(b);
+            if b == 0xFF {
+                out.push(0);
+            }
+        }
+        out.extend_from_slice(&[0xFF, 0xD9]);
+        out
+    }
+
+    #[test]
+    fn lossless_jpeg_roundtrips() {
+        let samples: Vec<u16> = (0..6 * 5 * 2).map(|i| ((i * 997) % 4096) as u16).collect();
+        let decoded = decode_ljpeg(&encode_ljpeg(6, 5, 2, &samples)).unwrap();
+        assert_eq!((decoded.width, decoded.height), (12, 5));
+        assert_eq!(decoded.samples, samples);
+    }
+
+    #[test]
+    fn malformed_lossless_jpeg_is_an_error() {
+        let valid = encode_ljpeg(6, 5, 1, &[100; 30]);
+        let at = |marker: u8| valid.windows(2).position(|w| w == [0xFF, marker]).unwrap();
+        let mut short_scan = valid[..at(0xDA)].to_vec();
+        short_scan.extend_from_slice(&[0xFF, 0xDA, 0, 4, 1, 1]);
+        let mut deep = valid.clone();
+        deep[at(0xC3) + 4] = 40;
+        for data in [
+            vec![0xFF, 0xD8, 0xFF, 0xC3, 0, 3, 12],
+            vec![0xFF, 0xD8, 0xFF, 0xDD, 0, 2],
+            short_scan,
+            deep,
+        ] {
+            assert!(decode_ljpeg(&data).is_err());
+        }
+    }
+
+    /// 4×4 RGGB mosaic of a grey scene under light that halves red.
+    fn grey_dng(compression: u16) -> Vec<u8> {
+        let (black, level) = (64u16, 2000u16);
+        let mosaic: Vec<u16> = (0..16)
+            .map(|i| match ((i / 4) % 2, i % 2) {
+                (0, 0) => black + level / 2,
+                (1, 1) => black + level * 3 / 4,
+                _ => black + level,
+            })
+            .collect();
+        let data = match compression {
+            7 => encode_ljpeg(4, 4, 1, &mosaic),
+            _ => mosaic.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        };
+        build_tiff(
+            vec![
+                (TAG_NEW_SUBFILE_TYPE, Value::Long(vec![0])),
+                (TAG_WIDTH, Value::Long(vec![4])),
+                (TAG_HEIGHT, Value::Long(vec![4])),
+                (TAG_BITS_PER_SAMPLE, Value::Short(vec![16])),
+                (TAG_COMPRESSION, Value::Short(vec![compression])),
+                (TAG_PHOTOMETRIC, Value::Short(vec![PHOTOMETRIC_CFA as u16])),
+                (TAG_ORIENTATION, Value::Short(vec![6])),
+                (TAG_ROWS_PER_STRIP, Value::Long(vec![4])),
+                (TAG_CFA_REPEAT_DIM, Value::Short(vec![2, 2])),
+                (TAG_CFA_PATTERN, Value::Byte(vec![0, 1, 1, 2])),
+                (TAG_DNG_VERSION, Value::Byte(vec![1, 4, 0, 0])),
+                (TAG_BLACK_LEVEL, Value::Short(vec![black])),
+                (TAG_WHITE_LEVEL, Value::Short(vec![4095])),
+                (
+                    TAG_AS_SHOT_NEUTRAL,
+                    Value::Rational(vec![(1, 2), (1, 1), (3, 4)]),
+                ),
+            ],
+            &data,
+        )
+    }
+
+    #[test]
+    fn develops_dng_with_as_shot_white_balance() {
+        for compression in [1, 7] {
+            let raw = read_raw(&grey_dng(compression)).unwrap();
+            assert_eq!((raw.width, raw.height, raw.black), (4, 4, 64.0));
+
+            let img = develop(&raw, &RawOptions::default());
+            assert_eq!((img.width(), img.height()), (4, 4));
+            let rgb = img.as_rgb16().unwrap();
+            for px in rgb.pixels() {
+                let [r, g, b] = px.0.map(i32::from);
+                assert!((r - g).abs() < 300 && (b - g).abs() < 300, "{:?}", px);
+            }
+
+            let darker = develop(
+                &raw,
+                &RawOptions {
+                    exposure: -1.0,
+                    ..RawOptions::default()
+                },
+            );
+            let mid = |img: &DynamicImage| img.as_rgb16().unwrap().get_pixel(1, 1).0[1];
+            assert!(mid(&darker) < mid(&img));
+        }
+    }
+
+    #[test]
+    fn opens_raw_through_open_image() {
+        let path =
+            std::env::temp_dir().join(format!("pixelforge_raw_{}.dng", uuid::Uuid::new_v4()));
+        std::fs::write(&path, grey_dng(1)).unwrap();
+        assert_eq!(sniff(&grey_dng(1), "tif"), Some("DNG"));
+        assert_eq!(dimensions(&path).unwrap(), (4, 4));
+        let img = operations::open_image(&path).unwrap();
+        assert_eq!(img.color(), image::ColorType::Rgb16);
+        std::fs::remove_file(&path).ok();
+    }
+    #[test]
+    fn develop_checks_the_memory_budget_from_tags() {
+        let bytes = build_tiff(
+            vec![
+                (TAG_WIDTH, Value::Long(vec![60_000])),
+                (TAG_HEIGHT, Value::Long(vec![40_000])),
+                (TAG_BITS_PER_SAMPLE, Value::Short(vec![16])),
+                (TAG_PHOTOMETRIC, Value::Short(vec![PHOTOMETRIC_CFA as u16])),
+                (TAG_DNG_VERSION, Value::Byte(vec![1, 4, 0, 0])),
+            ],
+            &[0; 16],
+        );
+        let path =
+            std::env::temp_dir().join(format!("pixelforge_raw_{}.dng", uuid::Uuid::new_v4()));
+        std::fs::write(&path, &bytes).unwrap();
+        let err = develop_file(&path, &RawOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("memory budget"), "{err}");
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn dimensions_come_from_tags_without_decoding() {
+        let bytes = build_tiff(
+            vec![
+                (TAG_WIDTH, Value::Long(vec![4])),
+                (TAG_HEIGHT, Value::Long(vec![4])),
+                (TAG_BITS_PER_SAMPLE, Value::Short(vec![16])),
+                (TAG_COMPRESSION, Value::Short(vec![7])),
+                (TAG_PHOTOMETRIC, Value::Short(vec![PHOTOMETRIC_CFA as u16])),
+                (TAG_ORIENTATION, Value::Short(vec![6])),
+                (TAG_DNG_VERSION, Value::Byte(vec![1, 4, 0, 0])),
+                (TAG_ACTIVE_AREA, Value::Long(vec![1, 0, 3, 4])),
+            ],
+            &[0xFF; 4],
+        );
+        let path =
+            std::env::temp_dir().join(format!("pixelforge_raw_{}.dng", uuid::Uuid::new_v4()));
+        std::fs::write(&path, &bytes).unwrap();
+        assert_eq!(dimensions(&path).unwrap(), (2, 4));
+        assert!(read_raw(&bytes).is_err());
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn negative_exposure_recovers_values_pushed_past_white() {
+        let raw = RawImage {
+            width: 1,
+            height: 1,
+            channels: 3,
+            data: vec![3000, 3000, 3000],
+            cfa: [[0, 1], [1, 2]],
+            black: 0.0,
+            white: 4000.0,
+            wb: Some([2.0, 1.0, 1.0]),
+            cam_xyz: None,
+            orientation: 1,
+        };
+        let img = develop(
+            &raw,
+            &RawOptions {
+                exposure: -1.0,
+                tone_curve: false,
+                ..RawOptions::default()
+            },
+        );
+        let [r, g, _] = img.as_rgb16().unwrap().get_pixel(0, 0).0;
+        let expect = |v: f32| (operations::linear_to_srgb(v) * 65535.0).round() as u16;
+        assert!(r.abs_diff(expect(0.75)) <= 1, "{r}");
+        assert!(g.abs_diff(expect(0.375)) <= 1, "{g}");
+    }
+
+    #[test]
+    fn decodes_sony_arw2() {
+        let (width, height) = (32, 2);
+        let pixels: Vec<u32> = (0..width * height)
+            .map(|i| 600 + ((i * 37) % 100) as u32)
+            .collect();
+        // Each block: 16 same-colour pixels, two columns apart
+        let mut data = Vec::new();
+        for row in 0..height {
+            for parity in 0..2 {
+                let block: Vec<u32> = (0..16)
+                    .map(|i| pixels[row * width + parity + i * 2])
+                    .collect();
+                let (imax, &max) = block.iter().enumerate().max_by_key(|p| p.1).unwrap();
+                let (imin, &min) = block
+                    .iter()
+                    .enumerate()
+                    .filter(|&(i, _)| i != imax)
+                    .min_by_key(|p| p.1)
+                    .unwrap();
+                let mut bits =
+                    (max | min << 11 | (imax as u32) << 22 | (imin as u32) << 26) as u128;
+                let mut at = 30;
+                for (i, &v) in block.iter().enumerate() {
+                    if i != imax && i != imin {
+                        bits |= ((v - min) as u128) << at;
+                        at += 7;
+                    }
+                }
+                data.extend_from_slice(&bits.to_le_bytes());
+            }
+        }
+        let knots = [8000u16, 10400, 12900, 14100];
+        let bytes = build_tiff(
+            vec![
+                (TAG_WIDTH, Value::Long(vec![width as u32])),
+                (TAG_HEIGHT, Value::Long(vec![height as u32])),
+                (TAG_BITS_PER_SAMPLE, Value::Short(vec![8])),
+                (TAG_COMPRESSION, Value::Short(vec![32767])),
+                (TAG_PHOTOMETRIC, Value::Short(vec![PHOTOMETRIC_CFA as u16])),
+                (TAG_SONY_CURVE, Value::Short(knots.to_vec())),
+            ],
+            &data,
+        );
+        let raw = read_raw(&bytes).unwrap();
+        let curve = sony_curve(&knots.map(f64::from));
+        let expected: Vec<u16> = pixels
+            .iter()
+            .map(|&p| (curve[p as usize * 2] >> 2) as u16)
+            .collect();
+        assert_eq!((raw.width, raw.height), (width, height));
+        assert_eq!(raw.data, expected);
+        assert_eq!(raw.black, 128.0);
+        assert_eq!(raw.white, (curve[0xffe] >> 2) as f32);
+    }
+
+    /// Big-endian TIFF holding one IFD of (tag, type, count, value bytes).
+    fn nikon_note_tiff(entries: &[(u16, u16, u32, Vec<u8>)]) -> Vec<u8> {
+        let mut out = b"MM\0*\0\0\0\x08".to_vec();
+        let mut extra = Vec::new();
+        let extra_start = 8 + 2 + entries.len() * 12 + 4;
+        out.extend_from_slice(&(entries.len() as u16).to_be_bytes());
+        for (tag, kind, count, bytes) in entries {
+            out.extend_from_slice(&tag.to_be_bytes());
+            out.extend_from_slice(&kind.to_be_bytes());
+            out.extend_from_slice(&count.to_be_bytes());
+            out.extend_from_slice(&((extra_start + extra.len()) as u32).to_be_bytes());
+            extra.extend_from_slice(bytes);
+        }
+        out.extend_from_slice(&0u32.to_be_bytes());
+        out.extend_from_slice(&extra);
+        out
+    }
+
+    #[test]
+    fn decodes_lossless_nef() {
+        let (width, height) = (6usize, 4usize);
+        let samples: Vec<u16> = (0..width * height)
+            .map(|i| ((i * 613 + 200) % 4096) as u16)
+            .collect();
+
+        // Canonical codes of the 12-bit lossless tree
+        let (counts, symbols) = &NIKON_TREES[2];
+        let mut codes = [(0u32, 0u32); 16];
+        let (mut code, mut k) = (0u32, 0);
+        for (len, &n) in counts.iter().enumerate() {
+            for _ in 0..n {
+                codes[symbols[k] as usize] = (code, len as u32 + 1);
+                code += 1;
+                k += 1;
+            }
+            code <<= 1;
+        }
+        let vpred = [[2048u16, 2048], [2048, 2048]];
+        let mut bits: Vec<bool> = Vec::new();
+        let mut put = |value: u32, n: u32| {
+            for i in (0..n).rev() {
+                bits.push(value >> i & 1 == 1);
+            }
+        };
+        for (i, &s) in samples.iter().enumerate() {
+            let (x, y) = (i % width, i / width);
+            let pred = match (x, y) {
+                (0 | 1, 0 | 1) => vpred[y][x] as i32,
+                (0 | 1, _) => samples[i - 2 * width] as i32,
+                _ => samples[i - 2] as i32,
+            };
+            let diff = s as i32 - pred;
+            let len = 32 - diff.unsigned_abs().leading_zeros();
+            let (code, code_len) = codes[len as usize];
+            put(code, code_len);
+            let extra = if diff < 0 { diff - 1 } else { diff };
+            put(extra as u32 & ((1 << len) - 1), len);
+        }
+        while !bits.len().is_multiple_of(8) {
+            bits.push(false);
+        }
+        let mut strip: Vec<u8> = bits
+            .chunks(8)
+            .map(|b| b.iter().fold(0u8, |acc, &bit| acc << 1 | bit as u8))
+            .collect();
+        strip.resize(strip.len().next_multiple_of(2), 0);
+
+        let mut table = vec![0x46, 0x30];
+        table.extend(vpred.iter().flatten().flat_map(|v| v.to_be_bytes()));
+        table.extend_from_slice(&[0, 0]);
+        let rational = |n: u32, d: u32| [n.to_be_bytes(), d.to_be_bytes()].concat();
+        let mut note = b"Nikon\0\x02\x10\0\0".to_vec();
+        note.extend(nikon_note_tiff(&[
+            (
+                NIKON_WB_LEVELS,
+                5,
+                4,
+                [
+                    rational(2, 1),
+                    rational(3, 2),
+                    rational(1, 1),
+                    rational(1, 1),
+                ]
+                .concat(),
+            ),
+            (
+                NIKON_BLACK_LEVEL,
+                3,
+                4,
+                [400u16; 4].iter().flat_map(|v| v.to_be_bytes()).collect(),
+            ),
+            (NIKON_DECODE_TABLE, 7, table.len() as u32, table),
+        ]));
+
+        // The Exif IFD follows the strip; its maker note follows the IFD
+        let exif_at = 8 + strip.len() as u32;
+        let mut data = strip.clone();
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&TAG_MAKER_NOTE.to_le_bytes());
+        data.extend_from_slice(&7u16.to_le_bytes());
+        data.extend_from_slice(&(note.len() as u32).to_le_bytes());
+        data.extend_from_slice(&(exif_at + 18).to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&note);
+
+        let bytes = build_tiff(
+            vec![
+                (TAG_NEW_SUBFILE_TYPE, Value::Long(vec![0])),
+                (TAG_WIDTH, Value::Long(vec![width as u32])),
+                (TAG_HEIGHT, Value::Long(vec![height as u32])),
+                (TAG_BITS_PER_SAMPLE, Value::Short(vec![12])),
+                (TAG_COMPRESSION, Value::Short(vec![34713])),
+                (TAG_PHOTOMETRIC, Value::Short(vec![PHOTOMETRIC_CFA as u16])),
+                (TAG_CFA_REPEAT_DIM, Value::Short(vec![2, 2])),
+                (TAG_CFA_PATTERN, Value::Byte(vec![0, 1, 1, 2])),
+                (TAG_EXIF_IFD, Value::Long(vec![exif_at])),
+            ],
+            &data,
+        );
+        assert_eq!(sniff(&bytes, "nef"), Some("NEF"));
+        let raw = read_raw(&bytes).unwrap();
+        assert_eq!((raw.width, raw.height), (width, height));
+        assert_eq!(raw.data, samples);
+        assert_eq!((raw.black, raw.white), (100.0, 4095.0));
+        assert_eq!(raw.wb, Some([2.0, 1.0, 1.5]));
+    }
+}
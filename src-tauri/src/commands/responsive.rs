@@ -0,0 +1,315 @@
+use crate::commands::batch;
+use crate::commands::export::{self, EncoderOptions, ExportOptions};
+use crate::commands::metadata::MetadataMode;
+use crate::commands::operations::{self, ResizeMode, ResizeOptions};
+use crate::commands::webp::WebpOptions;
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// ── Responsive image sets ───────────────────────────────────────────
+//
+// Each input rendered at several widths and formats for `srcset`, with a
+// manifest entry and a `<picture>` snippet that lists them.
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ResponsiveOptions {
+    /// Widths wider than the source are skipped
+    pub widths: Vec<u32>,
+    /// In `<picture>` order; the last is the `<img>` fallback
+    pub formats: Vec<String>,
+    pub quality: u8,
+    /// WebP defaults to lossy here, like the other web formats
+    pub encoder: EncoderOptions,
+    /// Stripped by default, since web copies rarely want camera data
+    pub metadata: MetadataMode,
+    /// The snippet's `sizes` attribute
+    pub sizes: String,
+    /// Prepended to file names in the snippet, e.g. `/img/`
+    pub url_prefix: String,
+}
+
+impl Default for ResponsiveOptions {
+    fn default() -> Self {
+        Self {
+            widths: vec![320, 640, 1280, 2560],
+            formats: vec!["avif".into(), "webp".into(), "jpeg".into()],
+            quality: 80,
+            encoder: EncoderOptions {
+                webp: WebpOptions {
+                    lossless: false,
+                    ..WebpOptions::default()
+                },
+                ..EncoderOptions::default()
+            },
+            metadata: MetadataMode::Strip,
+            sizes: "100vw".into(),
+            url_prefix: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResponsiveVariant {
+    pub file: String,
+    pub format: String,
+    pub width: u32,
+    pub height: u32,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResponsiveImage {
+    pub source: String,
+    pub width: u32,
+    pub height: u32,
+    pub variants: Vec<ResponsiveVariant>,
+    pub html: String,
+    /// Where `html` was written
+    pub snippet: String,
+}
+
+fn mime(format: image::ImageFormat) -> Result<&'static str, AppError> {
+    match format {
+        image::ImageFormat::Avif => Ok("image/avif"),
+        image::ImageFormat::WebP => Ok("image/webp"),
+        image::ImageFormat::Jpeg => Ok("image/jpeg"),
+        image::ImageFormat::Png => Ok("image/png"),
+        other => Err(AppError::UnsupportedFormat(format!(
+            "{other:?} is not a web image format"
+        ))),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Percent-encode a file name for a URL, so spaces and commas can't split a
+/// `srcset` candidate.
+fn url_encode(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            out.push(byte as char);
+        } else {
+            out.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    out
+}
+
+/// Widths to render: the requested ones that don't upscale, or the source
+/// width alone when every one would.
+pub fn plan_widths(requested: &[u32], source_width: u32) -> Vec<u32> {
+    let mut widths: Vec<u32> = requested
+        .iter()
+        .copied()
+        .filter(|&w| w > 0 && w <= source_width)
+        .collect();
+    widths.sort_unstable();
+    widths.dedup();
+    if widths.is_empty() {
+        widths.push(source_width);
+    }
+    widths
+}
+
+/// The `<picture>` snippet, with an empty `alt` for the page author to fill.
+fn picture_html(
+    variants: &[ResponsiveVariant],
+    formats: &[String],
+    options: &ResponsiveOptions,
+) -> Result<String, AppError> {
+    let url = |v: &ResponsiveVariant| format!("{}{}", options.url_prefix, url_encode(&v.file));
+    let srcset = |format: &str| {
+        variants
+            .iter()
+            .filter(|v| v.format == format)
+            .map(|v| format!("{} {}w", url(v), v.width))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let sizes = escape(&options.sizes);
+    let mut html = String::from("<picture>\n");
+    let (fallback, sources) = formats
+        .split_last()
+        .ok_or_else(|| AppError::General("No output formats selected".into()))?;
+    for format in sources {
+        html.push_str(&format!(
+            "  <source type=\"{}\" srcset=\"{}\" sizes=\"{}\">\n",
+            mime(export::image_format(format)?)?,
+            escape(&srcset(format)),
+            sizes
+        ));
+    }
+    let largest = variants
+        .iter()
+        .rev()
+        .find(|v| &v.format == fallback)
+        .ok_or_else(|| AppError::General("No fallback image was written".into()))?;
+    html.push_str(&format!(
+        "  <img src=\"{}\" srcset=\"{}\" sizes=\"{}\" width=\"{}\" height=\"{}\" alt=\"\" loading=\"lazy\" decoding=\"async\">\n",
+        escape(&url(largest)),
+        escape(&srcset(fallback)),
+        sizes,
+        largest.width,
+        largest.height
+    ));
+    html.push_str("</picture>\n");
+    Ok(html)
+}
+
+/// Render `input` into `output_dir` as `<stem>-<width>.<ext>` per width and
+/// format, and write its snippet to `<stem>.html`.
+pub fn render_set(
+    input: &str,
+    output_dir: &Path,
+    options: &ResponsiveOptions,
+) -> Result<ResponsiveImage, AppError> {
+    if options.formats.is_empty() {
+        return Err(AppError::General("No output formats selected".into()));
+    }
+    let formats = options
+        .formats
+        .iter()
+        .map(|format| {
+            let image_format = export::image_format(format)?;
+            mime(image_format)?;
+            Ok((format, image_format))
+        })
+        .collect::<Result<Vec<_>, AppError>>()?;
+
+    let img = operations::open_image(input)?;
+    let stem = Path::new(input)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("image");
+    // Inputs sharing a stem get `_2`, `_3`… like other batch outputs
+    let snippet_path = batch::unique_path(output_dir, stem, "html");
+    let stem = snippet_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(stem)
+        .to_string();
+    let export_options = ExportOptions {
+        metadata_source: Some(input.to_string()),
+        metadata: options.metadata,
+        encoder: options.encoder.clone(),
+        ..ExportOptions::default()
+    };
+    let by_width = ResizeOptions {
+        mode: ResizeMode::Width,
+        ..ResizeOptions::default()
+    };
+
+    let mut variants = Vec::new();
+    for &width in &plan_widths(&options.widths, img.width()) {
+        let resized = operations::resize_dynamic(&img, width, 0, "lanczos", &by_width)?;
+        for &(format, image_format) in &formats {
+            let file = format!("{}-{}.{}", stem, width, image_format.extensions_str()[0]);
+            let bytes = export::encode_still(
+                &resized,
+                input,
+                image_format,
+                options.quality,
+                &export_options,
+            )?;
+            std::fs::write(output_dir.join(&file), &bytes)
+                .map_err(|e| AppError::SaveFailed(e.to_string()))?;
+            variants.push(ResponsiveVariant {
+                file,
+                format: format.clone(),
+                width: resized.width(),
+                height: resized.height(),
+                bytes: bytes.len() as u64,
+            });
+        }
+    }
+
+    let html = picture_html(&variants, &options.formats, options)?;
+    std::fs::write(&snippet_path, &html).map_err(|e| AppError::SaveFailed(e.to_string()))?;
+    Ok(ResponsiveImage {
+        source: input.to_string(),
+        width: img.width(),
+        height: img.height(),
+        variants,
+        html,
+        snippet: snippet_path.to_string_lossy().into_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn widths_never_upscale() {
+        assert_eq!(plan_widths(&[320, 640, 1280, 2560], 1000), vec![320, 640]);
+        assert_eq!(plan_widths(&[640, 320, 640], 640), vec![320, 640]);
+        assert_eq!(plan_widths(&[320, 640], 200), vec![200]);
+    }
+
+    #[test]
+    fn set_writes_each_width_and_format_with_a_snippet() {
+        let id = uuid::Uuid::new_v4();
+        // A space and a comma, which would break srcset unencoded
+        let src = std::env::temp_dir().join(format!("pixelforge srcset,{id}.png"));
+        image::RgbImage::from_fn(700, 350, |x, y| {
+            image::Rgb([(x % 256) as u8, (y % 256) as u8, 90])
+        })
+        .save(&src)
+        .unwrap();
+        let dir = std::env::temp_dir().join(format!("pixelforge_srcset_out_{id}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut options = ResponsiveOptions {
+            widths: vec![160, 320, 1280],
+            url_prefix: "/img/".into(),
+            ..ResponsiveOptions::default()
+        };
+        options.encoder.avif.speed = 10;
+
+        let set = render_set(&src.to_string_lossy(), &dir, &options).unwrap();
+        assert_eq!(set.variants.len(), 6);
+        for variant in &set.variants {
+            let written = std::fs::metadata(dir.join(&variant.file)).unwrap().len();
+            assert_eq!(written, variant.bytes, "{}", variant.file);
+            assert_eq!(variant.height * 2, variant.width);
+        }
+        let jpeg = &set.variants[5];
+        assert_eq!((jpeg.format.as_str(), jpeg.width), ("jpeg", 320));
+        let decoded = image::open(dir.join(&jpeg.file)).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (320, 160));
+
+        let webp = std::fs::read(dir.join(&set.variants[1].file)).unwrap();
+        assert_eq!(&webp[12..16], b"VP8 ");
+
+        let stem = format!("pixelforge srcset,{id}");
+        let url = format!("pixelforge%20srcset%2C{id}");
+        assert!(set
+            .html
+            .starts_with("<picture>\n  <source type=\"image/avif\""));
+        assert!(set.html.contains(&format!(
+            "/img/{url}-160.webp 160w, /img/{url}-320.webp 320w"
+        )));
+        assert!(set
+            .html
+            .contains(&format!("<img src=\"/img/{url}-320.jpg\"")));
+        assert!(set.html.contains("width=\"320\" height=\"160\" alt=\"\""));
+        assert_eq!(Path::new(&set.snippet), dir.join(format!("{stem}.html")));
+        let snippet = std::fs::read_to_string(&set.snippet).unwrap();
+        assert_eq!(snippet, set.html);
+
+        options.formats = vec!["tiff".into()];
+        assert!(render_set(&src.to_string_lossy(), &dir, &options).is_err());
+
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::remove_file(&src).ok();
+    }
+}
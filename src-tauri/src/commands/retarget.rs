@@ -0,0 +1,774 @@
+use crate::commands::{ai, operations};
+use crate::error::AppError;
+use crate::models::session::OnnxState;
+use image::{DynamicImage, GrayImage, RgbaImage};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+
+/// Energy added to fully protected pixels; larger than any gradient sum.
+const PROTECT_ENERGY: f32 = 1.0e5;
+
+// ── Seam carving ────────────────────────────────────────────────────
+
+/// Load a protect mask from an image file. RGBA files (e.g. `remove_background`
+/// output) use their alpha channel, everything else its luminance.
+pub fn load_mask(path: &str, width: u32, height: u32) -> Result<GrayImage, AppError> {
+    let img = operations::open_image(path)?;
+    let mask = if img.color().has_alpha() {
+        let rgba = img.to_rgba8();
+        GrayImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+            image::Luma([rgba.get_pixel(x, y)[3]])
+        })
+    } else {
+        img.to_luma8()
+    };
+    if mask.dimensions() == (width, height) {
+        return Ok(mask);
+    }
+    Ok(image::imageops::resize(
+        &mask,
+        width,
+        height,
+        image::imageops::FilterType::Triangle,
+    ))
+}
+
+/// Pixels and protect weights for the current (shrinking or growing) image,
+/// stored row-major. Horizontal seams are handled by transposing.
+#[derive(Clone)]
+struct Carver {
+    width: usize,
+    height: usize,
+    pixels: Vec<[u8; 4]>,
+    protect: Vec<f32>,
+    luma: Vec<f32>,
+    /// Kept current as seams are removed; only pixels next to a removed seam
+    /// change, so only those are recomputed.
+    energy: Vec<f32>,
+}
+
+fn luma_of(p: &[u8; 4]) -> f32 {
+    0.299 * p[0] as f32 + 0.587 * p[1] as f32 + 0.114 * p[2] as f32
+}
+
+fn remove_from<T: Copy>(buf: &[T], width: usize, seam: &[usize]) -> Vec<T> {
+    let mut out = Vec::with_capacity(buf.len() - seam.len());
+    for (y, &sx) in seam.iter().enumerate() {
+        let row = &buf[y * width..(y + 1) * width];
+        out.extend_from_slice(&row[..sx]);
+        out.extend_from_slice(&row[sx + 1..]);
+    }
+    out
+}
+
+impl Carver {
+    fn new(img: &RgbaImage, mask: Option<&GrayImage>) -> Self {
+        let (w, h) = img.dimensions();
+        let pixels = img.pixels().map(|p| p.0).collect();
+        let protect = match mask {
+            Some(m) => m.pixels().map(|p| p[0] as f32 / 255.0).collect(),
+            None => vec![0.0; (w * h) as usize],
+        };
+        Self::with_pixels(w as usize, h as usize, pixels, protect)
+    }
+
+    fn with_pixels(width: usize, height: usize, pixels: Vec<[u8; 4]>, protect: Vec<f32>) -> Self {
+        let mut carver = Self {
+            width,
+            height,
+            luma: pixels.iter().map(luma_of).collect(),
+            pixels,
+            protect,
+            energy: Vec::new(),
+        };
+        carver.energy = (0..width * height)
+            .map(|i| carver.energy_at(i % width, i / width))
+            .collect();
+        carver
+    }
+
+    fn into_image(self) -> RgbaImage {
+        let raw = self.pixels.into_iter().flatten().collect();
+        RgbaImage::from_raw(self.width as u32, self.height as u32, raw)
+            .expect("carver buffer matches its dimensions")
+    }
+
+    fn transposed(&self) -> Self {
+        let (w, h) = (self.width, self.height);
+        let mut pixels = Vec::with_capacity(self.pixels.len());
+        let mut protect = Vec::with_capacity(self.protect.len());
+        for x in 0..w {
+            for y in 0..h {
+                pixels.push(self.pixels[y * w + x]);
+                protect.push(self.protect[y * w + x]);
+            }
+        }
+        Self::with_pixels(h, w, pixels, protect)
+    }
+
+    /// Gradient energy of the luminance (forward + backward differences, so
+    /// one-pixel detail still registers) plus the protect bias.
+    fn energy_at(&self, x: usize, y: usize) -> f32 {
+        let (w, h, luma) = (self.width, self.height, &self.luma);
+        let (up, down) = (y.saturating_sub(1), (y + 1).min(h - 1));
+        let (left, right) = (x.saturating_sub(1), (x + 1).min(w - 1));
+        let here = luma[y * w + x];
+        let dx = (luma[y * w + right] - here).abs() + (here - luma[y * w + left]).abs();
+        let dy = (luma[down * w + x] - here).abs() + (here - luma[up * w + x]).abs();
+        dx + dy + self.protect[y * w + x] * PROTECT_ENERGY
+    }
+
+    /// Lowest-cost 8-connected vertical seam, one column index per row.
+    fn find_seam(&self) -> Vec<usize> {
+        let (w, h) = (self.width, self.height);
+        let mut cost = self.energy.clone();
+        for y in 1..h {
+            for x in 0..w {
+                let prev = &cost[(y - 1) * w..y * w];
+                let mut best = prev[x];
+                if x > 0 {
+                    best = best.min(prev[x - 1]);
+                }
+                if x + 1 < w {
+                    best = best.min(prev[x + 1]);
+                }
+                cost[y * w + x] += best;
+            }
+        }
+
+        let last = &cost[(h - 1) * w..];
+        let mut x = last
+            .iter()
+            .enumerate()
+            .min_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(i, _)| i)
+            .unwrap_or(0);
+        let mut seam = vec![0; h];
+        seam[h - 1] = x;
+        for y in (0..h - 1).rev() {
+            let row = &cost[y * w..(y + 1) * w];
+            let lo = x.saturating_sub(1);
+            let hi = (x + 1).min(w - 1);
+            x = (lo..=hi)
+                .min_by(|&a, &b| row[a].total_cmp(&row[b]))
+                .unwrap_or(x);
+            seam[y] = x;
+        }
+        seam
+    }
+
+    fn remove_seam(&mut self, seam: &[usize]) {
+        self.pixels = remove_from(&self.pixels, self.width, seam);
+        self.protect = remove_from(&self.protect, self.width, seam);
+        self.luma = remove_from(&self.luma, self.width, seam);
+        self.energy = remove_from(&self.energy, self.width, seam);
+        self.width -= 1;
+        if self.width == 0 {
+            return;
+        }
+        // A pixel's neighbours changed if it sat beside the seam in its own
+        // row or, through the shift, beside it in the rows above and below
+        for y in 0..self.height {
+            let near = &seam[y.saturating_sub(1)..(y + 2).min(self.height)];
+            let lo = near.iter().min().copied().unwrap_or(0).saturating_sub(1);
+            let hi = near.iter().max().copied().unwrap_or(0).min(self.width - 1);
+            for x in lo..=hi {
+                self.energy[y * self.width + x] = self.energy_at(x, y);
+            }
+        }
+    }
+
+    /// Duplicate the `count` lowest-energy seams, blending each copy with its
+    /// right-hand neighbour. Seams are found on a scratch copy so the same
+    /// seam is never picked twice.
+    fn insert_seams(&mut self, count: usize) {
+        let (w, h) = (self.width, self.height);
+        let mut scratch = self.clone();
+        let mut columns: Vec<usize> = (0..h).flat_map(|_| 0..w).collect();
+        let mut inserts: Vec<Vec<usize>> = vec![Vec::with_capacity(count); h];
+        for _ in 0..count {
+            let seam = scratch.find_seam();
+            for (y, &sx) in seam.iter().enumerate() {
+                inserts[y].push(columns[y * scratch.width + sx]);
+            }
+            columns = remove_from(&columns, scratch.width, &seam);
+            scratch.remove_seam(&seam);
+        }
+
+        let new_w = w + count;
+        let mut pixels = Vec::with_capacity(new_w * h);
+        let mut protect = Vec::with_capacity(new_w * h);
+        for (y, xs) in inserts.iter_mut().enumerate() {
+            xs.sort_unstable();
+            let mut next = xs.iter().peekable();
+            for x in 0..w {
+                let p = self.pixels[y * w + x];
+                pixels.push(p);
+                protect.push(self.protect[y * w + x]);
+                while next.next_if_eq(&&x).is_some() {
+                    let q = self.pixels[y * w + (x + 1).min(w - 1)];
+                    pixels.push(std::array::from_fn(|c| {
+                        (p[c] as u16 + q[c] as u16).div_ceil(2) as u8
+                    }));
+                    protect.push(self.protect[y * w + x]);
+                }
+            }
+        }
+        *self = Self::with_pixels(new_w, h, pixels, protect);
+    }
+
+    fn retarget_width(&mut self, target: usize) {
+        let target = target.max(1);
+        while self.width > target {
+            let seam = self.find_seam();
+            self.remove_seam(&seam);
+        }
+        // Inserting more than half the width at once would duplicate the
+        // same region over and over, so grow in passes.
+        while self.width < target {
+            let count = (target - self.width).min((self.width / 2).max(1));
+            self.insert_seams(count);
+        }
+    }
+}
+
+/// Content-aware resize to `width` x `height`. With `prescale`, the image is
+/// first scaled uniformly to cover the target so only the overflowing axis
+/// needs carving.
+pub fn seam_carve(
+    img: &DynamicImage,
+    mask: Option<&GrayImage>,
+    width: u32,
+    height: u32,
+    prescale: bool,
+) -> DynamicImage {
+    let (mut src, mut mask) = (img.to_rgba8(), mask.cloned());
+    if prescale {
+        let scale = (width as f64 / src.width() as f64).max(height as f64 / src.height() as f64);
+        let sw = ((src.width() as f64 * scale).round() as u32).max(width);
+        let sh = ((src.height() as f64 * scale).round() as u32).max(height);
+        if (sw, sh) != src.dimensions() {
+            let filter = image::imageops::FilterType::Lanczos3;
+            src = operations::resize_linear(&DynamicImage::ImageRgba8(src), sw, sh, filter)
+                .to_rgba8();
+            mask = mask.map(|m| image::imageops::resize(&m, sw, sh, filter));
+        }
+    }
+
+    let mut carver = Carver::new(&src, mask.as_ref());
+    carver.retarget_width(width as usize);
+    let mut carver = carver.transposed();
+    carver.retarget_width(height as usize);
+    let out = carver.transposed().into_image();
+
+    if img.color().has_alpha() {
+        DynamicImage::ImageRgba8(out)
+    } else {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(out).to_rgb8())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SeamCarveOptions {
+    /// Scale uniformly to cover the target first, then carve only the excess
+    pub prescale: bool,
+    /// Protect the U²-Net foreground (needs the u2net model)
+    pub protect_foreground: bool,
+    /// Grayscale mask, or RGBA image whose alpha marks areas to keep
+    pub protect_mask_path: Option<String>,
+}
+
+impl Default for SeamCarveOptions {
+    fn default() -> Self {
+        Self {
+            prescale: true,
+            protect_foreground: false,
+            protect_mask_path: None,
+        }
+    }
+}
+
+/// Combine the requested protect sources into one mask (per-pixel maximum).
+fn protect_mask(
+    app: &AppHandle,
+    state: &OnnxState,
+    img: &DynamicImage,
+    protect_foreground: bool,
+    protect_mask_path: Option<&str>,
+) -> Result<Option<GrayImage>, AppError> {
+    let mut mask = match protect_mask_path {
+        Some(p) => Some(load_mask(p, img.width(), img.height())?),
+        None => None,
+    };
+    if protect_foreground {
+        let fg = ai::foreground_mask(app, state, img)?;
+        mask = Some(match mask {
+            Some(mut m) => {
+                for (a, b) in m.pixels_mut().zip(fg.pixels()) {
+                    a[0] = a[0].max(b[0]);
+                }
+                m
+            }
+            None => fg,
+        });
+    }
+    Ok(mask)
+}
+
+#[tauri::command]
+pub fn apply_seam_carve(
+    app: AppHandle,
+    state: State<'_, OnnxState>,
+    path: String,
+    width: u32,
+    height: u32,
+    options: Option<SeamCarveOptions>,
+) -> Result<String, AppError> {
+    use tauri::Emitter;
+
+    if width == 0 || height == 0 {
+        return Err(AppError::General(
+            "Width and height must be greater than 0".into(),
+        ));
+    }
+    let options = options.unwrap_or_default();
+
+    app.emit(
+        "operation-progress",
+        serde_json::json!({ "stage": "preprocessing", "percent": 10 }),
+    )
+    .ok();
+
+    let img = operations::open_image(&path)?;
+    let mask = protect_mask(
+        &app,
+        &state,
+        &img,
+        options.protect_foreground,
+        options.protect_mask_path.as_deref(),
+    )?;
+
+    app.emit(
+        "operation-progress",
+        serde_json::json!({ "stage": "carving", "percent": 40 }),
+    )
+    .ok();
+
+    let carved = seam_carve(&img, mask.as_ref(), width, height, options.prescale);
+    let output = operations::save_temp_image(&carved)?;
+
+    app.emit(
+        "operation-progress",
+        serde_json::json!({ "stage": "complete", "percent": 100 }),
+    )
+    .ok();
+
+    Ok(output)
+}
+
+// ── Smart crop ──────────────────────────────────────────────────────
+
+/// Longest side of the downscaled copy used for saliency scoring.
+const SALIENCY_SIZE: u32 = 256;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CropProposal {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Share of the image's total saliency inside the window (0–1)
+    pub score: f32,
+    pub used_foreground: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SmartCropResult {
+    pub crop: CropProposal,
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SmartCropOptions {
+    /// Crop the image instead of only proposing the window
+    pub apply: bool,
+    /// Treat the target as a size and scale the crop to exactly `width` x `height`
+    pub resize: bool,
+    /// Score with the U²-Net foreground mask when the model is installed
+    pub use_foreground: bool,
+}
+
+impl Default for SmartCropOptions {
+    fn default() -> Self {
+        Self {
+            apply: false,
+            resize: false,
+            use_foreground: true,
+        }
+    }
+}
+
+/// Per-pixel saliency in [0, 1]: luminance gradient and 8x8 block entropy,
+/// weighted towards the foreground mask when one is given.
+pub fn saliency_map(luma: &GrayImage, foreground: Option<&GrayImage>) -> Vec<f32> {
+    let (w, h) = luma.dimensions();
+    let (wu, hu) = (w as usize, h as usize);
+    let l = |x: u32, y: u32| luma.get_pixel(x, y)[0] as f32;
+
+    let mut edges = vec![0.0_f32; wu * hu];
+    for y in 0..h {
+        for x in 0..w {
+            let dx = l((x + 1).min(w - 1), y) - l(x.saturating_sub(1), y);
+            let dy = l(x, (y + 1).min(h - 1)) - l(x, y.saturating_sub(1));
+            edges[y as usize * wu + x as usize] = (dx * dx + dy * dy).sqrt();
+        }
+    }
+
+    const BLOCK: u32 = 8;
+    let mut entropy = vec![0.0_f32; wu * hu];
+    for by in (0..h).step_by(BLOCK as usize) {
+        for bx in (0..w).step_by(BLOCK as usize) {
+            let (bw, bh) = (BLOCK.min(w - bx), BLOCK.min(h - by));
+            let mut hist = [0_u32; 16];
+            for y in by..by + bh {
+                for x in bx..bx + bw {
+                    hist[(luma.get_pixel(x, y)[0] >> 4) as usize] += 1;
+                }
+            }
+            let n = (bw * bh) as f32;
+            let e: f32 = hist
+                .iter()
+                .filter(|&&c| c > 0)
+                .map(|&c| {
+                    let p = c as f32 / n;
+                    -p * p.log2()
+                })
+                .sum();
+            for y in by..by + bh {
+                for x in bx..bx + bw {
+                    entropy[y as usize * wu + x as usize] = e;
+                }
+            }
+        }
+    }
+
+    let normalize = |v: &mut Vec<f32>| {
+        let max = v.iter().cloned().fold(0.0_f32, f32::max);
+        if max > 0.0 {
+            v.iter_mut().for_each(|x| *x /= max);
+        }
+    };
+    normalize(&mut edges);
+    normalize(&mut entropy);
+
+    edges
+        .iter()
+        .zip(&entropy)
+        .enumerate()
+        .map(|(i, (&e, &h))| {
+            let heuristic = 0.5 * e + 0.5 * h;
+            match foreground {
+                Some(fg) => {
+                    let f = fg.as_raw()[i] as f32 / 255.0;
+                    0.3 * heuristic + 0.7 * f
+                }
+                None => heuristic,
+            }
+        })
+        .collect()
+}
+
+/// Largest window of aspect `target_w:target_h` that holds the most saliency.
+pub fn propose_crop(
+    img: &DynamicImage,
+    foreground: Option<&GrayImage>,
+    target_w: u32,
+    target_h: u32,
+) -> Result<CropProposal, AppError> {
+    if target_w == 0 || target_h == 0 {
+        return Err(AppError::General(
+            "Width and height must be greater than 0".into(),
+        ));
+    }
+    let (iw, ih) = (img.width(), img.height());
+    let ratio = target_w as f64 / target_h as f64;
+    let (crop_w, crop_h) = if iw as f64 / ih as f64 > ratio {
+        (((ih as f64 * ratio).round() as u32).clamp(1, iw), ih)
+    } else {
+        (iw, ((iw as f64 / ratio).round() as u32).clamp(1, ih))
+    };
+
+    let thumb = img.thumbnail(SALIENCY_SIZE, SALIENCY_SIZE).to_luma8();
+    let (tw, th) = thumb.dimensions();
+    let fg_small = foreground
+        .map(|m| image::imageops::resize(m, tw, th, image::imageops::FilterType::Triangle));
+    let saliency = saliency_map(&thumb, fg_small.as_ref());
+    let total: f32 = saliency.iter().sum::<f32>().max(f32::EPSILON);
+
+    // The window spans the full image on one axis, so only slide along the other
+    let horizontal = crop_w < iw;
+    let (len, win) = if horizontal {
+        (
+            tw as usize,
+            ((crop_w as f64 / iw as f64) * tw as f64).round() as usize,
+        )
+    } else {
+        (
+            th as usize,
+            ((crop_h as f64 / ih as f64) * th as f64).round() as usize,
+        )
+    };
+    let win = win.clamp(1, len);
+    let mut profile = vec![0.0_f32; len];
+    for (i, &v) in saliency.iter().enumerate() {
+        let pos = if horizontal {
+            i % tw as usize
+        } else {
+            i / tw as usize
+        };
+        profile[pos] += v;
+    }
+
+    let mut sum: f32 = profile[..win].iter().sum();
+    let center = (len - win) as f32 / 2.0;
+    let rank = |s: f32, start: usize| s - 1e-4 * total * (start as f32 - center).abs();
+    let (mut best_start, mut best_sum) = (0_usize, sum);
+    for start in 1..=len - win {
+        sum += profile[start + win - 1] - profile[start - 1];
+        if rank(sum, start) > rank(best_sum, best_start) {
+            best_start = start;
+            best_sum = sum;
+        }
+    }
+
+    let frac = best_start as f64 / len as f64;
+    let (x, y) = if horizontal {
+        (((frac * iw as f64).round() as u32).min(iw - crop_w), 0)
+    } else {
+        (0, ((frac * ih as f64).round() as u32).min(ih - crop_h))
+    };
+
+    Ok(CropProposal {
+        x,
+        y,
+        width: crop_w,
+        height: crop_h,
+        score: (best_sum / total).clamp(0.0, 1.0),
+        used_foreground: foreground.is_some(),
+    })
+}
+
+/// The U²-Net mask, but only when the model is already installed.
+fn installed_foreground(
+    app: &AppHandle,
+    state: &OnnxState,
+    img: &DynamicImage,
+) -> Result<Option<GrayImage>, AppError> {
+    if crate::models::manager::get_model_path(app, "u2net").is_err() {
+        return Ok(None);
+    }
+    ai::foreground_mask(app, state, img).map(Some)
+}
+
+/// Core function for batch processing: crop `path` to the most salient
+/// window with the aspect of the resize output and return the temp path of
+/// the crop. Width-only and other single-dimension modes resolve against the
+/// image first, like a plain resize.
+pub fn smart_crop_image(
+    app: &AppHandle,
+    state: &OnnxState,
+    path: &str,
+    width: u32,
+    height: u32,
+    resize: &operations::ResizeOptions,
+) -> Result<String, AppError> {
+    let img = operations::open_image(path)?;
+    let plan = operations::plan_resize(img.width(), img.height(), width, height, resize)?;
+    let foreground = installed_foreground(app, state, &img)?;
+    let crop = propose_crop(&img, foreground.as_ref(), plan.out_w, plan.out_h)?;
+    operations::save_temp_image(&img.crop_imm(crop.x, crop.y, crop.width, crop.height))
+}
+
+#[tauri::command]
+pub fn smart_crop(
+    app: AppHandle,
+    state: State<'_, OnnxState>,
+    path: String,
+    width: u32,
+    height: u32,
+    options: Option<SmartCropOptions>,
+) -> Result<SmartCropResult, AppError> {
+    let options = options.unwrap_or_default();
+    let img = operations::open_image(&path)?;
+    let foreground = if options.use_foreground {
+        installed_foreground(&app, &state, &img)?
+    } else {
+        None
+    };
+    let crop = propose_crop(&img, foreground.as_ref(), width, height)?;
+
+    let output_path = if options.apply {
+        let cropped = img.crop_imm(crop.x, crop.y, crop.width, crop.height);
+        let cropped = if options.resize {
+            operations::resize_dynamic(
+                &cropped,
+                width,
+                height,
+                "lanczos",
+                &operations::ResizeOptions::default(),
+            )?
+        } else {
+            cropped
+        };
+        Some(operations::save_temp_image(&cropped)?)
+    } else {
+        None
+    };
+
+    Ok(SmartCropResult { crop, output_path })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Left half busy stripes, right half flat gray.
+    fn half_busy(w: u32, h: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(image::RgbImage::from_fn(w, h, |x, _| {
+            if x < w / 2 {
+                let v = if x % 2 == 0 { 0 } else { 255 };
+                image::Rgb([v, v, v])
+            } else {
+                image::Rgb([128, 128, 128])
+            }
+        }))
+    }
+
+    fn stripe_count(img: &image::RgbImage) -> usize {
+        (0..img.width())
+            .filter(|&x| img.get_pixel(x, 0)[0] == 255)
+            .count()
+    }
+
+    #[test]
+    fn incremental_energy_matches_a_full_recompute() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_fn(40, 30, |x, y| {
+            let v = ((x * 37 + y * 91) % 256) as u8;
+            image::Rgb([v, v / 2, 255 - v])
+        }));
+        let mut carver = Carver::new(&img.to_rgba8(), None);
+        for _ in 0..12 {
+            let seam = carver.find_seam();
+            carver.remove_seam(&seam);
+            let fresh = Carver::with_pixels(
+                carver.width,
+                carver.height,
+                carver.pixels.clone(),
+                carver.protect.clone(),
+            );
+            assert_eq!(carver.energy, fresh.energy);
+        }
+    }
+
+    #[test]
+    fn removes_seams_from_flat_region() {
+        let img = half_busy(40, 10);
+        let out = seam_carve(&img, None, 30, 10, false).to_rgb8();
+        assert_eq!(out.dimensions(), (30, 10));
+        // Every white stripe of the busy half survives
+        assert_eq!(stripe_count(&out), 10);
+    }
+
+    #[test]
+    fn protect_mask_redirects_seams() {
+        // Protecting the flat half forces the seams through the busy one
+        let img = half_busy(40, 10);
+        let mask = GrayImage::from_fn(40, 10, |x, _| image::Luma([if x >= 20 { 255 } else { 0 }]));
+        let out = seam_carve(&img, Some(&mask), 30, 10, false).to_rgb8();
+        let gray = (0..30).filter(|&x| out.get_pixel(x, 0)[0] == 128).count();
+        assert_eq!(gray, 20);
+        assert!(stripe_count(&out) < 10);
+    }
+
+    #[test]
+    fn inserts_seams_to_grow() {
+        let img = half_busy(20, 8);
+        let out = seam_carve(&img, None, 34, 12, false);
+        assert_eq!((out.width(), out.height()), (34, 12));
+        assert_eq!(stripe_count(&out.to_rgb8()), 5);
+    }
+
+    #[test]
+    fn prescale_carves_only_one_axis() {
+        let img = half_busy(80, 60);
+        let out = seam_carve(&img, None, 30, 30, true);
+        assert_eq!((out.width(), out.height()), (30, 30));
+        assert_eq!(out.color(), image::ColorType::Rgb8);
+    }
+
+    #[test]
+    fn mask_from_rgba_uses_alpha() {
+        let rgba = RgbaImage::from_fn(4, 4, |x, _| image::Rgba([0, 0, 0, (x * 80) as u8]));
+        let path = operations::save_temp_image(&DynamicImage::ImageRgba8(rgba)).unwrap();
+        let mask = load_mask(&path, 4, 4).unwrap();
+        assert_eq!(mask.get_pixel(3, 0)[0], 240);
+        std::fs::remove_file(&path).ok();
+    }
+    /// Flat background with a textured square subject near the right edge.
+    fn subject_right(w: u32, h: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(image::RgbImage::from_fn(w, h, |x, y| {
+            if x > w * 3 / 4 && y > h / 3 && y < h * 2 / 3 {
+                let v = ((x * 37 + y * 91) % 256) as u8;
+                image::Rgb([v, 255 - v, v / 2])
+            } else {
+                image::Rgb([200, 200, 200])
+            }
+        }))
+    }
+
+    #[test]
+    fn smart_crop_follows_subject() {
+        let img = subject_right(400, 200);
+        let crop = propose_crop(&img, None, 1, 1).unwrap();
+        assert_eq!((crop.width, crop.height), (200, 200));
+        assert!(crop.x >= 150, "crop starts at {}", crop.x);
+        assert!(crop.score > 0.9);
+    }
+
+    #[test]
+    fn smart_crop_centers_on_flat_image() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(
+            300,
+            100,
+            image::Rgb([50, 50, 50]),
+        ));
+        let crop = propose_crop(&img, None, 1, 1).unwrap();
+        assert_eq!(crop.x, 100);
+    }
+
+    #[test]
+    fn smart_crop_prefers_foreground_mask() {
+        // Texture on the right, but the mask marks a flat area on the left
+        let img = subject_right(400, 200);
+        let mask = GrayImage::from_fn(400, 200, |x, _| {
+            image::Luma([if x < 100 { 255 } else { 0 }])
+        });
+        let crop = propose_crop(&img, Some(&mask), 1, 1).unwrap();
+        assert_eq!(crop.x, 0);
+        assert!(crop.used_foreground);
+    }
+
+    #[test]
+    fn smart_crop_vertical_window_and_matching_aspect() {
+        let img = subject_right(100, 300);
+        let crop = propose_crop(&img, None, 2, 1).unwrap();
+        assert_eq!((crop.width, crop.height), (100, 50));
+        assert!(crop.y >= 100 && crop.y <= 150, "crop starts at {}", crop.y);
+        let full = propose_crop(&img, None, 100, 300).unwrap();
+        assert_eq!((full.x, full.y, full.width, full.height), (0, 0, 100, 300));
+    }
+}
@@ -0,0 +1,219 @@
+use crate::commands::operations::{self, linear_to_srgb, srgb_to_linear};
+use crate::error::AppError;
+use image::{DynamicImage, Rgba32FImage};
+use serde::Deserialize;
+
+// ── Tone mapping ────────────────────────────────────────────────────
+//
+// OpenEXR and Radiance HDR decode to linear float samples that can exceed
+// 1.0. Tone mapping compresses that range into a displayable 8/16-bit sRGB
+// image. 8/16-bit inputs are linearized first, so the same operators work
+// as exposure adjustments on ordinary photos.
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ToneMapOperator {
+    /// Reinhard on luminance, which keeps hues
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic curve, per channel
+    #[default]
+    AcesFilmic,
+    /// Exposure and gamma only; everything above 1.0 clips
+    ExposureGamma,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ToneMapOptions {
+    pub operator: ToneMapOperator,
+    /// Stops applied before the curve
+    pub exposure: f32,
+    /// Display gamma; `None` encodes with the sRGB transfer curve
+    pub gamma: Option<f32>,
+    /// Reinhard's smallest luminance mapped to white; `None` never reaches it
+    pub white_point: Option<f32>,
+    /// 8 or 16
+    pub bit_depth: u8,
+}
+
+impl Default for ToneMapOptions {
+    fn default() -> Self {
+        Self {
+            operator: ToneMapOperator::AcesFilmic,
+            exposure: 0.0,
+            gamma: None,
+            white_point: None,
+            bit_depth: 8,
+        }
+    }
+}
+
+impl ToneMapOptions {
+    /// How float images are previewed: values as stored, sRGB-encoded, 16-bit.
+    pub fn display() -> Self {
+        Self {
+            operator: ToneMapOperator::ExposureGamma,
+            bit_depth: 16,
+            ..Self::default()
+        }
+    }
+}
+
+pub fn is_float(color: image::ColorType) -> bool {
+    matches!(color, image::ColorType::Rgb32F | image::ColorType::Rgba32F)
+}
+
+fn luminance(r: f32, g: f32, b: f32) -> f32 {
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+fn aces(x: f32) -> f32 {
+    (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+}
+
+/// Map linear RGB to display-referred linear RGB in [0, 1].
+fn map_pixel(rgb: [f32; 3], options: &ToneMapOptions) -> [f32; 3] {
+    let scale = 2f32.powf(options.exposure);
+    let [r, g, b] = rgb.map(|v| v.max(0.0) * scale);
+    match options.operator {
+        ToneMapOperator::Reinhard => {
+            let l = luminance(r, g, b);
+            if l <= 0.0 {
+                return [0.0; 3];
+            }
+            let white = options.white_point.map_or(0.0, |w| 1.0 / (w * w).max(1e-6));
+            let mapped = l * (1.0 + l * white) / (1.0 + l);
+            [r, g, b].map(|v| (v * mapped / l).min(1.0))
+        }
+        ToneMapOperator::AcesFilmic => [r, g, b].map(|v| aces(v).clamp(0.0, 1.0)),
+        ToneMapOperator::ExposureGamma => [r, g, b].map(|v| v.min(1.0)),
+    }
+}
+
+/// Tone-map to an sRGB image of `options.bit_depth`, keeping alpha.
+pub fn tone_map(img: &DynamicImage, options: &ToneMapOptions) -> DynamicImage {
+    let float = is_float(img.color());
+    let encode = |v: f32| match options.gamma {
+        Some(gamma) => v.clamp(0.0, 1.0).powf(1.0 / gamma.max(0.1)),
+        None => linear_to_srgb(v),
+    };
+    let mut out: Rgba32FImage = img.to_rgba32f();
+    for p in out.pixels_mut() {
+        let mut rgb = [p[0], p[1], p[2]];
+        if !float {
+            rgb = rgb.map(srgb_to_linear);
+        }
+        let [r, g, b] = map_pixel(rgb, options).map(encode);
+        *p = image::Rgba([r, g, b, p[3].clamp(0.0, 1.0)]);
+    }
+    let out = DynamicImage::ImageRgba32F(out);
+    match (options.bit_depth > 8, img.color().has_alpha()) {
+        (false, false) => DynamicImage::ImageRgb8(out.to_rgb8()),
+        (false, true) => DynamicImage::ImageRgba8(out.to_rgba8()),
+        (true, false) => DynamicImage::ImageRgb16(out.to_rgb16()),
+        (true, true) => DynamicImage::ImageRgba16(out.to_rgba16()),
+    }
+}
+
+/// Linear float copy for EXR/HDR export; sRGB images are linearized.
+pub fn to_float(img: &DynamicImage) -> DynamicImage {
+    if is_float(img.color()) {
+        return img.clone();
+    }
+    let mut lin = img.to_rgba32f();
+    for p in lin.pixels_mut() {
+        for c in 0..3 {
+            p[c] = srgb_to_linear(p[c]);
+        }
+    }
+    let lin = DynamicImage::ImageRgba32F(lin);
+    if img.color().has_alpha() {
+        lin
+    } else {
+        DynamicImage::ImageRgb32F(lin.to_rgb32f())
+    }
+}
+
+#[tauri::command]
+pub fn apply_tone_map(path: String, options: Option<ToneMapOptions>) -> Result<String, AppError> {
+    let img = operations::open_image(&path)?;
+    let options = options.unwrap_or_default();
+    if !matches!(options.bit_depth, 8 | 16) {
+        return Err(AppError::General("Bit depth must be 8 or 16".into()));
+    }
+    operations::save_temp_image(&tone_map(&img, &options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hdr_ramp() -> DynamicImage {
+        // Scene values 0, 0.18 (mid grey), 1, 4 and 16
+        let values = [0.0, 0.18, 1.0, 4.0, 16.0];
+        DynamicImage::ImageRgb32F(image::ImageBuffer::from_fn(5, 1, |x, _| {
+            image::Rgb([values[x as usize]; 3])
+        }))
+    }
+
+    #[test]
+    fn operators_compress_highlights_monotonically() {
+        for operator in [
+            ToneMapOperator::Reinhard,
+            ToneMapOperator::AcesFilmic,
+            ToneMapOperator::ExposureGamma,
+        ] {
+            let options = ToneMapOptions {
+                operator,
+                bit_depth: 16,
+                ..ToneMapOptions::default()
+            };
+            let out = tone_map(&hdr_ramp(), &options).to_rgb16();
+            let row: Vec<u16> = out.pixels().map(|p| p[0]).collect();
+            assert_eq!(row[0], 0, "{operator:?}");
+            assert!(
+                row.windows(2).all(|w| w[0] <= w[1]),
+                "{operator:?}: {row:?}"
+            );
+            if operator == ToneMapOperator::ExposureGamma {
+                // Plain sRGB encoding, clipping at 1.0
+                assert_eq!(row[2], u16::MAX);
+                assert!((row[1] as f32 / 65535.0 - linear_to_srgb(0.18)).abs() < 1e-3);
+            } else {
+                assert!(
+                    row[3] < u16::MAX && row[3] < row[4],
+                    "{operator:?}: {row:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn exposure_and_white_point_brighten() {
+        let base = ToneMapOptions {
+            operator: ToneMapOperator::Reinhard,
+            ..ToneMapOptions::default()
+        };
+        let plain = tone_map(&hdr_ramp(), &base).to_rgb8();
+        let brighter = tone_map(
+            &hdr_ramp(),
+            &ToneMapOptions {
+                exposure: 1.0,
+                ..base.clone()
+            },
+        )
+        .to_rgb8();
+        assert!(brighter.get_pixel(1, 0)[0] > plain.get_pixel(1, 0)[0]);
+
+        let white = tone_map(
+            &hdr_ramp(),
+            &ToneMapOptions {
+                white_point: Some(4.0),
+                ..base
+            },
+        )
+        .to_rgb8();
+        assert_eq!(white.get_pixel(3, 0)[0], 255);
+        assert!(plain.get_pixel(3, 0)[0] < 255);
+    }
+}
@@ -0,0 +1,119 @@
+use crate::error::AppError;
+use image::DynamicImage;
+use serde::Deserialize;
+
+// ── Lossy WebP ──────────────────────────────────────────────────────
+//
+// The image crate only writes lossless WebP, so lossy files go through
+// libwebp. Alpha is kept losslessly in an ALPH chunk next to the lossy color.
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WebpOptions {
+    /// Keep every pixel; lossy files are far smaller for photos
+    pub lossless: bool,
+    /// 0–100 for lossy; `None` uses the export's quality
+    pub quality: Option<u8>,
+}
+
+impl Default for WebpOptions {
+    fn default() -> Self {
+        Self {
+            lossless: true,
+            quality: None,
+        }
+    }
+}
+
+/// Append a RIFF chunk, padded to an even length.
+pub(crate) fn push_riff_chunk(out: &mut Vec<u8>, kind: &[u8], data: &[u8]) {
+    out.extend_from_slice(kind);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+/// Append the low three bytes of `v`, little-endian, as WebP's 24-bit fields.
+pub(crate) fn push_u24(out: &mut Vec<u8>, v: u32) {
+    out.extend_from_slice(&v.to_le_bytes()[..3]);
+}
+
+/// Encode as lossy WebP at `quality` (0–100), keeping alpha losslessly.
+pub fn encode_lossy(img: &DynamicImage, quality: u8) -> Result<Vec<u8>, AppError> {
+    let (width, height) = (img.width(), img.height());
+    // VP8 stores 14-bit dimensions
+    if width == 0 || height == 0 || width > 16383 || height > 16383 {
+        return Err(AppError::SaveFailed(format!(
+            "Lossy WebP cannot hold a {width}x{height} image"
+        )));
+    }
+    let quality = f32::from(quality.min(100));
+    let rgba = img
+        .color()
+        .has_alpha()
+        .then(|| img.to_rgba8())
+        .filter(|rgba| rgba.pixels().any(|p| p[3] < 255));
+    let encoded = match rgba {
+        Some(rgba) => {
+            webp::Encoder::from_rgba(rgba.as_raw(), width, height).encode_simple(false, quality)
+        }
+        None => {
+            let rgb = img.to_rgb8();
+            webp::Encoder::from_rgb(rgb.as_raw(), width, height).encode_simple(false, quality)
+        }
+    }
+    .map_err(|e| AppError::save_failed(format!("libwebp: {e:?}")))?;
+    Ok(encoded.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn photo(alpha: bool) -> DynamicImage {
+        DynamicImage::ImageRgba8(image::RgbaImage::from_fn(45, 30, |x, y| {
+            let edge = if x > 25 { 120 } else { 0 };
+            let a = if alpha { (x * 5) as u8 } else { 255 };
+            image::Rgba([(x * 5) as u8, (y * 8) as u8, (edge + x * 2) as u8, a])
+        }))
+    }
+
+    fn psnr(a: &image::RgbImage, b: &image::RgbImage) -> f64 {
+        let mse = a
+            .as_raw()
+            .iter()
+            .zip(b.as_raw())
+            .map(|(&x, &y)| (f64::from(x) - f64::from(y)).powi(2))
+            .sum::<f64>()
+            / a.as_raw().len() as f64;
+        10.0 * (255.0 * 255.0 / mse.max(1e-9)).log10()
+    }
+
+    #[test]
+    fn lossy_webp_decodes_close_to_the_source() {
+        let img = photo(false);
+        let high = encode_lossy(&img, 90).unwrap();
+        let decoded = image::load_from_memory_with_format(&high, image::ImageFormat::WebP).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (45, 30));
+        assert!(!decoded.color().has_alpha());
+        let score = psnr(&img.to_rgb8(), &decoded.to_rgb8());
+        assert!(score > 30.0, "{score:.1} dB");
+
+        let low = encode_lossy(&img, 20).unwrap();
+        assert!(low.len() < high.len());
+        let decoded = image::load_from_memory(&low).unwrap();
+        assert!(psnr(&img.to_rgb8(), &decoded.to_rgb8()) > 20.0);
+    }
+
+    #[test]
+    fn lossy_webp_keeps_alpha_exactly() {
+        let img = photo(true);
+        let bytes = encode_lossy(&img, 75).unwrap();
+        let decoded = image::load_from_memory(&bytes).unwrap().to_rgba8();
+        for (a, b) in img.to_rgba8().pixels().zip(decoded.pixels()) {
+            assert_eq!(a[3], b[3]);
+        }
+    }
+}
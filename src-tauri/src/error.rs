@@ -27,6 +27,13 @@ pub enum AppError {
     General(String),
 }
 
+impl AppError {
+    /// Wrap an encoder or writer error, for `map_err`.
+    pub fn save_failed(e: impl std::fmt::Display) -> Self {
+        AppError::SaveFailed(e.to_string())
+    }
+}
+
 impl Serialize for AppError {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
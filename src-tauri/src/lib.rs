@@ -14,6 +14,7 @@ pub fn run() {
             // Phase 1: Image viewer
             commands::image::get_image_info,
             commands::image::convert_image,
+            commands::metadata::get_image_metadata,
             // Phase 2: Operations + Export
             commands::operations::apply_crop,
             commands::operations::apply_resize,
@@ -27,6 +28,9 @@ pub fn run() {
             commands::operations::apply_blur,
             commands::operations::apply_sharpen,
             commands::export::save_image,
+            commands::export::save_image_to_size,
+            commands::export::estimate_export,
+            commands::export::preview_export,
             // Phase 3: AI + Model management
             commands::ai::remove_background,
             // Phase 4: AI Operations
@@ -40,6 +44,43 @@ pub fn run() {
             models::manager::delete_model,
             // Phase 5: Batch processing
             commands::batch::run_batch_resize_export,
+            // Phase 6: Lens correction
+            commands::lens::get_lens_profile,
+            commands::lens::apply_lens_correction,
+            // Phase 7: Content-aware retargeting
+            commands::retarget::apply_seam_carve,
+            commands::retarget::smart_crop,
+            // Phase 8: Canvas
+            commands::canvas::apply_canvas_size,
+            commands::canvas::apply_padding,
+            commands::canvas::apply_trim,
+            // Phase 9: Privacy
+            commands::metadata::scrub_metadata,
+            commands::batch::run_batch_scrub,
+            // Phase 10: RAW + HEIF import
+            commands::raw::develop_raw,
+            commands::heif::get_heif_auxiliary_images,
+            commands::heif::heif_supported,
+            // Phase 11: Animation
+            commands::animation::get_animation_info,
+            // Phase 12: Multi-page TIFF
+            commands::multipage::open_tiff_page,
+            commands::export::export_pages,
+            commands::export::save_multipage_tiff,
+            // Phase 13: HDR
+            commands::tonemap::apply_tone_map,
+            // Phase 14: Palette quantization
+            commands::quantize::quantize_image,
+            // Phase 15: Icon sets
+            commands::icons::export_icon_set,
+            // Phase 16: Responsive image sets
+            commands::batch::run_batch_responsive,
+            // Phase 17: Alpha flattening
+            commands::alpha::flatten_alpha,
+            // Phase 18: Large images
+            commands::large::get_memory_budget,
+            commands::large::set_memory_budget,
+            commands::large::process_large_image,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -2,7 +2,7 @@ import { useState } from "react";
 import { Download, Trash2, Sparkles, Loader2, ChevronDown, ChevronRight } from "lucide-react";
 import { useModelManager } from "../../hooks/useModelManager";
 import { useAppStore } from "../../stores/useAppStore";
-import { removeBackground, getAssetUrl } from "../../lib/tauri";
+import { removeBackground, getDisplayUrl } from "../../lib/tauri";
 import { UpscalePanel } from "./UpscalePanel";
 import { InpaintPanel } from "./InpaintPanel";
 import { StyleTransferPanel } from "./StyleTransferPanel";
@@ -62,9 +62,9 @@ export function AiPanel({ getMaskData, clearMask }: AiPanelProps) {
     if (!currentFilePath || !imageInfo) return;
     setProcessing(true);
     try {
-      const beforeUrl = getAssetUrl(currentFilePath);
+      const beforeUrl = await getDisplayUrl(currentFilePath);
       const resultPath = await removeBackground(currentFilePath);
-      const afterUrl = getAssetUrl(resultPath);
+      const afterUrl = await getDisplayUrl(resultPath);
       pushOperation({
         type: "ai",
         label: "Remove background",
@@ -2,12 +2,48 @@ import { useEffect, useMemo, useState } from "react";
 import { open } from "@tauri-apps/plugin-dialog";
 import { listen } from "@tauri-apps/api/event";
 import { FolderOpen, FolderOutput, Play, Loader2 } from "lucide-react";
-import { runBatchResizeExport } from "../../lib/tauri";
+import { isHeifSupported, runBatchResizeExport } from "../../lib/tauri";
 import type { BatchProgress, BatchResult } from "../../types/image";
 
 const FORMATS = ["png", "jpeg", "webp", "bmp", "tiff", "avif"] as const;
-const FILTERS = ["lanczos", "bilinear", "nearest"] as const;
-const IMAGE_EXTENSIONS = ["jpg", "jpeg", "png", "webp", "avif", "gif", "bmp", "tiff", "tif"];
+const FILTERS = ["lanczos", "catmullrom", "gaussian", "bilinear", "nearest"] as const;
+const IMAGE_EXTENSIONS = [
+  "jpg",
+  "jpeg",
+  "png",
+  "webp",
+  "avif",
+  "gif",
+  "bmp",
+  "tiff",
+  "tif",
+  "dng",
+  "cr2",
+  "nef",
+  "arw",
+  "heic",
+  "heif",
+  "hif",
+  "qoi",
+  "tga",
+  "pnm",
+  "pbm",
+  "pgm",
+  "ppm",
+  "pam",
+  "dds",
+  "ico",
+  "hdr",
+  "exr",
+  "ff",
+];
+const HEIF_EXTENSIONS = ["heic", "heif", "hif"];
+
+// HEIC/HEIF only decode in builds with the heif feature; hide them otherwise.
+async function openableExtensions(): Promise<string[]> {
+  if (await isHeifSupported()) return IMAGE_EXTENSIONS;
+  return IMAGE_EXTENSIONS.filter((ext) => !HEIF_EXTENSIONS.includes(ext));
+}
 
 function getErrorMessage(err: unknown, fallback: string): string {
   if (err instanceof Error) return err.message;
@@ -57,7 +93,7 @@ export function BatchPanel() {
     try {
       const selected = await open({
         multiple: true,
-        filters: [{ name: "Images", extensions: IMAGE_EXTENSIONS }],
+        filters: [{ name: "Images", extensions: await openableExtensions() }],
       });
 
       if (!selected) return;
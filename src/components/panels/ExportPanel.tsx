@@ -1,26 +1,67 @@
-import { useState } from "react";
+import { useEffect, useState } from "react";
 import { Save } from "lucide-react";
 import { save } from "@tauri-apps/plugin-dialog";
 import { useAppStore } from "../../stores/useAppStore";
-import { saveImage } from "../../lib/tauri";
+import { estimateExport, saveImage } from "../../lib/tauri";
+import type { ExportEstimate, MetadataMode } from "../../types/image";
 
 const FORMATS = [
   { value: "png", label: "PNG", ext: "png" },
   { value: "jpeg", label: "JPEG", ext: "jpg" },
   { value: "webp", label: "WebP", ext: "webp" },
+  { value: "gif", label: "GIF", ext: "gif" },
   { value: "bmp", label: "BMP", ext: "bmp" },
   { value: "tiff", label: "TIFF", ext: "tiff" },
   { value: "avif", label: "AVIF", ext: "avif" },
+  { value: "exr", label: "OpenEXR", ext: "exr" },
+  { value: "hdr", label: "Radiance HDR", ext: "hdr" },
 ];
 
+const METADATA_MODES: { value: MetadataMode; label: string }[] = [
+  { value: "keep", label: "Keep all" },
+  { value: "copyrightOnly", label: "Copyright & author only" },
+  { value: "strip", label: "Strip all" },
+];
+
+function formatFileSize(bytes: number): string {
+  if (bytes < 1024) return `${bytes} B`;
+  if (bytes < 1024 * 1024) return `${(bytes / 1024).toFixed(1)} KB`;
+  return `${(bytes / (1024 * 1024)).toFixed(1)} MB`;
+}
+
 export function ExportPanel() {
   const currentFilePath = useAppStore((s) => s.currentFilePath);
+  const originalFilePath = useAppStore((s) => s.originalFilePath);
+  const operationHistory = useAppStore((s) => s.operationHistory);
   const setError = useAppStore((s) => s.setError);
   const isProcessing = useAppStore((s) => s.isProcessing);
   const setProcessing = useAppStore((s) => s.setProcessing);
 
   const [format, setFormat] = useState("png");
   const [quality, setQuality] = useState(85);
+  const [metadata, setMetadata] = useState<MetadataMode>("keep");
+  const [estimate, setEstimate] = useState<ExportEstimate | null>(null);
+
+  useEffect(() => {
+    setEstimate(null);
+    if (!currentFilePath) return;
+    let cancelled = false;
+    // Wait for the quality slider to settle before encoding
+    const timer = setTimeout(() => {
+      estimateExport(currentFilePath, format, quality, {
+        metadataSource: originalFilePath ?? undefined,
+        metadata,
+      })
+        .then((result) => {
+          if (!cancelled) setEstimate(result);
+        })
+        .catch(() => {});
+    }, 300);
+    return () => {
+      cancelled = true;
+      clearTimeout(timer);
+    };
+  }, [currentFilePath, originalFilePath, format, quality, metadata]);
 
   const handleSave = async () => {
     if (!currentFilePath) return;
@@ -41,7 +82,12 @@ export function ExportPanel() {
       if (!dest) return;
 
       setProcessing(true);
-      await saveImage(currentFilePath, dest, format, quality);
+      await saveImage(currentFilePath, dest, format, quality, {
+        metadataSource: originalFilePath ?? undefined,
+        metadata,
+        software: "PixelForge",
+        history: operationHistory.map((entry) => entry.label),
+      });
     } catch (err: unknown) {
       const msg = err instanceof Error ? err.message : "Save failed";
       setError(msg);
@@ -71,6 +117,21 @@ export function ExportPanel() {
         </select>
       </div>
 
+      <div className="space-y-2">
+        <label className="text-xs text-(--color-text-secondary)">Metadata</label>
+        <select
+          value={metadata}
+          onChange={(e) => setMetadata(e.target.value as MetadataMode)}
+          className="w-full px-2 py-1.5 text-xs rounded bg-(--color-bg-tertiary) border border-(--color-border)"
+        >
+          {METADATA_MODES.map((m) => (
+            <option key={m.value} value={m.value}>
+              {m.label}
+            </option>
+          ))}
+        </select>
+      </div>
+
       {showQuality && (
         <div className="space-y-1">
           <div className="flex items-center justify-between">
@@ -90,6 +151,21 @@ export function ExportPanel() {
         </div>
       )}
 
+      {estimate && (
+        <div className="space-y-0.5 text-xs text-(--color-text-secondary)">
+          <div>
+            About {formatFileSize(estimate.bytes)}, encoded in{" "}
+            {Math.round(estimate.encodeMs)} ms
+          </div>
+          {/* AVIF can't be decoded here to measure */}
+          {estimate.psnr !== null && estimate.ssim !== null && (
+            <div>
+              PSNR {estimate.psnr.toFixed(1)} dB · SSIM {estimate.ssim.toFixed(3)}
+            </div>
+          )}
+        </div>
+      )}
+
       <button
         onClick={handleSave}
         disabled={!currentFilePath || isProcessing}
@@ -1,7 +1,7 @@
 import { Download, Trash2, Eraser, Loader2, PaintBucket, X } from "lucide-react";
 import { useModelManager } from "../../hooks/useModelManager";
 import { useAppStore } from "../../stores/useAppStore";
-import { applyInpainting, getAssetUrl } from "../../lib/tauri";
+import { applyInpainting, getDisplayUrl } from "../../lib/tauri";
 
 function formatBytes(bytes: number): string {
   if (bytes < 1024 * 1024) return `${(bytes / 1024).toFixed(0)} KB`;
@@ -35,7 +35,7 @@ export function InpaintPanel({ getMaskData, clearMask }: InpaintPanelProps) {
     if (!currentFilePath || !imageInfo || !getMaskData) return;
     setProcessing(true);
     try {
-      const beforeUrl = getAssetUrl(currentFilePath);
+      const beforeUrl = await getDisplayUrl(currentFilePath);
       const maskData = getMaskData();
       const resultPath = await applyInpainting(
         currentFilePath,
@@ -43,7 +43,7 @@ export function InpaintPanel({ getMaskData, clearMask }: InpaintPanelProps) {
         imageInfo.width,
         imageInfo.height,
       );
-      const afterUrl = getAssetUrl(resultPath);
+      const afterUrl = await getDisplayUrl(resultPath);
       pushOperation({
         type: "ai",
         label: "Inpainting",
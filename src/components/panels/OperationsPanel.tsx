@@ -130,6 +130,8 @@ function ResizeSection() {
         className="w-full px-2 py-1 text-xs rounded bg-(--color-bg-tertiary) border border-(--color-border)"
       >
         <option value="lanczos">Lanczos (best)</option>
+        <option value="catmullrom">Catmull-Rom (sharp)</option>
+        <option value="gaussian">Gaussian (soft)</option>
         <option value="bilinear">Bilinear (fast)</option>
         <option value="nearest">Nearest (pixel art)</option>
       </select>
@@ -2,7 +2,7 @@ import { useState } from "react";
 import { Download, Trash2, Paintbrush, Loader2 } from "lucide-react";
 import { useModelManager } from "../../hooks/useModelManager";
 import { useAppStore } from "../../stores/useAppStore";
-import { applyStyleTransfer, getAssetUrl } from "../../lib/tauri";
+import { applyStyleTransfer, getDisplayUrl } from "../../lib/tauri";
 
 const STYLES = [
   { id: "style-mosaic", name: "Mosaic" },
@@ -36,13 +36,13 @@ export function StyleTransferPanel() {
     if (!currentFilePath || !imageInfo) return;
     setProcessing(true);
     try {
-      const beforeUrl = getAssetUrl(currentFilePath);
+      const beforeUrl = await getDisplayUrl(currentFilePath);
       const resultPath = await applyStyleTransfer(
         currentFilePath,
         selectedStyle,
         strength / 100,
       );
-      const afterUrl = getAssetUrl(resultPath);
+      const afterUrl = await getDisplayUrl(resultPath);
       const styleName =
         STYLES.find((s) => s.id === selectedStyle)?.name ?? selectedStyle;
       pushOperation({
@@ -1,7 +1,7 @@
 import { Download, Trash2, ArrowUpRight, Loader2 } from "lucide-react";
 import { useModelManager } from "../../hooks/useModelManager";
 import { useAppStore } from "../../stores/useAppStore";
-import { upscaleImage, getAssetUrl } from "../../lib/tauri";
+import { upscaleImage, getDisplayUrl } from "../../lib/tauri";
 
 function formatBytes(bytes: number): string {
   if (bytes < 1024 * 1024) return `${(bytes / 1024).toFixed(0)} KB`;
@@ -27,9 +27,9 @@ export function UpscalePanel() {
     if (!currentFilePath || !imageInfo) return;
     setProcessing(true);
     try {
-      const beforeUrl = getAssetUrl(currentFilePath);
+      const beforeUrl = await getDisplayUrl(currentFilePath);
       const resultPath = await upscaleImage(currentFilePath, scale);
-      const afterUrl = getAssetUrl(resultPath);
+      const afterUrl = await getDisplayUrl(resultPath);
       pushOperation({
         type: "ai",
         label: `Upscale ${scale}x`,
@@ -36,6 +36,16 @@ export function ImageInfo() {
             {imageInfo.width} x {imageInfo.height}
           </span>
           <span>{imageInfo.format}</span>
+          {imageInfo.extensionMismatch && (
+            <span
+              className="text-amber-500"
+              title="The file extension doesn't match the image data"
+            >
+              extension mismatch
+            </span>
+          )}
+          {imageInfo.frameCount > 1 && <span>{imageInfo.frameCount} frames</span>}
+          {imageInfo.pageCount > 1 && <span>{imageInfo.pageCount} pages</span>}
           <span>{formatFileSize(imageInfo.fileSizeBytes)}</span>
           <span className="ml-auto">{Math.round(zoom * 100)}%</span>
         </div>
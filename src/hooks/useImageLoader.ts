@@ -1,7 +1,12 @@
 import { useCallback } from "react";
 import { open } from "@tauri-apps/plugin-dialog";
 import { useAppStore } from "../stores/useAppStore";
-import { getImageInfo, convertImage, getAssetUrl } from "../lib/tauri";
+import {
+  getImageInfo,
+  convertImage,
+  getAssetUrl,
+  isHeifSupported,
+} from "../lib/tauri";
 
 const IMAGE_EXTENSIONS = [
   "jpg",
@@ -13,7 +18,33 @@ const IMAGE_EXTENSIONS = [
   "bmp",
   "tiff",
   "tif",
+  "dng",
+  "cr2",
+  "nef",
+  "arw",
+  "heic",
+  "heif",
+  "hif",
+  "qoi",
+  "tga",
+  "pnm",
+  "pbm",
+  "pgm",
+  "ppm",
+  "pam",
+  "dds",
+  "ico",
+  "hdr",
+  "exr",
+  "ff",
 ];
+const HEIF_EXTENSIONS = ["heic", "heif", "hif"];
+
+// HEIC/HEIF only decode in builds with the heif feature; hide them otherwise.
+async function openableExtensions(): Promise<string[]> {
+  if (await isHeifSupported()) return IMAGE_EXTENSIONS;
+  return IMAGE_EXTENSIONS.filter((ext) => !HEIF_EXTENSIONS.includes(ext));
+}
 
 export function useImageLoader() {
   const { setImage, setLoading, setError, clearImage, setFilePaths } =
@@ -58,7 +89,7 @@ export function useImageLoader() {
         filters: [
           {
             name: "Images",
-            extensions: IMAGE_EXTENSIONS,
+            extensions: await openableExtensions(),
           },
         ],
       });
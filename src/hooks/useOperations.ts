@@ -1,6 +1,6 @@
 import { useCallback } from "react";
 import { useAppStore } from "../stores/useAppStore";
-import { getAssetUrl } from "../lib/tauri";
+import { getDisplayUrl } from "../lib/tauri";
 import * as tauri from "../lib/tauri";
 import type { OperationType } from "../types/image";
 
@@ -30,7 +30,7 @@ export function useOperations() {
       setProcessing(true);
       try {
         const resultPath = await invoke(currentFilePath);
-        const url = getAssetUrl(resultPath);
+        const url = await getDisplayUrl(resultPath);
         pushOperation({ type, label, imagePath: resultPath });
         if (imageInfo) {
           setImage(url, imageInfo);
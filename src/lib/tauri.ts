@@ -1,12 +1,49 @@
 import { invoke } from "@tauri-apps/api/core";
 import { convertFileSrc } from "@tauri-apps/api/core";
-import type { ImageInfo, ModelStatus, PaletteColor, ClassificationResult, BatchResizeRequest, BatchResult } from "../types/image";
+import type {
+  ImageInfo,
+  ImageMetadata,
+  ExportOptions,
+  ModelStatus,
+  PaletteColor,
+  ClassificationResult,
+  BatchResizeRequest,
+  BatchResult,
+  LensProfile,
+  LensCorrection,
+  ResizeOptions,
+  SeamCarveOptions,
+  SmartCropOptions,
+  SmartCropResult,
+  CanvasOptions,
+  TrimOptions,
+  ScrubOptions,
+  BatchScrubRequest,
+  RawOptions,
+  HeifAuxiliaryImage,
+  AnimationInfo,
+  ToneMapOptions,
+  TargetSize,
+  SizedExport,
+  ExportEstimate,
+  QuantizeOptions,
+  IconSetOptions,
+  ResponsiveSetRequest,
+  AlphaOptions,
+  LargeStep,
+  LargeImageOptions,
+  LargeImageResult,
+} from "../types/image";
 
 // Phase 1: Image loading
 export async function getImageInfo(path: string): Promise<ImageInfo> {
   return invoke<ImageInfo>("get_image_info", { path });
 }
 
+export async function getImageMetadata(path: string): Promise<ImageMetadata> {
+  return invoke<ImageMetadata>("get_image_metadata", { path });
+}
+
 export async function convertImage(path: string): Promise<string> {
   const bytes = await invoke<ArrayBuffer>("convert_image", { path });
   const blob = new Blob([bytes], { type: "image/png" });
@@ -17,6 +54,11 @@ export function getAssetUrl(path: string): string {
   return convertFileSrc(path);
 }
 
+/** Operations write float results as temp EXRs, which need converting to show */
+export async function getDisplayUrl(path: string): Promise<string> {
+  return path.endsWith(".exr") ? convertImage(path) : getAssetUrl(path);
+}
+
 // Phase 2: Image operations — each returns path to new temp file
 export async function applyCrop(
   path: string,
@@ -33,8 +75,9 @@ export async function applyResize(
   width: number,
   height: number,
   filter: string,
+  options?: ResizeOptions,
 ): Promise<string> {
-  return invoke<string>("apply_resize", { path, width, height, filter });
+  return invoke<string>("apply_resize", { path, width, height, filter, options });
 }
 
 export async function applyRotate(
@@ -89,8 +132,9 @@ export async function applyLightness(
 export async function applyBlur(
   path: string,
   sigma: number,
+  linearLight?: boolean,
 ): Promise<string> {
-  return invoke<string>("apply_blur", { path, sigma });
+  return invoke<string>("apply_blur", { path, sigma, linearLight });
 }
 
 export async function applySharpen(
@@ -107,8 +151,50 @@ export async function saveImage(
   destPath: string,
   format: string,
   quality: number,
+  options?: ExportOptions,
 ): Promise<void> {
-  return invoke<void>("save_image", { sourcePath, destPath, format, quality });
+  return invoke<void>("save_image", { sourcePath, destPath, format, quality, options });
+}
+
+export async function saveImageToSize(
+  sourcePath: string,
+  destPath: string,
+  format: "jpeg" | "webp" | "avif",
+  target: TargetSize,
+  options?: ExportOptions,
+): Promise<SizedExport> {
+  return invoke<SizedExport>("save_image_to_size", { sourcePath, destPath, format, target, options });
+}
+
+export async function estimateExport(
+  sourcePath: string,
+  format: string,
+  quality: number,
+  options?: ExportOptions,
+): Promise<ExportEstimate> {
+  return invoke<ExportEstimate>("estimate_export", { sourcePath, format, quality, options });
+}
+
+const PREVIEW_MIME: Record<string, string> = {
+  jpeg: "image/jpeg",
+  jpg: "image/jpeg",
+  png: "image/png",
+  gif: "image/gif",
+  webp: "image/webp",
+  bmp: "image/bmp",
+  avif: "image/avif",
+};
+
+/** Object URL of the export; formats the webview can't show come back as PNG. */
+export async function previewExport(
+  sourcePath: string,
+  format: string,
+  quality: number,
+  options?: ExportOptions,
+): Promise<string> {
+  const bytes = await invoke<ArrayBuffer>("preview_export", { sourcePath, format, quality, options });
+  const blob = new Blob([bytes], { type: PREVIEW_MIME[format] ?? "image/png" });
+  return URL.createObjectURL(blob);
 }
 
 // Phase 3: Model management
@@ -169,3 +255,161 @@ export async function applyInpainting(
 export async function runBatchResizeExport(request: BatchResizeRequest): Promise<BatchResult> {
   return invoke<BatchResult>("run_batch_resize_export", { request });
 }
+
+// Phase 6: Lens correction
+export async function getLensProfile(path: string): Promise<LensProfile | null> {
+  return invoke<LensProfile | null>("get_lens_profile", { path });
+}
+
+export async function applyLensCorrection(
+  path: string,
+  params: LensCorrection,
+): Promise<string> {
+  return invoke<string>("apply_lens_correction", { path, params });
+}
+
+// Phase 7: Content-aware retargeting
+export async function applySeamCarve(
+  path: string,
+  width: number,
+  height: number,
+  options?: SeamCarveOptions,
+): Promise<string> {
+  return invoke<string>("apply_seam_carve", { path, width, height, options });
+}
+
+export async function smartCrop(
+  path: string,
+  width: number,
+  height: number,
+  options?: SmartCropOptions,
+): Promise<SmartCropResult> {
+  return invoke<SmartCropResult>("smart_crop", { path, width, height, options });
+}
+
+// Phase 8: Canvas
+export async function applyCanvasSize(
+  path: string,
+  width: number,
+  height: number,
+  options?: CanvasOptions,
+): Promise<string> {
+  return invoke<string>("apply_canvas_size", { path, width, height, options });
+}
+
+export async function applyPadding(
+  path: string,
+  top: number,
+  right: number,
+  bottom: number,
+  left: number,
+  options?: CanvasOptions,
+): Promise<string> {
+  return invoke<string>("apply_padding", { path, top, right, bottom, left, options });
+}
+
+export async function applyTrim(path: string, options?: TrimOptions): Promise<string> {
+  return invoke<string>("apply_trim", { path, options });
+}
+
+// Phase 9: Privacy
+export async function scrubMetadata(
+  path: string,
+  destPath?: string,
+  options?: ScrubOptions,
+): Promise<string> {
+  return invoke<string>("scrub_metadata", { path, destPath, options });
+}
+
+export async function runBatchScrub(request: BatchScrubRequest): Promise<BatchResult> {
+  return invoke<BatchResult>("run_batch_scrub", { request });
+}
+
+// Phase 10: RAW + HEIF import
+export async function developRaw(path: string, options?: RawOptions): Promise<string> {
+  return invoke<string>("develop_raw", { path, options });
+}
+
+export async function isHeifSupported(): Promise<boolean> {
+  return invoke<boolean>("heif_supported");
+}
+
+export async function getHeifAuxiliaryImages(path: string): Promise<HeifAuxiliaryImage[]> {
+  return invoke<HeifAuxiliaryImage[]>("get_heif_auxiliary_images", { path });
+}
+
+// Phase 11: Animation
+export async function getAnimationInfo(path: string): Promise<AnimationInfo> {
+  return invoke<AnimationInfo>("get_animation_info", { path });
+}
+
+// Phase 12: Multi-page TIFF
+/** Decode one page (zero-based) to a temp PNG to edit */
+export async function openTiffPage(path: string, page: number): Promise<string> {
+  return invoke<string>("open_tiff_page", { path, page });
+}
+
+export async function exportPages(
+  path: string,
+  outputDir: string,
+  format: string,
+  quality: number,
+  options?: ExportOptions,
+): Promise<string[]> {
+  return invoke<string[]>("export_pages", { path, outputDir, format, quality, options });
+}
+
+export async function saveMultipageTiff(
+  sourcePaths: string[],
+  destPath: string,
+  options?: ExportOptions,
+): Promise<void> {
+  return invoke<void>("save_multipage_tiff", { sourcePaths, destPath, options });
+}
+
+// Phase 13: HDR
+export async function applyToneMap(path: string, options?: ToneMapOptions): Promise<string> {
+  return invoke<string>("apply_tone_map", { path, options });
+}
+
+// Phase 14: Palette quantization
+export async function quantizeImage(path: string, options?: QuantizeOptions): Promise<string> {
+  return invoke<string>("quantize_image", { path, options });
+}
+
+// Phase 15: Icon sets
+export async function exportIconSet(
+  sourcePath: string,
+  outputDir: string,
+  options?: IconSetOptions,
+): Promise<string[]> {
+  return invoke<string[]>("export_icon_set", { sourcePath, outputDir, options });
+}
+
+// Phase 16: Responsive image sets
+export async function runBatchResponsive(request: ResponsiveSetRequest): Promise<BatchResult> {
+  return invoke<BatchResult>("run_batch_responsive", { request });
+}
+
+// Phase 17: Alpha flattening
+export async function flattenAlpha(path: string, options?: AlphaOptions): Promise<string> {
+  return invoke<string>("flatten_alpha", { path, options });
+}
+
+// Phase 18: Large images
+export async function getMemoryBudget(): Promise<number> {
+  return invoke<number>("get_memory_budget");
+}
+
+export async function setMemoryBudget(bytes: number): Promise<void> {
+  return invoke<void>("set_memory_budget", { bytes });
+}
+
+export async function processLargeImage(
+  sourcePath: string,
+  destPath: string,
+  steps: LargeStep[],
+  options?: LargeImageOptions,
+): Promise<LargeImageResult> {
+  return invoke<LargeImageResult>("process_large_image", { sourcePath, destPath, steps, options });
+}
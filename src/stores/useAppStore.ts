@@ -1,5 +1,6 @@
 import { create } from "zustand";
 import { convertFileSrc } from "@tauri-apps/api/core";
+import { convertImage } from "../lib/tauri";
 import type { ImageInfo, OperationHistoryEntry } from "../types/image";
 
 export type ThemeMode = "light" | "dark" | "system";
@@ -165,6 +166,12 @@ export const useAppStore = create<AppState>((set, get) => ({
       currentFilePath: previousPath,
       imageUrl: previousPath ? convertFileSrc(previousPath) : null,
     });
+    // Float results are temp EXRs the webview can't show directly
+    if (previousPath?.endsWith(".exr")) {
+      void convertImage(previousPath).then((url) => {
+        if (get().currentFilePath === previousPath) set({ imageUrl: url });
+      });
+    }
   },
 
   setProcessing: (processing) => set({ isProcessing: processing }),
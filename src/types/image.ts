@@ -6,6 +6,151 @@ export interface ImageInfo {
   fileName: string;
   filePath: string;
   needsConversion: boolean;
+  extensionMismatch: boolean;
+  frameCount: number;
+  pageCount: number;
+}
+
+export interface MetadataField {
+  tag: string;
+  value: string;
+}
+
+export interface GpsPosition {
+  latitude: number;
+  longitude: number;
+  altitude: number | null;
+}
+
+export interface ExifMetadata {
+  make: string | null;
+  model: string | null;
+  lensModel: string | null;
+  software: string | null;
+  artist: string | null;
+  copyright: string | null;
+  description: string | null;
+  dateTaken: string | null;
+  exposureTime: string | null;
+  fNumber: number | null;
+  iso: number | null;
+  focalLength: number | null;
+  focalLength35mm: number | null;
+  orientation: number | null;
+  gps: GpsPosition | null;
+  fields: MetadataField[];
+}
+
+export interface XmpMetadata {
+  title: string | null;
+  description: string | null;
+  creators: string[];
+  rights: string | null;
+  keywords: string[];
+  rating: number | null;
+  creatorTool: string | null;
+  createDate: string | null;
+  packet: string;
+}
+
+export interface IptcMetadata {
+  objectName: string | null;
+  headline: string | null;
+  caption: string | null;
+  keywords: string[];
+  byLine: string | null;
+  credit: string | null;
+  copyright: string | null;
+  city: string | null;
+  country: string | null;
+  dateCreated: string | null;
+}
+
+export interface ImageMetadata {
+  exif: ExifMetadata | null;
+  xmp: XmpMetadata | null;
+  iptc: IptcMetadata | null;
+}
+
+export type MetadataMode = "keep" | "copyrightOnly" | "strip";
+
+export interface ExportOptions {
+  metadataSource?: string;
+  metadata?: MetadataMode;
+  software?: string;
+  history?: string[];
+  encoder?: EncoderOptions;
+}
+
+export type ChromaSubsampling = "444" | "422" | "420";
+
+export interface JpegOptions {
+  quality?: number;
+  progressive?: boolean;
+  chromaSubsampling?: ChromaSubsampling;
+  optimize?: boolean;
+}
+
+export type PngFilter = "none" | "sub" | "up" | "average" | "paeth" | "adaptive";
+
+export interface PngOptions {
+  compressionLevel?: number;
+  filter?: PngFilter;
+  quantize?: QuantizeOptions;
+}
+
+export interface GifOptions {
+  quantize?: QuantizeOptions;
+}
+
+export interface WebpOptions {
+  lossless?: boolean;
+  quality?: number;
+}
+
+export interface AvifOptions {
+  quality?: number;
+  speed?: number;
+  bitDepth?: 8 | 10;
+}
+
+export type TiffCompression = "none" | "lzw" | "deflate";
+
+export interface TiffOptions {
+  compression?: TiffCompression;
+}
+
+export interface TargetSize {
+  maxBytes: number;
+  minQuality?: number;
+  allowDownscale?: boolean;
+  minScale?: number;
+}
+
+export interface SizedExport {
+  path: string;
+  bytes: number;
+  quality: number;
+  width: number;
+  height: number;
+}
+
+export interface ExportEstimate {
+  bytes: number;
+  encodeMs: number;
+  /** Null for AVIF, which can be encoded but not decoded to score */
+  psnr: number | null;
+  ssim: number | null;
+}
+
+export interface EncoderOptions {
+  jpeg?: JpegOptions;
+  png?: PngOptions;
+  gif?: GifOptions;
+  webp?: WebpOptions;
+  avif?: AvifOptions;
+  tiff?: TiffOptions;
+  alpha?: AlphaOptions;
 }
 
 export interface AppError {
@@ -40,10 +185,31 @@ export interface CropParams {
   height: number;
 }
 
+export type ResizeFilter = "lanczos" | "catmullrom" | "gaussian" | "bilinear" | "nearest";
+
+export type ResizeMode =
+  | "exact"
+  | "fit"
+  | "fill"
+  | "pad"
+  | "width"
+  | "height"
+  | "longestEdge"
+  | "percentage";
+
+export interface ResizeOptions {
+  mode?: ResizeMode;
+  padColor?: [number, number, number, number];
+  percentage?: number;
+  noUpscale?: boolean;
+  linearLight?: boolean;
+}
+
 export interface ResizeParams {
   width: number;
   height: number;
-  filter: "lanczos" | "bilinear" | "nearest";
+  filter: ResizeFilter;
+  options?: ResizeOptions;
 }
 
 export interface SaveParams {
@@ -88,9 +254,14 @@ export interface BatchResizeRequest {
   outputDir: string;
   width: number;
   height: number;
-  filter: "lanczos" | "bilinear" | "nearest";
+  filter: ResizeFilter;
+  resizeOptions?: ResizeOptions;
+  smartCrop?: boolean;
   format: "png" | "jpeg" | "webp" | "bmp" | "tiff" | "avif";
   quality: number;
+  metadata?: MetadataMode;
+  encoder?: EncoderOptions;
+  targetSize?: TargetSize;
 }
 
 export interface BatchResult {
@@ -99,6 +270,7 @@ export interface BatchResult {
   failed: number;
   outputs: string[];
   errors: string[];
+  sized?: SizedExport[];
 }
 
 export interface BatchProgress {
@@ -107,3 +279,228 @@ export interface BatchProgress {
   percent: number;
   file: string;
 }
+
+// Phase 6: Lens correction
+/** A bundled lens profile, or one from lens-profiles.json in the app data directory, which takes precedence */
+export interface LensProfile {
+  make: string;
+  lens: string;
+  k1: number;
+  k2: number;
+  k3: number;
+  vignetting: [number, number, number];
+  caRed: number;
+  caBlue: number;
+}
+
+export interface LensCorrection {
+  /** Match a profile by EXIF make/lens; the values below then adjust it */
+  useProfile?: boolean;
+  k1?: number;
+  k2?: number;
+  k3?: number;
+  vignetting?: [number, number, number];
+  caRed?: number;
+  caBlue?: number;
+}
+
+// Phase 7: Content-aware retargeting
+export interface SeamCarveOptions {
+  prescale?: boolean;
+  protectForeground?: boolean;
+  protectMaskPath?: string;
+}
+
+export interface CropProposal {
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+  score: number;
+  usedForeground: boolean;
+}
+
+export interface SmartCropOptions {
+  apply?: boolean;
+  resize?: boolean;
+  useForeground?: boolean;
+}
+
+export interface SmartCropResult {
+  crop: CropProposal;
+  outputPath: string | null;
+}
+
+// Phase 8: Canvas
+export type CanvasAnchor =
+  | "topLeft"
+  | "top"
+  | "topRight"
+  | "left"
+  | "center"
+  | "right"
+  | "bottomLeft"
+  | "bottom"
+  | "bottomRight";
+
+export interface CanvasOptions {
+  anchor?: CanvasAnchor;
+  fill?: "color" | "transparent" | "edge" | "mirror" | "blur";
+  color?: [number, number, number, number];
+  blurSigma?: number;
+}
+
+export interface TrimOptions {
+  reference?: "auto" | "transparent" | "topLeft" | "bottomRight";
+  tolerance?: number;
+  padding?: number;
+}
+
+// Phase 9: Privacy
+export interface ScrubOptions {
+  location?: boolean;
+  serials?: boolean;
+  owner?: boolean;
+  thumbnail?: boolean;
+  makerNotes?: boolean;
+  timestamps?: boolean;
+}
+
+export interface BatchScrubRequest {
+  inputPaths: string[];
+  outputDir?: string;
+  options?: ScrubOptions;
+}
+
+// Phase 10: RAW + HEIF import
+export interface RawOptions {
+  exposure?: number;
+  whiteBalance?: "asShot" | "auto";
+  redGain?: number;
+  blueGain?: number;
+  toneCurve?: boolean;
+}
+
+export interface HeifAuxiliaryImage {
+  kind: string;
+  width: number;
+  height: number;
+  path: string;
+}
+
+// Phase 11: Animation
+export interface AnimationInfo {
+  frameCount: number;
+  /** Times the animation plays; 0 loops forever */
+  plays: number;
+  delaysMs: number[];
+}
+
+// Phase 13: HDR
+export interface ToneMapOptions {
+  operator?: "reinhard" | "acesFilmic" | "exposureGamma";
+  /** Stops applied before the curve */
+  exposure?: number;
+  /** Display gamma; sRGB encoding when omitted */
+  gamma?: number;
+  /** Reinhard luminance mapped to white */
+  whitePoint?: number;
+  bitDepth?: 8 | 16;
+}
+
+// Phase 14: Palette quantization
+export type QuantizeMethod = "medianCut" | "kMeans";
+
+export type Dither = "none" | "floydSteinberg" | "ordered";
+
+export interface QuantizeOptions {
+  colors?: number;
+  method?: QuantizeMethod;
+  dither?: Dither;
+  alphaAware?: boolean;
+}
+
+// Phase 15: Icon sets
+export interface IconSetOptions {
+  ico?: boolean;
+  /** 1–256 each */
+  icoSizes?: number[];
+  /** Web, iOS and Android PNGs plus web/site.webmanifest */
+  png?: boolean;
+  icns?: boolean;
+  /** Unsharp mask below 64 px */
+  sharpen?: boolean;
+  /** RGB fill behind the iOS icons and apple-touch-icon.png */
+  iosBackground?: [number, number, number];
+}
+
+// Phase 16: Responsive image sets
+export interface ResponsiveOptions {
+  /** Widths wider than the source are skipped */
+  widths?: number[];
+  /** In <picture> order; the last is the <img> fallback */
+  formats?: ("avif" | "webp" | "jpeg" | "png")[];
+  quality?: number;
+  /** WebP defaults to lossy */
+  encoder?: EncoderOptions;
+  metadata?: MetadataMode;
+  /** The snippet's sizes attribute */
+  sizes?: string;
+  urlPrefix?: string;
+}
+
+export interface ResponsiveSetRequest {
+  inputPaths: string[];
+  outputDir: string;
+  options?: ResponsiveOptions;
+}
+
+export interface ResponsiveVariant {
+  file: string;
+  format: string;
+  width: number;
+  height: number;
+  bytes: number;
+}
+
+/** One entry of manifest.json */
+export interface ResponsiveImage {
+  source: string;
+  width: number;
+  height: number;
+  variants: ResponsiveVariant[];
+  html: string;
+  snippet: string;
+}
+
+// Phase 17: Alpha flattening
+export interface AlphaOptions {
+  /** "auto" flattens for JPEG, BMP and HDR only */
+  flatten?: "auto" | "always" | "never";
+  matte?: "color" | "checkerboard";
+  color?: [number, number, number];
+  checkerSize?: number;
+  /** Premultiplied color for TIFF (associated alpha); EXR always is */
+  premultiplied?: boolean;
+}
+
+// Phase 18: Large images
+export type LargeStep =
+  | { op: "crop"; x: number; y: number; width: number; height: number }
+  | { op: "flipHorizontal" }
+  | { op: "brightness"; /** -255 to 255 */ value: number }
+  | { op: "contrast"; /** -100 to 100 */ value: number }
+  /** Averages each factor×factor block in linear light */
+  | { op: "downscale"; factor: number };
+
+export interface LargeImageOptions {
+  /** Output rows processed and written at a time */
+  stripRows?: number;
+}
+
+export interface LargeImageResult {
+  width: number;
+  height: number;
+  /** Estimated peak memory of the run */
+  workingBytes: number;
+}